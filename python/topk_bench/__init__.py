@@ -1,5 +1,7 @@
 # Native fns
 from .topk_bench import *
+from .metrics import read_metrics
+from .catalog import set_catalog, list_runs, load_run
 
 # Providers
 from .providers.topk import *
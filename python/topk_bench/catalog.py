@@ -0,0 +1,106 @@
+import json
+import os
+import sqlite3
+from collections.abc import Iterator
+from contextlib import closing, contextmanager
+
+import pyarrow as pa
+
+# Environment variable setting the catalog at import
+CATALOG_VAR = "TOPK_BENCH_CATALOG"
+
+_catalog: str | None = os.environ.get(CATALOG_VAR) or None
+
+_SCHEMA = """
+CREATE TABLE IF NOT EXISTS runs (
+    run_id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    ended_at TEXT,
+    metrics TEXT NOT NULL,
+    manifest TEXT NOT NULL,
+    summary TEXT NOT NULL
+)
+"""
+
+# Columns returned by `list_runs()`, in order
+_COLUMNS = ["run_id", "kind", "provider", "started_at", "ended_at", "metrics"]
+
+
+def set_catalog(path: str | None) -> None:
+    """Record every run exported by `write_metrics()` or `RunResults` in the SQLite
+    catalog at `path`, created if missing, or stop recording with `None`."""
+    global _catalog
+    _catalog = path
+
+
+def list_runs(
+    kind: str | None = None, provider: str | None = None, catalog: str | None = None
+) -> list[dict]:
+    """Runs recorded in the catalog, most recent first, optionally of one `kind` or
+    `provider` only. Each has its `run_id`, `kind`, `provider`, `started_at`, `ended_at`
+    and the `metrics` path it was exported to."""
+    query = f"SELECT {', '.join(_COLUMNS)} FROM runs"
+    conditions = {"kind": kind, "provider": provider}
+    conditions = {column: value for column, value in conditions.items() if value is not None}
+    if conditions:
+        query += " WHERE " + " AND ".join(f"{column} = ?" for column in conditions)
+    query += " ORDER BY started_at DESC"
+
+    with _connect(catalog) as db:
+        rows = db.execute(query, list(conditions.values())).fetchall()
+    return [dict(zip(_COLUMNS, row)) for row in rows]
+
+
+def load_run(run_id: str, catalog: str | None = None) -> dict:
+    """A run recorded in the catalog, as returned by `list_runs()` plus its `manifest`,
+    shaped like the manifest written next to the metrics, and its `summary` rows as a
+    table. Pass its `metrics` path to `read_metrics()` for the raw samples."""
+    with _connect(catalog) as db:
+        row = db.execute(
+            f"SELECT {', '.join(_COLUMNS)}, manifest, summary FROM runs WHERE run_id = ?",
+            [run_id],
+        ).fetchone()
+    if row is None:
+        raise KeyError(f"Run {run_id} is not in the catalog")
+
+    run = dict(zip(_COLUMNS, row))
+    run["manifest"] = json.loads(row[-2])
+    run["summary"] = pa.Table.from_pylist(json.loads(row[-1]))
+    return run
+
+
+@contextmanager
+def _connect(catalog: str | None) -> Iterator[sqlite3.Connection]:
+    """Connection to the catalog, committed and closed on exit. Using a connection as a
+    context manager only commits it."""
+    path = catalog or _catalog
+    if path is None:
+        raise ValueError(f"No catalog set, pass `catalog`, call set_catalog() or set {CATALOG_VAR}")
+
+    with closing(sqlite3.connect(path)) as db, db:
+        db.execute(_SCHEMA)
+        yield db
+
+
+def _record(entries: str) -> None:
+    """Called on export with the exported runs as JSON."""
+    if _catalog is None:
+        return
+
+    rows = [
+        (
+            entry["run_id"],
+            entry["kind"],
+            entry["provider"],
+            entry["started_at"],
+            entry["ended_at"],
+            entry["metrics"],
+            json.dumps(entry["manifest"]),
+            json.dumps(entry["summary"]),
+        )
+        for entry in json.loads(entries)
+    ]
+    with _connect(_catalog) as db:
+        db.executemany("INSERT OR REPLACE INTO runs VALUES (?, ?, ?, ?, ?, ?, ?, ?)", rows)
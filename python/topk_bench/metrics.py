@@ -0,0 +1,67 @@
+import pyarrow as pa
+import pyarrow.compute as pc
+import pyarrow.csv as pcsv
+import pyarrow.fs as pfs
+import pyarrow.json as pjson
+import pyarrow.parquet as pq
+
+from .topk_bench import METRICS_SCHEMA_VERSION
+
+# Leading columns of every export, in order
+COLUMNS = ["schema_version", "ts", "metric", "kind", "value"]
+
+
+def read_metrics(paths: list[str]) -> pa.Table:
+    """Read files written by `write_metrics()`, upgrading older exports to the current
+    column contract, into a single table.
+
+    Label columns missing from some files are filled with empty strings, as if the
+    samples didn't carry the label."""
+    tables = [_upgrade(_read(path)) for path in paths]
+    table = pa.concat_tables(tables, promote_options="default")
+
+    for i, name in enumerate(table.column_names):
+        if name not in COLUMNS:
+            table = table.set_column(i, name, pc.fill_null(table.column(i), ""))
+
+    return table
+
+
+def _read(path: str) -> pa.Table:
+    if not path.endswith((".csv", ".jsonl", ".ndjson")):
+        return pq.read_table(path)
+
+    fs, path = pfs.FileSystem.from_uri(path) if "://" in path else (pfs.LocalFileSystem(), path)
+    with fs.open_input_stream(path) as f:
+        table = pcsv.read_csv(f) if path.endswith(".csv") else pjson.read_json(f)
+
+    # Text formats don't keep types: timestamps may come back as strings, labels as numbers
+    for i, name in enumerate(table.column_names):
+        if name == "ts":
+            table = table.set_column(i, name, pc.cast(table.column(i), pa.timestamp("us")))
+        elif name not in COLUMNS:
+            table = table.set_column(i, name, pc.cast(table.column(i), pa.string()))
+    return table
+
+
+def _upgrade(table: pa.Table) -> pa.Table:
+    if "schema_version" in table.column_names:
+        version = table.column("schema_version")[0].as_py() if len(table) else METRICS_SCHEMA_VERSION
+        if version > METRICS_SCHEMA_VERSION:
+            raise ValueError(
+                f"Metrics schema version {version} is newer than {METRICS_SCHEMA_VERSION}, upgrade topk_bench"
+            )
+        return table
+
+    # Version 1: the distance `metric` label is a second `metric` column, and exports
+    # before metric kinds have no `kind` column
+    names = list(table.column_names)
+    if names.count("metric") > 1:
+        names[names.index("metric", names.index("metric") + 1)] = "label_metric"
+        table = table.rename_columns(names)
+    if "kind" not in names:
+        table = table.append_column("kind", pa.nulls(len(table), pa.string()))
+    table = table.append_column("schema_version", pa.array([1] * len(table), pa.int32()))
+
+    labels = [name for name in table.column_names if name not in COLUMNS]
+    return table.select(COLUMNS + sorted(labels))
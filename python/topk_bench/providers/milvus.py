@@ -1,6 +1,8 @@
+import importlib.metadata
+import math
 import os
 from pymilvus import DataType, MilvusClient
-from ..topk_bench import Document, Provider
+from ..topk_bench import Document, Provider, SetupConfig
 
 
 class MilvusProvider(Provider):
@@ -13,7 +15,17 @@ class MilvusProvider(Provider):
     def name(self) -> str:
         return "milvus"
 
-    def setup(self, collection: str):
+    def version(self) -> str:
+        return importlib.metadata.version("pymilvus")
+
+    def server_version(self) -> str:
+        return self.client.get_server_version()
+
+    def setup(self, collection: str, config: SetupConfig | None = None, **kwargs):
+        dimension = (config and config.dimension) or 768
+        index_param = (config and config.index_params) or {"nlist": 1024}
+        metric = METRICS[(config and config.metric) or "cosine"]
+
         schema = MilvusClient.create_schema(
             enable_dynamic_field=False,
         )
@@ -34,7 +46,7 @@ class MilvusProvider(Provider):
         schema.add_field(
             "dense_embedding",
             DataType.FLOAT_VECTOR,
-            dim=768,
+            dim=dimension,
             enable_index=True,
         )
         schema.add_field(
@@ -49,13 +61,19 @@ class MilvusProvider(Provider):
             enable_match=True,
             max_length=256,
         )
+        schema.add_field(
+            "tag",
+            DataType.VARCHAR,
+            max_length=256,
+            nullable=True,
+        )
 
         index_params = self.client.prepare_index_params()
         index_params.add_index(
             field_name="dense_embedding",
-            metric_type="COSINE",
+            metric_type=metric,
             index_type="IVF_FLAT",
-            param={"nlist": 1024},
+            param=index_param,
         )
         index_params.add_index(
             field_name="keyword_filter",
@@ -72,11 +90,14 @@ class MilvusProvider(Provider):
             collection_name=sanitize_collection(collection),
         )
 
-    def query_by_id(self, collection: str, id: str):
+    def query_by_id(
+        self, collection: str, id: str, consistency: str | None = None, **kwargs
+    ):
         result = self.client.query(
             collection_name=sanitize_collection(collection),
             ids=[id],
-            output_fields=["text", "int_filter", "keyword_filter"],
+            output_fields=["text", "int_filter", "keyword_filter", "tag"],
+            **consistency_kwargs(consistency),
         )
         return [to_document_from_query(hit) for hit in result]
 
@@ -87,13 +108,24 @@ class MilvusProvider(Provider):
         top_k: int,
         int_filter: int | None,
         keyword_filter: str | None,
+        metric: str = "cosine",
+        include_vectors: bool = False,
+        include_payload: bool = True,
+        search_params: dict | None = None,
+        consistency: str | None = None,
+        tag: str | None = None,
+        **kwargs,
     ) -> list[Document]:
         # Build filter
         filters = []
         if int_filter is not None:
             filters.append(f"int_filter <= {int_filter}")
         if keyword_filter is not None:
-            filters.append(f"TEXT_MATCH(keyword_filter, '{keyword_filter}')")
+            filters.append(
+                f"TEXT_MATCH(keyword_filter, {string_literal(keyword_filter)})"
+            )
+        if tag is not None:
+            filters.append(f"tag == {string_literal(tag)}")
 
         # Search
         results = self.client.search(
@@ -101,14 +133,23 @@ class MilvusProvider(Provider):
             data=[vector],
             anns_field="dense_embedding",
             limit=top_k,
+            search_params={"params": search_params} if search_params else None,
             filter=" and ".join(filters) if filters else None,
-            output_fields=["text", "int_filter", "keyword_filter"],
+            output_fields=(
+                ["text", "int_filter", "keyword_filter", "tag"]
+                if include_payload
+                else []
+            )
+            + (["dense_embedding"] if include_vectors else []),
+            **consistency_kwargs(consistency),
         )
 
         # Convert
-        return [to_document_from_search(hit) for hits in results for hit in hits]
+        return [
+            to_document_from_search(hit, metric) for hits in results for hit in hits
+        ]
 
-    def upsert(self, collection: str, docs: list[Document]):
+    def upsert(self, collection: str, docs: list[Document], **kwargs):
         self.client.upsert(
             collection_name=sanitize_collection(collection),
             data=[
@@ -120,6 +161,7 @@ class MilvusProvider(Provider):
                     else [],
                     "int_filter": doc.int_filter,
                     "keyword_filter": doc.keyword_filter,
+                    "tag": doc.tag,
                 }
                 for doc in docs
             ],
@@ -131,6 +173,28 @@ class MilvusProvider(Provider):
             filter=f"id in {ids}",
         )
 
+    def delete_by_filter(
+        self,
+        collection: str,
+        int_filter: int | None = None,
+        keyword_filter: str | None = None,
+    ):
+        filters = []
+        if int_filter is not None:
+            filters.append(f"int_filter <= {int_filter}")
+        if keyword_filter is not None:
+            filters.append(
+                f"TEXT_MATCH(keyword_filter, {string_literal(keyword_filter)})"
+            )
+
+        if not filters:
+            raise ValueError("delete_by_filter needs int_filter or keyword_filter")
+
+        self.client.delete(
+            collection_name=sanitize_collection(collection),
+            filter=" and ".join(filters),
+        )
+
     def delete_collection(self, collection: str):
         self.client.drop_collection(
             collection_name=sanitize_collection(collection),
@@ -139,14 +203,42 @@ class MilvusProvider(Provider):
     def list_collections(self):
         return self.client.list_collections()
 
+    def consistency_levels(self) -> list[str]:
+        return list(CONSISTENCY_LEVELS)
+
     def close(self):
         pass
 
 
+METRICS = {"cosine": "COSINE", "dot": "IP", "l2": "L2"}
+
+
+CONSISTENCY_LEVELS = {
+    "strong": "Strong",
+    "bounded": "Bounded",
+    "session": "Session",
+    "eventual": "Eventually",
+}
+
+
+def consistency_kwargs(consistency: str | None) -> dict:
+    if consistency is None:
+        return {}
+    if consistency not in CONSISTENCY_LEVELS:
+        raise ValueError(f"Milvus does not support consistency {consistency!r}")
+    return {"consistency_level": CONSISTENCY_LEVELS[consistency]}
+
+
 def sanitize_collection(collection: str) -> str:
     return collection.replace("-", "_")
 
 
+def string_literal(value: str) -> str:
+    """Quote a string for a Milvus filter expression."""
+    escaped = value.replace("\\", "\\\\").replace("'", "\\'").replace('"', '\\"')
+    return f"'{escaped}'"
+
+
 def to_document_from_query(entity: dict) -> Document:
     """Convert Milvus query result entity to Document."""
     return Document(
@@ -154,10 +246,19 @@ def to_document_from_query(entity: dict) -> Document:
         text=entity.get("text", ""),
         int_filter=entity.get("int_filter", 0),
         keyword_filter=entity.get("keyword_filter", ""),
+        tag=entity.get("tag"),
     )
 
 
-def to_document_from_search(hit: dict) -> Document:
+def to_score(distance: float | None, metric: str) -> float | None:
+    """Convert a hit's `distance` to the harness's scores. Milvus's L2 reports the squared
+    Euclidean distance."""
+    if distance is not None and metric == "l2":
+        return math.sqrt(max(distance, 0.0))
+    return distance
+
+
+def to_document_from_search(hit: dict, metric: str = "cosine") -> Document:
     """Convert Milvus search result hit to Document."""
     entity = hit.get("entity", {})
     return Document(
@@ -165,4 +266,7 @@ def to_document_from_search(hit: dict) -> Document:
         text=entity.get("text", ""),
         int_filter=entity.get("int_filter", 0),
         keyword_filter=entity.get("keyword_filter", ""),
+        dense_embedding=entity.get("dense_embedding"),
+        tag=entity.get("tag"),
+        score=to_score(hit.get("distance"), metric),
     )
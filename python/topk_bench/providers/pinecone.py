@@ -1,7 +1,9 @@
+import importlib.metadata
+import math
 import os
 from pinecone import QueryResponse, ServerlessSpec
 from pinecone.grpc import GRPCIndex, PineconeGRPC
-from ..topk_bench import Document, Provider
+from ..topk_bench import Document, Provider, SetupConfig
 
 
 class PineconeProvider(Provider):
@@ -28,24 +30,38 @@ class PineconeProvider(Provider):
     def name(self) -> str:
         return "pinecone"
 
-    def setup(self, collection: str):
+    def version(self) -> str:
+        return importlib.metadata.version("pinecone")
+
+    def setup(self, collection: str, config: SetupConfig | None = None, **kwargs):
         if self.client.has_index(collection):
             return
 
+        dimension = (config and config.dimension) or 768
+        metric = METRICS[(config and config.metric) or "cosine"]
+
         self.client.create_index(
             name=collection,
-            dimension=768,
-            metric="cosine",
+            dimension=dimension,
+            metric=metric,
             spec=ServerlessSpec(
                 cloud=self.cloud,
                 region=self.region,
             ),
         )
 
-    def query_by_id(self, collection: str, id: str):
+    def query_by_id(
+        self,
+        collection: str,
+        id: str,
+        namespace: str | None = None,
+        consistency: str | None = None,
+        **kwargs,
+    ):
+        check_consistency(consistency)
         index = self._get_index(collection)
 
-        results = index.fetch(ids=[id])
+        results = index.fetch(ids=[id], namespace=namespace)
 
         return [to_document(vector) for vector in results.vectors.values()]
 
@@ -56,7 +72,15 @@ class PineconeProvider(Provider):
         top_k: int,
         int_filter: int | None,
         keyword_filter: str | None,
+        metric: str = "cosine",
+        include_vectors: bool = False,
+        include_payload: bool = True,
+        namespace: str | None = None,
+        tag: str | None = None,
+        consistency: str | None = None,
+        **kwargs,
     ) -> list[Document]:
+        check_consistency(consistency)
         index = self._get_index(collection)
 
         # Build filter
@@ -65,54 +89,115 @@ class PineconeProvider(Provider):
             filt["int_filter"] = {"$lte": int_filter}
         if keyword_filter is not None:
             filt["keyword_filter"] = {"$in": [keyword_filter]}
+        if tag is not None:
+            filt["tag"] = {"$eq": tag}
 
         results: QueryResponse = index.query(
             vector=vector,
             top_k=top_k,
             filter=None if not filt else filt,
-            include_metadata=True,
+            include_metadata=include_payload,
+            include_values=include_vectors,
+            namespace=namespace,
         )
 
-        return [to_document(match) for match in results["matches"]]
+        return [to_document(match, metric) for match in results["matches"]]
 
-    def upsert(self, collection: str, docs: list[Document]):
+    def upsert(self, collection: str, docs: list[Document], **kwargs):
         index = self._get_index(collection)
 
-        index.upsert(
-            vectors=[
-                (
-                    doc.id,
-                    doc.dense_embedding,
-                    {
-                        "text": doc.text,
-                        "int_filter": doc.int_filter,
-                        "keyword_filter": doc.keyword_filter.split(" ")
-                        if isinstance(doc.keyword_filter, str)
-                        else doc.keyword_filter,
-                    },
-                )
-                for doc in docs
-            ]
-        )
+        # Pinecone upserts to one namespace at a time
+        by_namespace: dict[str | None, list[Document]] = {}
+        for doc in docs:
+            by_namespace.setdefault(doc.namespace, []).append(doc)
+
+        for namespace, namespace_docs in by_namespace.items():
+            index.upsert(
+                vectors=[
+                    (doc.id, doc.dense_embedding, to_metadata(doc))
+                    for doc in namespace_docs
+                ],
+                namespace=namespace,
+            )
+
+    def update(
+        self, collection: str, id: str, fields: dict, namespace: str | None = None
+    ):
+        index = self._get_index(collection)
+        index.update(id=id, set_metadata=fields, namespace=namespace)
 
     def delete_by_id(self, collection: str, ids: list[str]):
         index = self._get_index(collection)
         index.delete(ids=ids)
 
+    def delete_by_filter(
+        self,
+        collection: str,
+        int_filter: int | None = None,
+        keyword_filter: str | None = None,
+    ):
+        index = self._get_index(collection)
+
+        filt = {}
+        if int_filter is not None:
+            filt["int_filter"] = {"$lte": int_filter}
+        if keyword_filter is not None:
+            filt["keyword_filter"] = {"$in": [keyword_filter]}
+
+        index.delete(filter=filt)
+
     def delete_collection(self, collection: str):
         self.client.delete_index(collection)
 
     def list_collections(self):
         return [index.name for index in self.client.list_indexes()]
 
+    def consistency_levels(self) -> list[str]:
+        return ["eventual"]
+
     def close(self):
         pass
 
 
-def to_document(result: dict) -> Document:
+METRICS = {"cosine": "cosine", "dot": "dotproduct", "l2": "euclidean"}
+
+
+def check_consistency(consistency: str | None):
+    # Pinecone reads are eventually consistent, with no stronger option
+    if consistency not in (None, "eventual"):
+        raise ValueError(f"Pinecone does not support consistency {consistency!r}")
+
+
+def to_score(score: float | None, metric: str) -> float | None:
+    """Convert a match's `score` to the harness's scores. Pinecone's euclidean reports the
+    squared Euclidean distance."""
+    if score is not None and metric == "l2":
+        return math.sqrt(max(score, 0.0))
+    return score
+
+
+def to_document(result: dict, metric: str = "cosine") -> Document:
+    metadata = result["metadata"] or {}
     return Document(
         id=result["id"],
-        text=result["metadata"]["text"],
-        int_filter=int(result["metadata"]["int_filter"]),
-        keyword_filter=" ".join(result["metadata"]["keyword_filter"]),
+        text=metadata.get("text", ""),
+        int_filter=int(metadata.get("int_filter", 0)),
+        keyword_filter=" ".join(metadata.get("keyword_filter", [])),
+        dense_embedding=list(result["values"]) if result["values"] else None,
+        tag=metadata.get("tag"),
+        score=to_score(result["score"], metric),
     )
+
+
+def to_metadata(doc: Document) -> dict:
+    metadata = {
+        "text": doc.text,
+        "int_filter": doc.int_filter,
+        "keyword_filter": doc.keyword_filter.split(" ")
+        if isinstance(doc.keyword_filter, str)
+        else doc.keyword_filter,
+    }
+    # Pinecone metadata can't hold nulls
+    if doc.tag is not None:
+        metadata["tag"] = doc.tag
+    return metadata
@@ -1,6 +1,7 @@
+import importlib.metadata
 import os
 from qdrant_client import QdrantClient, models
-from ..topk_bench import Document, Provider
+from ..topk_bench import Document, Provider, SetupConfig, UpsertAck
 
 
 class QdrantProvider(Provider):
@@ -13,15 +14,24 @@ class QdrantProvider(Provider):
     def name(self) -> str:
         return "qdrant"
 
-    def setup(self, collection: str):
+    def version(self) -> str:
+        return importlib.metadata.version("qdrant-client")
+
+    def server_version(self) -> str:
+        return self.client.info().version
+
+    def setup(self, collection: str, config: SetupConfig | None = None, **kwargs):
+        dimension = (config and config.dimension) or 768
+        metric = METRICS[(config and config.metric) or "cosine"]
+
         if collection not in [
             c.name for c in self.client.get_collections().collections
         ]:
             self.client.create_collection(
                 collection_name=collection,
                 vectors_config=models.VectorParams(
-                    size=768,
-                    distance=models.Distance.COSINE,
+                    size=dimension,
+                    distance=metric,
                 ),
             )
 
@@ -37,11 +47,14 @@ class QdrantProvider(Provider):
                 field_schema=models.PayloadSchemaType.KEYWORD,
             )
 
-    def query_by_id(self, collection: str, id: str):
+    def query_by_id(
+        self, collection: str, id: str, consistency: str | None = None, **kwargs
+    ):
         result = self.client.retrieve(
             collection_name=collection,
-            ids=[int(id)],
+            ids=[point_id(id)],
             with_payload=True,
+            consistency=read_consistency(consistency),
         )
         return [to_document(row) for row in result]
 
@@ -52,6 +65,13 @@ class QdrantProvider(Provider):
         top_k: int,
         int_filter: int | None,
         keyword_filter: str | None,
+        metric: str = "cosine",
+        include_vectors: bool = False,
+        include_payload: bool = True,
+        search_params: dict | None = None,
+        consistency: str | None = None,
+        tag: str | None = None,
+        **kwargs,
     ) -> list[Document]:
         # Build filter
         filters = []
@@ -69,6 +89,10 @@ class QdrantProvider(Provider):
                     match=models.MatchValue(value=keyword_filter),
                 )
             )
+        if tag is not None:
+            filters.append(
+                models.FieldCondition(key="tag", match=models.MatchValue(value=tag))
+            )
         qfilter = None
         if filters:
             qfilter = models.Filter(must=filters)
@@ -77,18 +101,23 @@ class QdrantProvider(Provider):
             collection_name=collection,
             query=vector,
             limit=top_k,
-            with_payload=True,
+            with_payload=include_payload,
+            with_vectors=include_vectors,
             query_filter=qfilter,
+            search_params=models.SearchParams(**search_params)
+            if search_params
+            else None,
+            consistency=read_consistency(consistency),
         )
         return [to_document(point) for point in result.points]
 
-    def upsert(self, collection: str, docs: list[Document]):
+    def upsert(self, collection: str, docs: list[Document], **kwargs):
         try:
-            self.client.upsert(
+            result = self.client.upsert(
                 collection_name=collection,
                 points=[
                     models.PointStruct(
-                        id=int(doc.id),
+                        id=point_id(doc.id),
                         vector=doc.dense_embedding
                         if doc.dense_embedding is not None
                         else [],
@@ -98,6 +127,7 @@ class QdrantProvider(Provider):
                             "keyword_filter": doc.keyword_filter.split(" ")
                             if isinstance(doc.keyword_filter, str)
                             else doc.keyword_filter,
+                            "tag": doc.tag,
                         },
                     )
                     for doc in docs
@@ -109,10 +139,51 @@ class QdrantProvider(Provider):
             print(e)
             raise e
 
+        return UpsertAck(
+            queued=result.status != models.UpdateStatus.COMPLETED,
+            request_id=str(result.operation_id),
+        )
+
+    def update(self, collection: str, id: str, fields: dict):
+        self.client.set_payload(
+            collection_name=collection,
+            payload=fields,
+            points=[point_id(id)],
+            wait=True,
+        )
+
     def delete_by_id(self, collection: str, ids: list[str]):
         self.client.delete(
             collection_name=collection,
-            points_selector=models.PointIdsList(points=[int(id) for id in ids]),
+            points_selector=models.PointIdsList(points=[point_id(id) for id in ids]),
+        )
+
+    def delete_by_filter(
+        self,
+        collection: str,
+        int_filter: int | None = None,
+        keyword_filter: str | None = None,
+    ):
+        filters = []
+        if int_filter is not None:
+            filters.append(
+                models.FieldCondition(
+                    key="int_filter",
+                    range=models.Range(lte=int_filter),
+                )
+            )
+        if keyword_filter is not None:
+            filters.append(
+                models.FieldCondition(
+                    key="keyword_filter",
+                    match=models.MatchValue(value=keyword_filter),
+                )
+            )
+
+        self.client.delete(
+            collection_name=collection,
+            points_selector=models.FilterSelector(filter=models.Filter(must=filters)),
+            wait=True,
         )
 
     def delete_collection(self, collection: str):
@@ -121,10 +192,46 @@ class QdrantProvider(Provider):
     def list_collections(self):
         return [c.name for c in self.client.get_collections().collections]
 
+    def capabilities(self) -> dict[str, bool]:
+        # Point IDs are unsigned integers or UUIDs
+        return {"string_ids": False}
+
+    def consistency_levels(self) -> list[str]:
+        return list(CONSISTENCY_LEVELS)
+
     def close(self):
         pass
 
 
+METRICS = {
+    "cosine": models.Distance.COSINE,
+    "dot": models.Distance.DOT,
+    "l2": models.Distance.EUCLID,
+}
+
+
+# Read from all replicas for strong reads, a single replica (the default) for eventual ones
+CONSISTENCY_LEVELS = {
+    "strong": models.ReadConsistencyType.ALL,
+    "quorum": models.ReadConsistencyType.QUORUM,
+    "majority": models.ReadConsistencyType.MAJORITY,
+    "eventual": None,
+}
+
+
+def read_consistency(consistency: str | None):
+    if consistency is None:
+        return None
+    if consistency not in CONSISTENCY_LEVELS:
+        raise ValueError(f"Qdrant does not support consistency {consistency!r}")
+    return CONSISTENCY_LEVELS[consistency]
+
+
+def point_id(id: str) -> int | str:
+    """Qdrant point ID of a document ID: numeric IDs as integers, UUIDs as strings."""
+    return int(id) if id.isdigit() else id
+
+
 def to_document(point) -> Document:
     """Convert Qdrant point to Document."""
     payload = point.payload or {}
@@ -144,4 +251,7 @@ def to_document(point) -> Document:
         text=payload.get("text", ""),
         int_filter=payload.get("int_filter", 0),
         keyword_filter=keyword_filter,
+        dense_embedding=point.vector if isinstance(point.vector, list) else None,
+        tag=payload.get("tag"),
+        score=getattr(point, "score", None),
     )
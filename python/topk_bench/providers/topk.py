@@ -1,9 +1,10 @@
+import importlib.metadata
 import os
 import topk_sdk as t
 import topk_sdk.query as tq
 import topk_sdk.error as te
 import topk_sdk.schema as ts
-from ..topk_bench import Document, Provider
+from ..topk_bench import Document, Provider, SetupConfig
 
 
 class TopKProvider(Provider):
@@ -24,25 +25,35 @@ class TopKProvider(Provider):
     def name(self) -> str:
         return "topk"
 
-    def setup(self, collection: str):
+    def version(self) -> str:
+        return importlib.metadata.version("topk-sdk")
+
+    def setup(self, collection: str, config: SetupConfig | None = None, **kwargs):
+        dimension = (config and config.dimension) or 768
+        metric = METRICS[(config and config.metric) or "cosine"]
+
         try:
             self.client.collections().create(
                 collection,
                 schema={
                     "text": ts.text().required(),
-                    "dense_embedding": ts.f32_vector(dimension=768).index(
-                        ts.vector_index(metric="cosine")
+                    "dense_embedding": ts.f32_vector(dimension=dimension).index(
+                        ts.vector_index(metric=metric)
                     ),
                     "int_filter": ts.int().required(),
                     "keyword_filter": ts.text().required().index(ts.keyword_index()),
+                    "tag": ts.text(),
                 },
             )
         except te.CollectionAlreadyExistsError:
             pass
 
-    def query_by_id(self, collection: str, id: str):
+    def query_by_id(
+        self, collection: str, id: str, consistency: str | None = None, **kwargs
+    ):
+        check_consistency(consistency)
         results = self.client.collection(collection).query(
-            tq.select("text", "int_filter", "keyword_filter")
+            tq.select("text", "int_filter", "keyword_filter", "tag")
             .filter(tq.field("_id").eq(id))
             .limit(1)
         )
@@ -56,11 +67,20 @@ class TopKProvider(Provider):
         top_k: int,
         int_filter: int | None,
         keyword_filter: str | None,
+        metric: str = "cosine",
+        include_vectors: bool = False,
+        include_payload: bool = True,
+        tag: str | None = None,
+        consistency: str | None = None,
+        **kwargs,
     ) -> list[Document]:
+        check_consistency(consistency)
+        fields = ["text", "int_filter", "keyword_filter", "tag"] if include_payload else []
+        if include_vectors:
+            fields.append("dense_embedding")
+
         query = tq.select(
-            "text",
-            "int_filter",
-            "keyword_filter",
+            *fields,
             vector_distance=tq.fn.vector_distance("dense_embedding", vector),
         )
 
@@ -70,15 +90,23 @@ class TopKProvider(Provider):
         if keyword_filter is not None:
             query = query.filter(tq.field("keyword_filter").match_all(keyword_filter))
 
-        query = query.topk(tq.field("vector_distance"), top_k)
+        if tag is not None:
+            query = query.filter(tq.field("tag").eq(tag))
+
+        # Euclidean distance ranks the nearest documents lowest
+        query = query.topk(tq.field("vector_distance"), top_k, asc=metric == "l2")
 
         results = self.client.collection(collection).query(query)
 
         return [to_document(row) for row in results]
 
-    def upsert(self, collection: str, docs: list[Document]):
+    def upsert(self, collection: str, docs: list[Document], **kwargs):
         self.client.collection(collection).upsert([from_document(doc) for doc in docs])
 
+    def capabilities(self) -> dict[str, bool]:
+        # The client deletes by ID only, so delete() runs are skipped
+        return {"delete_by_filter": False}
+
     def delete_by_id(self, collection: str, ids: list[str]):
         self.client.collection(collection).delete(ids)
 
@@ -88,24 +116,42 @@ class TopKProvider(Provider):
     def list_collections(self):
         return [collection.name for collection in self.client.collections().list()]
 
+    def consistency_levels(self) -> list[str]:
+        return []
+
     def close(self):
         pass
 
 
+METRICS = {"cosine": "cosine", "dot": "dot_product", "l2": "euclidean"}
+
+
+def check_consistency(consistency: str | None):
+    # The adapter doesn't pass a consistency level to the client
+    if consistency is not None:
+        raise ValueError(f"TopK provider does not support consistency {consistency!r}")
+
+
 def to_document(row: dict) -> Document:
     return Document(
         id=row["_id"],
-        text=row["text"],
-        int_filter=row["int_filter"],
-        keyword_filter=row["keyword_filter"],
+        text=row.get("text", ""),
+        int_filter=row.get("int_filter", 0),
+        keyword_filter=row.get("keyword_filter", ""),
+        dense_embedding=row.get("dense_embedding"),
+        tag=row.get("tag"),
+        score=row.get("vector_distance"),
     )
 
 
 def from_document(doc: Document) -> dict:
-    return {
+    row = {
         "_id": doc.id,
         "text": doc.text,
         "dense_embedding": doc.dense_embedding,
         "int_filter": doc.int_filter,
         "keyword_filter": doc.keyword_filter,
     }
+    if doc.tag is not None:
+        row["tag"] = doc.tag
+    return row
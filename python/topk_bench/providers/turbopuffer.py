@@ -1,6 +1,8 @@
+import importlib.metadata
+import math
 import turbopuffer
 import os
-from ..topk_bench import Document, Provider
+from ..topk_bench import Document, Provider, SetupConfig
 
 
 class TurbopufferProvider(Provider):
@@ -9,14 +11,23 @@ class TurbopufferProvider(Provider):
             api_key=api_key or os.environ["TURBOPUFFER_API_KEY"],
             region=region or os.environ["TURBOPUFFER_REGION"],
         )
+        self.distance_metric = METRICS["cosine"]
 
     def name(self) -> str:
         return "turbopuffer"
 
-    def setup(self, namespace: str):
+    def version(self) -> str:
+        return importlib.metadata.version("turbopuffer")
+
+    def setup(self, namespace: str, config: SetupConfig | None = None, **kwargs):
+        # Turbopuffer sets the distance metric on write
+        self.distance_metric = METRICS[(config and config.metric) or "cosine"]
+
         if self.client.namespace(namespace).exists():
             return
 
+        dimension = (config and config.dimension) or 768
+
         # In Turbopuffer, namespaces are created implicitly when the first document is upserted.
         # However, we need the namespace to be already created, as we want to send empty queries
         # to warm up the connection (eg. ping). We use the same collection for warm up and real
@@ -24,23 +35,31 @@ class TurbopufferProvider(Provider):
         doc = Document(
             id="__bootstrap__",
             text="Hello, world!",
-            dense_embedding=[0.1] * 768,
+            dense_embedding=[0.1] * dimension,
             int_filter=1,
             keyword_filter="Hello",
         )
         self.upsert(namespace, [doc])
         self.delete_by_id(namespace, ids=[doc.id])
 
-    def query_by_id(self, namespace: str, id: str):
+    def query_by_id(
+        self, namespace: str, id: str, consistency: str | None = None, **kwargs
+    ):
         result = self.client.namespace(namespace).query(
             rank_by=("id", "desc"),
             filters=("id", "Eq", id),
             top_k=1,
-            include_attributes=["text", "int_filter", "keyword_filter"],
+            include_attributes=["text", "int_filter", "keyword_filter", "tag"],
+            **consistency_kwargs(consistency),
         )
 
         return [to_document(r) for r in (result.rows or [])]
 
+    def update(self, namespace: str, id: str, fields: dict):
+        self.client.namespace(namespace).write(
+            patch_rows=[{"id": id, **fields}],
+        )
+
     def delete_by_id(self, namespace: str, ids: list[str]):
         self.client.namespace(namespace).write(
             deletes=ids,
@@ -53,48 +72,120 @@ class TurbopufferProvider(Provider):
         top_k: int,
         int_filter: int | None = None,
         keyword_filter: str | None = None,
+        metric: str = "cosine",
+        include_vectors: bool = False,
+        include_payload: bool = True,
+        consistency: str | None = None,
+        tag: str | None = None,
+        **kwargs,
     ) -> list[Document]:
         filters = []
-        if int_filter:
+        if int_filter is not None:
             filters.append(("int_filter", "Lte", int_filter))
-        if keyword_filter:
+        if keyword_filter is not None:
             filters.append(("keyword_filter", "ContainsAllTokens", keyword_filter))
+        if tag is not None:
+            filters.append(("tag", "Eq", tag))
 
         result = self.client.namespace(namespace).query(
             rank_by=("vector", "ANN", vector),
             top_k=top_k,
             filters=None if len(filters) == 0 else ("And", tuple(filters)),
-            include_attributes=["text", "int_filter", "keyword_filter"],
+            include_attributes=(
+                ["text", "int_filter", "keyword_filter", "tag"]
+                if include_payload
+                else []
+            )
+            + (["vector"] if include_vectors else []),
+            **consistency_kwargs(consistency),
         )
-        return [to_document(r) for r in (result.rows or [])]
+        return [to_document(r, metric) for r in (result.rows or [])]
 
-    def upsert(self, namespace: str, docs: list[Document]):
+    def upsert(self, namespace: str, docs: list[Document], **kwargs):
         self.client.namespace(namespace).write(
             upsert_rows=[from_document(doc) for doc in docs],
-            distance_metric="cosine_distance",
+            distance_metric=self.distance_metric,
             schema={
                 "text": {"type": "string"},
                 "int_filter": {"type": "int"},
                 "keyword_filter": {"type": "string", "full_text_search": True},
+                "tag": {"type": "string"},
             },
         )
 
+    def delete_by_filter(
+        self,
+        namespace: str,
+        int_filter: int | None = None,
+        keyword_filter: str | None = None,
+    ):
+        filters = []
+        if int_filter is not None:
+            filters.append(("int_filter", "Lte", int_filter))
+        if keyword_filter is not None:
+            filters.append(("keyword_filter", "ContainsAllTokens", keyword_filter))
+
+        if not filters:
+            raise ValueError("delete_by_filter needs int_filter or keyword_filter")
+
+        self.client.namespace(namespace).write(
+            delete_by_filter=("And", tuple(filters)),
+        )
+
     def delete_collection(self, namespace: str):
         self.client.namespace(namespace).delete_all()
 
     def list_collections(self):
         return [ns.id for ns in self.client.namespaces()]
 
+    def consistency_levels(self) -> list[str]:
+        return list(CONSISTENCY_LEVELS)
+
     def close(self):
         self.client.close()
 
 
-def to_document(row: turbopuffer.types.namespace_query_response.Row) -> Document:
+# Turbopuffer has no dot product metric. On normalized embeddings cosine ranks the same,
+# and its similarity is the dot product.
+METRICS = {
+    "cosine": "cosine_distance",
+    "dot": "cosine_distance",
+    "l2": "euclidean_squared",
+}
+
+
+CONSISTENCY_LEVELS = ("strong", "eventual")
+
+
+def consistency_kwargs(consistency: str | None) -> dict:
+    if consistency is None:
+        return {}
+    if consistency not in CONSISTENCY_LEVELS:
+        raise ValueError(f"Turbopuffer does not support consistency {consistency!r}")
+    return {"consistency": {"level": consistency}}
+
+
+def to_score(dist: float | None, metric: str) -> float | None:
+    """Convert `$dist` to the harness's scores: similarity for cosine and dot, Euclidean
+    distance for l2."""
+    if dist is None:
+        return None
+    if metric == "l2":
+        return math.sqrt(max(dist, 0.0))
+    return 1.0 - dist
+
+
+def to_document(
+    row: turbopuffer.types.namespace_query_response.Row, metric: str = "cosine"
+) -> Document:
     return Document(
         id=row.id,
-        text=row.text,
-        int_filter=row.int_filter,
-        keyword_filter=row.keyword_filter,
+        text=getattr(row, "text", None) or "",
+        int_filter=getattr(row, "int_filter", None) or 0,
+        keyword_filter=getattr(row, "keyword_filter", None) or "",
+        dense_embedding=getattr(row, "vector", None),
+        tag=getattr(row, "tag", None),
+        score=to_score(getattr(row, "$dist", None), metric),
     )
 
 
@@ -105,4 +196,5 @@ def from_document(doc: Document) -> turbopuffer.types.namespace_query_response.R
         vector=doc.dense_embedding if doc.dense_embedding is not None else [],
         int_filter=doc.int_filter,
         keyword_filter=doc.keyword_filter,
+        tag=doc.tag,
     )
@@ -1,19 +1,163 @@
 from abc import ABC, abstractmethod
+from typing import Any, Callable
+
+METRICS_SCHEMA_VERSION: int
+"""Version of the column contract of files written by `write_metrics()`."""
+
+class Vector:
+    """Read-only `float32` embedding implementing the buffer protocol, e.g. for `numpy.asarray()`."""
+
+    def __len__(self) -> int: ...
+    def tolist(self) -> list[float]: ...
 
 class Document:
     id: str
     text: str
-    dense_embedding: list[float]
+    dense_embedding: list[float] | None
+    """Can be set from a list or a `float32`/`float64` buffer such as a numpy array."""
     int_filter: int
     keyword_filter: str
+    tag: str | None
+    score: float | None
+    namespace: str | None
+    """Namespace within the collection the document is written to, if it has them."""
+
+    @property
+    def dense_array(self) -> Vector | None:
+        """The embedding as a buffer, without converting it to a list of floats."""
+        ...
+    def to_dict(self, array: bool = False) -> dict[str, Any]:
+        """The fields as a dict, with `dense_embedding` as a `Vector` when `array` is set."""
+        ...
+    @staticmethod
+    def from_dict(dict: dict[str, Any]) -> Document: ...
+
+class Query:
+    vector: list[float]
+    ground_truth: list[int] | None
+    recall: dict[int, dict[str, list[int]]]
+    int_filter: int | None
+    keyword_filter: str | None
+    top_k: int | None
+    namespace: str | None
+
+    def __init__(
+        self,
+        vector: list[float],
+        ground_truth: list[int] | None = None,
+        recall: dict[int, dict[str, list[int]]] | None = None,
+        int_filter: int | None = None,
+        keyword_filter: str | None = None,
+        top_k: int | None = None,
+        namespace: str | None = None,
+    ) -> None:
+        """`ground_truth` is the expected doc IDs for the filters the query runs with, closest first."""
+        ...
+
+class SetupConfig:
+    dimension: int | None
+    metric: str
+    filterable_fields: list[str]
+    index_params: dict
+
+class UpsertAck:
+    """What `Provider.upsert()` may return about an acknowledged batch."""
+
+    accepted: int | None
+    """Documents the provider accepted, all of the batch if unset."""
+    queued: bool | None
+    """Whether the documents were only queued when `upsert()` returned, or already applied."""
+    request_id: str | None
+    """The provider's ID of the request."""
+    request_bytes: int | None
+    """Serialized request size in bytes."""
+
+    def __init__(
+        self,
+        accepted: int | None = None,
+        queued: bool | None = None,
+        request_id: str | None = None,
+        request_bytes: int | None = None,
+    ) -> None: ...
+
+class IngestConfig:
+    """Config of an `ingest()` run. `input` is a path or an Arrow stream, e.g. a `pyarrow.Table`. See the README for the optional arguments."""
+
+    def __init__(
+        self,
+        collection: str,
+        batch_size: int,
+        concurrency: int,
+        input: str | Any,
+        mode: str,
+        size: str,
+        cache_dir: str,
+        **kwargs: Any,
+    ) -> None: ...
+
+class QueryConfig:
+    """Config of a `query()` run. See the README for the optional arguments."""
+
+    def __init__(
+        self,
+        collection: str,
+        queries: str | list[Query],
+        top_k: int,
+        concurrency: int,
+        size: str,
+        timeout: int,
+        mode: str,
+        cache_dir: str,
+        **kwargs: Any,
+    ) -> None: ...
+
+class DeleteConfig:
+    """Config of a `delete()` run, deleting the documents matching the filters."""
+
+    def __init__(
+        self,
+        collection: str,
+        queries: str,
+        cache_dir: str,
+        int_filter: int | None = None,
+        keyword_filter: str | None = None,
+        top_k: int = 10,
+        probes: int = 10,
+        timeout: int = 60,
+    ) -> None:
+        """At least one of `int_filter` and `keyword_filter` is required. `queries` probe for deleted documents, `probes` per poll, for up to `timeout` seconds."""
+        ...
+
+class BackupConfig:
+    """Config of a `backup()` run, snapshotting `collection` and restoring it."""
+
+    def __init__(
+        self,
+        collection: str,
+        cache_dir: str,
+        restore_collection: str | None = None,
+        timeout: int = 600,
+        recall: QueryConfig | None = None,
+    ) -> None:
+        """`restore_collection` defaults to `collection`. `recall` queries the restored collection as a child run."""
+        ...
+
+class BackfillConfig:
+    """Config of a `backfill()` run, ingesting a second collection while serving queries."""
+
+    def __init__(
+        self, queries: QueryConfig, ingest: IngestConfig, baseline: bool = True
+    ) -> None:
+        """`ingest` must write to a different collection than `queries` read from. With `baseline`, the queries are served alone for `queries.timeout` seconds first."""
+        ...
 
 class Provider(ABC):
     @abstractmethod
-    def setup(self, collection: str):
+    def setup(self, collection: str, config: SetupConfig | None = None, **kwargs):
         pass
 
     @abstractmethod
-    def query_by_id(self, collection: str, id: str):
+    def query_by_id(self, collection: str, id: str, consistency: str | None = None, **kwargs):
         pass
 
     @abstractmethod
@@ -24,11 +168,19 @@ class Provider(ABC):
         top_k: int,
         int_filter: int | None,
         keyword_filter: str | None,
+        metric: str = "cosine",
+        include_vectors: bool = False,
+        include_payload: bool = True,
+        consistency: str | None = None,
+        search_params: dict | None = None,
+        **kwargs,
     ):
+        """`namespace` and `tag` are only passed when set: the namespace to query, and the `tag` results must have, for read-your-writes checks."""
         pass
 
     @abstractmethod
-    def upsert(self, collection: str, docs: list[dict]):
+    def upsert(self, collection: str, docs: list[dict], **kwargs) -> UpsertAck | int | None:
+        """May return an `UpsertAck`, or just the serialized request size in bytes, recorded as `bench.ingest.request_bytes`."""
         pass
 
     @abstractmethod
@@ -39,5 +191,250 @@ class Provider(ABC):
     def delete_collection(self, collection: str):
         pass
 
-def write_metrics(path: str):
+    def update(self, collection: str, id: str, fields: dict, **kwargs):
+        """Optional. Patch `fields` of a single document, used by `write_mode="update"`."""
+        pass
+
+    def delete_by_filter(
+        self,
+        collection: str,
+        int_filter: int | None = None,
+        keyword_filter: str | None = None,
+    ):
+        """Optional. Delete all documents matching the filters (same semantics as `query`), used by `delete`."""
+        pass
+
+    def snapshot(self, collection: str) -> str:
+        """Optional. Snapshot the collection and return the snapshot ID, used by `backup`."""
+        pass
+
+    def restore(self, collection: str, snapshot_id: str):
+        """Optional. Restore a snapshot into `collection`, used by `backup`."""
+        pass
+
+    def warm_connections(self, n: int) -> list[float] | None:
+        """Optional. Open `n` connections before the timed window and return their establishment latencies in ms."""
+        pass
+
+    def num_indexed(self, collection: str) -> int:
+        """Optional. Number of documents currently indexed, used by `wait_for_indexing`."""
+        pass
+
+    def count(self, collection: str) -> int:
+        """Optional. Total number of documents, compared against the documents sent after ingest."""
+        pass
+
+    def restart(self):
+        """Optional. Restart the server and return once it serves requests again, used by `verify_durability` on self-hosted targets."""
+        pass
+
+    def capabilities(self) -> dict[str, bool]:
+        """Optional. What the provider supports: `filters`, `query_by_id`, `update`, `delete_by_filter`, `snapshot`, `num_indexed`, `count`, `restart`, `string_ids`."""
+        pass
+
+    def consistency_levels(self) -> list[str]:
+        """Optional. Which of `strong`, `bounded`, `session`, `quorum`, `majority` and `eventual` the provider supports; other levels are rejected before a run starts."""
+        pass
+
+    def version(self) -> str:
+        """Optional. Version of the client SDK, recorded as the `provider_version` label and in the run manifest."""
+        pass
+
+    def server_version(self) -> str:
+        """Optional. Version of the server, recorded as the `server_version` label and in the run manifest."""
+        pass
+
+class SimulatedProvider(Provider):
+    """In-memory provider with a known lognormal latency and recall, to validate the harness's own measurements."""
+
+    def __init__(
+        self,
+        latency_ms: float = 5.0,
+        latency_sigma: float = 0.25,
+        spike_probability: float = 0.0,
+        spike_ms: float = 100.0,
+        recall: float = 1.0,
+        seed: int | None = None,
+    ) -> None: ...
+
+class Session:
+    """Isolated metrics registry. Pass to `ingest`, `query` and `write_metrics`."""
+
+    def __init__(
+        self, metrics_buffer: int = 100_000, metrics_overflow: str = "block"
+    ) -> None:
+        """`metrics_overflow` is `"block"`, `"drop-oldest"` or `"drop-newest"`."""
+        ...
+
+class Snapshot:
+    """Point-in-time view of collected metrics."""
+
+    def __len__(self) -> int: ...
+    def is_empty(self) -> bool: ...
+    def names(self) -> list[str]: ...
+    def kind(self, name: str) -> str | None:
+        """`"counter"`, `"gauge"` or `"histogram"`, `None` if not recorded."""
+        ...
+    def value(self, name: str) -> float:
+        """Total of a counter, latest value of a gauge or average of a histogram."""
+        ...
+    def latest(self, name: str) -> float: ...
+    def total(self, name: str) -> float: ...
+    def total_prefix(self, prefix: str) -> float: ...
+    def rate(self, name: str) -> float:
+        """Sum of `name` over the last second, e.g. queries/s for `bench.query.oks`."""
+        ...
+    def avg(self, name: str) -> float: ...
+    def quantile(self, name: str, quantile: float) -> float: ...
+
+class RunHandle:
+    """Handle to a run started by `ingest_async` or `query_async`."""
+
+    def status(self) -> str:
+        """`"running"`, `"aborting"`, `"completed"`, `"aborted"` or `"failed"`."""
+        ...
+    def wait(self, timeout: float | None = None) -> Any | None:
+        """Wait for the run and return its result (the run ID(s)), or `None` on timeout."""
+        ...
+    def abort(self) -> None:
+        """Stop the run cleanly; `wait()` blocks until it has wound down."""
+        ...
+
+def ingest(
+    provider: Provider,
+    config: IngestConfig,
+    session: Session | None = None,
+    on_stats: Callable[[dict], bool | None] | None = None,
+    run_id: str | None = None,
+    parent_run_id: str | None = None,
+) -> str:
+    """Run an ingest benchmark, returning its run ID."""
+    ...
+
+def query(
+    provider: Provider,
+    config: QueryConfig,
+    session: Session | None = None,
+    on_stats: Callable[[dict], bool | None] | None = None,
+    run_id: str | None = None,
+    parent_run_id: str | None = None,
+) -> list[str]:
+    """Run a query benchmark, returning the run IDs, one per search parameter set."""
+    ...
+
+def ingest_async(
+    provider: Provider,
+    config: IngestConfig,
+    session: Session | None = None,
+    on_stats: Callable[[dict], bool | None] | None = None,
+    run_id: str | None = None,
+    parent_run_id: str | None = None,
+) -> RunHandle: ...
+def query_async(
+    provider: Provider,
+    config: QueryConfig,
+    session: Session | None = None,
+    on_stats: Callable[[dict], bool | None] | None = None,
+    run_id: str | None = None,
+    parent_run_id: str | None = None,
+) -> RunHandle: ...
+def compare(
+    providers: dict[str, Provider],
+    config: QueryConfig,
+    session: Session | None = None,
+    run_id: str | None = None,
+    parent_run_id: str | None = None,
+) -> str:
+    """Run `config` against every provider at once on one query stream, returning the comparison's run ID. The first provider is the baseline."""
+    ...
+
+def delete(
+    provider: Provider,
+    config: DeleteConfig,
+    session: Session | None = None,
+    run_id: str | None = None,
+    parent_run_id: str | None = None,
+) -> str:
+    """Run a delete benchmark, returning its run ID."""
+    ...
+
+def backup(
+    provider: Provider,
+    config: BackupConfig,
+    session: Session | None = None,
+    run_id: str | None = None,
+    parent_run_id: str | None = None,
+) -> str:
+    """Run a snapshot and restore benchmark, returning its run ID."""
+    ...
+
+def backfill(
+    provider: Provider,
+    config: BackfillConfig,
+    session: Session | None = None,
+    run_id: str | None = None,
+    parent_run_id: str | None = None,
+) -> str:
+    """Run a backfill-while-serving benchmark, returning its run ID."""
+    ...
+
+def write_metrics(path: str, session: Session | None = None):
+    """Write collected metrics as parquet, or as CSV or JSON Lines for `.csv` or `.jsonl` paths."""
     pass
+
+def snapshot(
+    run_id: str | None = None, session: Session | None = None, phase: str | None = None
+) -> Snapshot:
+    """Snapshot the metrics of a run, or of every run in the session, optionally of one `phase` only."""
+    ...
+
+def purge_cache(cache_dir: str, max_size: int | None = None) -> int:
+    """Evict cached datasets down to `max_size` bytes, or delete all of them without `max_size`. Only downloaded datasets are deleted, not in-progress downloads or other files. Returns the bytes freed."""
+    ...
+
+def verify_dataset(path: str, cache_dir: str) -> list[str]:
+    """Check a docs or queries parquet file for problems before a run. Returns an empty list if none."""
+    ...
+
+def generate_queries(
+    docs: str,
+    output: str,
+    cache_dir: str,
+    num_queries: int = 1000,
+    top_k: int = 100,
+    int_filters: list[int] = [10000],
+    keyword_filters: list[str] = ["10000"],
+    noise: float = 0.0,
+    metric: str = "cosine",
+    seed: int | None = None,
+) -> int:
+    """Write a queries file sampled from a docs file, with exact ground truth for every filter pair. Returns the number of queries."""
+    ...
+
+def register_datasets(path: str) -> list[str]:
+    """Register the `[datasets.<name>]` tables of a TOML file for use as a config's `size`. Returns their names."""
+    ...
+
+def datasets() -> dict[str, dict]:
+    """Registered datasets by name, with their `docs`, `queries`, `dimension`, `num_docs` and `ground_truth_top_k`."""
+    ...
+
+def calibrate(
+    concurrencies: list[int] = [1, 2, 4, 8, 16],
+    duration_secs: float = 5.0,
+    dimension: int = 768,
+) -> list[dict[str, float]]:
+    """Measure the harness's own overhead per query and highest dispatch rate against a no-op provider, one dict per concurrency."""
+    ...
+
+def set_output(output: str) -> None:
+    """Print progress reports and logs as `"auto"`, `"color"`, `"plain"`, `"quiet"` or `"json-lines"`, or forward them to Python's `logging` with `"python"`."""
+    ...
+
+def init_logging(level: str = "info", filters: str | None = None) -> None:
+    """Log at `level`, with per-module `filters` such as `"topk_bench=debug,aws=warn"` on top, replacing `RUST_LOG`."""
+    ...
+
+def set_python_threads(threads: int | None = None) -> None:
+    """Call providers from `threads` dedicated Python threads, or the blocking pool with `None`."""
+    ...
@@ -0,0 +1,48 @@
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::ingest::IngestConfig;
+use crate::query::QueryConfig;
+
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct BackfillConfig {
+    /// Queries served from the primary collection.
+    pub queries: QueryConfig,
+    /// Ingest of the second collection, run at full speed while the queries are served.
+    pub ingest: IngestConfig,
+    /// Serve the queries for `queries.timeout` seconds before the backfill, as a baseline.
+    pub baseline: bool,
+}
+
+impl BackfillConfig {
+    /// Files the run reads, for the run manifest.
+    pub fn datasets(&self) -> Vec<String> {
+        let mut datasets = self.queries.datasets();
+        datasets.extend(self.ingest.datasets());
+        datasets
+    }
+}
+
+#[pymethods]
+impl BackfillConfig {
+    #[new]
+    #[pyo3(signature = (queries, ingest, baseline=true))]
+    fn new(queries: QueryConfig, ingest: IngestConfig, baseline: bool) -> PyResult<Self> {
+        if queries.collection == ingest.collection {
+            return Err(PyValueError::new_err(
+                "ingest must write to a different collection than queries read from",
+            ));
+        }
+        if queries.search_params.len() > 1 {
+            return Err(PyValueError::new_err(
+                "queries must not sweep over search_params",
+            ));
+        }
+
+        Ok(Self {
+            queries,
+            ingest,
+            baseline,
+        })
+    }
+}
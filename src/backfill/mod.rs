@@ -0,0 +1,200 @@
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::{
+    ingest,
+    provider::PyProvider,
+    query,
+    telemetry::{
+        metrics::{Recorder, Registry},
+        output::report,
+        RunIds, RunInfo, Snapshot,
+    },
+};
+
+mod config;
+pub use config::BackfillConfig;
+
+/// Benchmark ingesting a second collection while the primary one serves queries.
+///
+/// Queries first run alone as a baseline, then again while the backfill ingests at full
+/// speed until it completes. Both and the ingest are child runs; the run itself records
+/// how query throughput and latency compare between them.
+pub async fn start(
+    provider: PyProvider,
+    config: BackfillConfig,
+    registry: Registry,
+    ids: RunIds,
+) -> anyhow::Result<String> {
+    let run_id = ids.generate();
+
+    let (metrics_tx, metrics_rx) = registry.channel();
+    let metrics_task_handle = tokio::spawn(registry.clone().consume(metrics_rx));
+
+    let provider_name = provider.name().await?;
+    let provider_version = provider.version().await?;
+    let m = Recorder::new(
+        metrics_tx,
+        [
+            ("run_id", run_id.clone()),
+            ("parent_run_id", ids.parent_label()),
+            ("provider", provider_name.clone()),
+            ("collection", config.queries.collection.clone()),
+            ("backfill_collection", config.ingest.collection.clone()),
+            ("baseline", config.baseline.to_string()),
+        ]
+        .into_iter()
+        .chain(provider_version.labels()),
+    );
+    registry
+        .start_run(RunInfo::new(
+            "backfill",
+            &m,
+            provider_version,
+            config.datasets(),
+            &config.queries.cache_dir,
+        ))
+        .await;
+    info!(
+        ?config,
+        "Benchmarking backfill while serving on {provider_name}"
+    );
+    let child_ids = || RunIds::new(None, Some(run_id.clone()));
+
+    // Queries alone
+    let baseline = if config.baseline {
+        let start = Utc::now();
+        let started = Instant::now();
+        let run_ids = query::start(
+            config.queries.clone(),
+            provider.clone(),
+            registry.clone(),
+            None,
+            child_ids(),
+            CancellationToken::new(),
+        )
+        .await?;
+        // Leave out the recall pass that follows the timed window
+        let end = start + chrono::Duration::seconds(config.queries.timeout as i64);
+        let elapsed = started
+            .elapsed()
+            .as_secs_f64()
+            .min(config.queries.timeout as f64);
+        Some(query_stats(&registry, &run_ids, start, end, elapsed).await)
+    } else {
+        None
+    };
+
+    // Queries while the backfill runs, until it completes
+    let stop_queries = CancellationToken::new();
+    let _stop_queries_on_drop = stop_queries.clone().drop_guard();
+    let queries = tokio::spawn(query::start(
+        {
+            let mut queries = config.queries.clone();
+            queries.timeout = u64::MAX;
+            queries
+        },
+        provider.clone(),
+        registry.clone(),
+        None,
+        child_ids(),
+        stop_queries.clone(),
+    ));
+
+    let start = Utc::now();
+    let started = Instant::now();
+    let ingest_run_id = ingest::start(
+        provider.clone(),
+        config.ingest.clone(),
+        registry.clone(),
+        None,
+        child_ids(),
+        CancellationToken::new(),
+    )
+    .await?;
+    let end = Utc::now();
+    let elapsed = started.elapsed().as_secs_f64();
+    info!(ingest_run_id, "Backfill completed in {elapsed:.2}s");
+
+    stop_queries.cancel();
+    let run_ids = queries.await??;
+    let during = query_stats(&registry, &run_ids, start, end, elapsed).await;
+
+    // Interference
+    m.gauge("bench.backfill.qps", during.qps);
+    m.gauge("bench.backfill.latency_p50_ms", during.p50);
+    m.gauge("bench.backfill.latency_p99_ms", during.p99);
+    if let Some(baseline) = &baseline {
+        m.gauge("bench.backfill.baseline_qps", baseline.qps);
+        m.gauge("bench.backfill.baseline_latency_p50_ms", baseline.p50);
+        m.gauge("bench.backfill.baseline_latency_p99_ms", baseline.p99);
+    }
+    print_interference(
+        &format!("{}@{}", provider_name, config.queries.size),
+        baseline.as_ref(),
+        &during,
+    );
+
+    // Flush remaining metrics
+    drop(m);
+    metrics_task_handle.await??;
+    registry.finish_run(&run_id).await;
+
+    Ok(run_id)
+}
+
+/// Throughput and latency of the queries served between `start` and `end`.
+struct QueryStats {
+    qps: f64,
+    p50: f64,
+    p99: f64,
+}
+
+async fn query_stats(
+    registry: &Registry,
+    run_ids: &[String],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    elapsed: f64,
+) -> QueryStats {
+    let mut metrics = Vec::new();
+    for run_id in run_ids {
+        metrics.extend(registry.snapshot(run_id).await.metrics);
+    }
+    let stats = Snapshot::new(metrics).since(start).until(end);
+
+    QueryStats {
+        qps: stats.total("bench.query.oks") / elapsed.max(f64::EPSILON),
+        p50: stats.quantile("bench.query.latency_ms", 0.50),
+        p99: stats.quantile("bench.query.latency_ms", 0.99),
+    }
+}
+
+fn print_interference(prefix: &str, baseline: Option<&QueryStats>, during: &QueryStats) {
+    let change = |during: f64, baseline: f64| {
+        if baseline > 0.0 {
+            format!(" ({:+.1}%)", (during / baseline - 1.0) * 100.0)
+        } else {
+            "".to_string()
+        }
+    };
+    let baseline_qps = baseline.map_or(0.0, |b| b.qps);
+    let baseline_p50 = baseline.map_or(0.0, |b| b.p50);
+    let baseline_p99 = baseline.map_or(0.0, |b| b.p99);
+
+    report(format!(
+        "{:>16}] {} QPS: {}{}, Latency: {}{}, {}{}",
+        prefix,
+        "Queries during backfill:".green().bold(),
+        format!("{:.2}", during.qps).blue().bold(),
+        change(during.qps, baseline_qps),
+        format!("p50={:.2}ms", during.p50).yellow().bold(),
+        change(during.p50, baseline_p50),
+        format!("p99={:.2}ms", during.p99).magenta().bold(),
+        change(during.p99, baseline_p99),
+    ));
+}
@@ -0,0 +1,60 @@
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::query::QueryConfig;
+
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct BackupConfig {
+    pub collection: String,
+    /// Collection the snapshot is restored into. Defaults to `collection`.
+    pub restore_collection: Option<String>,
+    pub cache_dir: String,
+    /// Give up waiting for the restored collection to reach the snapshotted document count
+    /// after this many seconds.
+    pub timeout: u64,
+    /// Queries run against the restored collection, as a child run, to measure its recall.
+    pub recall: Option<QueryConfig>,
+}
+
+impl BackupConfig {
+    pub fn restore_collection(&self) -> String {
+        self.restore_collection
+            .clone()
+            .unwrap_or_else(|| self.collection.clone())
+    }
+
+    /// Files the run reads, for the run manifest.
+    pub fn datasets(&self) -> Vec<String> {
+        self.recall
+            .as_ref()
+            .map(QueryConfig::datasets)
+            .unwrap_or_default()
+    }
+}
+
+#[pymethods]
+impl BackupConfig {
+    #[new]
+    #[pyo3(signature = (collection, cache_dir, restore_collection=None, timeout=600, recall=None))]
+    fn new(
+        collection: String,
+        cache_dir: String,
+        restore_collection: Option<String>,
+        timeout: u64,
+        recall: Option<QueryConfig>,
+    ) -> PyResult<Self> {
+        if restore_collection.as_deref() == Some("") {
+            return Err(PyValueError::new_err(
+                "restore_collection must not be empty",
+            ));
+        }
+
+        Ok(Self {
+            collection,
+            restore_collection,
+            cache_dir,
+            timeout,
+            recall,
+        })
+    }
+}
@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant};
+
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{
+    provider::{Capability, PyProvider},
+    query,
+    telemetry::{
+        metrics::{Recorder, Registry},
+        RunIds, RunInfo,
+    },
+};
+
+mod config;
+pub use config::BackupConfig;
+
+/// Benchmark snapshotting a collection and restoring it.
+///
+/// Measures how long the snapshot and restore calls take, how long until the restored
+/// collection holds all snapshotted documents, and optionally its recall.
+pub async fn start(
+    provider: PyProvider,
+    config: BackupConfig,
+    registry: Registry,
+    ids: RunIds,
+) -> anyhow::Result<String> {
+    let run_id = ids.generate();
+
+    let (metrics_tx, metrics_rx) = registry.channel();
+    let metrics_task_handle = tokio::spawn(registry.clone().consume(metrics_rx));
+
+    let provider_name = provider.name().await?;
+    let provider_version = provider.version().await?;
+    let restore_collection = config.restore_collection();
+    let m = Recorder::new(
+        metrics_tx,
+        [
+            ("run_id", run_id.clone()),
+            ("parent_run_id", ids.parent_label()),
+            ("provider", provider_name.clone()),
+            ("collection", config.collection.clone()),
+            ("restore_collection", restore_collection.clone()),
+            ("timeout", config.timeout.to_string()),
+        ]
+        .into_iter()
+        .chain(provider_version.labels()),
+    );
+    let mut capabilities = provider.capabilities().await?;
+    let supported = capabilities.require(Capability::Snapshot, "skipping the run");
+    capabilities.record(&m);
+    registry
+        .start_run(
+            RunInfo::new(
+                "backup",
+                &m,
+                provider_version,
+                config.datasets(),
+                &config.cache_dir,
+            )
+            .with_notes(capabilities.notes()),
+        )
+        .await;
+    if !supported {
+        drop(m);
+        metrics_task_handle.await??;
+        registry.finish_run(&run_id).await;
+        return Ok(run_id);
+    }
+    info!(
+        ?config,
+        "Benchmarking snapshot and restore on {provider_name}"
+    );
+
+    let count_before = provider.count(config.collection.clone()).await?;
+
+    // Snapshot
+    let start = Instant::now();
+    let snapshot_id = provider.snapshot(config.collection.clone()).await?;
+    let latency = start.elapsed();
+    m.observe("bench.backup.snapshot_ms", latency.as_millis() as f64);
+    info!(
+        snapshot_id,
+        "Snapshot completed in {:.2}s",
+        latency.as_secs_f64()
+    );
+
+    // Restore
+    let start = Instant::now();
+    provider
+        .restore(restore_collection.clone(), snapshot_id)
+        .await?;
+    let latency = start.elapsed();
+    m.observe("bench.backup.restore_ms", latency.as_millis() as f64);
+    info!("Restore completed in {:.2}s", latency.as_secs_f64());
+
+    // Wait until the restored collection holds all snapshotted documents
+    if let Some(expected) = count_before {
+        wait_for_restore(
+            &provider,
+            &restore_collection,
+            expected,
+            Duration::from_secs(config.timeout),
+            &m,
+            start,
+        )
+        .await?;
+    }
+
+    // Recall of the restored collection
+    if let Some(mut recall) = config.recall.clone() {
+        recall.collection = restore_collection.clone();
+        let run_ids = query::start(
+            recall,
+            provider.clone(),
+            registry.clone(),
+            None,
+            RunIds::new(None, Some(run_id.clone())),
+            CancellationToken::new(),
+        )
+        .await?;
+
+        for recall_run_id in run_ids {
+            let stats = registry.snapshot(&recall_run_id).await;
+            if stats.count("bench.query.recall") == 0 {
+                warn!(
+                    recall_run_id,
+                    "No recall measured on the restored collection"
+                );
+                continue;
+            }
+            let avg = stats.avg("bench.query.recall");
+            m.gauge_with(
+                "bench.backup.recall",
+                avg,
+                &[("recall_run_id", &recall_run_id)],
+            );
+            info!(recall_run_id, avg, "Recall of the restored collection");
+        }
+    }
+
+    // Flush remaining metrics
+    drop(m);
+    metrics_task_handle.await??;
+    registry.finish_run(&run_id).await;
+
+    Ok(run_id)
+}
+
+/// Poll the restored collection's count until it reaches `expected` or `timeout` elapses.
+async fn wait_for_restore(
+    provider: &PyProvider,
+    collection: &str,
+    expected: u64,
+    timeout: Duration,
+    m: &Recorder,
+    start: Instant,
+) -> anyhow::Result<()> {
+    loop {
+        let count = provider.count(collection.to_string()).await?.unwrap_or(0);
+        m.gauge("bench.backup.restored_docs", count as f64);
+
+        if count >= expected {
+            let ready = start.elapsed();
+            m.observe("bench.backup.restore_ready_ms", ready.as_millis() as f64);
+            info!(
+                count,
+                "Restored collection ready after {:.2}s",
+                ready.as_secs_f64()
+            );
+            return Ok(());
+        }
+
+        if start.elapsed() > timeout {
+            m.incr("bench.backup.restore_timeouts", 1.0);
+            warn!(count, expected, "Restored collection is missing documents");
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
@@ -0,0 +1,151 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use tracing::{info, warn};
+
+/// Environment variable capping the size of the dataset cache, e.g. `50GB`.
+const MAX_SIZE_VAR: &str = "TOPK_BENCH_CACHE_MAX_SIZE";
+
+/// Suffix of the sidecar files holding the remote ETag of a cached file. Every downloaded
+/// file has one, empty without an ETag, which marks it as the cache's to evict.
+const ETAG_SUFFIX: &str = ".etag";
+
+/// ETag the cached file at `path` was downloaded with, if recorded.
+pub fn etag(path: &Path) -> Option<String> {
+    fs::read_to_string(sidecar(path))
+        .ok()
+        .filter(|etag| !etag.is_empty())
+}
+
+/// Whether the cached file at `path` was downloaded before sidecars were written, so its
+/// ETag is unknown.
+pub fn is_legacy(path: &Path) -> bool {
+    path.exists() && !sidecar(path).exists()
+}
+
+/// Record a file downloaded before sidecars were written, with the remote ETag it was
+/// found to match, if any.
+pub fn adopt(path: &Path, etag: Option<&str>) -> anyhow::Result<()> {
+    fs::write(sidecar(path), etag.unwrap_or_default())?;
+
+    Ok(())
+}
+
+/// Record a cached file after downloading it, and evict older files if the cache is
+/// over its size cap. The download is kept even if eviction fails.
+pub fn insert(cache_dir: &str, path: &Path, etag: Option<&str>) -> anyhow::Result<()> {
+    fs::write(sidecar(path), etag.unwrap_or_default())?;
+
+    let evicted = max_size().and_then(|max_size| match max_size {
+        Some(max_size) => evict(cache_dir, max_size, Some(path)).map(|_| ()),
+        None => Ok(()),
+    });
+    if let Err(error) = evicted {
+        warn!(%error, "Failed to evict cached files");
+    }
+
+    Ok(())
+}
+
+/// Mark a cached file as used, for LRU eviction.
+pub fn touch(path: &Path) {
+    let touched = fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(SystemTime::now()));
+    if let Err(error) = touched {
+        warn!(?path, %error, "Failed to update cached file access time");
+    }
+}
+
+/// Delete every cached file. Returns the number of bytes freed.
+pub fn clear(cache_dir: &str) -> anyhow::Result<u64> {
+    evict(cache_dir, 0, None)
+}
+
+/// Evict least recently used files until the cache is at most `max_size` bytes, never
+/// evicting `keep`. Only downloaded files are counted and evicted, not in-progress
+/// downloads or files the cache didn't write. Downloads from before sidecars are only
+/// counted once [`adopt`]ed. Returns the number of bytes freed.
+pub fn evict(cache_dir: &str, max_size: u64, keep: Option<&Path>) -> anyhow::Result<u64> {
+    let mut files = Vec::new();
+    list_files(Path::new(cache_dir), &mut files)?;
+
+    let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_by_key(|(_, _, modified)| *modified);
+
+    let mut freed = 0;
+    for (path, len, _) in files {
+        if size <= max_size {
+            break;
+        }
+        if keep == Some(path.as_path()) {
+            continue;
+        }
+
+        info!(?path, len, "Evicting cached file");
+        fs::remove_file(&path)?;
+        let _ = fs::remove_file(sidecar(&path));
+        size -= len;
+        freed += len;
+    }
+
+    Ok(freed)
+}
+
+fn max_size() -> anyhow::Result<Option<u64>> {
+    match env::var(MAX_SIZE_VAR) {
+        Ok(value) => Ok(Some(parse_size(&value).ok_or_else(|| {
+            anyhow::anyhow!("Invalid {MAX_SIZE_VAR}: {value}, expected e.g. `50GB`")
+        })?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parse a size in bytes with an optional decimal `KB`, `MB`, `GB` or `TB` suffix.
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim().to_ascii_uppercase();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(i) => value.split_at(i),
+        None => (value.as_str(), ""),
+    };
+    let multiplier = match unit.trim() {
+        "" | "B" => 1e0,
+        "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        _ => return None,
+    };
+
+    Some((number.parse::<f64>().ok()? * multiplier) as u64)
+}
+
+fn sidecar(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(ETAG_SUFFIX);
+    PathBuf::from(sidecar)
+}
+
+/// Downloaded files under `dir` with their size and last use: the files with a sidecar.
+fn list_files(dir: &Path, files: &mut Vec<(PathBuf, u64, SystemTime)>) -> anyhow::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            list_files(&path, files)?;
+        } else if !path.to_string_lossy().ends_with(ETAG_SUFFIX) && sidecar(&path).exists() {
+            files.push((path, metadata.len(), metadata.modified()?));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use pyo3::prelude::*;
+use tokio::task::JoinSet;
+
+use crate::provider::{PyProvider, QueryOptions, SimulatedProvider};
+use crate::telemetry::output::report;
+use crate::telemetry::quantile_of_sorted;
+
+/// How fast the harness dispatches queries to a provider that returns straight away, at
+/// one concurrency.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub concurrency: usize,
+    /// Queries completed per second across all workers
+    pub requests_per_sec: f64,
+    /// Latency of a query, all of it harness overhead
+    pub overhead_p50_ms: f64,
+    pub overhead_p99_ms: f64,
+    /// Time a query waited for a Python thread and the GIL
+    pub queue_wait_p99_ms: f64,
+}
+
+impl Calibration {
+    pub fn to_values(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("concurrency", self.concurrency as f64),
+            ("requests_per_sec", self.requests_per_sec),
+            ("overhead_p50_ms", self.overhead_p50_ms),
+            ("overhead_p99_ms", self.overhead_p99_ms),
+            ("queue_wait_p99_ms", self.queue_wait_p99_ms),
+        ]
+    }
+}
+
+/// Benchmark the no-op [`SimulatedProvider`] for `duration` at each of `concurrencies`,
+/// through the same dispatch path as a query run, and report the harness's overhead per
+/// query and the highest rate it reached.
+pub async fn calibrate(
+    concurrencies: Vec<usize>,
+    duration: Duration,
+    dimension: usize,
+) -> anyhow::Result<Vec<Calibration>> {
+    let provider = Python::with_gil(|py| -> PyResult<PyProvider> {
+        Bound::new(py, SimulatedProvider::noop())?
+            .into_any()
+            .extract()
+    })?;
+    let vector = Arc::new(vec![0.0; dimension]);
+
+    let mut calibrations = Vec::with_capacity(concurrencies.len());
+    for concurrency in concurrencies {
+        let deadline = Instant::now() + duration;
+        let mut workers = JoinSet::new();
+        for _ in 0..concurrency {
+            let provider = provider.clone();
+            let vector = vector.clone();
+            workers.spawn(async move {
+                let mut samples = Vec::new();
+                while Instant::now() < deadline {
+                    let start = Instant::now();
+                    let (_, timings) = provider
+                        .query(
+                            "calibrate".to_string(),
+                            vector.clone(),
+                            10,
+                            None,
+                            None,
+                            QueryOptions::default(),
+                        )
+                        .await?;
+                    samples.push((start.elapsed(), timings.queue_wait));
+                }
+                Ok::<_, PyErr>(samples)
+            });
+        }
+
+        let mut overheads = Vec::new();
+        let mut queue_waits = Vec::new();
+        while let Some(samples) = workers.join_next().await {
+            for (overhead, queue_wait) in samples?? {
+                overheads.push(overhead.as_secs_f64() * 1000.0);
+                queue_waits.push(queue_wait.as_secs_f64() * 1000.0);
+            }
+        }
+        overheads.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        queue_waits.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let calibration = Calibration {
+            concurrency,
+            requests_per_sec: overheads.len() as f64 / duration.as_secs_f64(),
+            overhead_p50_ms: quantile_of_sorted(&overheads, 0.50),
+            overhead_p99_ms: quantile_of_sorted(&overheads, 0.99),
+            queue_wait_p99_ms: quantile_of_sorted(&queue_waits, 0.99),
+        };
+        report(format!(
+            "{:>16}] Concurrency {}: {}, Overhead: {}, {}, Queue wait: {}",
+            "calibrate",
+            concurrency,
+            format!("{:.2} queries/s", calibration.requests_per_sec)
+                .blue()
+                .bold(),
+            format!("p50={:.3}ms", calibration.overhead_p50_ms)
+                .yellow()
+                .bold(),
+            format!("p99={:.3}ms", calibration.overhead_p99_ms)
+                .magenta()
+                .bold(),
+            format!("p99={:.3}ms", calibration.queue_wait_p99_ms).bold(),
+        ));
+        calibrations.push(calibration);
+    }
+
+    if let Some(best) = calibrations
+        .iter()
+        .max_by(|a, b| a.requests_per_sec.total_cmp(&b.requests_per_sec))
+    {
+        report(format!(
+            "{:>16}] {} {} at concurrency {}",
+            "calibrate",
+            "Max dispatch rate:".green().bold(),
+            format!("{:.2} queries/s", best.requests_per_sec).bold(),
+            best.concurrency,
+        ));
+    }
+
+    Ok(calibrations)
+}
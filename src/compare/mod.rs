@@ -0,0 +1,384 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use async_channel::{Receiver, Sender};
+use colored::Colorize;
+use tokio::{
+    sync::{Barrier, Mutex},
+    task::JoinSet,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{
+    data::Query,
+    provider::{ProviderVersion, PyProvider},
+    query::{self, QueryConfig},
+    telemetry::{
+        metrics::{Phase, Recorder, Registry},
+        output::report,
+        RunIds, RunInfo, Snapshot,
+    },
+};
+
+/// A query run's part in a comparison: the target it measures, its copy of the shared
+/// query stream, and the start every target waits for.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub target: String,
+    pub queries: Receiver<Query>,
+    ready: Arc<Barrier>,
+}
+
+impl Comparison {
+    /// Wait until the runs of all targets are ready to send queries, or `abort`.
+    pub async fn ready(&self, abort: &CancellationToken) {
+        tokio::select! {
+            _ = self.ready.wait() => {}
+            _ = abort.cancelled() => {}
+        }
+    }
+}
+
+/// Benchmark the same query workload against several provider instances at once, e.g.
+/// two regions or two cluster sizes.
+///
+/// Each target is a child query run labeled `target`. The runs read the same sequence of
+/// queries, each at its own pace, and start their timed windows together, so their
+/// latencies cover the same queries over the same time. The run itself records how the
+/// targets compare, the first being the baseline.
+pub async fn start(
+    targets: Vec<(String, PyProvider)>,
+    config: QueryConfig,
+    registry: Registry,
+    ids: RunIds,
+) -> anyhow::Result<String> {
+    if targets.len() < 2 {
+        anyhow::bail!("compare needs at least two targets");
+    }
+    if config.mode == "lookup" {
+        anyhow::bail!("compare does not support lookup mode");
+    }
+    if config.search_params.len() > 1 {
+        anyhow::bail!("compare does not support sweeping over search_params");
+    }
+    let run_id = ids.generate();
+
+    let (metrics_tx, metrics_rx) = registry.channel();
+    let metrics_task_handle = tokio::spawn(registry.clone().consume(metrics_rx));
+
+    let names: Vec<String> = targets.iter().map(|(name, _)| name.clone()).collect();
+    let m = Recorder::new(
+        metrics_tx,
+        [
+            ("run_id", run_id.clone()),
+            ("parent_run_id", ids.parent_label()),
+            ("collection", config.collection.clone()),
+            ("size", config.size.clone()),
+            ("mode", config.mode.clone()),
+            ("targets", names.join(",")),
+        ],
+    );
+    registry
+        .start_run(RunInfo::new(
+            "compare",
+            &m,
+            ProviderVersion::default(),
+            config.datasets(),
+            &config.cache_dir,
+        ))
+        .await;
+    info!(?config, ?names, "Comparing targets");
+
+    // One query stream, replayed to every target
+    let abort = CancellationToken::new();
+    let mut sources = JoinSet::new();
+    let (queries_tx, queries_rx) = async_channel::bounded::<Query>(1000);
+    query::spawn_query_source(&config, &mut sources, queries_tx, abort.clone()).await?;
+    let replay = Arc::new(Replay::new(queries_rx, targets.len()));
+
+    let ready = Arc::new(Barrier::new(targets.len()));
+    let mut runs = JoinSet::new();
+    for (i, (target, provider)) in targets.into_iter().enumerate() {
+        let (tx, rx) = async_channel::bounded::<Query>(1000);
+        sources.spawn(replay.clone().forward(i, tx));
+
+        let mut config = config.clone();
+        config.comparison = Some(Comparison {
+            target: target.clone(),
+            queries: rx,
+            ready: ready.clone(),
+        });
+        let run = query::start(
+            config,
+            provider,
+            registry.clone(),
+            None,
+            RunIds::new(None, Some(run_id.clone())),
+            abort.clone(),
+        );
+        runs.spawn(async move { (target, run.await) });
+    }
+
+    // A failed target stops the others
+    let mut run_ids = HashMap::new();
+    let mut failure = None;
+    while let Some(result) = runs.join_next().await {
+        match result? {
+            (target, Ok(ids)) => {
+                run_ids.insert(target, ids);
+            }
+            (target, Err(e)) => {
+                error!(target, error = ?e, "Target failed, stopping the comparison");
+                abort.cancel();
+                failure.get_or_insert(e.context(format!("Target {target} failed")));
+            }
+        }
+    }
+    sources.abort_all();
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    let steady = if config.warmup {
+        Phase::Warmup
+    } else {
+        Phase::Load
+    };
+    let log = replay.log.lock().await;
+    let mut baseline: Option<TargetStats> = None;
+    for (i, target) in names.iter().enumerate() {
+        let labels = [("target", target.as_str())];
+        m.gauge_with("bench.compare.max_lag", log.max_lags[i] as f64, &labels);
+        m.incr_with(
+            "bench.compare.skipped_queries",
+            log.skipped[i] as f64,
+            &labels,
+        );
+        if log.skipped[i] > 0 {
+            warn!(
+                target,
+                skipped = log.skipped[i],
+                "Target fell over {MAX_LAG} queries behind and skipped some"
+            );
+        }
+
+        // One run per target, without a search_params sweep
+        let Some(child) = run_ids[target].first() else {
+            continue;
+        };
+        let snapshot = registry.snapshot(child).await;
+        // Measured after the timed window
+        let recall =
+            (snapshot.count("bench.query.recall") > 0).then(|| snapshot.avg("bench.query.recall"));
+        let timed = snapshot.phase(steady.as_str());
+        let window = window_secs(&timed, "bench.query.oks");
+        let stats = TargetStats {
+            qps: if window > 0.0 {
+                timed.total("bench.query.oks") / window
+            } else {
+                0.0
+            },
+            p50: timed.quantile("bench.query.latency_ms", 0.50),
+            p99: timed.quantile("bench.query.latency_ms", 0.99),
+            recall,
+        };
+
+        m.gauge_with("bench.compare.qps", stats.qps, &labels);
+        m.gauge_with("bench.compare.latency_p50_ms", stats.p50, &labels);
+        m.gauge_with("bench.compare.latency_p99_ms", stats.p99, &labels);
+        if let Some(recall) = stats.recall {
+            m.gauge_with("bench.compare.recall", recall, &labels);
+        }
+        print_comparison(
+            &format!("{}@{}", target, config.size),
+            &stats,
+            baseline.as_ref(),
+        );
+        baseline.get_or_insert(stats);
+    }
+
+    // Flush remaining metrics
+    drop(m);
+    metrics_task_handle.await??;
+    registry.finish_run(&run_id).await;
+
+    Ok(run_id)
+}
+
+/// Seconds between the first and last sample of `name`, the timed window the run
+/// actually sent queries in.
+fn window_secs(stats: &Snapshot, name: &str) -> f64 {
+    let mut timestamps = stats
+        .metrics
+        .iter()
+        .filter(|metric| metric.name == name)
+        .map(|metric| metric.timestamp);
+    let Some(first) = timestamps.next() else {
+        return 0.0;
+    };
+    let (first, last) = timestamps.fold((first, first), |(first, last), timestamp| {
+        (first.min(timestamp), last.max(timestamp))
+    });
+
+    (last - first).num_milliseconds() as f64 / 1000.0
+}
+
+/// Most queries a target can fall behind the furthest ahead before it skips ahead, which
+/// bounds the memory of the [`Replay`] log.
+const MAX_LAG: usize = 10_000;
+
+/// The shared query stream, read by every target at its own pace. Queries are kept until
+/// every target still running has read them, so a slow target doesn't hold back the
+/// others. A target more than [`MAX_LAG`] queries behind skips the oldest ones.
+struct Replay {
+    source: Receiver<Query>,
+    log: Mutex<Log>,
+}
+
+struct Log {
+    queries: VecDeque<Query>,
+    /// Position in the stream of the first kept query.
+    first: usize,
+    /// Position of the next query of each target, `None` once it stopped reading.
+    cursors: Vec<Option<usize>>,
+    /// Most queries each target was behind the furthest ahead.
+    max_lags: Vec<usize>,
+    /// Queries each target skipped for falling [`MAX_LAG`] behind.
+    skipped: Vec<usize>,
+}
+
+impl Replay {
+    fn new(source: Receiver<Query>, targets: usize) -> Self {
+        Self {
+            source,
+            log: Mutex::new(Log {
+                queries: VecDeque::new(),
+                first: 0,
+                cursors: vec![Some(0); targets],
+                max_lags: vec![0; targets],
+                skipped: vec![0; targets],
+            }),
+        }
+    }
+
+    /// Send target `i` the stream's queries until either the stream or the target's run
+    /// ends.
+    async fn forward(self: Arc<Self>, i: usize, tx: Sender<Query>) -> anyhow::Result<()> {
+        while let Some(query) = self.next(i).await {
+            if tx.send(query).await.is_err() {
+                break;
+            }
+        }
+        let mut log = self.log.lock().await;
+        log.cursors[i] = None;
+        log.trim();
+
+        Ok(())
+    }
+
+    /// The next query of target `i`, read from the source when it is the furthest ahead.
+    async fn next(&self, i: usize) -> Option<Query> {
+        let mut log = self.log.lock().await;
+        let cursor = log.cursors[i]?;
+        if cursor == log.first + log.queries.len() {
+            // Others wait on the lock for the query instead of reading past it
+            let query = self.source.recv().await.ok()?;
+            if log.queries.len() == MAX_LAG {
+                log.skip_oldest();
+            }
+            log.queries.push_back(query);
+        }
+
+        let lag = log.first + log.queries.len() - cursor - 1;
+        log.max_lags[i] = log.max_lags[i].max(lag);
+
+        let query = log.queries[cursor - log.first].clone();
+        log.cursors[i] = Some(cursor + 1);
+        log.trim();
+        Some(query)
+    }
+}
+
+impl Log {
+    /// Drop the oldest kept query, moving the targets that haven't read it past it.
+    fn skip_oldest(&mut self) {
+        self.queries.pop_front();
+        self.first += 1;
+
+        for (cursor, skipped) in self.cursors.iter_mut().zip(&mut self.skipped) {
+            if let Some(cursor) = cursor.as_mut().filter(|cursor| **cursor < self.first) {
+                *cursor = self.first;
+                *skipped += 1;
+            }
+        }
+    }
+
+    /// Drop the queries every target has read.
+    fn trim(&mut self) {
+        let Some(slowest) = self.cursors.iter().flatten().min().copied() else {
+            self.queries.clear();
+            return;
+        };
+        while self.first < slowest && self.queries.pop_front().is_some() {
+            self.first += 1;
+        }
+    }
+}
+
+/// Throughput, latency and recall of a target over the timed window.
+#[derive(Debug, Clone)]
+struct TargetStats {
+    qps: f64,
+    p50: f64,
+    p99: f64,
+    recall: Option<f64>,
+}
+
+fn print_comparison(prefix: &str, stats: &TargetStats, baseline: Option<&TargetStats>) {
+    let change = |value: f64, baseline: Option<f64>| match baseline {
+        Some(baseline) if baseline > 0.0 => format!(" ({:+.1}%)", (value / baseline - 1.0) * 100.0),
+        _ => "".to_string(),
+    };
+
+    report(format!(
+        "{:>16}] {} QPS: {}{}, Latency: {}{}, {}{}{}",
+        prefix,
+        "Comparison:".green().bold(),
+        format!("{:.2}", stats.qps).blue().bold(),
+        change(stats.qps, baseline.map(|b| b.qps)),
+        format!("p50={:.2}ms", stats.p50).yellow().bold(),
+        change(stats.p50, baseline.map(|b| b.p50)),
+        format!("p99={:.2}ms", stats.p99).magenta().bold(),
+        change(stats.p99, baseline.map(|b| b.p99)),
+        match stats.recall {
+            Some(recall) => format!(", Recall: {}", format!("{recall:.4}").bold()),
+            None => "".to_string(),
+        },
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn slow_target_skips_ahead_past_the_max_lag() {
+        let (tx, rx) = async_channel::unbounded();
+        for i in 0..MAX_LAG + 5 {
+            tx.send(Query::new(vec![i as f32])).await.unwrap();
+        }
+        let replay = Replay::new(rx, 2);
+
+        for i in 0..MAX_LAG + 5 {
+            assert_eq!(replay.next(0).await.unwrap().dense, vec![i as f32]);
+        }
+        // The oldest 5 were dropped before target 1 read them
+        assert_eq!(replay.next(1).await.unwrap().dense, vec![5.0]);
+
+        let log = replay.log.lock().await;
+        assert_eq!(log.queries.len(), MAX_LAG - 1);
+        assert_eq!(log.skipped, [0, 5]);
+        assert_eq!(log.max_lags, [0, MAX_LAG - 1]);
+    }
+}
@@ -0,0 +1,28 @@
+/// Validates that dense embeddings share a single dimension.
+#[derive(Debug, Clone, Default)]
+pub struct Dimension {
+    /// Expected dimension. When unset, the first embedding seen sets it.
+    pub expected: Option<usize>,
+    /// Pad with zeros or truncate mismatched embeddings instead of failing.
+    pub resize: bool,
+}
+
+impl Dimension {
+    pub fn check(&mut self, vector: &mut Vec<f32>) -> anyhow::Result<()> {
+        let expected = *self.expected.get_or_insert(vector.len());
+
+        if vector.len() == expected {
+            return Ok(());
+        }
+
+        if self.resize {
+            vector.resize(expected, 0.0);
+            return Ok(());
+        }
+
+        anyhow::bail!(
+            "Embedding has dimension {}, expected {expected}. Set `resize_vectors=True` to pad or truncate.",
+            vector.len()
+        )
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Distance metric used to build indexes and score results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Distance {
+    /// Cosine similarity, higher is closer.
+    #[default]
+    Cosine,
+    /// Dot product, higher is closer.
+    Dot,
+    /// Euclidean distance, lower is closer.
+    L2,
+}
+
+impl Distance {
+    /// Whether a higher score means a closer match.
+    pub fn higher_is_closer(&self) -> bool {
+        matches!(self, Distance::Cosine | Distance::Dot)
+    }
+
+    /// Exact score between two vectors, in the same form providers are expected to report.
+    pub fn score(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Distance::Cosine => {
+                let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+                for (x, y) in a.iter().zip(b) {
+                    dot += x * y;
+                    norm_a += x * x;
+                    norm_b += y * y;
+                }
+                if norm_a == 0.0 || norm_b == 0.0 {
+                    0.0
+                } else {
+                    dot / (norm_a.sqrt() * norm_b.sqrt())
+                }
+            }
+            Distance::Dot => a.iter().zip(b).map(|(x, y)| x * y).sum(),
+            Distance::L2 => a
+                .iter()
+                .zip(b)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+                .sqrt(),
+        }
+    }
+}
+
+impl FromStr for Distance {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cosine" => Ok(Distance::Cosine),
+            "dot" => Ok(Distance::Dot),
+            "l2" => Ok(Distance::L2),
+            _ => anyhow::bail!("Invalid metric: {s}"),
+        }
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Distance::Cosine => write!(f, "cosine"),
+            Distance::Dot => write!(f, "dot"),
+            Distance::L2 => write!(f, "l2"),
+        }
+    }
+}
@@ -1,8 +1,13 @@
+use std::io::{self, Write};
+
 use arrow::datatypes::Int32Type;
 use arrow_array::{
     types::Float64Type, Array, LargeListArray, LargeStringArray, PrimitiveArray, RecordBatch,
 };
-use pyo3::prelude::*;
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+use serde::Serialize;
+
+use super::vector::{extract_vector, Vector};
 
 #[pyclass]
 #[derive(Debug, Clone)]
@@ -20,11 +25,19 @@ pub struct Document {
     pub keyword_filter: String,
 
     // Only set when upserting. We don't fetch raw vectors during queries.
-    #[pyo3(get, set)]
+    #[pyo3(get)]
     pub dense_embedding: Option<Vec<f32>>,
 
     #[pyo3(get, set)]
     pub tag: Option<String>,
+
+    // Namespace (partition) the document is written to, if the collection has them.
+    #[pyo3(get, set)]
+    pub namespace: Option<String>,
+
+    // Only set on query results, when the provider reports a score.
+    #[pyo3(get, set)]
+    pub score: Option<f32>,
 }
 
 impl Document {
@@ -40,31 +53,177 @@ impl Document {
                 .map(|v| v.len() * std::mem::size_of::<f32>())
                 .unwrap_or(0)
     }
+
+    /// Size of the document encoded as a JSON object, an estimate of its size on the wire
+    /// that includes the encoding overhead `approx_size` leaves out.
+    pub fn json_size(&self) -> usize {
+        // `"key":value` per field, with commas between them and braces around
+        let field = |key: &str, len: usize| key.len() + 3 + len + 1;
+
+        1 + field("id", json_len(&self.id))
+            + field("text", json_len(&self.text))
+            + field("int_filter", json_len(&self.int_filter))
+            + field("keyword_filter", json_len(&self.keyword_filter))
+            + self
+                .dense_embedding
+                .as_ref()
+                .map_or(0, |v| field("dense_embedding", json_len(v)))
+            + self
+                .tag
+                .as_ref()
+                .map_or(0, |tag| field("tag", json_len(tag)))
+            + self
+                .namespace
+                .as_ref()
+                .map_or(0, |namespace| field("namespace", json_len(namespace)))
+    }
+
+    /// Pad the `text` field with filler bytes until it is at least `size` bytes long.
+    pub fn pad_text(&mut self, size: usize) {
+        if self.text.len() < size {
+            let padding = size - self.text.len();
+            self.text.extend(std::iter::repeat_n('x', padding));
+        }
+    }
 }
 
 #[pymethods]
 impl Document {
     #[new]
-    #[pyo3(signature = (id, text, int_filter, keyword_filter, dense_embedding=None, tag=None))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (id, text, int_filter, keyword_filter, dense_embedding=None, tag=None, score=None, namespace=None))]
     fn new(
         id: String,
         text: String,
         int_filter: u32,
         keyword_filter: String,
-        dense_embedding: Option<Vec<f32>>,
+        dense_embedding: Option<&Bound<'_, PyAny>>,
         tag: Option<String>,
-    ) -> Self {
-        Self {
+        score: Option<f32>,
+        namespace: Option<String>,
+    ) -> PyResult<Self> {
+        Ok(Self {
             id,
             text,
             int_filter,
             keyword_filter,
-            dense_embedding,
+            dense_embedding: dense_embedding.map(extract_vector).transpose()?,
             tag,
+            score,
+            namespace,
+        })
+    }
+
+    /// Accepts a list of floats or a `float32`/`float64` buffer such as a numpy array.
+    #[setter]
+    fn set_dense_embedding(&mut self, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.dense_embedding = (!value.is_none())
+            .then(|| extract_vector(value))
+            .transpose()?;
+        Ok(())
+    }
+
+    /// The embedding as a read-only `float32` buffer, for `numpy.asarray()`.
+    #[getter]
+    fn dense_array(&self) -> Option<Vector> {
+        self.dense_embedding.clone().map(Vector::new)
+    }
+
+    /// The fields as a dict, with `dense_embedding` as a buffer instead of a list with `array`.
+    #[pyo3(signature = (array=false))]
+    fn to_dict<'py>(&self, py: Python<'py>, array: bool) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("id", &self.id)?;
+        dict.set_item("text", &self.text)?;
+        dict.set_item("int_filter", self.int_filter)?;
+        dict.set_item("keyword_filter", &self.keyword_filter)?;
+        if array {
+            dict.set_item("dense_embedding", self.dense_array())?;
+        } else {
+            dict.set_item("dense_embedding", &self.dense_embedding)?;
         }
+        dict.set_item("tag", &self.tag)?;
+        dict.set_item("score", self.score)?;
+        dict.set_item("namespace", &self.namespace)?;
+        Ok(dict)
+    }
+
+    /// Inverse of `to_dict`. Missing optional fields are `None`.
+    #[staticmethod]
+    fn from_dict(dict: &Bound<'_, PyDict>) -> PyResult<Self> {
+        let required = |key: &str| {
+            dict.get_item(key)?
+                .filter(|value| !value.is_none())
+                .ok_or_else(|| PyValueError::new_err(format!("Document is missing `{key}`")))
+        };
+        let optional =
+            |key: &str| -> PyResult<_> { Ok(dict.get_item(key)?.filter(|value| !value.is_none())) };
+
+        Ok(Self {
+            id: required("id")?.extract()?,
+            text: required("text")?.extract()?,
+            int_filter: required("int_filter")?.extract()?,
+            keyword_filter: required("keyword_filter")?.extract()?,
+            dense_embedding: optional("dense_embedding")?
+                .map(|value| extract_vector(&value))
+                .transpose()?,
+            tag: optional("tag")?.map(|value| value.extract()).transpose()?,
+            score: optional("score")?
+                .map(|value| value.extract())
+                .transpose()?,
+            namespace: optional("namespace")?
+                .map(|value| value.extract())
+                .transpose()?,
+        })
     }
 }
 
+/// Length of `value` encoded as JSON, without allocating the encoding.
+fn json_len<T: Serialize + ?Sized>(value: &T) -> usize {
+    struct Counter(usize);
+
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    serde_json::to_writer(&mut counter, value).expect("JSON encoding can't fail");
+    counter.0
+}
+
+/// Just the `dense` column of a batch of documents, e.g. read with a projection.
+pub fn parse_embeddings(batch: &RecordBatch) -> Vec<Vec<f32>> {
+    let list = batch
+        .column_by_name("dense")
+        .expect("dense column not found")
+        .as_any()
+        .downcast_ref::<LargeListArray>()
+        .expect("dense column is not LargeList<Float64>");
+
+    let mut out = Vec::with_capacity(list.len());
+    for i in 0..list.len() {
+        if list.is_null(i) {
+            out.push(Vec::new());
+            continue;
+        }
+        let sub = list.value(i); // each row’s vector
+        let floats = sub
+            .as_any()
+            .downcast_ref::<PrimitiveArray<Float64Type>>()
+            .expect("inner type not Float64Array");
+        let vec: Vec<f32> = floats.values().iter().map(|v| *v as f32).collect();
+        out.push(vec);
+    }
+    out
+}
+
 pub fn parse_from_batch(batch: RecordBatch) -> Vec<Document> {
     let id = batch
         .column_by_name("id")
@@ -80,30 +239,7 @@ pub fn parse_from_batch(batch: RecordBatch) -> Vec<Document> {
         .downcast_ref::<LargeStringArray>()
         .expect("text column is not a LargeStringArray");
 
-    let mut dense = {
-        let list = batch
-            .column_by_name("dense")
-            .expect("dense column not found")
-            .as_any()
-            .downcast_ref::<LargeListArray>()
-            .expect("dense column is not LargeList<Float64>");
-
-        let mut out = Vec::with_capacity(list.len());
-        for i in 0..list.len() {
-            if list.is_null(i) {
-                out.push(Vec::new());
-                continue;
-            }
-            let sub = list.value(i); // each row’s vector
-            let floats = sub
-                .as_any()
-                .downcast_ref::<PrimitiveArray<Float64Type>>()
-                .expect("inner type not Float64Array");
-            let vec: Vec<f32> = floats.values().iter().map(|v| *v as f32).collect();
-            out.push(vec);
-        }
-        out
-    };
+    let dense = parse_embeddings(&batch);
 
     let int_filter = batch
         .column_by_name("int_filter")
@@ -120,10 +256,9 @@ pub fn parse_from_batch(batch: RecordBatch) -> Vec<Document> {
         .expect("keyword_filter column is not a LargeStringArray");
 
     let mut rows = Vec::with_capacity(batch.num_rows());
-    for i in 0..batch.num_rows() {
+    for (i, dense_embedding) in dense.into_iter().enumerate() {
         let id = id.value(i).to_string();
         let text = text.value(i).to_string();
-        let dense_embedding = std::mem::take(&mut dense[i]);
         let int_filter = int_filter.value(i) as u32;
         let keyword_filter = keyword_filter.value(i).to_string();
 
@@ -134,6 +269,8 @@ pub fn parse_from_batch(batch: RecordBatch) -> Vec<Document> {
             int_filter,
             keyword_filter,
             tag: None,
+            score: None,
+            namespace: None,
         });
     }
 
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::Context;
+use arrow::array::AsArray;
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, UInt32Type};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+
+use crate::data::UNFILTERED;
+use crate::s3::open_file;
+
+/// Whether a document with `int_value` and the `keywords` tokens matches the filter pair, as
+/// providers filter: `int_value <= int_filter` and a token equal to `keyword_filter`. The
+/// parts of [`UNFILTERED`] match every document.
+pub(crate) fn matches(
+    int_value: u32,
+    keywords: &str,
+    int_filter: u32,
+    keyword_filter: &str,
+) -> bool {
+    let (unfiltered_int, unfiltered_keyword) = UNFILTERED;
+
+    (int_filter == unfiltered_int || int_value <= int_filter)
+        && (keyword_filter == unfiltered_keyword
+            || keywords
+                .split_whitespace()
+                .any(|token| token == keyword_filter))
+}
+
+/// Filter values of the documents of a docs file, to count the documents a filter pair
+/// matches.
+#[derive(Default)]
+pub struct FilterCounts {
+    /// Sorted `int_filter` values of the documents, by their `keyword_filter`
+    by_keywords: HashMap<String, Vec<u32>>,
+    total: usize,
+}
+
+impl FilterCounts {
+    /// Read the `int_filter` and `keyword_filter` columns of the docs file.
+    pub async fn load(docs: &str, cache_dir: &str) -> anyhow::Result<Self> {
+        let file = open_file(docs, cache_dir).await?;
+
+        tokio::task::spawn_blocking(move || {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+            let projection =
+                ProjectionMask::columns(builder.parquet_schema(), ["int_filter", "keyword_filter"]);
+            let reader = builder.with_projection(projection).build()?;
+
+            let mut counts = FilterCounts::default();
+            for batch in reader {
+                let batch = batch?;
+                let int_filter = batch
+                    .column_by_name("int_filter")
+                    .context("int_filter column not found")?;
+                let int_filter = cast(int_filter, &DataType::UInt32)?;
+                let keyword_filter = batch
+                    .column_by_name("keyword_filter")
+                    .context("keyword_filter column not found")?;
+                let keyword_filter = cast(keyword_filter, &DataType::Utf8)?;
+
+                let docs = int_filter
+                    .as_primitive::<UInt32Type>()
+                    .iter()
+                    .zip(keyword_filter.as_string::<i32>().iter());
+                for (int_value, keywords) in docs {
+                    counts.push(int_value.unwrap_or_default(), keywords.unwrap_or_default());
+                }
+            }
+            if counts.total == 0 {
+                anyhow::bail!("No documents in the docs file");
+            }
+
+            Ok(counts.sorted())
+        })
+        .await?
+    }
+
+    /// Add a document by its `int_filter` and `keyword_filter`, sorted by [`Self::sorted`].
+    fn push(&mut self, int_value: u32, keywords: &str) {
+        match self.by_keywords.get_mut(keywords) {
+            Some(values) => values.push(int_value),
+            None => {
+                self.by_keywords
+                    .insert(keywords.to_string(), vec![int_value]);
+            }
+        }
+        self.total += 1;
+    }
+
+    fn sorted(mut self) -> Self {
+        for values in self.by_keywords.values_mut() {
+            values.sort_unstable();
+        }
+
+        self
+    }
+
+    /// Share of the documents the filter pair matches.
+    pub fn share(&self, int_filter: u32, keyword_filter: &str) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        let matching: usize = self
+            .by_keywords
+            .iter()
+            .filter(|(keywords, _)| matches(0, keywords, UNFILTERED.0, keyword_filter))
+            .map(|(_, values)| match int_filter == UNFILTERED.0 {
+                true => values.len(),
+                false => values.partition_point(|v| *v <= int_filter),
+            })
+            .sum();
+
+        matching as f64 / self.total as f64
+    }
+}
+
+impl fmt::Debug for FilterCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterCounts")
+            .field("keywords", &self.by_keywords.len())
+            .field("total", &self.total)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_counts_the_documents_matching_both_filters() {
+        let mut counts = FilterCounts::default();
+        for i in (0..100).rev() {
+            counts.push(i, if i % 4 == 0 { "a b" } else { "b" });
+        }
+        let counts = counts.sorted();
+
+        let (unfiltered_int, unfiltered_keyword) = UNFILTERED;
+        assert_eq!(counts.share(unfiltered_int, unfiltered_keyword), 1.0);
+        assert_eq!(counts.share(9, unfiltered_keyword), 0.1);
+        assert_eq!(counts.share(unfiltered_int, "a"), 0.25);
+        assert_eq!(counts.share(unfiltered_int, "b"), 1.0);
+        // 0, 4 and 8
+        assert_eq!(counts.share(9, "a"), 0.03);
+        assert_eq!(counts.share(unfiltered_int, "c"), 0.0);
+    }
+}
@@ -0,0 +1,460 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::sync::Arc;
+
+use anyhow::Context;
+use arrow::array::{Float32Builder, Int64Builder, ListBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::data::doc::parse_from_batch;
+use crate::data::filters;
+use crate::data::query::RECALL_COLUMN_PREFIX;
+use crate::data::{standard_normal, Distance, Document};
+use crate::s3::open_file;
+use crate::telemetry::write_batch;
+
+/// How to generate a query set from a docs file.
+#[derive(Debug, Clone)]
+pub struct QuerySetSpec {
+    pub num_queries: usize,
+    /// Depth of the ground truth of each query
+    pub top_k: usize,
+    /// Ground truth is computed for every pair of these filters
+    pub int_filters: Vec<u32>,
+    pub keyword_filters: Vec<String>,
+    /// Standard deviation of the Gaussian noise added to each component of a sampled
+    /// vector, relative to the vector's root mean square
+    pub noise: f64,
+    pub metric: Distance,
+    pub seed: Option<u64>,
+}
+
+/// Sample the vectors of `spec.num_queries` documents of `docs`, perturb them with
+/// `spec.noise`, and write them to `output` with exact ground truth for each filter pair,
+/// in the columnar layout query files are read in. Returns the number of queries written.
+///
+/// The filter pair [`UNFILTERED`](super::UNFILTERED) is the one unfiltered runs look up, so
+/// the parts of it are computed without filtering on `int_filter` or `keyword_filter`
+/// respectively.
+pub async fn generate_queries(
+    docs: &str,
+    output: &str,
+    cache_dir: &str,
+    spec: QuerySetSpec,
+) -> anyhow::Result<usize> {
+    let file = open_file(docs, cache_dir).await?;
+
+    let batch = tokio::task::spawn_blocking(move || {
+        let mut rng = match spec.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+
+        let mut vectors = sample_vectors(file.try_clone()?, spec.num_queries, &mut rng)?;
+        if vectors.is_empty() {
+            anyhow::bail!("No documents with vectors in the docs file");
+        }
+        if spec.noise > 0.0 {
+            for vector in &mut vectors {
+                perturb(vector, spec.noise, &mut rng);
+            }
+        }
+
+        let filters: Vec<(u32, String)> = spec
+            .int_filters
+            .iter()
+            .flat_map(|int_filter| {
+                spec.keyword_filters
+                    .iter()
+                    .map(move |keyword_filter| (*int_filter, keyword_filter.clone()))
+            })
+            .collect();
+        let neighbors = exact_neighbors(file, &vectors, &filters, &spec)?;
+
+        to_batch(vectors, &filters, neighbors)
+    })
+    .await??;
+
+    let num_queries = batch.num_rows();
+    write_batch(batch, output).await?;
+
+    Ok(num_queries)
+}
+
+/// Uniform random sample of up to `size` document vectors, by reservoir sampling.
+fn sample_vectors(file: File, size: usize, rng: &mut StdRng) -> anyhow::Result<Vec<Vec<f32>>> {
+    let mut reservoir = Vec::with_capacity(size);
+    let mut seen = 0usize;
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+    for batch in reader {
+        for doc in parse_from_batch(batch?) {
+            let Some(vector) = doc.dense_embedding.filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            seen += 1;
+            if reservoir.len() < size {
+                reservoir.push(vector);
+            } else {
+                let i = rng.random_range(0..seen);
+                if i < size {
+                    reservoir[i] = vector;
+                }
+            }
+        }
+    }
+
+    Ok(reservoir)
+}
+
+fn perturb(vector: &mut [f32], noise: f64, rng: &mut StdRng) {
+    let rms = (vector.iter().map(|v| (v * v) as f64).sum::<f64>() / vector.len() as f64).sqrt();
+    let stddev = noise * rms;
+    for v in vector {
+        *v += (stddev * standard_normal(rng)) as f32;
+    }
+}
+
+/// Candidate neighbor, ordered by how close it is.
+#[derive(Debug, Clone, Copy)]
+struct Neighbor {
+    closeness: f32,
+    id: i64,
+}
+
+impl PartialEq for Neighbor {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Neighbor {}
+
+impl PartialOrd for Neighbor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.closeness.total_cmp(&other.closeness)
+    }
+}
+
+/// The `top_k` closest matches of one query, farthest on top.
+type Nearest = BinaryHeap<Reverse<Neighbor>>;
+
+/// Exact nearest neighbors of every vector among the documents matching each filter pair,
+/// indexed by vector then filter pair. Queries are split across threads, one batch of
+/// documents at a time.
+fn exact_neighbors(
+    file: File,
+    vectors: &[Vec<f32>],
+    filters: &[(u32, String)],
+    spec: &QuerySetSpec,
+) -> anyhow::Result<Vec<Vec<Nearest>>> {
+    let mut nearest: Vec<Vec<Nearest>> = vectors
+        .iter()
+        .map(|_| filters.iter().map(|_| Nearest::new()).collect())
+        .collect();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = vectors.len().div_ceil(threads);
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+    for batch in reader {
+        let docs = parse_from_batch(batch?)
+            .into_iter()
+            .filter(|doc| doc.dense_embedding.as_ref().is_some_and(|v| !v.is_empty()))
+            .map(|doc| {
+                let id = doc.id.parse::<i64>().with_context(|| {
+                    format!("Ground truth needs numeric document IDs, got {}", doc.id)
+                })?;
+                let matches: Vec<bool> = filters
+                    .iter()
+                    .map(|(int_filter, keyword_filter)| matches(&doc, *int_filter, keyword_filter))
+                    .collect();
+                Ok((id, doc.dense_embedding.unwrap_or_default(), matches))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        std::thread::scope(|scope| {
+            for (vectors, nearest) in vectors.chunks(chunk).zip(nearest.chunks_mut(chunk)) {
+                let docs = &docs;
+                scope.spawn(move || {
+                    for (vector, nearest) in vectors.iter().zip(nearest) {
+                        for (id, embedding, matches) in docs {
+                            let score = spec.metric.score(vector, embedding);
+                            let closeness = if spec.metric.higher_is_closer() {
+                                score
+                            } else {
+                                -score
+                            };
+                            let neighbor = Neighbor { closeness, id: *id };
+
+                            for (heap, _) in nearest.iter_mut().zip(matches).filter(|(_, m)| **m) {
+                                if heap.len() < spec.top_k {
+                                    heap.push(Reverse(neighbor));
+                                } else if heap.peek().is_some_and(|Reverse(far)| neighbor > *far) {
+                                    heap.pop();
+                                    heap.push(Reverse(neighbor));
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    Ok(nearest)
+}
+
+/// Whether `doc` matches the filter pair, see [`filters::matches`].
+fn matches(doc: &Document, int_filter: u32, keyword_filter: &str) -> bool {
+    filters::matches(
+        doc.int_filter,
+        &doc.keyword_filter,
+        int_filter,
+        keyword_filter,
+    )
+}
+
+fn to_batch(
+    vectors: Vec<Vec<f32>>,
+    filters: &[(u32, String)],
+    nearest: Vec<Vec<Nearest>>,
+) -> anyhow::Result<RecordBatch> {
+    let mut dense = ListBuilder::new(Float32Builder::new());
+    for vector in vectors {
+        dense.values().append_slice(&vector);
+        dense.append(true);
+    }
+
+    let mut recall: Vec<ListBuilder<Int64Builder>> = filters
+        .iter()
+        .map(|_| ListBuilder::new(Int64Builder::new()))
+        .collect();
+    for heaps in nearest {
+        for (column, heap) in recall.iter_mut().zip(heaps) {
+            // Closest first
+            let ids = heap.into_sorted_vec().into_iter().map(|Reverse(n)| n.id);
+            column.values().append_slice(&ids.collect::<Vec<_>>());
+            column.append(true);
+        }
+    }
+
+    let mut columns: Vec<(String, ArrayRef)> =
+        vec![("dense".to_string(), Arc::new(dense.finish()))];
+    for ((int_filter, keyword_filter), mut column) in filters.iter().zip(recall) {
+        columns.push((
+            format!("{RECALL_COLUMN_PREFIX}{int_filter}_{keyword_filter}"),
+            Arc::new(column.finish()),
+        ));
+    }
+
+    Ok(RecordBatch::try_from_iter(columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Float64Builder, LargeListBuilder};
+    use arrow_array::{Int32Array, LargeStringArray};
+
+    use super::*;
+    use crate::data::load_from_path;
+    use crate::data::query::sample_from_path;
+    use crate::data::{IdCodec, Query, UNFILTERED};
+    use crate::query::recall::calculate_recall;
+    use crate::query::QueryConfig;
+
+    const DIMENSION: usize = 8;
+
+    fn random_docs(count: usize, rng: &mut StdRng) -> Vec<Document> {
+        (0..count)
+            .map(|i| Document {
+                id: i.to_string(),
+                text: format!("doc {i}"),
+                int_filter: (i % 10) as u32,
+                keyword_filter: if i % 3 == 0 { "a b" } else { "b" }.to_string(),
+                dense_embedding: Some((0..DIMENSION).map(|_| rng.random::<f32>() - 0.5).collect()),
+                tag: None,
+                namespace: None,
+                score: None,
+            })
+            .collect()
+    }
+
+    /// Write `docs` in the layout of the dataset docs files.
+    async fn write_docs(docs: &[Document], path: &str) {
+        let mut dense = LargeListBuilder::new(Float64Builder::new());
+        for doc in docs {
+            let vector = doc.dense_embedding.as_ref().unwrap();
+            dense
+                .values()
+                .append_slice(&vector.iter().map(|v| *v as f64).collect::<Vec<_>>());
+            dense.append(true);
+        }
+
+        let batch = RecordBatch::try_from_iter([
+            (
+                "id",
+                Arc::new(LargeStringArray::from_iter_values(
+                    docs.iter().map(|d| &d.id),
+                )) as ArrayRef,
+            ),
+            (
+                "text",
+                Arc::new(LargeStringArray::from_iter_values(
+                    docs.iter().map(|d| &d.text),
+                )),
+            ),
+            ("dense", Arc::new(dense.finish())),
+            (
+                "int_filter",
+                Arc::new(Int32Array::from_iter_values(
+                    docs.iter().map(|d| d.int_filter as i32),
+                )),
+            ),
+            (
+                "keyword_filter",
+                Arc::new(LargeStringArray::from_iter_values(
+                    docs.iter().map(|d| &d.keyword_filter),
+                )),
+            ),
+        ])
+        .unwrap();
+        write_batch(batch, path).await.unwrap();
+    }
+
+    /// IDs of the `top_k` documents closest to `vector` matching the filter pair, closest first.
+    fn brute_force(
+        docs: &[Document],
+        vector: &[f32],
+        (int_filter, keyword_filter): (u32, &str),
+        top_k: usize,
+    ) -> Vec<i64> {
+        let mut scored: Vec<(f32, i64)> = docs
+            .iter()
+            .filter(|doc| matches(doc, int_filter, keyword_filter))
+            .map(|doc| {
+                let embedding = doc.dense_embedding.as_ref().unwrap();
+                (
+                    Distance::Cosine.score(vector, embedding),
+                    doc.id.parse().unwrap(),
+                )
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(top_k).map(|(_, id)| id).collect()
+    }
+
+    #[tokio::test]
+    async fn generated_queries_have_exact_ground_truth_per_filter_pair() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs_path = dir
+            .path()
+            .join("docs.parquet")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let queries_path = dir
+            .path()
+            .join("queries.parquet")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let cache_dir = dir.path().to_str().unwrap();
+
+        let docs = random_docs(300, &mut StdRng::seed_from_u64(1));
+        write_docs(&docs, &docs_path).await;
+
+        let (unfiltered_int, unfiltered_keyword) = UNFILTERED;
+        let spec = QuerySetSpec {
+            num_queries: 20,
+            top_k: 5,
+            int_filters: vec![unfiltered_int, 4],
+            keyword_filters: vec![unfiltered_keyword.to_string(), "a".to_string()],
+            noise: 0.0,
+            metric: Distance::Cosine,
+            seed: Some(42),
+        };
+        let written = generate_queries(&docs_path, &queries_path, cache_dir, spec)
+            .await
+            .unwrap();
+        assert_eq!(written, 20);
+
+        let queries = load_from_path(&queries_path, cache_dir).await.unwrap();
+        assert_eq!(queries.len(), 20);
+        let config = QueryConfig::for_test(5);
+        let mut found_doc_zero = false;
+        for query in queries {
+            // Without noise, each query is a document's own vector
+            let closest = brute_force(&docs, &query.dense, UNFILTERED, 1)[0];
+            assert_eq!(
+                docs[closest as usize].dense_embedding.as_ref(),
+                Some(&query.dense)
+            );
+
+            for int_filter in [unfiltered_int, 4] {
+                for keyword_filter in [unfiltered_keyword, "a"] {
+                    let expected =
+                        brute_force(&docs, &query.dense, (int_filter, keyword_filter), 5);
+                    assert_eq!(query.recall[&int_filter][keyword_filter], expected);
+
+                    // Leaving out any one neighbor, doc ID 0 included, costs its share of recall
+                    let filtered = Query {
+                        int_filter: Some(int_filter),
+                        keyword_filter: Some(keyword_filter.to_string()),
+                        ..query.clone()
+                    };
+                    for missing in &expected {
+                        let results: Vec<Document> = expected
+                            .iter()
+                            .filter(|id| *id != missing)
+                            .map(|id| docs[*id as usize].clone())
+                            .collect();
+                        let recall =
+                            calculate_recall(&results, &filtered, &config, &IdCodec::default());
+                        assert_eq!(recall.unwrap(), 0.8, "without doc {missing}");
+                    }
+                    found_doc_zero |= expected.contains(&0);
+                }
+            }
+        }
+
+        // Doc ID 0 is a neighbor like any other
+        assert!(found_doc_zero);
+
+        // The load phase's sample is read without the ground truth
+        let sampled = sample_from_path(&queries_path, cache_dir, 100)
+            .await
+            .unwrap();
+        assert_eq!(sampled.len(), 20);
+        assert!(sampled.iter().all(|query| query.recall.is_empty()));
+    }
+
+    #[test]
+    fn perturb_adds_noise_relative_to_the_vector_rms() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let original: Vec<f32> = (0..10_000)
+            .map(|i| if i % 2 == 0 { 2.0 } else { -2.0 })
+            .collect();
+        let mut vector = original.clone();
+        perturb(&mut vector, 0.1, &mut rng);
+
+        // The vector's rms is 2, so the noise has a standard deviation of 0.2
+        let noise_rms = (vector
+            .iter()
+            .zip(&original)
+            .map(|(v, o)| ((v - o) as f64).powi(2))
+            .sum::<f64>()
+            / vector.len() as f64)
+            .sqrt();
+        assert!((noise_rms - 0.2).abs() < 0.01, "noise rms {noise_rms}");
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::stable_hash;
+
+/// How dataset document IDs are presented to providers.
+///
+/// Dataset IDs are numeric. Every scheme encodes them reversibly so that
+/// freshness probes and recall can map provider IDs back to the dataset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdScheme {
+    /// Numeric IDs as stored in the dataset, e.g. `42`.
+    #[default]
+    Numeric,
+    /// UUIDs derived from the numeric ID, e.g. `00000000-0000-0000-0000-00000000002a`.
+    Uuid,
+    /// Numeric IDs with a string prefix, e.g. `doc-42`.
+    Prefixed,
+}
+
+const PREFIX: &str = "doc-";
+
+impl IdScheme {
+    pub fn encode(&self, id: u64) -> String {
+        match self {
+            IdScheme::Numeric => id.to_string(),
+            IdScheme::Uuid => uuid::Uuid::from_u128(id as u128).to_string(),
+            IdScheme::Prefixed => format!("{PREFIX}{id}"),
+        }
+    }
+
+    pub fn decode(&self, id: &str) -> Option<u64> {
+        match self {
+            IdScheme::Numeric => id.parse().ok(),
+            IdScheme::Uuid => uuid::Uuid::parse_str(id)
+                .ok()
+                .and_then(|uuid| u64::try_from(uuid.as_u128()).ok()),
+            IdScheme::Prefixed => id.strip_prefix(PREFIX)?.parse().ok(),
+        }
+    }
+}
+
+/// Maps dataset IDs to provider IDs using an [`IdScheme`] and an optional prefix.
+#[derive(Debug, Clone, Default)]
+pub struct IdCodec {
+    pub scheme: IdScheme,
+    /// Prefix prepended to every encoded ID, used to namespace IDs per run.
+    pub prefix: Option<String>,
+}
+
+impl IdCodec {
+    pub fn encode(&self, id: u64) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{prefix}{}", self.scheme.encode(id)),
+            None => self.scheme.encode(id),
+        }
+    }
+
+    /// Whether encoded IDs are arbitrary strings, rather than numbers or UUIDs.
+    pub fn is_string(&self) -> bool {
+        self.scheme == IdScheme::Prefixed || self.prefix.is_some()
+    }
+
+    /// Decode a provider ID. IDs without the prefix (e.g. written by another
+    /// run) are decoded with the scheme alone.
+    pub fn decode(&self, id: &str) -> Option<u64> {
+        let id = self
+            .prefix
+            .as_deref()
+            .and_then(|prefix| id.strip_prefix(prefix))
+            .unwrap_or(id);
+
+        self.scheme.decode(id)
+    }
+}
+
+/// Prefix used to namespace document IDs written by a run: 8 hex digits of a hash of the
+/// whole run ID, so custom run IDs of any characters work, and run IDs starting alike
+/// don't share a namespace.
+pub fn run_id_prefix(run_id: &str) -> String {
+    format!("{:08x}-", stable_hash(run_id.as_bytes()) >> 32)
+}
+
+impl FromStr for IdScheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "numeric" => Ok(IdScheme::Numeric),
+            "uuid" => Ok(IdScheme::Uuid),
+            "prefixed" => Ok(IdScheme::Prefixed),
+            _ => anyhow::bail!("Invalid id_scheme: {s}"),
+        }
+    }
+}
+
+impl fmt::Display for IdScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdScheme::Numeric => write!(f, "numeric"),
+            IdScheme::Uuid => write!(f, "uuid"),
+            IdScheme::Prefixed => write!(f, "prefixed"),
+        }
+    }
+}
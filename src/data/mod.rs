@@ -1,7 +1,41 @@
+mod dimension;
+pub use dimension::Dimension;
+
+mod distance;
+pub use distance::Distance;
+
 mod doc;
-pub use doc::parse_from_batch;
 pub use doc::Document;
+pub use doc::{parse_embeddings, parse_from_batch};
+
+mod filters;
+pub use filters::FilterCounts;
+
+mod generate;
+pub use generate::{generate_queries, QuerySetSpec};
+
+mod id;
+pub use id::{run_id_prefix, IdCodec, IdScheme};
+
+mod namespace;
+pub(crate) use namespace::stable_hash;
+pub use namespace::{NamespaceDistribution, Namespaces};
+
+mod normal;
+pub(crate) use normal::standard_normal;
 
 mod query;
-pub use query::load_from_path;
-pub use query::Query;
+pub use query::{load_from_path, load_ground_truth};
+pub use query::{Query, QuerySource, UNFILTERED};
+
+mod registry;
+pub use registry::{dataset, datasets, register_datasets, Dataset};
+
+mod transform;
+pub use transform::{DocumentTransform, PyDocumentTransform};
+
+mod vector;
+pub use vector::Vector;
+
+mod verify;
+pub use verify::verify_dataset;
@@ -0,0 +1,100 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use rand::prelude::*;
+
+/// How documents and queries are spread over namespaces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NamespaceDistribution {
+    /// Every namespace equally likely.
+    #[default]
+    Uniform,
+    /// The `i`-th namespace weighted `1 / (i + 1)`, a few large and busy tenants and a
+    /// long tail of small ones.
+    Zipfian,
+}
+
+impl FromStr for NamespaceDistribution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(NamespaceDistribution::Uniform),
+            "zipfian" => Ok(NamespaceDistribution::Zipfian),
+            _ => anyhow::bail!("Invalid namespace distribution: {s}"),
+        }
+    }
+}
+
+impl fmt::Display for NamespaceDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamespaceDistribution::Uniform => write!(f, "uniform"),
+            NamespaceDistribution::Zipfian => write!(f, "zipfian"),
+        }
+    }
+}
+
+/// Namespaces (partitions) within a collection, named `ns-0`, `ns-1`, ...
+///
+/// Documents are assigned a namespace by a hash of their ID, so a document is always
+/// written to and looked up in the same one, and queries sample one from the same
+/// distribution.
+#[derive(Debug, Clone)]
+pub struct Namespaces {
+    /// Cumulative weight of each namespace, the last one 1
+    cumulative: Arc<Vec<f64>>,
+}
+
+impl Namespaces {
+    pub fn new(count: usize, distribution: NamespaceDistribution) -> Self {
+        let weights = (0..count).map(|i| match distribution {
+            NamespaceDistribution::Uniform => 1.0,
+            NamespaceDistribution::Zipfian => 1.0 / (i + 1) as f64,
+        });
+        let total: f64 = weights.clone().sum();
+        let cumulative = weights
+            .scan(0.0, |sum, weight| {
+                *sum += weight / total;
+                Some(*sum)
+            })
+            .collect();
+
+        Self {
+            cumulative: Arc::new(cumulative),
+        }
+    }
+
+    /// Namespace at `position` in `0..1` of the distribution.
+    fn at(&self, position: f64) -> String {
+        let i = self
+            .cumulative
+            .partition_point(|sum| *sum <= position)
+            .min(self.cumulative.len() - 1);
+        format!("ns-{i}")
+    }
+
+    /// Namespace the document with `id` belongs to.
+    pub fn of(&self, id: &str) -> String {
+        self.at(stable_hash(id.as_bytes()) as f64 / (u64::MAX as f64 + 1.0))
+    }
+
+    /// Random namespace for a query.
+    pub fn sample(&self) -> String {
+        self.at(rand::rng().random())
+    }
+}
+
+/// FNV-1a, stable across builds unlike the standard library's hasher, so separate ingest
+/// and query runs agree on where each document lives. Finished with the SplitMix64 mixer,
+/// so that sequential IDs spread over the whole range.
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    let hash = bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+
+    let hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
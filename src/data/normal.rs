@@ -0,0 +1,32 @@
+use rand::Rng;
+
+/// Sample of the standard normal distribution, by the Box-Muller transform of two uniform
+/// samples.
+pub(crate) fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let (u1, u2) = (1.0 - rng.random::<f64>(), rng.random::<f64>());
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn standard_normal_has_zero_mean_and_unit_variance() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let samples: Vec<f64> = (0..100_000).map(|_| standard_normal(&mut rng)).collect();
+
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|z| (z - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.01, "mean {mean}");
+        assert!((variance - 1.0).abs() < 0.02, "variance {variance}");
+
+        // About 95% of samples within 1.96 standard deviations
+        let within = samples.iter().filter(|z| z.abs() <= 1.96).count() as f64;
+        assert!((within / samples.len() as f64 - 0.95).abs() < 0.005);
+    }
+}
@@ -1,52 +1,497 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::sync::Arc;
 
+use anyhow::Context;
+use arrow::array::AsArray;
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Float32Type, Int64Type, UInt32Type, UInt64Type};
 use arrow::json::LineDelimitedWriter;
-use arrow_array::RecordBatch;
+use arrow_array::{Array, ArrayRef, RecordBatch};
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+use rand::seq::IndexedRandom;
+use rand::Rng;
 
 use crate::s3::open_file;
 
+/// Ground truth doc IDs by filter pair.
+pub type Recall =
+    HashMap</*int_filter*/ u32, HashMap</*keyword_filter*/ String, /*doc IDs*/ Vec<i64>>>;
+
 #[allow(dead_code)]
+#[pyclass]
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct Query {
+    #[pyo3(get, set, name = "vector")]
     pub dense: Vec<f32>,
-    pub recall:
-        HashMap</*int_filter*/ u32, HashMap</*keyword_filter*/ String, /*doc IDs*/ Vec<i64>>>,
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub recall: Recall,
+    /// Ground truth doc IDs for whichever filters the query runs with, closest first.
+    /// Takes precedence over `recall`. Only set on queries passed from Python.
+    #[pyo3(get, set)]
+    #[serde(skip)]
+    pub ground_truth: Option<Vec<i64>>,
+    /// Per-query overrides of the configured filters and `top_k`, e.g. from replayed
+    /// production logs. Read from optional `int_filter`, `keyword_filter` and `top_k` columns.
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub int_filter: Option<u32>,
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub keyword_filter: Option<String>,
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub top_k: Option<u32>,
+    /// Namespace to query instead of one sampled from the configured distribution. Read
+    /// from an optional `namespace` column.
+    #[pyo3(get, set)]
+    #[serde(default)]
+    pub namespace: Option<String>,
+}
+
+impl Query {
+    /// Query without ground truth or overrides.
+    pub fn new(dense: Vec<f32>) -> Self {
+        Self {
+            dense,
+            recall: Recall::new(),
+            ground_truth: None,
+            int_filter: None,
+            keyword_filter: None,
+            top_k: None,
+            namespace: None,
+        }
+    }
+
+    /// Build a query from a `Query` object, or a dict with a `vector` and optional
+    /// `int_filter`, `keyword_filter`, `top_k` and `namespace`.
+    pub fn from_py(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(query) = obj.extract::<Query>() {
+            return Ok(query);
+        }
+        let dict = obj.downcast::<PyDict>()?;
+        let item = |key: &str| -> PyResult<Option<Bound<'_, PyAny>>> {
+            Ok(dict.get_item(key)?.filter(|v| !v.is_none()))
+        };
+
+        let dense = item("vector")?
+            .ok_or_else(|| PyValueError::new_err("Query is missing `vector`"))?
+            .extract()?;
+
+        Ok(Self {
+            int_filter: item("int_filter")?.map(|v| v.extract()).transpose()?,
+            keyword_filter: item("keyword_filter")?.map(|v| v.extract()).transpose()?,
+            top_k: item("top_k")?.map(|v| v.extract()).transpose()?,
+            namespace: item("namespace")?.map(|v| v.extract()).transpose()?,
+            ..Self::new(dense)
+        })
+    }
+}
+
+#[pymethods]
+impl Query {
+    #[new]
+    #[pyo3(signature = (vector, ground_truth=None, recall=None, int_filter=None, keyword_filter=None, top_k=None, namespace=None))]
+    fn py_new(
+        vector: Vec<f32>,
+        ground_truth: Option<Vec<i64>>,
+        recall: Option<Recall>,
+        int_filter: Option<u32>,
+        keyword_filter: Option<String>,
+        top_k: Option<u32>,
+        namespace: Option<String>,
+    ) -> Self {
+        Self {
+            recall: recall.unwrap_or_default(),
+            ground_truth,
+            int_filter,
+            keyword_filter,
+            top_k,
+            namespace,
+            ..Self::new(vector)
+        }
+    }
+}
+
+/// Where a run's queries come from.
+#[derive(Debug, Clone)]
+pub enum QuerySource {
+    /// Query file (local, `s3://` or `https://`)
+    Path(String),
+    /// Queries passed from Python
+    List(Arc<Vec<Query>>),
+}
+
+impl QuerySource {
+    /// A path, or a list of `Query` objects.
+    pub fn extract(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(path) = obj.extract::<String>() {
+            return Ok(QuerySource::Path(path));
+        }
+
+        let queries: Vec<Query> = obj.extract().map_err(|_| {
+            PyValueError::new_err("queries must be a path or a list of Query objects")
+        })?;
+        if queries.is_empty() {
+            return Err(PyValueError::new_err("queries must not be empty"));
+        }
+
+        Ok(QuerySource::List(Arc::new(queries)))
+    }
+
+    /// Path of the query file, `None` for queries passed from Python.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            QuerySource::Path(path) => Some(path),
+            QuerySource::List(_) => None,
+        }
+    }
+
+    /// The path, or the number of queries passed from Python.
+    pub fn label(&self) -> String {
+        match self {
+            QuerySource::Path(path) => path.clone(),
+            QuerySource::List(queries) => format!("<{} queries>", queries.len()),
+        }
+    }
+
+    /// A uniform random sample of up to `size` queries. Queries read from a file are read
+    /// without their ground truth, which only the recall pass needs.
+    pub async fn sample(&self, cache_dir: &str, size: usize) -> anyhow::Result<Vec<Query>> {
+        match self {
+            QuerySource::Path(path) => sample_from_path(path, cache_dir, size).await,
+            QuerySource::List(queries) => Ok(queries
+                .choose_multiple(&mut rand::rng(), size)
+                .cloned()
+                .collect()),
+        }
+    }
+
+    pub async fn count(&self, cache_dir: &str) -> anyhow::Result<usize> {
+        match self {
+            QuerySource::Path(path) => count_queries(path, cache_dir).await,
+            QuerySource::List(queries) => Ok(queries.len()),
+        }
+    }
+
+    /// Stream queries to `f` in order, see [`stream_from_path`].
+    pub async fn stream(
+        &self,
+        cache_dir: &str,
+        mut f: impl FnMut(Query) -> anyhow::Result<()> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        match self {
+            QuerySource::Path(path) => stream_from_path(path, cache_dir, f).await,
+            QuerySource::List(queries) => {
+                let queries = queries.clone();
+                // `f` may block on a full channel
+                tokio::task::spawn_blocking(move || queries.iter().cloned().try_for_each(&mut f))
+                    .await?
+            }
+        }
+    }
 }
 
+/// Number of queries decoded at a time when reading query files.
+const QUERY_BATCH_SIZE: usize = 1024;
+
 pub async fn load_from_path(path: &str, cache_dir: &str) -> anyhow::Result<Vec<Query>> {
     let file = open_file(path, cache_dir).await?;
 
     // Load queries in a blocking task to avoid blocking the async runtime
-    let batches = tokio::task::spawn_blocking(move || {
-        ParquetRecordBatchReaderBuilder::try_new(file)?
-            .build()?
-            .collect::<Result<Vec<RecordBatch>, _>>()
+    tokio::task::spawn_blocking(move || {
+        let mut queries = Vec::new();
+        read_queries(file, true, |query| {
+            queries.push(query);
+            Ok(())
+        })?;
+
+        Ok(queries)
+    })
+    .await?
+}
+
+/// Stream queries to `f` one record batch at a time, without materializing the whole file.
+/// Stops at the first error returned by `f`.
+pub async fn stream_from_path(
+    path: &str,
+    cache_dir: &str,
+    f: impl FnMut(Query) -> anyhow::Result<()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let file = open_file(path, cache_dir).await?;
+
+    tokio::task::spawn_blocking(move || read_queries(file, true, f)).await?
+}
+
+/// Uniform random sample of up to `size` queries without their ground truth, using
+/// reservoir sampling so at most `size` queries are held in memory.
+pub async fn sample_from_path(
+    path: &str,
+    cache_dir: &str,
+    size: usize,
+) -> anyhow::Result<Vec<Query>> {
+    let file = open_file(path, cache_dir).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let mut rng = rand::rng();
+        let mut reservoir = Vec::with_capacity(size);
+        let mut seen = 0usize;
+
+        read_queries(file, false, |query| {
+            seen += 1;
+            if reservoir.len() < size {
+                reservoir.push(query);
+            } else {
+                let i = rng.random_range(0..seen);
+                if i < size {
+                    reservoir[i] = query;
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(reservoir)
     })
-    .await??;
+    .await?
+}
+
+/// Load ground truth stored separately from the query vectors, keyed by the index of the
+/// query in its query file. Rows have a `query` index column and either a nested `recall`
+/// column or `recall_{int_filter}_{keyword_filter}` columns.
+pub async fn load_ground_truth(
+    path: &str,
+    cache_dir: &str,
+) -> anyhow::Result<HashMap<usize, Recall>> {
+    let file = open_file(path, cache_dir).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)?
+            .with_batch_size(QUERY_BATCH_SIZE)
+            .build()?;
+
+        let mut ground_truth = HashMap::new();
+        for batch in reader {
+            let batch = batch?;
 
-    Ok(batches
-        .iter()
-        .map(|batch| -> anyhow::Result<Vec<Query>> {
-            let batch = batch_to_buffer(batch)?;
+            if batch.column_by_name("recall").is_some() {
+                #[derive(serde::Deserialize)]
+                struct Row {
+                    query: usize,
+                    recall: Recall,
+                }
 
-            // Deserialize each JSON line to PqQuery
-            let mut vectors = Vec::new();
+                for line in batch_to_buffer(&batch)?.lines() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
 
-            for line in batch.lines() {
-                if line.trim().is_empty() {
-                    continue;
+                    let row: Row = serde_json::from_str(line)?;
+                    ground_truth.insert(row.query, row.recall);
                 }
+            } else {
+                let index = column(&batch, "query", &DataType::UInt64)?
+                    .context("query column not found")?;
+                let index = index.as_primitive::<UInt64Type>();
+                let recall_columns = RecallColumns::new(&batch)?;
 
-                vectors.push(serde_json::from_str(line)?);
+                for row in (0..batch.num_rows()).filter(|&row| index.is_valid(row)) {
+                    ground_truth.insert(index.value(row) as usize, recall_columns.at(row));
+                }
             }
+        }
 
-            Ok(vectors)
-        })
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
-        .flatten()
-        .collect())
+        Ok(ground_truth)
+    })
+    .await?
+}
+
+/// Number of queries in a query file, from the parquet metadata.
+pub async fn count_queries(path: &str, cache_dir: &str) -> anyhow::Result<usize> {
+    let file = open_file(path, cache_dir).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+        Ok(builder.metadata().file_metadata().num_rows() as usize)
+    })
+    .await?
+}
+
+/// Read the queries of `file` into `f`, with their ground truth if `ground_truth` is set.
+pub(super) fn read_queries(
+    file: File,
+    ground_truth: bool,
+    mut f: impl FnMut(Query) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    if !ground_truth {
+        // The ground truth columns can make up most of the file
+        let schema = builder.parquet_schema();
+        let roots = schema
+            .root_schema()
+            .get_fields()
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| {
+                field.name() != "recall" && !field.name().starts_with(RECALL_COLUMN_PREFIX)
+            })
+            .map(|(i, _)| i);
+        let projection = ProjectionMask::roots(schema, roots);
+        builder = builder.with_projection(projection);
+    }
+    let reader = builder.with_batch_size(QUERY_BATCH_SIZE).build()?;
+
+    for batch in reader {
+        for query in parse_batch(&batch?)? {
+            f(query)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_batch(batch: &RecordBatch) -> anyhow::Result<Vec<Query>> {
+    // Nested `recall` ground truth goes through JSON, everything else is parsed natively
+    if batch.column_by_name("recall").is_none() {
+        return parse_columnar(batch);
+    }
+
+    let batch = batch_to_buffer(batch)?;
+
+    // Deserialize each JSON line to PqQuery
+    let mut queries = Vec::new();
+
+    for line in batch.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        queries.push(serde_json::from_str(line)?);
+    }
+
+    Ok(queries)
+}
+
+/// Prefix of columnar ground-truth columns, named `recall_{int_filter}_{keyword_filter}`.
+pub(super) const RECALL_COLUMN_PREFIX: &str = "recall_";
+
+/// Filter pair the ground truth of unfiltered queries is stored under. Both filters match
+/// every document of the public datasets.
+pub const UNFILTERED: (u32, &str) = (10000, "10000");
+
+/// Parse queries from a columnar layout: `dense`, optional `recall_{int_filter}_{keyword_filter}`
+/// doc ID list columns, and optional `int_filter`, `keyword_filter`, `top_k` and `namespace`
+/// columns.
+fn parse_columnar(batch: &RecordBatch) -> anyhow::Result<Vec<Query>> {
+    let dense =
+        column(batch, "dense", &list_of(DataType::Float32))?.context("dense column not found")?;
+    let dense = dense.as_list::<i32>();
+
+    let recall_columns = RecallColumns::new(batch)?;
+
+    let int_filter = column(batch, "int_filter", &DataType::UInt32)?;
+    let keyword_filter = column(batch, "keyword_filter", &DataType::Utf8)?;
+    let top_k = column(batch, "top_k", &DataType::UInt32)?;
+    let namespace = column(batch, "namespace", &DataType::Utf8)?;
+
+    let mut queries = Vec::with_capacity(batch.num_rows());
+    for row in 0..batch.num_rows() {
+        let vector = if dense.is_null(row) {
+            Vec::new()
+        } else {
+            dense
+                .value(row)
+                .as_primitive::<Float32Type>()
+                .values()
+                .to_vec()
+        };
+
+        queries.push(Query {
+            recall: recall_columns.at(row),
+            int_filter: int_filter
+                .as_ref()
+                .map(|c| c.as_primitive::<UInt32Type>())
+                .filter(|c| c.is_valid(row))
+                .map(|c| c.value(row)),
+            keyword_filter: keyword_filter
+                .as_ref()
+                .map(|c| c.as_string::<i32>())
+                .filter(|c| c.is_valid(row))
+                .map(|c| c.value(row).to_string()),
+            top_k: top_k
+                .as_ref()
+                .map(|c| c.as_primitive::<UInt32Type>())
+                .filter(|c| c.is_valid(row))
+                .map(|c| c.value(row)),
+            namespace: namespace
+                .as_ref()
+                .map(|c| c.as_string::<i32>())
+                .filter(|c| c.is_valid(row))
+                .map(|c| c.value(row).to_string()),
+            ..Query::new(vector)
+        });
+    }
+
+    Ok(queries)
+}
+
+/// Columnar ground truth, one `recall_{int_filter}_{keyword_filter}` list column per filter pair.
+struct RecallColumns(Vec<(u32, String, ArrayRef)>);
+
+impl RecallColumns {
+    fn new(batch: &RecordBatch) -> anyhow::Result<Self> {
+        let mut columns = Vec::new();
+        for field in batch.schema().fields() {
+            let Some(key) = field.name().strip_prefix(RECALL_COLUMN_PREFIX) else {
+                continue;
+            };
+            let (int_filter, keyword_filter) = key
+                .split_once('_')
+                .and_then(|(i, k)| Some((i.parse::<u32>().ok()?, k.to_string())))
+                .with_context(|| format!("Invalid ground truth column: {}", field.name()))?;
+            let ids = column(batch, field.name(), &list_of(DataType::Int64))?
+                .expect("column exists in schema");
+
+            columns.push((int_filter, keyword_filter, ids));
+        }
+
+        Ok(Self(columns))
+    }
+
+    /// Ground truth of `row`, skipping null entries.
+    fn at(&self, row: usize) -> Recall {
+        let mut recall = Recall::new();
+        for (int_filter, keyword_filter, ids) in &self.0 {
+            let ids = ids.as_list::<i32>();
+            if ids.is_null(row) {
+                continue;
+            }
+            recall.entry(*int_filter).or_default().insert(
+                keyword_filter.clone(),
+                ids.value(row).as_primitive::<Int64Type>().values().to_vec(),
+            );
+        }
+
+        recall
+    }
+}
+
+fn list_of(item: DataType) -> DataType {
+    DataType::List(Arc::new(Field::new_list_field(item, true)))
+}
+
+/// Column `name` cast to `data_type`, if present.
+fn column(
+    batch: &RecordBatch,
+    name: &str,
+    data_type: &DataType,
+) -> anyhow::Result<Option<ArrayRef>> {
+    batch
+        .column_by_name(name)
+        .map(|c| cast(c, data_type).with_context(|| format!("Invalid {name} column")))
+        .transpose()
 }
 
 fn batch_to_buffer(batch: &RecordBatch) -> anyhow::Result<String> {
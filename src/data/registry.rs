@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use anyhow::Context;
+use once_cell::sync::Lazy;
+
+/// A named dataset: its documents, its queries with ground truth, and what the ground
+/// truth covers.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Dataset {
+    #[serde(skip)]
+    pub name: String,
+    /// Documents, ingested and written in `read_write` mode
+    pub docs: String,
+    /// Queries with their ground truth
+    pub queries: String,
+    /// Dimension of the dense embeddings
+    pub dimension: usize,
+    pub num_docs: u64,
+    /// Deepest `top_k` the ground truth has neighbors for
+    #[serde(default = "default_ground_truth_top_k")]
+    pub ground_truth_top_k: u32,
+}
+
+fn default_ground_truth_top_k() -> u32 {
+    100
+}
+
+/// Datasets by name, starting with the public `topk-bench` ones.
+static REGISTRY: Lazy<RwLock<BTreeMap<String, Dataset>>> = Lazy::new(|| {
+    let builtin = [("100k", 100_000), ("1m", 1_000_000), ("10m", 10_000_000)];
+    let datasets = builtin
+        .into_iter()
+        .map(|(name, num_docs)| {
+            let dataset = Dataset {
+                name: name.to_string(),
+                docs: format!("s3://topk-bench/docs-{name}.parquet"),
+                queries: format!("s3://topk-bench/queries-{name}.parquet"),
+                dimension: 768,
+                num_docs,
+                ground_truth_top_k: 100,
+            };
+            (name.to_string(), dataset)
+        })
+        .collect();
+
+    RwLock::new(datasets)
+});
+
+/// Registered dataset called `name`.
+pub fn dataset(name: &str) -> anyhow::Result<Dataset> {
+    let registry = REGISTRY.read().unwrap();
+    registry.get(name).cloned().with_context(|| {
+        format!(
+            "Unknown dataset: {name}, registered: {}",
+            registry.keys().cloned().collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+/// All registered datasets, by name.
+pub fn datasets() -> Vec<Dataset> {
+    REGISTRY.read().unwrap().values().cloned().collect()
+}
+
+#[derive(serde::Deserialize)]
+struct RegistryFile {
+    datasets: BTreeMap<String, Dataset>,
+}
+
+/// Register the datasets of the TOML file at `path`, replacing registered ones of the same
+/// name. Returns their names.
+///
+/// Each dataset is a `[datasets.<name>]` table with `docs`, `queries`, `dimension`,
+/// `num_docs` and optionally `ground_truth_top_k`.
+pub fn register_datasets(path: &str) -> anyhow::Result<Vec<String>> {
+    let file: RegistryFile = config::Config::builder()
+        .add_source(config::File::new(path, config::FileFormat::Toml))
+        .build()
+        .and_then(|config| config.try_deserialize())
+        .with_context(|| format!("Invalid dataset registry: {path}"))?;
+
+    for (name, dataset) in &file.datasets {
+        if dataset.dimension == 0 || dataset.num_docs == 0 {
+            anyhow::bail!("Dataset {name} must have a positive dimension and num_docs");
+        }
+    }
+
+    let mut registry = REGISTRY.write().unwrap();
+    let mut names = Vec::with_capacity(file.datasets.len());
+    for (name, dataset) in file.datasets {
+        registry.insert(
+            name.clone(),
+            Dataset {
+                name: name.clone(),
+                ..dataset
+            },
+        );
+        names.push(name);
+    }
+
+    Ok(names)
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use pyo3::prelude::*;
+
+use crate::data::{Dimension, Document, IdCodec, IdScheme, Namespaces};
+
+/// Transformations applied to documents read from the dataset before they are written.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentTransform {
+    /// Pad `text` to this many bytes.
+    pub payload_size: Option<usize>,
+    /// ID encoding used when writing documents.
+    pub ids: IdCodec,
+    /// Dimension check applied to dense embeddings.
+    pub dimension: Dimension,
+    /// Namespaces documents without one are assigned to, by their written ID.
+    pub namespaces: Option<Namespaces>,
+}
+
+impl DocumentTransform {
+    pub fn apply(&mut self, doc: &mut Document) -> anyhow::Result<()> {
+        if let Some(vector) = doc.dense_embedding.as_mut() {
+            self.dimension
+                .check(vector)
+                .with_context(|| format!("Invalid embedding for document {}", doc.id))?;
+        }
+
+        if self.ids.scheme != IdScheme::Numeric || self.ids.prefix.is_some() {
+            let id = doc
+                .id
+                .parse::<u64>()
+                .with_context(|| format!("Dataset ID is not numeric: {}", doc.id))?;
+            doc.id = self.ids.encode(id);
+        }
+
+        if let Some(namespaces) = &self.namespaces {
+            if doc.namespace.is_none() {
+                doc.namespace = Some(namespaces.of(&doc.id));
+            }
+        }
+
+        if let Some(size) = self.payload_size {
+            doc.pad_text(size);
+        }
+
+        Ok(())
+    }
+}
+
+/// Python callable applied to each document before it is written.
+///
+/// The callable receives a `Document` and either mutates it in place and returns `None`,
+/// or returns a replacement `Document`.
+#[derive(Debug, Clone)]
+pub struct PyDocumentTransform {
+    pub callable: Arc<Py<PyAny>>,
+}
+
+impl PyDocumentTransform {
+    pub fn apply_batch(&self, docs: Vec<Document>) -> PyResult<Vec<Document>> {
+        Python::with_gil(|py| {
+            let callable = self.callable.bind(py);
+
+            docs.into_iter()
+                .map(|doc| {
+                    let doc = Bound::new(py, doc)?;
+                    let result = callable.call1((doc.clone(),))?;
+                    if result.is_none() {
+                        Ok(doc.borrow().clone())
+                    } else {
+                        result.extract::<Document>()
+                    }
+                })
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,101 @@
+use std::ffi::CStr;
+use std::mem::size_of;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+use pyo3::{buffer::PyBuffer, exceptions::PyBufferError, ffi, prelude::*};
+
+/// Read-only `float32` embedding exposed through the buffer protocol, so
+/// `numpy.asarray(doc.dense_array)` wraps it without converting each value to a Python float.
+#[pyclass(frozen)]
+pub struct Vector {
+    values: Vec<f32>,
+    // Pointed to by the buffers handed out, which keep the object alive
+    shape: [ffi::Py_ssize_t; 1],
+    strides: [ffi::Py_ssize_t; 1],
+}
+
+impl Vector {
+    pub fn new(values: Vec<f32>) -> Self {
+        Self {
+            shape: [values.len() as ffi::Py_ssize_t],
+            strides: [size_of::<f32>() as ffi::Py_ssize_t],
+            values,
+        }
+    }
+}
+
+#[pymethods]
+impl Vector {
+    fn __len__(&self) -> usize {
+        self.values.len()
+    }
+
+    fn tolist(&self) -> Vec<f32> {
+        self.values.clone()
+    }
+
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Vector is read-only"));
+        }
+
+        let vector = slf.get();
+        // SAFETY: the values, shape and strides are never mutated and outlive the view,
+        // which holds a reference to `slf` until it is released
+        unsafe {
+            (*view).buf = vector.values.as_ptr() as *mut c_void;
+            (*view).len = (vector.values.len() * size_of::<f32>()) as ffi::Py_ssize_t;
+            (*view).readonly = 1;
+            (*view).itemsize = size_of::<f32>() as ffi::Py_ssize_t;
+            (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+                FORMAT.as_ptr() as *mut _
+            } else {
+                ptr::null_mut()
+            };
+            (*view).ndim = 1;
+            (*view).shape = if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+                vector.shape.as_ptr() as *mut _
+            } else {
+                ptr::null_mut()
+            };
+            (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+                vector.strides.as_ptr() as *mut _
+            } else {
+                ptr::null_mut()
+            };
+            (*view).suboffsets = ptr::null_mut();
+            (*view).internal = ptr::null_mut();
+            (*view).obj = slf.into_any().into_ptr();
+        }
+
+        Ok(())
+    }
+}
+
+/// `struct` format of a native `float32`.
+const FORMAT: &CStr = c"f";
+
+/// Embedding from a list of floats, a `Vector` or any `float32`/`float64` buffer such as a
+/// numpy array. Buffers are copied in one go instead of value by value.
+pub fn extract_vector(obj: &Bound<'_, PyAny>) -> PyResult<Vec<f32>> {
+    if let Ok(vector) = obj.downcast::<Vector>() {
+        return Ok(vector.get().values.clone());
+    }
+    if let Ok(buffer) = PyBuffer::<f32>::get(obj) {
+        return buffer.to_vec(obj.py());
+    }
+    if let Ok(buffer) = PyBuffer::<f64>::get(obj) {
+        let values = buffer.to_vec(obj.py())?;
+        return Ok(values.into_iter().map(|v| v as f32).collect());
+    }
+
+    obj.extract()
+}
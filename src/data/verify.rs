@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use arrow::datatypes::{DataType, Schema};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::data::doc::parse_from_batch;
+use crate::data::query::read_queries;
+use crate::s3::open_file;
+
+/// Columns of a docs file and their types, as `parse_from_batch` reads them.
+const DOC_COLUMNS: [(&str, &str); 5] = [
+    ("id", "LargeUtf8"),
+    ("text", "LargeUtf8"),
+    ("dense", "LargeList<Float64>"),
+    ("int_filter", "Int32"),
+    ("keyword_filter", "LargeUtf8"),
+];
+
+/// Check a docs or queries parquet file, returning the problems found. Files with an `id`
+/// column are checked as docs, others as queries.
+///
+/// Docs are checked for their schema, unique IDs, consistent embedding dimensions and
+/// NaN or zero vectors. Queries are checked for their vectors the same way, and for the
+/// share of queries with ground truth for each filter pair.
+pub async fn verify_dataset(path: &str, cache_dir: &str) -> anyhow::Result<Vec<String>> {
+    let file = open_file(path, cache_dir).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let schema = ParquetRecordBatchReaderBuilder::try_new(file.try_clone()?)?
+            .schema()
+            .clone();
+
+        if schema.field_with_name("id").is_ok() {
+            verify_docs(file, &schema)
+        } else {
+            verify_queries(file)
+        }
+    })
+    .await?
+}
+
+fn verify_docs(file: File, schema: &Schema) -> anyhow::Result<Vec<String>> {
+    let mut problems = Vec::new();
+    for (name, expected) in DOC_COLUMNS {
+        match schema.field_with_name(name) {
+            Ok(field) if type_name(field.data_type()) == expected => {}
+            Ok(field) => problems.push(format!(
+                "Column `{name}` is {}, expected {expected}",
+                type_name(field.data_type())
+            )),
+            Err(_) => problems.push(format!("Column `{name}` is missing")),
+        }
+    }
+    // Rows can't be read with the wrong schema
+    if !problems.is_empty() {
+        return Ok(problems);
+    }
+
+    let mut vectors = Vectors::default();
+    let mut ids = HashSet::new();
+    let mut duplicates = 0;
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+    for batch in reader {
+        for doc in parse_from_batch(batch?) {
+            // IDs are hashed to bound memory, a collision is vanishingly unlikely
+            if !ids.insert(hash(&doc.id)) {
+                duplicates += 1;
+            }
+            vectors.add(doc.dense_embedding.as_deref().unwrap_or_default());
+        }
+    }
+
+    if duplicates > 0 {
+        problems.push(format!("{duplicates} duplicate IDs"));
+    }
+    problems.extend(vectors.problems("docs"));
+
+    Ok(problems)
+}
+
+fn verify_queries(file: File) -> anyhow::Result<Vec<String>> {
+    let mut vectors = Vectors::default();
+    // Queries with ground truth per (int_filter, keyword_filter)
+    let mut coverage = BTreeMap::<(u32, String), usize>::new();
+    let mut empty = BTreeMap::<(u32, String), usize>::new();
+
+    let result = read_queries(file, true, |query| {
+        vectors.add(&query.dense);
+        for (int_filter, by_keyword) in query.recall {
+            for (keyword_filter, ids) in by_keyword {
+                let key = (int_filter, keyword_filter);
+                if ids.is_empty() {
+                    *empty.entry(key.clone()).or_default() += 1;
+                }
+                *coverage.entry(key).or_default() += 1;
+            }
+        }
+        Ok(())
+    });
+    if let Err(error) = result {
+        return Ok(vec![format!("Invalid queries file: {error:#}")]);
+    }
+
+    let mut problems = Vec::new();
+    for ((int_filter, keyword_filter), covered) in &coverage {
+        if *covered < vectors.count {
+            problems.push(format!(
+                "Ground truth for int_filter={int_filter} keyword_filter={keyword_filter} covers {covered} of {} queries",
+                vectors.count
+            ));
+        }
+    }
+    for ((int_filter, keyword_filter), empty) in &empty {
+        problems.push(format!(
+            "Ground truth for int_filter={int_filter} keyword_filter={keyword_filter} is empty for {empty} queries"
+        ));
+    }
+    problems.extend(vectors.problems("queries"));
+
+    Ok(problems)
+}
+
+/// Dimensions and degenerate values of the vectors in a file.
+#[derive(Default)]
+struct Vectors {
+    count: usize,
+    /// Number of vectors of each dimension
+    dimensions: BTreeMap<usize, usize>,
+    non_finite: usize,
+    zero: usize,
+}
+
+impl Vectors {
+    fn add(&mut self, vector: &[f32]) {
+        self.count += 1;
+        *self.dimensions.entry(vector.len()).or_default() += 1;
+        if vector.iter().any(|v| !v.is_finite()) {
+            self.non_finite += 1;
+        } else if !vector.is_empty() && vector.iter().all(|v| *v == 0.0) {
+            self.zero += 1;
+        }
+    }
+
+    fn problems(&self, rows: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.count == 0 {
+            problems.push(format!("No {rows}"));
+        }
+        if let Some(missing) = self.dimensions.get(&0) {
+            problems.push(format!("{missing} {rows} without a vector"));
+        }
+        let dimensions: Vec<String> = self
+            .dimensions
+            .iter()
+            .filter(|(dimension, _)| **dimension > 0)
+            .map(|(dimension, count)| format!("{count} of dimension {dimension}"))
+            .collect();
+        if dimensions.len() > 1 {
+            problems.push(format!(
+                "Inconsistent vector dimensions: {}",
+                dimensions.join(", ")
+            ));
+        }
+        if self.non_finite > 0 {
+            problems.push(format!(
+                "{} {rows} with NaN or infinite vector values",
+                self.non_finite
+            ));
+        }
+        if self.zero > 0 {
+            problems.push(format!("{} {rows} with all-zero vectors", self.zero));
+        }
+
+        problems
+    }
+}
+
+fn type_name(data_type: &DataType) -> String {
+    match data_type {
+        DataType::LargeList(item) => format!("LargeList<{}>", type_name(item.data_type())),
+        DataType::List(item) => format!("List<{}>", type_name(item.data_type())),
+        data_type => format!("{data_type:?}"),
+    }
+}
+
+fn hash(id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,63 @@
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct DeleteConfig {
+    pub collection: String,
+    /// Queries file (local or `s3://`) whose vectors are used to probe for deleted documents.
+    pub queries: String,
+    pub cache_dir: String,
+    /// Delete documents with `int_filter <= int_filter`.
+    pub int_filter: Option<u32>,
+    /// Delete documents matching the keyword token.
+    pub keyword_filter: Option<String>,
+    pub top_k: u32,
+    /// Number of probe queries issued per poll.
+    pub probes: usize,
+    /// Give up waiting for deleted documents to disappear after this many seconds.
+    pub timeout: u64,
+}
+
+impl DeleteConfig {
+    /// Files the run reads, for the run manifest.
+    pub fn datasets(&self) -> Vec<String> {
+        vec![self.queries.clone()]
+    }
+}
+
+#[pymethods]
+impl DeleteConfig {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (collection, queries, cache_dir, int_filter=None, keyword_filter=None, top_k=10, probes=10, timeout=60))]
+    fn new(
+        collection: String,
+        queries: String,
+        cache_dir: String,
+        int_filter: Option<u32>,
+        keyword_filter: Option<String>,
+        top_k: u32,
+        probes: usize,
+        timeout: u64,
+    ) -> PyResult<Self> {
+        if int_filter.is_none() && keyword_filter.is_none() {
+            return Err(PyValueError::new_err(
+                "At least one of int_filter or keyword_filter is required",
+            ));
+        }
+        if probes == 0 {
+            return Err(PyValueError::new_err("probes must be positive"));
+        }
+
+        Ok(Self {
+            collection,
+            queries,
+            cache_dir,
+            int_filter,
+            keyword_filter,
+            top_k,
+            probes,
+            timeout,
+        })
+    }
+}
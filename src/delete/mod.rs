@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::{
+    data::load_from_path,
+    provider::{Capability, PyProvider, QueryOptions},
+    telemetry::{
+        metrics::{Recorder, Registry},
+        RunIds, RunInfo,
+    },
+};
+
+mod config;
+pub use config::DeleteConfig;
+
+/// Benchmark a bulk delete by filter.
+///
+/// Measures how long the delete call takes and how long until no probe query
+/// with the same filter returns documents anymore.
+pub async fn start(
+    provider: PyProvider,
+    config: DeleteConfig,
+    registry: Registry,
+    ids: RunIds,
+) -> anyhow::Result<String> {
+    let run_id = ids.generate();
+
+    let (metrics_tx, metrics_rx) = registry.channel();
+    let metrics_task_handle = tokio::spawn(registry.clone().consume(metrics_rx));
+
+    let provider_name = provider.name().await?;
+    let provider_version = provider.version().await?;
+    let m = Recorder::new(
+        metrics_tx,
+        [
+            ("run_id", run_id.clone()),
+            ("parent_run_id", ids.parent_label()),
+            ("provider", provider_name.clone()),
+            ("collection", config.collection.clone()),
+            ("queries", config.queries.clone()),
+            (
+                "int_filter",
+                config.int_filter.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+            (
+                "keyword_filter",
+                config.keyword_filter.clone().unwrap_or_default(),
+            ),
+            ("top_k", config.top_k.to_string()),
+        ]
+        .into_iter()
+        .chain(provider_version.labels()),
+    );
+    let mut capabilities = provider.capabilities().await?;
+    let supported = capabilities.require(Capability::DeleteByFilter, "skipping the run");
+    capabilities.record(&m);
+    registry
+        .start_run(
+            RunInfo::new(
+                "delete",
+                &m,
+                provider_version,
+                config.datasets(),
+                &config.cache_dir,
+            )
+            .with_notes(capabilities.notes()),
+        )
+        .await;
+    if !supported {
+        drop(m);
+        metrics_task_handle.await??;
+        registry.finish_run(&run_id).await;
+        return Ok(run_id);
+    }
+    info!(?config, "Benchmarking delete by filter on {provider_name}");
+
+    let probes = load_from_path(&config.queries, &config.cache_dir)
+        .await?
+        .into_iter()
+        .take(config.probes)
+        .map(|query| Arc::new(query.dense))
+        .collect::<Vec<_>>();
+
+    let count_before = provider.count(config.collection.clone()).await?;
+
+    // Delete
+    let start = Instant::now();
+    provider
+        .delete_by_filter(
+            config.collection.clone(),
+            config.int_filter,
+            config.keyword_filter.clone(),
+        )
+        .await?;
+    let latency = start.elapsed();
+    m.observe("bench.delete.latency_ms", latency.as_millis() as f64);
+    info!("Delete completed in {:.2}s", latency.as_secs_f64());
+
+    // Wait until deleted documents disappear from query results
+    let deadline = Duration::from_secs(config.timeout);
+    loop {
+        let mut residual = 0;
+        for vector in &probes {
+            residual += provider
+                .query(
+                    config.collection.clone(),
+                    vector.clone(),
+                    config.top_k,
+                    config.int_filter,
+                    config.keyword_filter.clone(),
+                    QueryOptions::default(),
+                )
+                .await?
+                .0
+                .len();
+        }
+        m.gauge("bench.delete.residual_docs", residual as f64);
+
+        if residual == 0 {
+            let visibility = start.elapsed();
+            m.observe("bench.delete.visibility_ms", visibility.as_millis() as f64);
+            info!(
+                "Deleted documents disappeared after {:.2}s",
+                visibility.as_secs_f64()
+            );
+            break;
+        }
+
+        if start.elapsed() > deadline {
+            m.incr("bench.delete.visibility_timeouts", 1.0);
+            warn!(residual, "Deleted documents are still returned by queries");
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    if let (Some(before), Some(after)) = (
+        count_before,
+        provider.count(config.collection.clone()).await?,
+    ) {
+        let deleted = before.saturating_sub(after);
+        m.gauge("bench.delete.deleted_docs", deleted as f64);
+        info!(before, after, deleted, "Document count after delete");
+    }
+
+    // Flush remaining metrics
+    drop(m);
+    metrics_task_handle.await??;
+    registry.finish_run(&run_id).await;
+
+    Ok(run_id)
+}
@@ -0,0 +1,159 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::RwLock;
+
+use async_channel::{Receiver, Sender};
+use pyo3::{
+    exceptions::{PyKeyboardInterrupt, PyRuntimeError, PyValueError},
+    prelude::*,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// Environment variable setting the number of Python dispatch threads at import.
+pub const THREADS_VAR: &str = "TOPK_BENCH_PYTHON_THREADS";
+
+/// Most calls a dispatch thread runs per GIL acquisition while more are queued.
+const MAX_SLICE: usize = 32;
+
+type Job = Box<dyn FnOnce(Python<'_>) + Send>;
+
+/// Fixed pool of threads dedicated to Python calls, fed from a shared queue.
+struct Pool {
+    tx: Sender<Job>,
+    threads: usize,
+}
+
+static POOL: RwLock<Option<Pool>> = RwLock::new(None);
+
+/// Run Python calls on `threads` dedicated threads, or on the runtime's blocking pool
+/// with `None`. Threads of a previous pool exit once its queued calls are done.
+pub fn configure(threads: Option<usize>) -> anyhow::Result<()> {
+    if threads == Some(0) {
+        anyhow::bail!("Number of Python threads must be positive");
+    }
+
+    let pool = threads
+        .map(|threads| -> anyhow::Result<Pool> {
+            let (tx, rx) = async_channel::unbounded();
+            for i in 0..threads {
+                let rx = rx.clone();
+                std::thread::Builder::new()
+                    .name(format!("topk-bench-python-{i}"))
+                    .spawn(move || work(rx))?;
+            }
+            info!(threads, "Dispatching Python calls to dedicated threads");
+
+            Ok(Pool { tx, threads })
+        })
+        .transpose()?;
+
+    *POOL.write().unwrap() = pool;
+
+    Ok(())
+}
+
+/// Number of dedicated dispatch threads, `None` when using the blocking pool.
+pub fn threads() -> Option<usize> {
+    POOL.read().unwrap().as_ref().map(|pool| pool.threads)
+}
+
+/// Stop the dispatch threads before Python finalizes.
+pub fn shutdown() {
+    *POOL.write().unwrap() = None;
+}
+
+/// Run Python code from async code, acquiring the GIL on another thread.
+///
+/// Tokio <> GIL Interaction:
+/// - This function is called from async code running on the Tokio runtime
+/// - By default tokio::task::spawn_blocking() runs `f` on the runtime's blocking thread
+///   pool, acquiring the GIL per call with Python::with_gil()
+/// - With dedicated threads (see [`configure`]) `f` is queued to the pool instead, whose
+///   threads hold the GIL across up to [`MAX_SLICE`] queued calls
+/// - This works because the GIL is released before block_on() in the caller (see ingest.rs)
+///
+/// Why this works:
+/// - The GIL is not held by the thread blocked in block_on() (released via allow_threads)
+/// - Blocking pool and dispatch threads can acquire the GIL
+/// - No deadlock because the GIL is available for acquisition
+pub(crate) async fn run_py<F, R>(f: F) -> PyResult<R>
+where
+    F: FnOnce(Python<'_>) -> PyResult<R> + Send + 'static,
+    R: Send + 'static,
+{
+    let tx = POOL.read().unwrap().as_ref().map(|pool| pool.tx.clone());
+    let Some(tx) = tx else {
+        return tokio::task::spawn_blocking(move || Python::with_gil(f))
+            .await
+            .map_err(|e| PyValueError::new_err(format!("Failed to run Python code: {e}")))?;
+    };
+
+    let (result_tx, result_rx) = tokio::sync::oneshot::channel();
+    let job: Job = Box::new(move |py| {
+        let _ = result_tx.send(f(py));
+    });
+    tx.send(job)
+        .await
+        .map_err(|_| PyValueError::new_err("Python dispatch threads were shut down"))?;
+
+    result_rx
+        .await
+        .map_err(|_| PyValueError::new_err("Failed to run Python code: call panicked"))?
+}
+
+/// [`run_py`] for a call belonging to a run that `abort` stops.
+///
+/// Fails without calling into Python once `abort` is cancelled, stops waiting for a call
+/// in progress when it is, and cancels it when the call raises `KeyboardInterrupt`, so an
+/// interrupt in one provider call stops the whole run instead of a single worker.
+pub(crate) async fn run_py_abortable<F, R>(abort: &CancellationToken, f: F) -> PyResult<R>
+where
+    F: FnOnce(Python<'_>) -> PyResult<R> + Send + 'static,
+    R: Send + 'static,
+{
+    if abort.is_cancelled() {
+        return Err(aborted());
+    }
+
+    let token = abort.clone();
+    let call = run_py(move |py| {
+        let result = f(py);
+        if let Err(error) = &result {
+            if error.is_instance_of::<PyKeyboardInterrupt>(py) {
+                info!("Keyboard interrupt received, aborting run");
+                token.cancel();
+            }
+        }
+        result
+    });
+
+    tokio::select! {
+        result = call => result,
+        _ = abort.cancelled() => Err(aborted()),
+    }
+}
+
+fn aborted() -> PyErr {
+    PyRuntimeError::new_err("Run aborted")
+}
+
+fn work(rx: Receiver<Job>) {
+    // Wait for work without holding the GIL
+    while let Ok(job) = rx.recv_blocking() {
+        Python::with_gil(|py| {
+            run(job, py);
+            for _ in 1..MAX_SLICE {
+                match rx.try_recv() {
+                    Ok(job) => run(job, py),
+                    Err(_) => break,
+                }
+            }
+        });
+    }
+}
+
+fn run(job: Job, py: Python<'_>) {
+    if panic::catch_unwind(AssertUnwindSafe(|| job(py))).is_err() {
+        error!("Python call panicked");
+    }
+}
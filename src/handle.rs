@@ -0,0 +1,132 @@
+use std::future::Future;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use pyo3::{exceptions::PyValueError, prelude::*, IntoPyObjectExt};
+use tokio_util::sync::CancellationToken;
+
+use crate::dispatch::run_py;
+
+/// Handle to a run started in the background by `ingest_async()` or `query_async()`.
+#[pyclass]
+#[derive(Clone)]
+pub struct RunHandle {
+    shared: Arc<Shared>,
+    abort: CancellationToken,
+}
+
+struct Shared {
+    result: Mutex<Option<Finished>>,
+    done: Condvar,
+}
+
+/// A finished run's result, and whether it had been aborted by the time it finished.
+struct Finished {
+    result: Result<Py<PyAny>, String>,
+    aborted: bool,
+}
+
+impl RunHandle {
+    /// Spawn `future` on `runtime`, aborting it cleanly through `abort`.
+    pub fn spawn<F, T>(
+        runtime: &tokio::runtime::Handle,
+        abort: CancellationToken,
+        future: F,
+    ) -> Self
+    where
+        F: Future<Output = anyhow::Result<T>> + Send + 'static,
+        T: for<'py> IntoPyObject<'py> + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            result: Mutex::new(None),
+            done: Condvar::new(),
+        });
+
+        let task_shared = shared.clone();
+        let task_abort = abort.clone();
+        let run = runtime.spawn(future);
+        runtime.spawn(async move {
+            // A panicking run still finishes, as failed, so that `wait()` returns
+            let result = match run.await {
+                // Converted off the runtime's workers, which shouldn't block on the GIL
+                Ok(Ok(value)) => run_py(move |py| value.into_py_any(py))
+                    .await
+                    .map_err(|e| e.to_string()),
+                Ok(Err(e)) => Err(format!("{e:?}")),
+                Err(e) => Err(format!("Run panicked: {e}")),
+            };
+
+            *task_shared.result.lock().unwrap() = Some(Finished {
+                result,
+                aborted: task_abort.is_cancelled(),
+            });
+            task_shared.done.notify_all();
+        });
+
+        Self { shared, abort }
+    }
+}
+
+#[pymethods]
+impl RunHandle {
+    /// `"running"`, `"aborting"`, `"completed"`, `"aborted"` or `"failed"`.
+    fn status(&self) -> &'static str {
+        match (
+            &*self.shared.result.lock().unwrap(),
+            self.abort.is_cancelled(),
+        ) {
+            (None, false) => "running",
+            (None, true) => "aborting",
+            (Some(Finished { result: Err(_), .. }), _) => "failed",
+            (Some(Finished { aborted: false, .. }), _) => "completed",
+            (Some(Finished { aborted: true, .. }), _) => "aborted",
+        }
+    }
+
+    /// Wait up to `timeout` seconds, or until the run finishes without a timeout. Returns
+    /// the run's result, or `None` if it is still running; raises if the run failed.
+    #[pyo3(signature = (timeout=None))]
+    fn wait(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Option<Py<PyAny>>> {
+        let timeout = match timeout {
+            // Waiting forever is waiting without a timeout
+            Some(timeout) if timeout == f64::INFINITY => None,
+            Some(timeout) => Some(Duration::try_from_secs_f64(timeout).map_err(|_| {
+                PyValueError::new_err("timeout must be a non-negative number of seconds")
+            })?),
+            None => None,
+        };
+
+        let shared = self.shared.clone();
+        let finished = py.allow_threads(move || {
+            let guard = shared.result.lock().unwrap();
+            let guard = match timeout {
+                Some(timeout) => {
+                    shared
+                        .done
+                        .wait_timeout_while(guard, timeout, |r| r.is_none())
+                        .unwrap()
+                        .0
+                }
+                None => shared.done.wait_while(guard, |r| r.is_none()).unwrap(),
+            };
+            guard.is_some()
+        });
+        if !finished {
+            return Ok(None);
+        }
+
+        match &*self.shared.result.lock().unwrap() {
+            Some(Finished {
+                result: Ok(value), ..
+            }) => Ok(Some(value.clone_ref(py))),
+            Some(Finished { result: Err(e), .. }) => Err(PyValueError::new_err(e.clone())),
+            None => unreachable!("run finished"),
+        }
+    }
+
+    /// Stop the run. It winds down like a run stopped by its stats callback; use `wait()`
+    /// to block until it has.
+    fn abort(&self) {
+        self.abort.cancel();
+    }
+}
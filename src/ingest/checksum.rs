@@ -0,0 +1,202 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use colored::Colorize;
+use rand::prelude::*;
+use tracing::{debug, warn};
+
+use crate::data::Document;
+use crate::ingest::freshness::lookup;
+use crate::provider::PyProvider;
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::output::report;
+
+/// Uniform random sample of acknowledged documents, kept as their IDs and content hashes
+/// so they can be read back and compared after the ingest.
+#[derive(Debug, Clone)]
+pub struct ChecksumSample {
+    reservoir: Arc<Mutex<Reservoir>>,
+    size: usize,
+}
+
+#[derive(Debug, Default)]
+struct Reservoir {
+    seen: usize,
+    docs: Vec<Sampled>,
+}
+
+/// A sampled document, as it was acknowledged.
+#[derive(Debug, Clone)]
+pub struct Sampled {
+    pub id: String,
+    hash: u64,
+    /// Whether the provider reported only queueing the document
+    pub queued: bool,
+}
+
+/// What reading a sampled document back found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadBack {
+    Intact,
+    Mismatched,
+    Missing,
+    /// The lookups failed
+    Failed,
+}
+
+impl ChecksumSample {
+    pub fn new(size: usize) -> Self {
+        Self {
+            reservoir: Arc::new(Mutex::new(Reservoir::default())),
+            size,
+        }
+    }
+
+    /// Offer acknowledged documents to the sample, `queued` if the provider reported only
+    /// queueing them.
+    pub fn offer(&self, docs: &[Document], queued: bool) {
+        let mut rng = rand::rng();
+        let mut reservoir = self
+            .reservoir
+            .lock()
+            .expect("checksum sample lock poisoned");
+        for doc in docs {
+            let sampled = Sampled {
+                id: doc.id.clone(),
+                hash: content_hash(doc),
+                queued,
+            };
+            reservoir.seen += 1;
+            if reservoir.docs.len() < self.size {
+                reservoir.docs.push(sampled);
+            } else {
+                let i = rng.random_range(0..reservoir.seen);
+                if i < self.size {
+                    reservoir.docs[i] = sampled;
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.reservoir
+            .lock()
+            .expect("checksum sample lock poisoned")
+            .docs
+            .is_empty()
+    }
+
+    /// Read the sampled documents back and compare their content with what was written,
+    /// then print and record how many were missing or differ. Returns what was found
+    /// for each document.
+    pub async fn verify(
+        &self,
+        provider: &PyProvider,
+        collection: &str,
+        consistency: Option<String>,
+        m: &Recorder,
+        prefix: &str,
+    ) -> anyhow::Result<Vec<(Sampled, ReadBack)>> {
+        let docs = self
+            .reservoir
+            .lock()
+            .expect("checksum sample lock poisoned")
+            .docs
+            .clone();
+        let found = read_back(
+            &docs,
+            provider,
+            collection,
+            consistency,
+            m,
+            "bench.ingest.checksum",
+        )
+        .await;
+
+        let count = |outcome| found.iter().filter(|(_, f)| *f == outcome).count();
+        let mismatches = count(ReadBack::Mismatched);
+        let (missing, failed) = (count(ReadBack::Missing), count(ReadBack::Failed));
+        let checked = count(ReadBack::Intact) + mismatches;
+
+        m.gauge("bench.ingest.checksum_checked", checked as f64);
+        m.gauge("bench.ingest.checksum_missing", missing as f64);
+        m.gauge("bench.ingest.checksum_mismatches", mismatches as f64);
+        let mismatch_rate = if checked > 0 {
+            mismatches as f64 / checked as f64
+        } else {
+            0.0
+        };
+        m.gauge("bench.ingest.checksum_mismatch_rate", mismatch_rate);
+
+        report(format!(
+            "{:>16}] {} Sampled: {}, Missing: {}, Mismatched: {} ({:.2}%), Lookup failures: {}",
+            prefix,
+            "Checksums:".green().bold(),
+            docs.len().to_string().bold(),
+            missing.to_string().yellow().bold(),
+            mismatches.to_string().red().bold(),
+            mismatch_rate * 100.0,
+            failed.to_string().bold(),
+        ));
+
+        if missing > 0 || mismatches > 0 {
+            warn!(
+                sampled = docs.len(),
+                missing, mismatches, "Documents read back differ from the ones written"
+            );
+        }
+
+        Ok(found)
+    }
+}
+
+/// Look each of `docs` up, recording `{area}.query_by_id_*` metrics, and compare it with
+/// the document written.
+pub async fn read_back(
+    docs: &[Sampled],
+    provider: &PyProvider,
+    collection: &str,
+    consistency: Option<String>,
+    m: &Recorder,
+    area: &str,
+) -> Vec<(Sampled, ReadBack)> {
+    let mut found = Vec::with_capacity(docs.len());
+    for sampled in docs {
+        let lookup = lookup(
+            m,
+            area,
+            provider,
+            collection,
+            &sampled.id,
+            consistency.clone(),
+        )
+        .await;
+
+        let outcome = match lookup {
+            Some(Some(doc)) if content_hash(&doc) == sampled.hash => ReadBack::Intact,
+            Some(Some(_)) => {
+                debug!(
+                    id = sampled.id,
+                    "Document read back differs from the one written"
+                );
+                ReadBack::Mismatched
+            }
+            Some(None) => ReadBack::Missing,
+            None => ReadBack::Failed,
+        };
+        found.push((sampled.clone(), outcome));
+    }
+
+    found
+}
+
+/// Hash of the fields providers store and return as written.
+fn content_hash(doc: &Document) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    doc.id.hash(&mut hasher);
+    doc.text.hash(&mut hasher);
+    doc.int_filter.hash(&mut hasher);
+    doc.keyword_filter.hash(&mut hasher);
+    hasher.finish()
+}
@@ -1,4 +1,16 @@
-use pyo3::prelude::*;
+use std::sync::Arc;
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+
+use crate::data::{
+    run_id_prefix, Dimension, Distance, DocumentTransform, IdCodec, IdScheme,
+    NamespaceDistribution, Namespaces,
+};
+use crate::ingest::{FreshnessProbes, Input, Phases, ReadAhead};
+use crate::provider::{check_consistency, ProviderParams, SetupConfig};
+use crate::query::QueryConfig;
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::{Reporting, RunResults};
 
 #[pyclass]
 #[derive(Clone, Debug)]
@@ -6,32 +18,301 @@ pub struct IngestConfig {
     pub collection: String,
     pub batch_size: usize,
     pub concurrency: usize,
-    pub input: String,
+    pub input: Input,
     pub mode: String,
     pub size: String,
     pub cache_dir: String,
+    /// Pad each document's `text` to this many bytes before upserting.
+    pub payload_size: Option<usize>,
+    /// ID scheme used when writing documents.
+    pub id_scheme: IdScheme,
+    /// Prefix written document IDs with a run-scoped namespace.
+    pub namespace_ids: bool,
+    /// Spread documents over this many namespaces within the collection.
+    pub namespaces: Option<usize>,
+    /// How documents are spread over `namespaces`.
+    pub namespace_distribution: NamespaceDistribution,
+    /// Expected dense embedding dimension. Defaults to the first embedding's dimension.
+    pub dimension: Option<usize>,
+    /// Pad or truncate embeddings to `dimension` instead of failing on a mismatch.
+    pub resize_vectors: bool,
+    /// Distance metric passed to `Provider.setup`.
+    pub metric: Distance,
+    /// Provider-specific index parameters passed to `Provider.setup`.
+    pub index_params: Option<Arc<Py<PyDict>>>,
+    /// After the last upsert, block until the provider reports all documents indexed.
+    pub wait_for_indexing: bool,
+    /// Read consistency level for freshness reads, e.g. `"strong"` or `"eventual"`.
+    pub consistency: Option<String>,
+    /// Seconds between progress reports and stats callbacks.
+    pub report_interval_secs: f64,
+    /// Seconds of recent metrics throughput and latency percentiles are computed over.
+    /// Defaults to one second for throughput and the whole run for percentiles.
+    pub report_window_secs: Option<f64>,
+    /// Maximum freshness probes in flight across all writers. Unbounded by default.
+    pub freshness_concurrency: Option<usize>,
+    /// Fraction of successful batches whose freshness is probed.
+    pub freshness_sample_rate: f64,
+    /// Provider-specific keyword arguments passed to `setup`, `upsert` and `query`.
+    pub provider_params: Option<ProviderParams>,
+    /// Give up on a batch after this many failed upserts. Retries forever by default.
+    pub max_upsert_attempts: Option<u32>,
+    /// Fraction of the input bulk loaded at full speed before trickling in the rest.
+    pub bulk_fraction: Option<f64>,
+    /// Documents/s written after the bulk load. Required with `bulk_fraction`.
+    pub trickle_rate: Option<f64>,
+    /// Queries run while the remaining documents trickle in.
+    pub trickle_queries: Option<QueryConfig>,
+    /// After the ingest, read back this many random documents and compare their content.
+    pub checksum_samples: Option<usize>,
+    /// After the checksums, restart the provider and read the intact sampled documents back
+    /// again, measuring data loss across the restart.
+    pub verify_durability: bool,
+    /// Most batches read ahead of the writers.
+    pub read_ahead: usize,
+    /// Read fewer batches ahead while the writers lag behind the producer.
+    pub adaptive_read_ahead: bool,
+    /// Parquet path (local or `s3://`) documents of batches given up on after
+    /// `max_upsert_attempts` are written to. `{run_id}` is substituted.
+    pub quarantine: Option<String>,
+    /// Write the run's metrics, summary, manifest and log under `{results_dir}/{run_id}/`
+    /// (local or `s3://`).
+    pub results_dir: Option<String>,
 }
 
 #[pymethods]
 impl IngestConfig {
     #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (collection, batch_size, concurrency, input, mode, size, cache_dir, payload_size=None, id_scheme="numeric".to_string(), namespace_ids=false, namespaces=None, namespace_distribution="uniform".to_string(), dimension=None, resize_vectors=false, metric="cosine".to_string(), index_params=None, wait_for_indexing=false, consistency=None, report_interval_secs=1.0, report_window_secs=None, freshness_concurrency=None, freshness_sample_rate=1.0, provider_params=None, max_upsert_attempts=None, bulk_fraction=None, trickle_rate=None, trickle_queries=None, checksum_samples=None, verify_durability=false, read_ahead=100, adaptive_read_ahead=false, quarantine=None, results_dir=None))]
     fn new(
         collection: String,
         batch_size: usize,
         concurrency: usize,
-        input: String,
+        input: Bound<'_, PyAny>,
         mode: String,
         size: String,
         cache_dir: String,
-    ) -> Self {
-        Self {
+        payload_size: Option<usize>,
+        id_scheme: String,
+        namespace_ids: bool,
+        namespaces: Option<usize>,
+        namespace_distribution: String,
+        dimension: Option<usize>,
+        resize_vectors: bool,
+        metric: String,
+        index_params: Option<Py<PyDict>>,
+        wait_for_indexing: bool,
+        consistency: Option<String>,
+        report_interval_secs: f64,
+        report_window_secs: Option<f64>,
+        freshness_concurrency: Option<usize>,
+        freshness_sample_rate: f64,
+        provider_params: Option<Bound<'_, PyDict>>,
+        max_upsert_attempts: Option<u32>,
+        bulk_fraction: Option<f64>,
+        trickle_rate: Option<f64>,
+        trickle_queries: Option<QueryConfig>,
+        checksum_samples: Option<usize>,
+        verify_durability: bool,
+        read_ahead: usize,
+        adaptive_read_ahead: bool,
+        quarantine: Option<String>,
+        results_dir: Option<String>,
+    ) -> PyResult<Self> {
+        let id_scheme = id_scheme
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        let metric = metric
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        let namespace_distribution = namespace_distribution
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        if namespaces == Some(0) {
+            return Err(PyValueError::new_err("namespaces must be positive"));
+        }
+        check_consistency(consistency.as_deref())
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        Reporting::new(report_interval_secs, report_window_secs)
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        if freshness_concurrency == Some(0) {
+            return Err(PyValueError::new_err(
+                "freshness_concurrency must be positive",
+            ));
+        }
+        if !(0.0..=1.0).contains(&freshness_sample_rate) {
+            return Err(PyValueError::new_err(
+                "freshness_sample_rate must be between 0 and 1",
+            ));
+        }
+        if max_upsert_attempts == Some(0) {
+            return Err(PyValueError::new_err(
+                "max_upsert_attempts must be positive",
+            ));
+        }
+        match (bulk_fraction, trickle_rate) {
+            (Some(fraction), _) if !(fraction > 0.0 && fraction < 1.0) => {
+                return Err(PyValueError::new_err(
+                    "bulk_fraction must be between 0 and 1, exclusive",
+                ));
+            }
+            (Some(_), None) => {
+                return Err(PyValueError::new_err(
+                    "trickle_rate is required with bulk_fraction",
+                ));
+            }
+            (_, Some(rate)) if !(rate > 0.0 && rate.is_finite()) => {
+                return Err(PyValueError::new_err("trickle_rate must be positive"));
+            }
+            (None, Some(_)) => {
+                return Err(PyValueError::new_err("trickle_rate requires bulk_fraction"));
+            }
+            _ => {}
+        }
+        if trickle_queries.is_some() && bulk_fraction.is_none() {
+            return Err(PyValueError::new_err(
+                "trickle_queries requires bulk_fraction",
+            ));
+        }
+        if checksum_samples == Some(0) {
+            return Err(PyValueError::new_err("checksum_samples must be positive"));
+        }
+        if verify_durability && checksum_samples.is_none() {
+            return Err(PyValueError::new_err(
+                "verify_durability requires checksum_samples",
+            ));
+        }
+        if read_ahead == 0 {
+            return Err(PyValueError::new_err("read_ahead must be positive"));
+        }
+        if quarantine.is_some() && max_upsert_attempts.is_none() {
+            return Err(PyValueError::new_err(
+                "quarantine requires max_upsert_attempts",
+            ));
+        }
+
+        Ok(Self {
             collection,
             batch_size,
             concurrency,
-            input,
+            input: Input::extract(&input)?,
             mode,
             size,
             cache_dir,
+            payload_size,
+            id_scheme,
+            namespace_ids,
+            namespaces,
+            namespace_distribution,
+            dimension,
+            resize_vectors,
+            metric,
+            index_params: index_params.map(Arc::new),
+            wait_for_indexing,
+            consistency,
+            report_interval_secs,
+            report_window_secs,
+            freshness_concurrency,
+            freshness_sample_rate,
+            provider_params: provider_params
+                .map(|params| ProviderParams::new(&params))
+                .transpose()?,
+            max_upsert_attempts,
+            bulk_fraction,
+            trickle_rate,
+            trickle_queries,
+            checksum_samples,
+            verify_durability,
+            read_ahead,
+            adaptive_read_ahead,
+            quarantine,
+            results_dir,
+        })
+    }
+}
+
+impl IngestConfig {
+    pub fn ids(&self, run_id: &str) -> IdCodec {
+        IdCodec {
+            scheme: self.id_scheme,
+            prefix: self.namespace_ids.then(|| run_id_prefix(run_id)),
+        }
+    }
+
+    pub fn namespaces(&self) -> Option<Namespaces> {
+        self.namespaces
+            .map(|count| Namespaces::new(count, self.namespace_distribution))
+    }
+
+    pub fn read_ahead(&self, m: &Recorder) -> ReadAhead {
+        ReadAhead::new(
+            self.read_ahead,
+            self.concurrency,
+            self.adaptive_read_ahead,
+            m.clone(),
+        )
+    }
+
+    /// Where failed batches are quarantined: `quarantine`, or `quarantine.parquet` in the
+    /// run's results. Batches are only given up on with `max_upsert_attempts`.
+    pub fn quarantine_path(&self, run_id: &str, results: Option<&RunResults>) -> Option<String> {
+        self.max_upsert_attempts?;
+        match &self.quarantine {
+            Some(path) => Some(path.replace("{run_id}", run_id)),
+            None => results.map(|results| results.path("quarantine.parquet")),
+        }
+    }
+
+    /// Files the run reads, for the run manifest.
+    pub fn datasets(&self) -> Vec<String> {
+        self.input.path().map(str::to_string).into_iter().collect()
+    }
+
+    /// Bulk and trickle phases, if the ingest is phased.
+    pub fn phases(&self) -> Option<Phases> {
+        Some(Phases {
+            bulk_fraction: self.bulk_fraction?,
+            trickle_rate: self.trickle_rate?,
+            queries: self.trickle_queries.clone(),
+        })
+    }
+
+    pub fn setup(&self) -> SetupConfig {
+        SetupConfig {
+            dimension: self.dimension,
+            metric: self.metric.to_string(),
+            filterable_fields: vec!["int_filter".to_string(), "keyword_filter".to_string()],
+            index_params: self.index_params.clone(),
+        }
+    }
+
+    pub fn freshness(&self) -> FreshnessProbes {
+        FreshnessProbes::new(
+            self.consistency.clone(),
+            self.freshness_concurrency,
+            self.freshness_sample_rate,
+        )
+    }
+
+    pub fn reporting(&self) -> Reporting {
+        Reporting::new(self.report_interval_secs, self.report_window_secs)
+            .expect("validated in constructor")
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        Dimension {
+            expected: self.dimension,
+            resize: self.resize_vectors,
+        }
+    }
+
+    pub fn transform(&self, run_id: &str) -> DocumentTransform {
+        DocumentTransform {
+            payload_size: self.payload_size,
+            ids: self.ids(run_id),
+            dimension: self.dimension(),
+            namespaces: self.namespaces(),
         }
     }
 }
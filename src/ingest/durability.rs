@@ -0,0 +1,95 @@
+use std::time::Instant;
+
+use colored::Colorize;
+use tracing::{info, warn};
+
+use crate::ingest::checksum::{read_back, ReadBack, Sampled};
+use crate::provider::PyProvider;
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::output::report;
+
+/// Restart the provider through its `restart` hook, then read back the sampled documents
+/// the checksum verification found intact and print and record how many were lost or
+/// changed across the restart.
+///
+/// Documents the provider only reported queueing are counted separately, since losing
+/// them is what an asynchronous acknowledgement allows.
+pub async fn verify_durability(
+    checked: Vec<(Sampled, ReadBack)>,
+    provider: &PyProvider,
+    collection: &str,
+    consistency: Option<String>,
+    m: &Recorder,
+    prefix: &str,
+) -> anyhow::Result<()> {
+    let intact: Vec<Sampled> = checked
+        .into_iter()
+        .filter(|(_, found)| *found == ReadBack::Intact)
+        .map(|(sampled, _)| sampled)
+        .collect();
+
+    let start = Instant::now();
+    provider.restart().await?;
+    let restart_ms = start.elapsed().as_secs_f64() * 1000.0;
+    m.gauge("bench.ingest.restart_ms", restart_ms);
+    info!("Provider restarted in {:.2}s", restart_ms / 1000.0);
+
+    let found = read_back(
+        &intact,
+        provider,
+        collection,
+        consistency,
+        m,
+        "bench.ingest.durability",
+    )
+    .await;
+
+    let count = |outcome, queued: Option<bool>| {
+        found
+            .iter()
+            .filter(|(sampled, f)| *f == outcome && queued.is_none_or(|q| sampled.queued == q))
+            .count()
+    };
+    let lost = count(ReadBack::Missing, None);
+    let changed = count(ReadBack::Mismatched, None);
+    let lost_queued =
+        count(ReadBack::Missing, Some(true)) + count(ReadBack::Mismatched, Some(true));
+    let failed = count(ReadBack::Failed, None);
+    let verified = intact.len() - failed;
+    let loss_rate = if verified > 0 {
+        (lost + changed) as f64 / verified as f64
+    } else {
+        0.0
+    };
+
+    m.gauge("bench.ingest.durability_checked", verified as f64);
+    m.gauge("bench.ingest.durability_lost", lost as f64);
+    m.gauge("bench.ingest.durability_changed", changed as f64);
+    m.gauge("bench.ingest.durability_lost_queued", lost_queued as f64);
+    m.gauge("bench.ingest.durability_loss_rate", loss_rate);
+
+    report(format!(
+        "{:>16}] {} Restart: {}, Checked: {}, Lost: {}, Changed: {} ({:.2}%), Of them queued: {}, Lookup failures: {}",
+        prefix,
+        "Durability:".green().bold(),
+        format!("{:.2}s", restart_ms / 1000.0).bold(),
+        verified.to_string().bold(),
+        lost.to_string().red().bold(),
+        changed.to_string().red().bold(),
+        loss_rate * 100.0,
+        lost_queued.to_string().yellow().bold(),
+        failed.to_string().bold(),
+    ));
+
+    if lost + changed > lost_queued {
+        warn!(
+            verified,
+            lost,
+            changed,
+            lost_queued,
+            "Documents not acknowledged as queued were lost across a restart"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use rand::prelude::*;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{error, warn};
+
+use crate::data::Document;
+use crate::provider::{ErrorClass, PyProvider};
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::output::report;
+use crate::telemetry::Snapshot;
+
+/// Consecutive failed lookups after which a freshness probe gives up.
+const MAX_LOOKUP_ATTEMPTS: u32 = 5;
+
+/// How long a probe polls for a document before giving up on it, so a write the provider
+/// silently dropped doesn't keep the writer waiting forever.
+const MAX_FRESHNESS: Duration = Duration::from_secs(120);
+
+/// Probes that may wait for a free slot of the pool, per slot, before batches are skipped.
+const QUEUED_PROBES_PER_SLOT: usize = 10;
+
+/// Freshness probes run by writers after successful upserts, shared by all writers of a
+/// run so probing doesn't flood the provider with `query_by_id` calls.
+#[derive(Debug, Clone)]
+pub struct FreshnessProbes {
+    consistency: Option<String>,
+    /// Bounds the probes in flight across all writers. `None` is unbounded.
+    permits: Option<Arc<Semaphore>>,
+    /// Bounds the probes in flight or waiting for a permit.
+    queue: Option<Arc<Semaphore>>,
+    /// Fraction of batches probed.
+    sample_rate: f64,
+    /// IDs currently being probed.
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl FreshnessProbes {
+    pub fn new(consistency: Option<String>, concurrency: Option<usize>, sample_rate: f64) -> Self {
+        Self {
+            consistency,
+            permits: concurrency.map(|n| Arc::new(Semaphore::new(n))),
+            queue: concurrency.map(|n| Arc::new(Semaphore::new(n * (1 + QUEUED_PROBES_PER_SLOT)))),
+            sample_rate,
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Probe every batch without a concurrency limit.
+    pub fn unbounded(consistency: Option<String>) -> Self {
+        Self::new(consistency, None, 1.0)
+    }
+
+    /// Start probing the freshness of `id` in `tasks`, timed from now. While the pool is
+    /// full the probe waits for a slot, so slow writes aren't left out of the measurement.
+    /// Batches not sampled are skipped, as are batches arriving while the wait queue is
+    /// full or `id` is already being probed.
+    pub fn spawn(
+        &self,
+        tasks: &mut JoinSet<anyhow::Result<()>>,
+        m: &Recorder,
+        provider: PyProvider,
+        collection: String,
+        id: String,
+    ) {
+        if self.sample_rate < 1.0 && !rand::rng().random_bool(self.sample_rate) {
+            return;
+        }
+
+        let queued = match &self.queue {
+            Some(queue) => match queue.clone().try_acquire_owned() {
+                Ok(queued) => Some(queued),
+                Err(_) => {
+                    m.incr("bench.ingest.freshness_skipped", 1.0);
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        if !self
+            .in_flight
+            .lock()
+            .expect("freshness lock poisoned")
+            .insert(id.clone())
+        {
+            m.incr("bench.ingest.freshness_coalesced", 1.0);
+            return;
+        }
+
+        let acked = Instant::now();
+        let mut probe = Probe {
+            id,
+            in_flight: self.in_flight.clone(),
+            _queued: queued,
+            _permit: None,
+        };
+        let permits = self.permits.clone();
+        let consistency = self.consistency.clone();
+        let m = m.clone();
+        tasks.spawn(async move {
+            if let Some(permits) = permits {
+                probe._permit = Some(permits.acquire_owned().await?);
+                m.observe(
+                    "bench.ingest.freshness_queue_ms",
+                    acked.elapsed().as_millis() as f64,
+                );
+            }
+            measure_freshness(m, provider, collection, probe, consistency, acked).await
+        });
+    }
+}
+
+/// An in-flight probe, released from the pool when dropped.
+struct Probe {
+    id: String,
+    in_flight: Arc<Mutex<HashSet<String>>>,
+    _queued: Option<OwnedSemaphorePermit>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for Probe {
+    fn drop(&mut self) {
+        self.in_flight
+            .lock()
+            .expect("freshness lock poisoned")
+            .remove(&self.id);
+    }
+}
+
+/// Measure the freshness of a document acknowledged at `acked` by querying it until it is
+/// found, or [`MAX_FRESHNESS`] has passed.
+async fn measure_freshness(
+    m: Recorder,
+    provider: PyProvider,
+    collection: String,
+    probe: Probe,
+    consistency: Option<String>,
+    acked: Instant,
+) -> anyhow::Result<()> {
+    loop {
+        let Some(doc) = lookup(
+            &m,
+            "bench.ingest",
+            &provider,
+            &collection,
+            &probe.id,
+            consistency.clone(),
+        )
+        .await
+        else {
+            m.incr("bench.ingest.freshness_abandoned", 1.0);
+            return Ok(());
+        };
+
+        if doc.is_some() {
+            break;
+        }
+        if acked.elapsed() >= MAX_FRESHNESS {
+            warn!(
+                id = probe.id,
+                "Document not visible in time, abandoning freshness probe"
+            );
+            m.incr("bench.ingest.freshness_abandoned", 1.0);
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    m.observe(
+        "bench.ingest.freshness_latency_ms",
+        acked.elapsed().as_millis() as f64,
+    );
+
+    Ok(())
+}
+
+/// Look up a document by ID, recording `{prefix}.query_by_id_*` availability and latency
+/// metrics. Errors are retried with exponential backoff; returns `None` once
+/// [`MAX_LOOKUP_ATTEMPTS`] consecutive lookups failed or once the run is aborted.
+pub(super) async fn lookup(
+    m: &Recorder,
+    prefix: &str,
+    provider: &PyProvider,
+    collection: &str,
+    id: &str,
+    consistency: Option<String>,
+) -> Option<Option<Document>> {
+    for attempt in 1..=MAX_LOOKUP_ATTEMPTS {
+        let s = Instant::now();
+        let result = provider
+            .query_by_id(collection.to_string(), id.to_string(), consistency.clone())
+            .await;
+
+        m.incr(&format!("{prefix}.query_by_id_requests"), 1.0);
+        match result {
+            Ok(doc) => {
+                m.incr(&format!("{prefix}.query_by_id_oks"), 1.0);
+                m.observe(
+                    &format!("{prefix}.query_by_id_latency_ms"),
+                    s.elapsed().as_millis() as f64,
+                );
+                return Some(doc);
+            }
+            Err(_) if provider.is_aborted() => return None,
+            Err(error) => {
+                let class = ErrorClass::of(&error);
+                m.incr(
+                    &format!("{prefix}.query_by_id_errors.{}", class.as_str()),
+                    1.0,
+                );
+                error!(?error, attempt, "Failed to query document by ID");
+
+                let backoff = 50 * 2u64.pow(attempt - 1);
+                let jitter = rand::rng().random_range(0..backoff);
+                tokio::time::sleep(Duration::from_millis(backoff + jitter)).await;
+            }
+        }
+    }
+
+    warn!(
+        id,
+        "Giving up on freshness probe after {MAX_LOOKUP_ATTEMPTS} failed lookups"
+    );
+    None
+}
+
+/// Availability of the `{prefix}.query_by_id_*` lookups in percent, `None` without lookups.
+pub(super) fn lookup_availability(stats: &Snapshot, prefix: &str) -> Option<f64> {
+    let requests = stats.total(&format!("{prefix}.query_by_id_requests"));
+    let errors = stats.total_prefix(&format!("{prefix}.query_by_id_errors."));
+
+    (requests > 0.0).then(|| (1.0 - errors / requests) * 100.0)
+}
+
+/// Lookup segment of a progress report line, empty without lookups.
+pub(super) fn format_lookups(stats: &Snapshot, prefix: &str) -> String {
+    let Some(availability) = lookup_availability(stats, prefix) else {
+        return "".to_string();
+    };
+
+    format!(
+        ", Lookups: {:.2}% p50={:.2}ms p99={:.2}ms",
+        availability,
+        stats.quantile(&format!("{prefix}.query_by_id_latency_ms"), 0.50),
+        stats.quantile(&format!("{prefix}.query_by_id_latency_ms"), 0.99),
+    )
+    .bold()
+    .to_string()
+}
+
+/// Print the point-read summary of the freshness probe lookups at the end of a run.
+pub fn print_lookup_summary(stats: &Snapshot, prefix: &str) {
+    for area in ["bench.ingest", "bench.update"] {
+        let Some(availability) = lookup_availability(stats, area) else {
+            continue;
+        };
+        let latency = format!("{area}.query_by_id_latency_ms");
+
+        report(format!(
+            "{:>16}] {} {} lookups, {}, Latency: {}, {}, {}",
+            prefix,
+            "Point reads:".green().bold(),
+            stats.total(&format!("{area}.query_by_id_requests")),
+            format!("{availability:.2}%").bold(),
+            format!("p50={:.2}ms", stats.quantile(&latency, 0.50))
+                .yellow()
+                .bold(),
+            format!("p99={:.2}ms", stats.quantile(&latency, 0.99))
+                .magenta()
+                .bold(),
+            format!("max={:.2}ms", stats.quantile(&latency, 1.0)).bold(),
+        ));
+    }
+}
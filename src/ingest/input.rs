@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::sync::Arc;
+
+use anyhow::Context;
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::ffi_stream::{ArrowArrayStreamReader, FFI_ArrowArrayStream};
+use arrow_array::{ArrayRef, RecordBatch};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyCapsule};
+
+use crate::s3::open_file;
+
+/// Documents to ingest.
+#[derive(Debug, Clone)]
+pub enum Input {
+    /// Parquet file (local, `s3://` or `https://`)
+    Path(String),
+    /// Object exporting an Arrow C stream, e.g. a pyarrow `Table` or `RecordBatchReader`
+    Arrow {
+        object: Arc<Py<PyAny>>,
+        label: String,
+    },
+}
+
+/// Opened input, read one record batch at a time.
+pub enum Source {
+    Parquet(File),
+    Arrow {
+        stream: ArrowArrayStreamReader,
+        /// Known for tables, not for readers
+        rows: Option<usize>,
+    },
+}
+
+/// Record batches of at most the requested size, with the columns documents are parsed from.
+pub type Batches = Box<dyn Iterator<Item = anyhow::Result<RecordBatch>> + Send>;
+
+/// Columns documents are parsed from, which Arrow input is cast to.
+fn doc_columns() -> [(&'static str, DataType); 5] {
+    [
+        ("id", DataType::LargeUtf8),
+        ("text", DataType::LargeUtf8),
+        (
+            "dense",
+            DataType::LargeList(Arc::new(Field::new_list_field(DataType::Float64, true))),
+        ),
+        ("int_filter", DataType::Int32),
+        ("keyword_filter", DataType::LargeUtf8),
+    ]
+}
+
+impl Input {
+    /// A path, or an object implementing `__arrow_c_stream__`.
+    pub fn extract(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(path) = obj.extract::<String>() {
+            return Ok(Input::Path(path));
+        }
+        if !obj.hasattr("__arrow_c_stream__")? {
+            return Err(PyValueError::new_err(
+                "input must be a path or an Arrow table or stream, e.g. a pyarrow Table",
+            ));
+        }
+
+        let label = format!("<{}>", obj.get_type().qualname()?);
+        Ok(Input::Arrow {
+            object: Arc::new(obj.clone().unbind()),
+            label,
+        })
+    }
+
+    /// Path of the input file, `None` for Arrow input.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Input::Path(path) => Some(path),
+            Input::Arrow { .. } => None,
+        }
+    }
+
+    /// The path, or the type of the Arrow input.
+    pub fn label(&self) -> String {
+        match self {
+            Input::Path(path) => path.clone(),
+            Input::Arrow { label, .. } => label.clone(),
+        }
+    }
+
+    pub async fn open(&self, cache_dir: &str) -> anyhow::Result<Source> {
+        match self {
+            Input::Path(path) => Ok(Source::Parquet(open_file(path, cache_dir).await?)),
+            Input::Arrow { object, .. } => {
+                let object = object.clone();
+                tokio::task::spawn_blocking(move || {
+                    Python::with_gil(|py| export_stream(object.bind(py)))
+                        .context("Failed to read Arrow input")
+                })
+                .await?
+            }
+        }
+    }
+}
+
+/// Export `obj` through the Arrow PyCapsule interface, taking ownership of the stream.
+fn export_stream(obj: &Bound<'_, PyAny>) -> anyhow::Result<Source> {
+    let capsule = obj.call_method0("__arrow_c_stream__")?;
+    let capsule = capsule
+        .downcast::<PyCapsule>()
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    if capsule.name()?.and_then(|name| name.to_str().ok()) != Some("arrow_array_stream") {
+        anyhow::bail!("`__arrow_c_stream__` did not return an Arrow array stream");
+    }
+
+    // SAFETY: the capsule holds an `ArrowArrayStream`, which `from_raw` moves out of,
+    // leaving a released stream behind for the capsule's destructor
+    let stream = unsafe {
+        ArrowArrayStreamReader::from_raw(capsule.pointer() as *mut FFI_ArrowArrayStream)
+    }?;
+    // Tables and data frames have a length, readers don't
+    let rows = obj.len().ok();
+
+    Ok(Source::Arrow { stream, rows })
+}
+
+impl Source {
+    /// Number of documents, if known up front, and the record batches to read them from.
+    pub fn batches(self, batch_size: usize) -> anyhow::Result<(Option<usize>, Batches)> {
+        match self {
+            Source::Parquet(file) => {
+                let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+                let rows = builder.metadata().file_metadata().num_rows().max(0) as usize;
+                let reader = builder.with_batch_size(batch_size).build()?;
+
+                Ok((
+                    Some(rows),
+                    Box::new(reader.map(|batch| Ok(batch?))) as Batches,
+                ))
+            }
+            Source::Arrow { stream, rows } => {
+                let batches = stream.flat_map(move |batch| {
+                    match batch.map_err(anyhow::Error::from).and_then(conform) {
+                        Ok(batch) => split(batch, batch_size).into_iter().map(Ok).collect(),
+                        Err(error) => vec![Err(error)],
+                    }
+                });
+
+                Ok((rows, Box::new(batches) as Batches))
+            }
+        }
+    }
+}
+
+/// Cast the document columns of `batch` to the types documents are parsed from, e.g.
+/// `string` and `list<float>` columns from pandas or polars.
+fn conform(batch: RecordBatch) -> anyhow::Result<RecordBatch> {
+    let mut fields = Vec::new();
+    let mut columns: Vec<ArrayRef> = Vec::new();
+    for (name, data_type) in doc_columns() {
+        let column = batch
+            .column_by_name(name)
+            .with_context(|| format!("Arrow input is missing column `{name}`"))?;
+        let column = cast(column, &data_type)
+            .with_context(|| format!("Column `{name}` can't be read as {data_type}"))?;
+
+        fields.push(Field::new(name, data_type, true));
+        columns.push(column);
+    }
+
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Slices of `batch` with at most `size` rows each.
+fn split(batch: RecordBatch, size: usize) -> Vec<RecordBatch> {
+    (0..batch.num_rows())
+        .step_by(size.max(1))
+        .map(|offset| batch.slice(offset, size.min(batch.num_rows() - offset)))
+        .collect()
+}
@@ -1,38 +1,88 @@
 use std::{
-    fs::File,
+    future::Future,
+    pin::Pin,
     time::{Duration, Instant},
 };
 
 use async_channel::{Receiver, Sender};
 use colored::Colorize;
-use parquet::arrow::arrow_reader::ParquetRecordBatchReader;
 use rand::prelude::*;
 use tokio::{
     signal::ctrl_c,
-    sync::mpsc,
     task::{JoinHandle, JoinSet},
 };
-use tracing::{error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    data::{parse_from_batch, Document},
-    provider::PyProvider,
-    s3::open_file,
+    data::{parse_from_batch, Document, DocumentTransform, IdCodec},
+    dispatch,
+    provider::{Batch, Capability, ErrorClass, PyProvider},
     telemetry::{
-        metrics::{consume_metrics, snapshot_metrics, Metric, Recorder},
-        Snapshot,
+        metrics::{Recorder, Registry},
+        output::report,
+        Reporting, RunIds, RunInfo, RunResults, Snapshot, StatsCallback,
     },
 };
 
+mod checksum;
+pub use checksum::ChecksumSample;
+
 mod config;
 pub use config::IngestConfig;
 
-pub async fn start(provider: PyProvider, config: IngestConfig) -> anyhow::Result<()> {
-    let run_id = uuid::Uuid::new_v4().to_string();
+mod durability;
+use durability::verify_durability;
+
+mod input;
+pub use input::{Input, Source};
+
+mod phased;
+use phased::run_phases;
+pub use phased::Phases;
+
+mod freshness;
+use freshness::{format_lookups, lookup_availability};
+pub use freshness::{print_lookup_summary, FreshnessProbes};
+
+mod recent;
+pub use recent::{RecentWrite, RecentWrites};
+
+mod quarantine;
+pub use quarantine::Quarantine;
+
+mod read_ahead;
+pub use read_ahead::ReadAhead;
 
-    let (metrics_tx, metrics_rx) = mpsc::unbounded_channel::<Metric>();
+mod reconcile;
+use reconcile::reconcile;
+pub use reconcile::Tally;
 
+mod update;
+pub use update::{print_update_stats, spawn_updaters};
+
+pub async fn start(
+    provider: PyProvider,
+    config: IngestConfig,
+    registry: Registry,
+    on_stats: Option<StatsCallback>,
+    ids: RunIds,
+    abort: CancellationToken,
+) -> anyhow::Result<String> {
+    let run_id = ids.generate();
+    let results = config
+        .results_dir
+        .as_deref()
+        .map(|dir| RunResults::create(dir, &run_id))
+        .transpose()?;
+
+    let (metrics_tx, metrics_rx) = registry.channel();
+
+    let provider = provider
+        .with_params(config.provider_params.clone())
+        .with_namespaces(config.namespaces());
     let provider_name = provider.name().await?;
+    let provider_version = provider.version().await?;
     let m = Recorder::new(
         metrics_tx,
         [
@@ -40,76 +90,376 @@ pub async fn start(provider: PyProvider, config: IngestConfig) -> anyhow::Result
             ("collection", config.collection.clone()),
             ("batch_size", config.batch_size.to_string()),
             ("concurrency", config.concurrency.to_string()),
-            ("input", config.input.clone()),
+            ("input", config.input.label()),
             ("size", config.size.clone()),
             ("run_id", run_id.clone()),
+            ("parent_run_id", ids.parent_label()),
             ("mode", config.mode.clone()),
-        ],
+            (
+                "payload_size",
+                config
+                    .payload_size
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("id_scheme", config.id_scheme.to_string()),
+            ("namespace_ids", config.namespace_ids.to_string()),
+            (
+                "namespaces",
+                config.namespaces.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+            (
+                "namespace_distribution",
+                config.namespace_distribution.to_string(),
+            ),
+            ("wait_for_indexing", config.wait_for_indexing.to_string()),
+            (
+                "report_interval_secs",
+                config.report_interval_secs.to_string(),
+            ),
+            (
+                "report_window_secs",
+                config
+                    .report_window_secs
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "dimension",
+                config.dimension.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+            ("metric", config.metric.to_string()),
+            (
+                "consistency",
+                config.consistency.clone().unwrap_or_default(),
+            ),
+            (
+                "freshness_concurrency",
+                config
+                    .freshness_concurrency
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "freshness_sample_rate",
+                config.freshness_sample_rate.to_string(),
+            ),
+            (
+                "python_threads",
+                dispatch::threads()
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "results_dir",
+                config.results_dir.clone().unwrap_or_default(),
+            ),
+            (
+                "provider_params",
+                config
+                    .provider_params
+                    .as_ref()
+                    .map(|p| p.label.clone())
+                    .unwrap_or_default(),
+            ),
+            (
+                "max_upsert_attempts",
+                config
+                    .max_upsert_attempts
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "bulk_fraction",
+                config
+                    .bulk_fraction
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "trickle_rate",
+                config
+                    .trickle_rate
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "checksum_samples",
+                config
+                    .checksum_samples
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("verify_durability", config.verify_durability.to_string()),
+            ("read_ahead", config.read_ahead.to_string()),
+            (
+                "adaptive_read_ahead",
+                config.adaptive_read_ahead.to_string(),
+            ),
+        ]
+        .into_iter()
+        .chain(provider_version.labels()),
     );
 
+    // Skip what the provider doesn't support
+    provider
+        .check_consistency(config.consistency.as_deref())
+        .await?;
+    let mut config = config;
+    let mut capabilities = provider.capabilities().await?;
+    if config.ids(&run_id).is_string() && !capabilities.supports(Capability::StringIds) {
+        anyhow::bail!(
+            "Provider does not support string IDs, use id_scheme=\"numeric\" or \"uuid\" without namespace_ids"
+        );
+    }
+    if (config.freshness_sample_rate > 0.0 || config.checksum_samples.is_some())
+        && !capabilities.require(
+            Capability::QueryById,
+            "skipping freshness probes and checksums",
+        )
+    {
+        config.freshness_sample_rate = 0.0;
+        config.checksum_samples = None;
+        config.verify_durability = false;
+    }
+    if config.verify_durability
+        && !capabilities.require(Capability::Restart, "skipping durability verification")
+    {
+        config.verify_durability = false;
+    }
+    if (config.wait_for_indexing || config.bulk_fraction.is_some())
+        && !capabilities.require(Capability::NumIndexed, "not waiting for indexing")
+    {
+        config.wait_for_indexing = false;
+    }
+    capabilities.record(&m);
+
+    registry
+        .start_run(
+            RunInfo::new(
+                "ingest",
+                &m,
+                provider_version,
+                config.datasets(),
+                &config.cache_dir,
+            )
+            .with_notes(capabilities.notes()),
+        )
+        .await;
+
     // Load dataset
-    let file = open_file(&config.input, config.cache_dir.clone()).await?;
+    let source = config.input.open(&config.cache_dir).await?;
 
     info!(?config, "Benchmarking {provider_name}");
-    provider.setup(config.collection.clone()).await?;
+    provider
+        .setup(config.collection.clone(), config.setup())
+        .await?;
+    warm_connections(&provider, config.concurrency, &m).await?;
 
-    // Spawn batch producer
-    let (tx, rx) = async_channel::bounded::<Vec<Document>>(100);
-    spawn_batch_producer(file, config.batch_size, tx);
+    // Consume metrics
+    let metrics_task_handle = tokio::spawn(registry.clone().consume(metrics_rx));
 
     let mut tasks = JoinSet::new();
-
-    // Spawn writers
-    tasks.spawn(spawn_writers(
-        provider.clone(),
-        config.collection.clone(),
-        config.concurrency,
-        m.clone(),
-        rx,
-    ));
+    let tally = Tally::default();
+    let checksums = config.checksum_samples.map(ChecksumSample::new);
+    let quarantine_path = config.quarantine_path(&run_id, results.as_ref());
+    let quarantine = quarantine_path.as_ref().map(|_| Quarantine::default());
+    // Calls made by the workload stop once the run is aborted
+    let workload = provider.clone().with_abort(abort.clone());
+
+    // Spawn producer and writers, in two phases if configured
+    let writers: Pin<Box<dyn Future<Output = anyhow::Result<u64>> + Send>> = match config.phases() {
+        Some(phases) => Box::pin(run_phases(
+            phases,
+            workload.clone(),
+            config.clone(),
+            source,
+            tally.clone(),
+            checksums.clone(),
+            quarantine.clone(),
+            m.clone(),
+            registry.clone(),
+            run_id.clone(),
+            abort.clone(),
+        )),
+        None => {
+            let (tx, rx) = async_channel::bounded::<Vec<Document>>(config.read_ahead);
+            let producer = spawn_batch_producer(
+                source,
+                config.batch_size,
+                config.transform(&run_id),
+                tally.clone(),
+                config.read_ahead(&m),
+                abort.clone(),
+                tx,
+            );
+            let writers = spawn_writers(
+                workload.clone(),
+                config.collection.clone(),
+                config.concurrency,
+                config.ids(&run_id),
+                config.freshness(),
+                config.max_upsert_attempts,
+                None,
+                tally.clone(),
+                checksums.clone(),
+                quarantine.clone(),
+                m.clone(),
+                rx,
+            );
+            Box::pin(async move {
+                let upserted = writers.await?;
+                // Writers finish once the producer closes the channel, surface its errors
+                producer.await??;
+                Ok(upserted)
+            })
+        }
+    };
 
     // Spawn metrics reporter
     tasks.spawn(spawn_metrics_reporter(
+        registry.clone(),
         run_id.clone(),
         format!("{}@{}", provider_name, config.size),
+        config.reporting(),
+        on_stats,
     ));
 
-    // Consume metrics
-    tasks.spawn(consume_metrics(metrics_rx));
-
     // Control-C
-    tasks.spawn(async {
+    let interrupt = abort.clone();
+    tasks.spawn(async move {
         ctrl_c().await?;
         info!("Ctrl-C received, aborting ingest");
+        interrupt.cancel();
+        Ok(())
+    });
+    let aborted = abort.clone();
+    tasks.spawn(async move {
+        aborted.cancelled().await;
+        info!("Ingest aborted");
         Ok(())
     });
 
     let start = Instant::now();
-    while let Some(_) = tasks.join_next().await {
-        tasks.abort_all();
-        break;
-    }
+    let upserted = tokio::select! {
+        result = writers => Some(result?),
+        _ = tasks.join_next() => None,
+    };
     info!("Ingest completed in {:.2}s", start.elapsed().as_secs_f64());
 
+    // Writers also finish early when a provider call aborts the run
+    if let Some(upserted) = upserted.filter(|_| !abort.is_cancelled()) {
+        if config.wait_for_indexing {
+            tokio::select! {
+                result = wait_for_indexing(&provider, &config.collection, upserted, &m) => result?,
+                _ = tasks.join_next() => {}
+            }
+        }
+
+        if let Some(checksums) = &checksums {
+            let prefix = format!("{}@{}", provider_name, config.size);
+            tokio::select! {
+                result = async {
+                    let checked = checksums
+                        .verify(
+                            &provider,
+                            &config.collection,
+                            config.consistency.clone(),
+                            &m,
+                            &prefix,
+                        )
+                        .await?;
+                    if config.verify_durability {
+                        verify_durability(
+                            checked,
+                            &provider,
+                            &config.collection,
+                            config.consistency.clone(),
+                            &m,
+                            &prefix,
+                        )
+                        .await?;
+                    }
+                    anyhow::Ok(())
+                } => result?,
+                _ = tasks.join_next() => {}
+            }
+        }
+    }
+    tasks.abort_all();
+
+    if let (Some(quarantine), Some(path)) = (&quarantine, &quarantine_path) {
+        quarantine.write(&run_id, path, &m).await?;
+    }
+
+    reconcile(
+        &provider,
+        &config.collection,
+        &tally,
+        &m,
+        &format!("{}@{}", provider_name, config.size),
+    )
+    .await?;
+
+    // Flush remaining metrics
+    drop(m);
+    metrics_task_handle.await??;
+
+    print_lookup_summary(
+        &registry.snapshot(&run_id).await,
+        &format!("{}@{}", provider_name, config.size),
+    );
+
     provider.close().await?;
+    registry.finish_run(&run_id).await;
+    if let Some(results) = results {
+        results.write(&registry, &run_id).await?;
+    }
+
+    Ok(run_id)
+}
+
+/// Warm up provider connections before the timed window and record what the provider reports.
+pub async fn warm_connections(provider: &PyProvider, n: usize, m: &Recorder) -> anyhow::Result<()> {
+    let latencies = provider.warm_connections(n).await?;
+    if latencies.is_empty() {
+        return Ok(());
+    }
+
+    m.gauge("bench.connections.established", latencies.len() as f64);
+    for latency in &latencies {
+        m.observe("bench.connections.latency_ms", *latency);
+    }
+    info!(count = latencies.len(), "Warmed up connections");
 
     Ok(())
 }
 
 // Spawn batch producer task
 pub fn spawn_batch_producer(
-    file: File,
+    source: Source,
     batch_size: usize,
+    mut transform: DocumentTransform,
+    tally: Tally,
+    mut read_ahead: ReadAhead,
+    abort: CancellationToken,
     tx: Sender<Vec<Document>>,
 ) -> JoinHandle<anyhow::Result<()>> {
     tokio::task::spawn_blocking(move || {
-        let mut batch_reader = ParquetRecordBatchReader::try_new(file, batch_size)?;
+        let (_, batches) = source.batches(batch_size)?;
 
-        while let Some(batch) = batch_reader.next() {
-            let documents = parse_from_batch(batch?);
+        for batch in batches {
+            if abort.is_cancelled() {
+                break;
+            }
+            let mut documents = parse_from_batch(batch?);
+            for doc in documents.iter_mut() {
+                transform.apply(doc)?;
+            }
+            tally.read(documents.len());
 
-            // Use send_blocking since we're in a blocking task
-            tx.send_blocking(documents)?;
+            // Blocking, since we're in a blocking task
+            read_ahead.send_blocking(&tx, documents, &abort)?;
         }
 
         Ok(())
@@ -117,33 +467,53 @@ pub fn spawn_batch_producer(
 }
 
 // Spawn writer tasks
+#[allow(clippy::too_many_arguments)]
 pub async fn spawn_writers(
     provider: PyProvider,
     collection: String,
     concurrency: usize,
+    ids: IdCodec,
+    freshness: FreshnessProbes,
+    max_attempts: Option<u32>,
+    recent: Option<RecentWrites>,
+    tally: Tally,
+    checksums: Option<ChecksumSample>,
+    quarantine: Option<Quarantine>,
     m: Recorder,
     rx: Receiver<Vec<Document>>,
-) -> anyhow::Result<()> {
-    let mut writers = JoinSet::<anyhow::Result<()>>::new();
+) -> anyhow::Result<u64> {
+    let mut writers = JoinSet::<anyhow::Result<u64>>::new();
 
-    for _ in 0..concurrency {
+    for worker_id in 0..concurrency {
+        let worker_id = worker_id.to_string();
         let collection = collection.clone();
         let rx = rx.clone();
         let provider = provider.clone();
         let m = m.clone();
+        let ids = ids.clone();
+        let freshness = freshness.clone();
+        let recent = recent.clone();
+        let tally = tally.clone();
+        let checksums = checksums.clone();
+        let quarantine = quarantine.clone();
 
         writers.spawn(async move {
             // Spawn freshness tasks
             let mut freshness_tasks = JoinSet::new();
+            let mut upserted = 0;
 
             // Writer task
-            loop {
+            'writer: loop {
                 let recv_start = Instant::now();
-                let documents = match rx.recv().await {
-                    Ok(documents) => documents,
-                    Err(_) => break, // Channel closed
+                let documents = tokio::select! {
+                    biased;
+                    _ = provider.aborted() => break,
+                    documents = rx.recv() => match documents {
+                        Ok(documents) => documents,
+                        Err(_) => break, // Channel closed
+                    },
                 };
-                m.record(
+                m.observe(
                     "bench.ingest.recv_latency_ms",
                     recv_start.elapsed().as_millis() as f64,
                 );
@@ -151,51 +521,107 @@ pub async fn spawn_writers(
                 let doc_count = documents.len();
                 let provider = provider.clone();
 
-                // Upsert loop
-                loop {
-                    let documents = documents.clone();
+                // Calculate encoded size from parsed documents
+                let byte_size: usize = documents.iter().map(|doc| doc.approx_size()).sum();
 
-                    // Calculate encoded size from parsed documents
-                    let byte_size: usize = documents.iter().map(|doc| doc.approx_size()).sum();
+                // Find the document with the max ID in the batch
+                let max_id = documents
+                    .iter()
+                    .max_by_key(|doc| ids.decode(&doc.id))
+                    .map(|doc| doc.id.clone())
+                    .expect("Failed to find max ID");
 
-                    // Calculate max ID from batch
-                    let max_id = documents
-                        .iter()
-                        .map(|doc| doc.id.parse::<u64>().expect("Failed to parse ID as u64"))
-                        .max()
-                        .expect("Failed to find max ID")
-                        .to_string();
+                // Shared across retries, so the batch isn't copied per attempt
+                let batch = Batch::new(documents);
 
+                // Upsert loop
+                let mut attempts = 0;
+                loop {
+                    attempts += 1;
                     let s = Instant::now();
-                    let result = provider.upsert(collection.clone(), documents).await;
+                    let result = provider.upsert(collection.clone(), batch.clone()).await;
 
-                    m.record("bench.ingest.requests", 1.0);
+                    m.incr("bench.ingest.requests", 1.0);
                     match result {
-                        Ok(_) => {
-                            m.record("bench.ingest.oks", 1.0);
-                            m.record("bench.ingest.upserted_docs", doc_count as f64);
-                            m.record("bench.ingest.upserted_bytes", byte_size as f64);
-                            m.record("bench.ingest.latency_ms", s.elapsed().as_millis() as f64);
-
-                            // After a successful upsert, measure the freshness of the document.
-                            freshness_tasks.spawn(measure_freshness(
-                                m.clone(),
-                                provider.clone(),
-                                collection.clone(),
-                                max_id,
-                            ));
+                        Ok(ack) => {
+                            let accepted = ack.accepted(doc_count);
+                            let durability = ack.durability();
+                            upserted += accepted as u64;
+                            tally.acknowledged(accepted, ack.queued == Some(true));
+                            // Documents the provider rejected are as lost as a failed batch
+                            tally.failed(doc_count - accepted);
+                            m.incr("bench.ingest.oks", 1.0);
+                            m.incr("bench.ingest.upserted_docs", doc_count as f64);
+                            m.incr("bench.ingest.upserted_bytes", byte_size as f64);
+                            m.incr_with(
+                                "bench.ingest.accepted_docs",
+                                accepted as f64,
+                                &[("ack", durability)],
+                            );
+                            if accepted < doc_count {
+                                m.incr("bench.ingest.rejected_docs", (doc_count - accepted) as f64);
+                            }
+                            match ack.request_bytes {
+                                Some(bytes) => m.incr_with(
+                                    "bench.ingest.request_bytes",
+                                    bytes as f64,
+                                    &[("request_bytes_source", "provider")],
+                                ),
+                                None => m.incr_with(
+                                    "bench.ingest.request_bytes",
+                                    batch.json_size() as f64,
+                                    &[("request_bytes_source", "estimate")],
+                                ),
+                            }
+                            let latency_ms = s.elapsed().as_millis() as f64;
+                            m.observe_with(
+                                "bench.ingest.latency_ms",
+                                latency_ms,
+                                &[("worker_id", worker_id.as_str()), ("ack", durability)],
+                            );
+                            if let Some(request_id) = &ack.request_id {
+                                debug!(worker_id, request_id, latency_ms, "Upserted batch");
+                            }
+
+                            // Which documents of a partially accepted batch were rejected
+                            // is unknown, so none of them are read back
+                            if accepted == doc_count {
+                                if let Some(recent) = &recent {
+                                    for doc in batch.docs.iter() {
+                                        recent.push(doc);
+                                    }
+                                }
+                                if let Some(checksums) = &checksums {
+                                    checksums.offer(&batch.docs, ack.queued == Some(true));
+                                }
+
+                                // After a successful upsert, measure the freshness of the document.
+                                freshness.spawn(
+                                    &mut freshness_tasks,
+                                    &m,
+                                    provider.clone(),
+                                    collection.clone(),
+                                    max_id.clone(),
+                                );
+                            }
 
                             break;
                         }
+                        Err(_) if provider.is_aborted() => {
+                            tally.failed(doc_count);
+                            break 'writer;
+                        }
                         Err(error) => {
-                            m.record("bench.ingest.errors", 1.0);
-
-                            // TODO: use signal to propagate to the `tokio::select!` block
-                            if error.to_string().contains("KeyboardInterrupt") {
-                                info!("Keyboard interrupt received, aborting writers");
+                            let class = ErrorClass::of(&error);
+                            m.incr(&format!("bench.ingest.errors.{}", class.as_str()), 1.0);
+                            error!(?error, "Failed to upsert documents");
+                            if max_attempts.is_some_and(|max| attempts >= max) {
+                                error!(attempts, doc_count, "Giving up on batch");
+                                tally.failed(doc_count);
+                                if let Some(quarantine) = &quarantine {
+                                    quarantine.add(batch.docs.clone(), &error, attempts);
+                                }
                                 break;
-                            } else {
-                                error!(?error, "Failed to upsert documents");
                             }
 
                             // Sleep
@@ -204,6 +630,13 @@ pub async fn spawn_writers(
                         }
                     }
                 }
+
+                if let Some(conversion) = batch.conversion_time() {
+                    m.observe(
+                        "bench.ingest.conversion_ms",
+                        conversion.as_secs_f64() * 1000.0,
+                    );
+                }
             }
 
             // Wait for freshness tasks
@@ -211,57 +644,149 @@ pub async fn spawn_writers(
                 res??;
             }
 
-            Ok(())
+            Ok(upserted)
         });
     }
 
     // Spawn writer clients
+    let mut upserted = 0;
     while let Some(res) = writers.join_next().await {
-        res??;
+        upserted += res??;
     }
 
+    Ok(upserted)
+}
+
+/// Block until the provider reports at least `expected` documents as indexed.
+async fn wait_for_indexing(
+    provider: &PyProvider,
+    collection: &str,
+    expected: u64,
+    m: &Recorder,
+) -> anyhow::Result<()> {
+    info!(expected, "Waiting for indexing...");
+    let start = Instant::now();
+
+    loop {
+        let Some(indexed) = provider.num_indexed(collection.to_string()).await? else {
+            warn!("Provider does not implement `num_indexed`, not waiting for indexing");
+            return Ok(());
+        };
+
+        if indexed >= expected {
+            break;
+        }
+
+        debug!(indexed, expected, "Indexing in progress");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    let duration = start.elapsed();
+    m.observe(
+        "bench.ingest.indexing_drain_ms",
+        duration.as_millis() as f64,
+    );
+    info!("Indexing completed in {:.2}s", duration.as_secs_f64());
+
     Ok(())
 }
 
 // metrics reporter task
-async fn spawn_metrics_reporter(run_id: String, prefix: String) -> anyhow::Result<()> {
-    let mut ticker = tokio::time::interval(Duration::from_secs(1));
-    // Skip the immediate first tick to align with 1-second boundaries
-    ticker.tick().await;
+async fn spawn_metrics_reporter(
+    registry: Registry,
+    run_id: String,
+    prefix: String,
+    reporting: Reporting,
+    on_stats: Option<StatsCallback>,
+) -> anyhow::Result<()> {
+    let mut ticker = reporting.ticker().await;
 
     loop {
-        // Sleep for 1 second
         ticker.tick().await;
 
         // Get current stats
-        let stats = snapshot_metrics(&run_id).await;
+        let stats = reporting.snapshot(&registry, &run_id).await;
 
         // Check if metrics exist (not just if they're zero)
         if stats.is_empty() {
-            println!("{}] Waiting for metrics...", prefix);
+            report(format!("{}] Waiting for metrics...", prefix));
             continue;
         }
 
-        print_writer_stats(&stats, prefix.clone())
+        print_writer_stats(&stats, prefix.clone());
+
+        if let Some(on_stats) = &on_stats {
+            if !on_stats.call(&run_id, writer_stats(&stats)).await? {
+                info!("Stats callback requested stop, aborting ingest");
+                return Ok(());
+            }
+        }
     }
 }
 
+/// Writer stats passed to the stats callback.
+pub fn writer_stats(stats: &Snapshot) -> Vec<(&'static str, f64)> {
+    let requests_total = stats.total("bench.ingest.requests");
+    let errors_total = stats.total_prefix("bench.ingest.errors.");
+
+    vec![
+        ("ingest_requests", requests_total),
+        ("ingest_errors", errors_total),
+        (
+            "ingest_upserted_bytes",
+            stats.total("bench.ingest.upserted_bytes"),
+        ),
+        (
+            "ingest_request_bytes",
+            stats.total("bench.ingest.request_bytes"),
+        ),
+        (
+            "ingest_throughput_bytes",
+            stats.instantaneous_rate("bench.ingest.upserted_bytes"),
+        ),
+        (
+            "ingest_latency_p50_ms",
+            stats.quantile("bench.ingest.latency_ms", 0.50),
+        ),
+        (
+            "ingest_latency_p99_ms",
+            stats.quantile("bench.ingest.latency_ms", 0.99),
+        ),
+        (
+            "ingest_freshness_max_ms",
+            stats.quantile("bench.ingest.freshness_latency_ms", 1.0),
+        ),
+        (
+            "ingest_lookup_availability",
+            lookup_availability(stats, "bench.ingest").unwrap_or(100.0),
+        ),
+        (
+            "ingest_lookup_latency_p50_ms",
+            stats.quantile("bench.ingest.query_by_id_latency_ms", 0.50),
+        ),
+        (
+            "ingest_lookup_latency_p99_ms",
+            stats.quantile("bench.ingest.query_by_id_latency_ms", 0.99),
+        ),
+    ]
+}
+
 pub fn print_writer_stats(stats: &Snapshot, prefix: String) {
     let requests_total = stats.total("bench.ingest.requests");
-    let errors_total = stats.total("bench.ingest.errors");
+    let errors_total = stats.total_prefix("bench.ingest.errors.");
     let availability = if requests_total > 0.0 {
         (1.0 - errors_total / requests_total) * 100.0
     } else {
         100.0
     };
 
-    println!(
-        "{prefix:>16}] {} {} Throughput: {}, Latency: {}, {}{}{}",
+    report(format!(
+        "{prefix:>16}] {} {} Throughput: {}, Latency: {}, {}{}{}{}",
         // Availability
         match availability {
-            a if a == 100.0 => format!("100%").green().bold(),
+            100.0 => "100%".to_string().green().bold(),
             a if a > 99.0 => format!("{:.2}%", a).yellow().bold(),
-            a if a.is_nan() => format!("...").bold(),
+            a if a.is_nan() => "...".to_string().bold(),
             a => format!("{:.2}%", a).red().bold(),
         },
         // Total
@@ -303,6 +828,8 @@ pub fn print_writer_stats(stats: &Snapshot, prefix: String) {
                     .to_string()
             }
         },
+        // Freshness probe lookups
+        format_lookups(stats, "bench.ingest"),
         // Recv
         {
             let recv_max = stats.quantile("bench.ingest.recv_latency_ms", 1.0);
@@ -312,38 +839,92 @@ pub fn print_writer_stats(stats: &Snapshot, prefix: String) {
                 format!(", Skew max={:.2}ms", recv_max).bold().to_string()
             }
         },
-    );
+    ));
 }
 
-/// Measure the freshness of a document by querying it until it is found.
-async fn measure_freshness(
-    m: Recorder,
-    provider: PyProvider,
-    collection: String,
-    id: String,
-) -> anyhow::Result<()> {
-    let start = Instant::now();
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use pyo3::prelude::*;
+    use pyo3::types::PyDict;
+
+    use super::*;
+    use crate::provider::{Provider, UpsertAck};
+    use crate::telemetry::metrics::Registry;
+
+    /// Provider rejecting every document it is sent, which it then never finds.
+    fn rejecting_provider() -> PyProvider {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| -> PyResult<PyProvider> {
+            let globals = PyDict::new(py);
+            globals.set_item("Provider", py.get_type::<Provider>())?;
+            globals.set_item("UpsertAck", py.get_type::<UpsertAck>())?;
+            let code = CString::new(
+                "class Rejecting(Provider):\n\
+                 \x20   def upsert(self, collection, docs, **kwargs):\n\
+                 \x20       return UpsertAck(accepted=0)\n\
+                 \x20   def query_by_id(self, collection, id, **kwargs):\n\
+                 \x20       return []\n\
+                 provider = Rejecting()\n",
+            )?;
+            py.run(&code, Some(&globals), None)?;
+            globals.get_item("provider")?.unwrap().extract()
+        })
+        .unwrap()
+    }
 
-    loop {
-        // TODO: latency of `query_by_id`
-        let s = Instant::now();
-        let doc = provider.query_by_id(collection.clone(), id.clone()).await?;
-        m.record(
-            "bench.ingest.query_by_id_latency_ms",
-            s.elapsed().as_millis() as f64,
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rejected_batches_are_not_read_back() {
+        let registry = Registry::default();
+        let (tx, rx) = registry.channel();
+        let m = Recorder::new(tx, [("run_id", "run")]);
+        let recent = RecentWrites::new(10);
+        let checksums = ChecksumSample::new(10);
+        let tally = Tally::default();
+
+        let (batches_tx, batches_rx) = async_channel::bounded(1);
+        let docs = (0..3)
+            .map(|i| Document {
+                id: i.to_string(),
+                text: format!("doc {i}"),
+                int_filter: 0,
+                keyword_filter: String::new(),
+                dense_embedding: Some(vec![1.0, 0.0]),
+                tag: None,
+                namespace: None,
+                score: None,
+            })
+            .collect();
+        batches_tx.send(docs).await.unwrap();
+        drop(batches_tx);
+
+        // Probing the rejected batch's max ID would poll for it forever
+        let writers = spawn_writers(
+            rejecting_provider(),
+            "rejecting".to_string(),
+            1,
+            IdCodec::default(),
+            FreshnessProbes::unbounded(None),
+            None,
+            Some(recent.clone()),
+            tally.clone(),
+            Some(checksums.clone()),
+            None,
+            m,
+            batches_rx,
         );
-
-        if doc.is_some() {
-            break;
-        }
-
-        tokio::time::sleep(Duration::from_millis(10)).await;
+        let upserted = tokio::time::timeout(Duration::from_secs(10), writers)
+            .await
+            .expect("writers waited on a probe")
+            .unwrap();
+        registry.clone().consume(rx).await.unwrap();
+
+        assert_eq!(upserted, 0);
+        assert!(recent.sample().is_none());
+        assert!(checksums.is_empty());
+        let stats = registry.snapshot("run").await;
+        assert_eq!(stats.total("bench.ingest.rejected_docs"), 3.0);
+        assert_eq!(stats.count("bench.ingest.query_by_id_requests"), 0);
     }
-
-    m.record(
-        "bench.ingest.freshness_latency_ms",
-        start.elapsed().as_millis() as f64,
-    );
-
-    Ok(())
 }
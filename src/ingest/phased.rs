@@ -0,0 +1,197 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use async_channel::Sender;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::data::{parse_from_batch, Document, DocumentTransform};
+use crate::ingest::{
+    spawn_writers, wait_for_indexing, ChecksumSample, IngestConfig, Quarantine, ReadAhead, Source,
+    Tally,
+};
+use crate::provider::PyProvider;
+use crate::query::{self, QueryConfig};
+use crate::telemetry::metrics::{Recorder, Registry};
+use crate::telemetry::RunIds;
+
+/// Two-phase ingest: bulk load a fraction of the input at full speed, wait for the
+/// provider to index it, then trickle in the rest at a fixed rate.
+#[derive(Debug, Clone)]
+pub struct Phases {
+    /// Fraction of the input loaded in the bulk phase
+    pub bulk_fraction: f64,
+    /// Documents/s written in the trickle phase
+    pub trickle_rate: f64,
+    /// Queries run against the collection during the trickle phase, as a child run
+    pub queries: Option<QueryConfig>,
+}
+
+/// Run both phases, labeling their metrics with `phase`. Returns the number of
+/// documents upserted.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_phases(
+    phases: Phases,
+    provider: PyProvider,
+    config: IngestConfig,
+    source: Source,
+    tally: Tally,
+    checksums: Option<ChecksumSample>,
+    quarantine: Option<Quarantine>,
+    m: Recorder,
+    registry: Registry,
+    run_id: String,
+    abort: CancellationToken,
+) -> anyhow::Result<u64> {
+    let (bulk_tx, bulk_rx) = async_channel::bounded::<Vec<Document>>(config.read_ahead);
+    let (trickle_tx, trickle_rx) = async_channel::bounded::<Vec<Document>>(config.read_ahead);
+    let (go_tx, go_rx) = oneshot::channel();
+    let producer = spawn_phased_producer(
+        source,
+        config.batch_size,
+        config.transform(&run_id),
+        tally.clone(),
+        &phases,
+        config.read_ahead(&m),
+        abort.clone(),
+        bulk_tx,
+        go_rx,
+        trickle_tx,
+    );
+
+    let writers = |m: Recorder, rx| {
+        spawn_writers(
+            provider.clone(),
+            config.collection.clone(),
+            config.concurrency,
+            config.ids(&run_id),
+            config.freshness(),
+            config.max_upsert_attempts,
+            None,
+            tally.clone(),
+            checksums.clone(),
+            quarantine.clone(),
+            m,
+            rx,
+        )
+    };
+
+    // Bulk phase
+    let start = Instant::now();
+    let bulk_m = m.with_label("phase", "bulk");
+    let bulk = writers(bulk_m.clone(), bulk_rx).await?;
+    info!(
+        docs = bulk,
+        "Bulk phase completed in {:.2}s",
+        start.elapsed().as_secs_f64()
+    );
+    match wait_for_indexing(&provider, &config.collection, bulk, &bulk_m).await {
+        // Provider calls fail once the run is aborted
+        _ if abort.is_cancelled() => return Ok(bulk),
+        result => result?,
+    }
+
+    // Trickle phase, with queries until it completes
+    let _ = go_tx.send(());
+    let stop_queries = abort.child_token();
+    let _stop_queries_on_drop = stop_queries.clone().drop_guard();
+    let queries = phases.queries.map(|queries| {
+        tokio::spawn(query::start(
+            queries,
+            provider.clone(),
+            registry.clone(),
+            None,
+            RunIds::new(None, Some(run_id.clone())),
+            stop_queries.clone(),
+        ))
+    });
+
+    let start = Instant::now();
+    let trickle = writers(m.with_label("phase", "trickle"), trickle_rx).await?;
+    info!(
+        docs = trickle,
+        "Trickle phase completed in {:.2}s",
+        start.elapsed().as_secs_f64()
+    );
+
+    stop_queries.cancel();
+    if let Some(queries) = queries {
+        let run_ids = queries.await??;
+        info!(?run_ids, "Trickle queries stopped");
+    }
+    producer.await??;
+
+    Ok(bulk + trickle)
+}
+
+/// Read the input, sending the first `bulk_fraction` of it to `bulk_tx` and, once `go`
+/// fires, the rest to `trickle_tx` at `trickle_rate`.
+#[allow(clippy::too_many_arguments)]
+fn spawn_phased_producer(
+    source: Source,
+    batch_size: usize,
+    mut transform: DocumentTransform,
+    tally: Tally,
+    phases: &Phases,
+    mut read_ahead: ReadAhead,
+    abort: CancellationToken,
+    bulk_tx: Sender<Vec<Document>>,
+    go: oneshot::Receiver<()>,
+    trickle_tx: Sender<Vec<Document>>,
+) -> JoinHandle<anyhow::Result<()>> {
+    let bulk_fraction = phases.bulk_fraction;
+    let trickle_rate = phases.trickle_rate;
+
+    tokio::task::spawn_blocking(move || {
+        let (rows, batch_reader) = source.batches(batch_size)?;
+        let rows = rows.context("bulk_fraction needs an input with a known number of rows")?;
+        let bulk_docs = (rows as f64 * bulk_fraction).round() as usize;
+
+        let mut bulk = Some((bulk_tx, go));
+        let mut sent = 0;
+        let mut next = Instant::now();
+
+        for batch in batch_reader {
+            if abort.is_cancelled() {
+                break;
+            }
+            let mut documents = parse_from_batch(batch?);
+            for doc in documents.iter_mut() {
+                transform.apply(doc)?;
+            }
+            tally.read(documents.len());
+
+            while !documents.is_empty() {
+                if let Some((tx, _)) = &bulk {
+                    let rest = documents.split_off(documents.len().min(bulk_docs - sent));
+                    sent += documents.len();
+                    if !documents.is_empty() {
+                        read_ahead.send_blocking(tx, documents, &abort)?;
+                    }
+                    documents = rest;
+
+                    if sent >= bulk_docs {
+                        // Closing the channel ends the bulk phase
+                        let (tx, go) = bulk.take().expect("bulk phase");
+                        drop(tx);
+                        go.blocking_recv()?;
+                        next = Instant::now();
+                        read_ahead.reset();
+                    }
+                } else {
+                    std::thread::sleep(next.saturating_duration_since(Instant::now()));
+                    next += Duration::from_secs_f64(documents.len() as f64 / trickle_rate);
+                    read_ahead.send_blocking(
+                        &trickle_tx,
+                        std::mem::take(&mut documents),
+                        &abort,
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
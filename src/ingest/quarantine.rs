@@ -0,0 +1,120 @@
+use std::sync::{Arc, Mutex};
+
+use arrow::datatypes::{DataType, Field, Float64Type, Schema};
+use arrow_array::builder::{LargeListBuilder, PrimitiveBuilder};
+use arrow_array::{ArrayRef, Int32Array, LargeStringArray, RecordBatch, StringArray, UInt32Array};
+use pyo3::PyErr;
+use tracing::info;
+
+use crate::data::Document;
+use crate::provider::ErrorClass;
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::write_batch;
+
+/// Batches given up on after exhausting their upsert attempts, kept to be written out
+/// for re-ingesting later.
+#[derive(Debug, Clone, Default)]
+pub struct Quarantine {
+    batches: Arc<Mutex<Vec<QuarantinedBatch>>>,
+}
+
+#[derive(Debug)]
+struct QuarantinedBatch {
+    docs: Arc<Vec<Document>>,
+    error: String,
+    error_class: ErrorClass,
+    attempts: u32,
+}
+
+impl Quarantine {
+    /// Quarantine `docs` after their last failed upsert.
+    pub fn add(&self, docs: Arc<Vec<Document>>, error: &PyErr, attempts: u32) {
+        self.batches
+            .lock()
+            .expect("quarantine lock poisoned")
+            .push(QuarantinedBatch {
+                docs,
+                error: error.to_string(),
+                error_class: ErrorClass::of(error),
+                attempts,
+            });
+    }
+
+    /// Write the quarantined documents to `path` (local or `s3://`) in the input schema,
+    /// with the run ID, error and attempts of their batch. Nothing is written if no batch
+    /// failed.
+    pub async fn write(&self, run_id: &str, path: &str, m: &Recorder) -> anyhow::Result<()> {
+        let batches = std::mem::take(&mut *self.batches.lock().expect("quarantine lock poisoned"));
+        let count: usize = batches.iter().map(|batch| batch.docs.len()).sum();
+        m.gauge("bench.ingest.quarantined_docs", count as f64);
+        if count == 0 {
+            return Ok(());
+        }
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::LargeUtf8, false),
+            Field::new("text", DataType::LargeUtf8, false),
+            Field::new(
+                "dense",
+                DataType::LargeList(Arc::new(Field::new_list_field(DataType::Float64, true))),
+                true,
+            ),
+            Field::new("int_filter", DataType::Int32, false),
+            Field::new("keyword_filter", DataType::LargeUtf8, false),
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("error", DataType::Utf8, false),
+            Field::new("error_class", DataType::Utf8, false),
+            Field::new("attempts", DataType::UInt32, false),
+        ]));
+
+        let docs = || {
+            batches
+                .iter()
+                .flat_map(|batch| batch.docs.iter().map(move |doc| (batch, doc)))
+        };
+
+        let mut dense = LargeListBuilder::new(PrimitiveBuilder::<Float64Type>::new());
+        for (_, doc) in docs() {
+            match &doc.dense_embedding {
+                Some(vector) => {
+                    dense
+                        .values()
+                        .append_slice(&vector.iter().map(|v| *v as f64).collect::<Vec<_>>());
+                    dense.append(true);
+                }
+                None => dense.append(false),
+            }
+        }
+
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(LargeStringArray::from_iter_values(
+                docs().map(|(_, doc)| doc.id.as_str()),
+            )),
+            Arc::new(LargeStringArray::from_iter_values(
+                docs().map(|(_, doc)| doc.text.as_str()),
+            )),
+            Arc::new(dense.finish()),
+            Arc::new(Int32Array::from_iter_values(
+                docs().map(|(_, doc)| doc.int_filter as i32),
+            )),
+            Arc::new(LargeStringArray::from_iter_values(
+                docs().map(|(_, doc)| doc.keyword_filter.as_str()),
+            )),
+            Arc::new(StringArray::from(vec![run_id; count])),
+            Arc::new(StringArray::from_iter_values(
+                docs().map(|(batch, _)| batch.error.as_str()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                docs().map(|(batch, _)| batch.error_class.as_str()),
+            )),
+            Arc::new(UInt32Array::from_iter_values(
+                docs().map(|(batch, _)| batch.attempts),
+            )),
+        ];
+
+        write_batch(RecordBatch::try_new(schema, arrays)?, path).await?;
+        info!("{count} quarantined documents written to {path}");
+
+        Ok(())
+    }
+}
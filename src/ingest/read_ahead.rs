@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use async_channel::Sender;
+use tokio_util::sync::CancellationToken;
+
+use crate::data::Document;
+use crate::telemetry::metrics::Recorder;
+
+/// How far the producer reads ahead of the writers, in batches.
+///
+/// The channel holds up to `capacity` batches. Adaptive read-ahead keeps fewer while the
+/// writers lag: the limit halves whenever the producer finds it reached, down to one batch
+/// per writer, and doubles whenever the producer finds the channel empty, meaning writers
+/// were waiting for batches.
+#[derive(Debug, Clone)]
+pub struct ReadAhead {
+    capacity: usize,
+    min: usize,
+    adaptive: bool,
+    limit: usize,
+    /// Sizes of the batches sent most recently, the ones still in the channel at the back
+    sizes: VecDeque<u64>,
+    buffered_bytes: u64,
+    m: Recorder,
+}
+
+impl ReadAhead {
+    pub fn new(capacity: usize, writers: usize, adaptive: bool, m: Recorder) -> Self {
+        Self {
+            capacity,
+            min: writers.clamp(1, capacity),
+            adaptive,
+            limit: capacity,
+            sizes: VecDeque::new(),
+            buffered_bytes: 0,
+            m,
+        }
+    }
+
+    /// Send a batch to the writers, blocking while the read-ahead is full.
+    pub fn send_blocking(
+        &mut self,
+        tx: &Sender<Vec<Document>>,
+        documents: Vec<Document>,
+        abort: &CancellationToken,
+    ) -> anyhow::Result<()> {
+        if self.adaptive {
+            if tx.is_empty() {
+                self.limit = (self.limit * 2).min(self.capacity);
+            } else if tx.len() >= self.limit {
+                self.limit = (self.limit / 2).max(self.min);
+                while tx.len() >= self.limit && !abort.is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+
+        let size: usize = documents.iter().map(|doc| doc.approx_size()).sum();
+        tx.send_blocking(documents)?;
+        self.sizes.push_back(size as u64);
+        self.buffered_bytes += size as u64;
+
+        // Batches received since are gone from the front
+        while self.sizes.len() > tx.len() {
+            let size = self.sizes.pop_front().expect("sizes not empty");
+            self.buffered_bytes -= size;
+        }
+        self.m
+            .gauge("bench.ingest.buffered_bytes", self.buffered_bytes as f64);
+        if self.adaptive {
+            self.m
+                .gauge("bench.ingest.read_ahead_batches", self.limit as f64);
+        }
+
+        Ok(())
+    }
+
+    /// Start over for a new channel.
+    pub fn reset(&mut self) {
+        self.limit = self.capacity;
+        self.sizes.clear();
+        self.buffered_bytes = 0;
+    }
+}
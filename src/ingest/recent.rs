@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use rand::prelude::*;
+
+use crate::data::Document;
+
+/// A document write acknowledged by the provider.
+#[derive(Debug, Clone)]
+pub struct RecentWrite {
+    pub id: String,
+    pub text: String,
+    pub tag: Option<String>,
+    pub namespace: Option<String>,
+    /// Only kept for tagged writes, to query for the document by its tag.
+    pub dense: Option<Arc<Vec<f32>>>,
+    pub acked: Instant,
+}
+
+/// Bounded buffer of the most recently acknowledged writes, shared between writers and readers.
+#[derive(Debug, Clone)]
+pub struct RecentWrites {
+    writes: Arc<Mutex<VecDeque<RecentWrite>>>,
+    capacity: usize,
+}
+
+impl RecentWrites {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            writes: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, doc: &Document) {
+        let write = RecentWrite {
+            id: doc.id.clone(),
+            text: doc.text.clone(),
+            tag: doc.tag.clone(),
+            namespace: doc.namespace.clone(),
+            dense: doc
+                .dense_embedding
+                .clone()
+                .filter(|_| doc.tag.is_some())
+                .map(Arc::new),
+            acked: Instant::now(),
+        };
+
+        let mut writes = self.writes.lock().expect("recent writes lock poisoned");
+        if writes.len() == self.capacity {
+            writes.pop_front();
+        }
+        writes.push_back(write);
+    }
+
+    /// A random recent write, if any.
+    pub fn sample(&self) -> Option<RecentWrite> {
+        let writes = self.writes.lock().expect("recent writes lock poisoned");
+        if writes.is_empty() {
+            return None;
+        }
+
+        let i = rand::rng().random_range(0..writes.len());
+        writes.get(i).cloned()
+    }
+}
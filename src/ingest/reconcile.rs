@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use colored::Colorize;
+use tracing::{debug, warn};
+
+use crate::provider::PyProvider;
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::output::report;
+
+/// Documents counted as they pass through an ingest run, shared by the producer and
+/// writers so the counts survive the run being stopped.
+#[derive(Debug, Clone, Default)]
+pub struct Tally {
+    read: Arc<AtomicU64>,
+    acknowledged: Arc<AtomicU64>,
+    queued: Arc<AtomicU64>,
+    failed: Arc<AtomicU64>,
+}
+
+impl Tally {
+    /// Documents read from the input file.
+    pub fn read(&self, n: usize) {
+        self.read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// Documents the provider acknowledged, `queued` if it reported only queueing them.
+    pub fn acknowledged(&self, n: usize, queued: bool) {
+        self.acknowledged.fetch_add(n as u64, Ordering::Relaxed);
+        if queued {
+            self.queued.fetch_add(n as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Total documents acknowledged so far.
+    pub fn acknowledged_docs(&self) -> u64 {
+        self.acknowledged.load(Ordering::Relaxed)
+    }
+
+    /// Documents given up on after exhausting their upsert attempts, or rejected by the
+    /// provider.
+    pub fn failed(&self, n: usize) {
+        self.failed.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+/// Reconcile the documents read, acknowledged and failed with the provider's count, then
+/// print and record the result.
+///
+/// Documents read but neither acknowledged nor failed were still waiting to be sent or in flight
+/// when the run stopped.
+pub async fn reconcile(
+    provider: &PyProvider,
+    collection: &str,
+    tally: &Tally,
+    m: &Recorder,
+    prefix: &str,
+) -> anyhow::Result<()> {
+    let read = tally.read.load(Ordering::Relaxed);
+    let acknowledged = tally.acknowledged.load(Ordering::Relaxed);
+    let queued = tally.queued.load(Ordering::Relaxed);
+    let failed = tally.failed.load(Ordering::Relaxed);
+    let unwritten = read.saturating_sub(acknowledged + failed);
+
+    m.gauge("bench.ingest.read_docs", read as f64);
+    m.gauge("bench.ingest.acknowledged_docs", acknowledged as f64);
+    m.gauge("bench.ingest.queued_docs", queued as f64);
+    m.gauge("bench.ingest.failed_docs", failed as f64);
+    m.gauge("bench.ingest.unwritten_docs", unwritten as f64);
+
+    let count = provider.count(collection.to_string()).await?;
+    let missing = match count {
+        Some(count) => {
+            let missing = acknowledged.saturating_sub(count);
+            m.gauge("bench.ingest.provider_docs", count as f64);
+            m.gauge("bench.ingest.missing_docs", missing as f64);
+            Some(missing)
+        }
+        None => {
+            debug!("Provider does not implement `count`, skipping count verification");
+            None
+        }
+    };
+
+    report(format!(
+        "{:>16}] {} Read: {}, Acknowledged: {} ({} queued), Failed: {}, Unwritten: {}, Provider count: {}, Missing: {}",
+        prefix,
+        "Reconciliation:".green().bold(),
+        read.to_string().bold(),
+        acknowledged.to_string().blue().bold(),
+        queued.to_string().yellow().bold(),
+        failed.to_string().red().bold(),
+        unwritten.to_string().yellow().bold(),
+        count.map_or("n/a".to_string(), |c| c.to_string()).bold(),
+        missing.map_or("n/a".to_string(), |c| c.to_string()).red().bold(),
+    ));
+
+    if failed > 0 || unwritten > 0 || missing.is_some_and(|m| m > 0) {
+        warn!(
+            read,
+            acknowledged,
+            failed,
+            unwritten,
+            ?count,
+            ?missing,
+            "Not all documents made it into the collection"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use async_channel::Receiver;
+use colored::Colorize;
+use rand::prelude::*;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+use super::freshness::{format_lookups, lookup};
+use crate::{
+    data::Document,
+    provider::{ErrorClass, PyProvider},
+    telemetry::{metrics::Recorder, output::report, Snapshot},
+};
+
+/// Spawn update tasks that patch the `text` of each received document in place.
+///
+/// Returns the number of acknowledged updates.
+pub async fn spawn_updaters(
+    provider: PyProvider,
+    collection: String,
+    concurrency: usize,
+    consistency: Option<String>,
+    max_attempts: Option<u32>,
+    m: Recorder,
+    rx: Receiver<Vec<Document>>,
+) -> anyhow::Result<u64> {
+    let mut updaters = JoinSet::<anyhow::Result<u64>>::new();
+
+    for _ in 0..concurrency {
+        let collection = collection.clone();
+        let rx = rx.clone();
+        let provider = provider.clone();
+        let m = m.clone();
+        let consistency = consistency.clone();
+
+        updaters.spawn(async move {
+            let mut freshness_tasks = JoinSet::new();
+            let mut updated = 0;
+
+            while let Ok(documents) = rx.recv().await {
+                let last = documents.len().saturating_sub(1);
+
+                for (i, doc) in documents.into_iter().enumerate() {
+                    // Append a revision marker so the patch is observable
+                    let text = format!("{} rev-{}", doc.text, rand::rng().random::<u32>());
+                    let fields = HashMap::from([("text".to_string(), text.clone())]);
+
+                    match update_one(&provider, &collection, &doc.id, fields, max_attempts, &m)
+                        .await
+                    {
+                        Updated::Ok => updated += 1,
+                        // Nothing to probe for an update that never landed
+                        Updated::Failed => continue,
+                        Updated::Aborted => {
+                            info!("Run aborted, stopping updaters");
+                            return Ok(updated);
+                        }
+                    }
+
+                    // Measure freshness of the last update in each batch
+                    if i == last {
+                        freshness_tasks.spawn(measure_update_freshness(
+                            m.clone(),
+                            provider.clone(),
+                            collection.clone(),
+                            doc.id,
+                            text,
+                            consistency.clone(),
+                        ));
+                    }
+                }
+            }
+
+            while let Some(res) = freshness_tasks.join_next().await {
+                res??;
+            }
+
+            Ok(updated)
+        });
+    }
+
+    let mut updated = 0;
+    while let Some(res) = updaters.join_next().await {
+        updated += res??;
+    }
+
+    Ok(updated)
+}
+
+/// Outcome of [`update_one`].
+enum Updated {
+    Ok,
+    /// Gave up after `max_attempts` failed attempts.
+    Failed,
+    Aborted,
+}
+
+/// Update a single document, retrying on errors up to `max_attempts` times.
+async fn update_one(
+    provider: &PyProvider,
+    collection: &str,
+    id: &str,
+    fields: HashMap<String, String>,
+    max_attempts: Option<u32>,
+    m: &Recorder,
+) -> Updated {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let s = Instant::now();
+        let result = provider
+            .update(collection.to_string(), id.to_string(), fields.clone())
+            .await;
+
+        m.incr("bench.update.requests", 1.0);
+        match result {
+            Ok(()) => {
+                m.incr("bench.update.oks", 1.0);
+                m.observe("bench.update.latency_ms", s.elapsed().as_millis() as f64);
+                return Updated::Ok;
+            }
+            Err(_) if provider.is_aborted() => return Updated::Aborted,
+            Err(error) => {
+                let class = ErrorClass::of(&error);
+                m.incr(&format!("bench.update.errors.{}", class.as_str()), 1.0);
+                error!(?error, "Failed to update document");
+                if max_attempts.is_some_and(|max| attempts >= max) {
+                    error!(attempts, id, "Giving up on update");
+                    m.incr("bench.update.failed", 1.0);
+                    return Updated::Failed;
+                }
+
+                let jitter = rand::rng().random_range(10..100);
+                tokio::time::sleep(Duration::from_millis(jitter)).await;
+            }
+        }
+    }
+}
+
+/// Measure how long it takes until an update is visible by querying the document by ID.
+async fn measure_update_freshness(
+    m: Recorder,
+    provider: PyProvider,
+    collection: String,
+    id: String,
+    text: String,
+    consistency: Option<String>,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+
+    loop {
+        let Some(doc) = lookup(
+            &m,
+            "bench.update",
+            &provider,
+            &collection,
+            &id,
+            consistency.clone(),
+        )
+        .await
+        else {
+            m.incr("bench.update.freshness_abandoned", 1.0);
+            return Ok(());
+        };
+
+        if doc.is_some_and(|doc| doc.text == text) {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    m.observe(
+        "bench.update.freshness_latency_ms",
+        start.elapsed().as_millis() as f64,
+    );
+
+    Ok(())
+}
+
+pub fn print_update_stats(stats: &Snapshot, prefix: String) {
+    let requests_total = stats.total("bench.update.requests");
+    let errors_total = stats.total_prefix("bench.update.errors.");
+    let availability = if requests_total > 0.0 {
+        (1.0 - errors_total / requests_total) * 100.0
+    } else {
+        100.0
+    };
+
+    report(format!(
+        "{prefix:>16}] {} Updates: {}, Latency: {}, {}{}{}",
+        // Availability
+        match availability {
+            100.0 => "100%".to_string().green().bold(),
+            a if a > 99.0 => format!("{:.2}%", a).yellow().bold(),
+            a => format!("{:.2}%", a).red().bold(),
+        },
+        // Throughput
+        format!(
+            "{:.2} updates/s",
+            stats.instantaneous_rate("bench.update.oks")
+        )
+        .blue()
+        .bold(),
+        // Latency
+        format!(
+            "p50={:.2}ms",
+            stats.quantile("bench.update.latency_ms", 0.50)
+        )
+        .yellow()
+        .bold(),
+        format!(
+            "p99={:.2}ms",
+            stats.quantile("bench.update.latency_ms", 0.99)
+        )
+        .magenta()
+        .bold(),
+        // Freshness
+        {
+            let freshness_max = stats.quantile("bench.update.freshness_latency_ms", 1.0);
+            if freshness_max == 0.0 {
+                "".to_string()
+            } else {
+                format!(", Freshness max={:.2}ms", freshness_max)
+                    .bold()
+                    .to_string()
+            }
+        },
+        // Freshness probe lookups
+        format_lookups(stats, "bench.update"),
+    ));
+}
@@ -1,15 +1,27 @@
-use colored::control;
 use once_cell::sync::Lazy;
-use pyo3::{exceptions::PyValueError, prelude::*};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Mutex;
+use std::time::Duration;
 use tokio::runtime::Runtime;
+use tokio_util::sync::CancellationToken;
 
+mod backfill;
+mod backup;
+mod compare;
+mod delete;
 mod ingest;
 mod query;
 
+mod cache;
+mod calibrate;
 mod data;
+mod dispatch;
+mod handle;
 mod provider;
 mod s3;
+mod session;
 mod telemetry;
 
 pub(crate) static RUNTIME: Lazy<Mutex<Option<Runtime>>> = Lazy::new(|| {
@@ -22,17 +34,42 @@ pub(crate) static RUNTIME: Lazy<Mutex<Option<Runtime>>> = Lazy::new(|| {
 
 #[pymodule]
 fn topk_bench(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
-    // Force colored output
-    control::set_override(true);
-
     m.add_class::<data::Document>()?;
+    m.add_class::<data::Query>()?;
+    m.add_class::<data::Vector>()?;
     m.add_class::<provider::Provider>()?;
+    m.add_class::<provider::SimulatedProvider>()?;
+    m.add_class::<provider::SetupConfig>()?;
+    m.add_class::<provider::UpsertAck>()?;
     m.add_class::<query::QueryConfig>()?;
     m.add_class::<ingest::IngestConfig>()?;
+    m.add_class::<delete::DeleteConfig>()?;
+    m.add_class::<backup::BackupConfig>()?;
+    m.add_class::<backfill::BackfillConfig>()?;
+    m.add_class::<session::Session>()?;
+    m.add_class::<telemetry::PySnapshot>()?;
+    m.add_class::<handle::RunHandle>()?;
 
     m.add_function(wrap_pyfunction!(ingest_fn, m)?)?;
     m.add_function(wrap_pyfunction!(query_fn, m)?)?;
+    m.add_function(wrap_pyfunction!(ingest_async, m)?)?;
+    m.add_function(wrap_pyfunction!(query_async, m)?)?;
+    m.add_function(wrap_pyfunction!(delete_fn, m)?)?;
+    m.add_function(wrap_pyfunction!(backup_fn, m)?)?;
+    m.add_function(wrap_pyfunction!(backfill_fn, m)?)?;
+    m.add_function(wrap_pyfunction!(compare_fn, m)?)?;
     m.add_function(wrap_pyfunction!(write_metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot, m)?)?;
+    m.add_function(wrap_pyfunction!(purge_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_dataset, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_queries, m)?)?;
+    m.add_function(wrap_pyfunction!(register_datasets, m)?)?;
+    m.add_function(wrap_pyfunction!(datasets, m)?)?;
+    m.add_function(wrap_pyfunction!(calibrate_fn, m)?)?;
+    m.add_function(wrap_pyfunction!(set_output, m)?)?;
+    m.add_function(wrap_pyfunction!(init_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(set_python_threads, m)?)?;
+    m.add("METRICS_SCHEMA_VERSION", telemetry::SCHEMA_VERSION)?;
 
     // Install telemetry
     py.allow_threads(|| {
@@ -45,6 +82,13 @@ fn topk_bench(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     })
     .map_err(|e| PyValueError::new_err(format!("Failed to init telemetry: {e}")))?;
 
+    if let Ok(threads) = std::env::var(dispatch::THREADS_VAR) {
+        let threads = threads.parse().map_err(|e| {
+            PyValueError::new_err(format!("Invalid {}: {e}", dispatch::THREADS_VAR))
+        })?;
+        set_python_threads(Some(threads))?;
+    }
+
     // Register cleanup function to shut down Tokio runtime before Python finalizes
     // This prevents GIL errors from Tokio threads trying to access Python during shutdown
     let cleanup_fn = wrap_pyfunction!(shutdown_runtime, m)?;
@@ -58,8 +102,14 @@ fn topk_bench(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
 /// This prevents Tokio threads from trying to access Python after it has started finalizing.
 #[pyfunction]
 fn shutdown_runtime(py: Python<'_>) {
+    // Python's logging can't be called into once it finalizes
+    if telemetry::output::current() == telemetry::output::Output::Python {
+        let _ = telemetry::output::set(telemetry::output::Output::Plain);
+    }
+
     // Tokio threads might try to access Python during shutdown, so we release the GIL first
     py.allow_threads(|| {
+        dispatch::shutdown();
         if let Ok(mut runtime_guard) = RUNTIME.lock() {
             let _runtime = runtime_guard.take();
             // Runtime is dropped here
@@ -67,58 +117,404 @@ fn shutdown_runtime(py: Python<'_>) {
     });
 }
 
+/// Run a future on the shared runtime.
+///
+/// The runtime lock is only held while cloning the handle, so several sessions
+/// can block on the runtime concurrently from different Python threads.
+fn block_on<F: Future>(future: F) -> anyhow::Result<F::Output> {
+    Ok(runtime_handle()?.block_on(future))
+}
+
+fn runtime_handle() -> anyhow::Result<tokio::runtime::Handle> {
+    RUNTIME
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|runtime| runtime.handle().clone())
+        .ok_or_else(|| anyhow::anyhow!("Runtime was shut down"))
+}
+
+/// Run an ingest benchmark, returning its run ID.
 #[pyfunction(name = "ingest")]
-#[pyo3(signature = (provider, config))]
+#[pyo3(signature = (provider, config, session=None, on_stats=None, run_id=None, parent_run_id=None))]
 pub(crate) fn ingest_fn(
     py: Python<'_>,
     provider: provider::PyProvider,
     config: ingest::IngestConfig,
-) -> PyResult<()> {
+    session: Option<session::Session>,
+    on_stats: Option<Py<PyAny>>,
+    run_id: Option<String>,
+    parent_run_id: Option<String>,
+) -> PyResult<String> {
+    let registry = session::registry(session);
+    let on_stats = on_stats.map(telemetry::StatsCallback::new);
+    let ids = telemetry::RunIds::new(run_id, parent_run_id);
+
     py.allow_threads(|| {
-        let runtime_guard = RUNTIME.lock().unwrap();
-        if let Some(ref runtime) = *runtime_guard {
-            runtime.block_on(async move { ingest::start(provider, config).await })
-        } else {
-            Err(anyhow::anyhow!("Runtime was shut down"))
-        }
+        block_on(ingest::start(
+            provider,
+            config,
+            registry,
+            on_stats,
+            ids,
+            CancellationToken::new(),
+        ))?
     })
-    .map_err(|e| PyValueError::new_err(format!("Failed to ingest: {e}")))?;
-
-    Ok(())
+    .map_err(|e| PyValueError::new_err(format!("Failed to ingest: {e}")))
 }
 
+/// Run a query benchmark, returning the run IDs, one per search parameter set.
 #[pyfunction(name = "query")]
-#[pyo3(signature = (provider, config))]
+#[pyo3(signature = (provider, config, session=None, on_stats=None, run_id=None, parent_run_id=None))]
 pub(crate) fn query_fn(
     py: Python<'_>,
     provider: provider::PyProvider,
     config: query::QueryConfig,
-) -> PyResult<()> {
+    session: Option<session::Session>,
+    on_stats: Option<Py<PyAny>>,
+    run_id: Option<String>,
+    parent_run_id: Option<String>,
+) -> PyResult<Vec<String>> {
+    let registry = session::registry(session);
+    let on_stats = on_stats.map(telemetry::StatsCallback::new);
+    let ids = telemetry::RunIds::new(run_id, parent_run_id);
+
     py.allow_threads(|| {
-        let runtime_guard = RUNTIME.lock().unwrap();
-        if let Some(ref runtime) = *runtime_guard {
-            runtime.block_on(async move { query::start(config, provider).await })
-        } else {
-            Err(anyhow::anyhow!("Runtime was shut down"))
-        }
+        block_on(query::start(
+            config,
+            provider,
+            registry,
+            on_stats,
+            ids,
+            CancellationToken::new(),
+        ))?
     })
-    .map_err(|e| PyValueError::new_err(format!("Failed to query: {e:?}")))?;
+    .map_err(|e| PyValueError::new_err(format!("Failed to query: {e:?}")))
+}
+
+/// Start an ingest benchmark in the background, returning a handle to it.
+#[pyfunction]
+#[pyo3(signature = (provider, config, session=None, on_stats=None, run_id=None, parent_run_id=None))]
+pub(crate) fn ingest_async(
+    provider: provider::PyProvider,
+    config: ingest::IngestConfig,
+    session: Option<session::Session>,
+    on_stats: Option<Py<PyAny>>,
+    run_id: Option<String>,
+    parent_run_id: Option<String>,
+) -> PyResult<handle::RunHandle> {
+    let registry = session::registry(session);
+    let on_stats = on_stats.map(telemetry::StatsCallback::new);
+    let ids = telemetry::RunIds::new(run_id, parent_run_id);
+    let abort = CancellationToken::new();
+
+    let runtime = runtime_handle().map_err(|e| PyValueError::new_err(format!("{e}")))?;
+    let future = ingest::start(provider, config, registry, on_stats, ids, abort.clone());
+    Ok(handle::RunHandle::spawn(&runtime, abort, future))
+}
+
+/// Start a query benchmark in the background, returning a handle to it.
+#[pyfunction]
+#[pyo3(signature = (provider, config, session=None, on_stats=None, run_id=None, parent_run_id=None))]
+pub(crate) fn query_async(
+    provider: provider::PyProvider,
+    config: query::QueryConfig,
+    session: Option<session::Session>,
+    on_stats: Option<Py<PyAny>>,
+    run_id: Option<String>,
+    parent_run_id: Option<String>,
+) -> PyResult<handle::RunHandle> {
+    let registry = session::registry(session);
+    let on_stats = on_stats.map(telemetry::StatsCallback::new);
+    let ids = telemetry::RunIds::new(run_id, parent_run_id);
+    let abort = CancellationToken::new();
+
+    let runtime = runtime_handle().map_err(|e| PyValueError::new_err(format!("{e}")))?;
+    let future = query::start(config, provider, registry, on_stats, ids, abort.clone());
+    Ok(handle::RunHandle::spawn(&runtime, abort, future))
+}
+
+/// Run a delete benchmark, returning its run ID.
+#[pyfunction(name = "delete")]
+#[pyo3(signature = (provider, config, session=None, run_id=None, parent_run_id=None))]
+pub(crate) fn delete_fn(
+    py: Python<'_>,
+    provider: provider::PyProvider,
+    config: delete::DeleteConfig,
+    session: Option<session::Session>,
+    run_id: Option<String>,
+    parent_run_id: Option<String>,
+) -> PyResult<String> {
+    let registry = session::registry(session);
+    let ids = telemetry::RunIds::new(run_id, parent_run_id);
+
+    py.allow_threads(|| block_on(delete::start(provider, config, registry, ids))?)
+        .map_err(|e| PyValueError::new_err(format!("Failed to delete: {e:?}")))
+}
+
+/// Run a snapshot and restore benchmark, returning its run ID.
+#[pyfunction(name = "backup")]
+#[pyo3(signature = (provider, config, session=None, run_id=None, parent_run_id=None))]
+pub(crate) fn backup_fn(
+    py: Python<'_>,
+    provider: provider::PyProvider,
+    config: backup::BackupConfig,
+    session: Option<session::Session>,
+    run_id: Option<String>,
+    parent_run_id: Option<String>,
+) -> PyResult<String> {
+    let registry = session::registry(session);
+    let ids = telemetry::RunIds::new(run_id, parent_run_id);
+
+    py.allow_threads(|| block_on(backup::start(provider, config, registry, ids))?)
+        .map_err(|e| PyValueError::new_err(format!("Failed to back up: {e:?}")))
+}
+
+/// Run a backfill-while-serving benchmark, returning its run ID.
+#[pyfunction(name = "backfill")]
+#[pyo3(signature = (provider, config, session=None, run_id=None, parent_run_id=None))]
+pub(crate) fn backfill_fn(
+    py: Python<'_>,
+    provider: provider::PyProvider,
+    config: backfill::BackfillConfig,
+    session: Option<session::Session>,
+    run_id: Option<String>,
+    parent_run_id: Option<String>,
+) -> PyResult<String> {
+    let registry = session::registry(session);
+    let ids = telemetry::RunIds::new(run_id, parent_run_id);
+
+    py.allow_threads(|| block_on(backfill::start(provider, config, registry, ids))?)
+        .map_err(|e| PyValueError::new_err(format!("Failed to backfill: {e:?}")))
+}
+
+/// Run the same query benchmark against several providers at once, returning the run ID
+/// of the comparison. `providers` maps target names to providers, the first being the
+/// baseline.
+#[pyfunction(name = "compare")]
+#[pyo3(signature = (providers, config, session=None, run_id=None, parent_run_id=None))]
+pub(crate) fn compare_fn(
+    py: Python<'_>,
+    providers: &Bound<'_, PyDict>,
+    config: query::QueryConfig,
+    session: Option<session::Session>,
+    run_id: Option<String>,
+    parent_run_id: Option<String>,
+) -> PyResult<String> {
+    let targets = providers
+        .iter()
+        .map(|(name, provider)| Ok((name.extract()?, provider.extract()?)))
+        .collect::<PyResult<Vec<(String, provider::PyProvider)>>>()?;
+    let registry = session::registry(session);
+    let ids = telemetry::RunIds::new(run_id, parent_run_id);
+
+    py.allow_threads(|| block_on(compare::start(targets, config, registry, ids))?)
+        .map_err(|e| PyValueError::new_err(format!("Failed to compare: {e:?}")))
+}
+
+#[pyfunction]
+#[pyo3(signature = (path, session=None))]
+pub(crate) fn write_metrics(
+    py: Python<'_>,
+    path: &str,
+    session: Option<session::Session>,
+) -> PyResult<()> {
+    let registry = session::registry(session);
+
+    py.allow_threads(|| block_on(telemetry::export(&registry, path))?)
+        .map_err(|e| PyValueError::new_err(format!("Failed to write metrics: {e:?}")))?;
 
     Ok(())
 }
 
+/// Snapshot the metrics of a run, or of all runs in the session if `run_id` is not given.
+/// With `phase`, only the metrics recorded during that phase of the runs.
 #[pyfunction]
-#[pyo3(signature = (path,))]
-pub(crate) fn write_metrics(py: Python<'_>, path: &str) -> PyResult<()> {
-    py.allow_threads(|| {
-        let runtime_guard = RUNTIME.lock().unwrap();
-        if let Some(ref runtime) = *runtime_guard {
-            runtime.block_on(telemetry::export(&path))
-        } else {
-            Err(anyhow::anyhow!("Runtime was shut down"))
-        }
+#[pyo3(signature = (run_id=None, session=None, phase=None))]
+pub(crate) fn snapshot(
+    py: Python<'_>,
+    run_id: Option<String>,
+    session: Option<session::Session>,
+    phase: Option<String>,
+) -> PyResult<telemetry::PySnapshot> {
+    let registry = session::registry(session);
+
+    let snapshot = py
+        .allow_threads(|| {
+            block_on(async {
+                let snapshot = match run_id {
+                    Some(run_id) => registry.snapshot(&run_id).await,
+                    None => registry.snapshot_all().await,
+                };
+                match phase {
+                    Some(phase) => snapshot.phase(&phase),
+                    None => snapshot,
+                }
+            })
+        })
+        .map_err(|e| PyValueError::new_err(format!("Failed to snapshot metrics: {e:?}")))?;
+
+    Ok(snapshot.into())
+}
+
+/// Delete cached datasets, least recently used first, until the cache is at most
+/// `max_size` bytes. Without `max_size` every cached dataset is deleted. Other files in
+/// `cache_dir`, such as in-progress downloads, are left alone. Returns the bytes freed.
+#[pyfunction]
+#[pyo3(signature = (cache_dir, max_size=None))]
+pub(crate) fn purge_cache(py: Python<'_>, cache_dir: &str, max_size: Option<u64>) -> PyResult<u64> {
+    py.allow_threads(|| match max_size {
+        Some(max_size) => cache::evict(cache_dir, max_size, None),
+        None => cache::clear(cache_dir),
     })
-    .map_err(|e| PyValueError::new_err(format!("Failed to write metrics: {e:?}")))?;
+    .map_err(|e| PyValueError::new_err(format!("Failed to purge cache: {e:?}")))
+}
 
-    Ok(())
+/// Check a docs or queries parquet file (local or `s3://`) before a run, returning the
+/// problems found: schema mismatches, duplicate IDs, inconsistent dimensions, NaN or zero
+/// vectors and incomplete ground truth. An empty list means the file looks fine.
+#[pyfunction]
+pub(crate) fn verify_dataset(py: Python<'_>, path: &str, cache_dir: &str) -> PyResult<Vec<String>> {
+    py.allow_threads(|| block_on(data::verify_dataset(path, cache_dir))?)
+        .map_err(|e| PyValueError::new_err(format!("Failed to verify dataset: {e:?}")))
+}
+
+/// Generate a queries file at `output` from the docs file `docs`: the vectors of
+/// `num_queries` sampled documents, plus Gaussian noise of `noise` times their root mean
+/// square per component, with exact ground truth up to `top_k` for every pair of
+/// `int_filters` and `keyword_filters`. Returns the number of queries written.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (docs, output, cache_dir, num_queries=1000, top_k=100, int_filters=vec![data::UNFILTERED.0], keyword_filters=vec![data::UNFILTERED.1.to_string()], noise=0.0, metric="cosine".to_string(), seed=None))]
+pub(crate) fn generate_queries(
+    py: Python<'_>,
+    docs: &str,
+    output: &str,
+    cache_dir: &str,
+    num_queries: usize,
+    top_k: usize,
+    int_filters: Vec<u32>,
+    keyword_filters: Vec<String>,
+    noise: f64,
+    metric: String,
+    seed: Option<u64>,
+) -> PyResult<usize> {
+    if num_queries == 0 || top_k == 0 {
+        return Err(PyValueError::new_err(
+            "num_queries and top_k must be positive",
+        ));
+    }
+    if int_filters.is_empty() || keyword_filters.is_empty() {
+        return Err(PyValueError::new_err(
+            "int_filters and keyword_filters must not be empty",
+        ));
+    }
+    if noise < 0.0 {
+        return Err(PyValueError::new_err("noise must not be negative"));
+    }
+    let metric = metric
+        .parse()
+        .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+
+    let spec = data::QuerySetSpec {
+        num_queries,
+        top_k,
+        int_filters,
+        keyword_filters,
+        noise,
+        metric,
+        seed,
+    };
+    py.allow_threads(|| block_on(data::generate_queries(docs, output, cache_dir, spec))?)
+        .map_err(|e| PyValueError::new_err(format!("Failed to generate queries: {e:?}")))
+}
+
+/// Register the datasets of a TOML file, each a `[datasets.<name>]` table with `docs`,
+/// `queries`, `dimension`, `num_docs` and optionally `ground_truth_top_k`, to use their
+/// name as a config's `size`. Returns the names registered.
+#[pyfunction]
+pub(crate) fn register_datasets(path: &str) -> PyResult<Vec<String>> {
+    data::register_datasets(path).map_err(|e| PyValueError::new_err(format!("{e:#}")))
+}
+
+/// Registered datasets by name, with their `docs`, `queries`, `dimension`, `num_docs` and
+/// `ground_truth_top_k`.
+#[pyfunction]
+pub(crate) fn datasets(py: Python<'_>) -> PyResult<HashMap<String, Bound<'_, PyDict>>> {
+    data::datasets()
+        .into_iter()
+        .map(|dataset| {
+            let dict = PyDict::new(py);
+            dict.set_item("docs", dataset.docs)?;
+            dict.set_item("queries", dataset.queries)?;
+            dict.set_item("dimension", dataset.dimension)?;
+            dict.set_item("num_docs", dataset.num_docs)?;
+            dict.set_item("ground_truth_top_k", dataset.ground_truth_top_k)?;
+            Ok((dataset.name, dict))
+        })
+        .collect()
+}
+
+/// Measure the harness's own overhead per query and highest dispatch rate on this machine,
+/// against a provider that returns straight away, for `duration_secs` at each of
+/// `concurrencies`. Returns one dict of results per concurrency.
+#[pyfunction(name = "calibrate")]
+#[pyo3(signature = (concurrencies=vec![1, 2, 4, 8, 16], duration_secs=5.0, dimension=768))]
+pub(crate) fn calibrate_fn(
+    py: Python<'_>,
+    concurrencies: Vec<usize>,
+    duration_secs: f64,
+    dimension: usize,
+) -> PyResult<Vec<HashMap<&'static str, f64>>> {
+    if concurrencies.is_empty() || concurrencies.contains(&0) {
+        return Err(PyValueError::new_err(
+            "concurrencies must be a non-empty list of positive values",
+        ));
+    }
+    if duration_secs <= 0.0 {
+        return Err(PyValueError::new_err("duration_secs must be positive"));
+    }
+
+    let calibrations = py
+        .allow_threads(|| {
+            block_on(calibrate::calibrate(
+                concurrencies,
+                Duration::from_secs_f64(duration_secs),
+                dimension,
+            ))?
+        })
+        .map_err(|e| PyValueError::new_err(format!("Failed to calibrate: {e:?}")))?;
+
+    Ok(calibrations
+        .iter()
+        .map(|calibration| calibration.to_values().into_iter().collect())
+        .collect())
+}
+
+/// Set how progress reports and logs are printed: `"auto"`, `"color"`, `"plain"`, `"quiet"`
+/// or `"json-lines"`, or forward them to Python's `logging` with `"python"`.
+#[pyfunction]
+pub(crate) fn set_output(output: &str) -> PyResult<()> {
+    let output = output
+        .parse()
+        .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+
+    telemetry::output::set(output)
+        .map_err(|e| PyValueError::new_err(format!("Failed to set output: {e}")))
+}
+
+/// Log at `level` (`"error"`, `"warn"`, `"info"`, `"debug"`, `"trace"` or `"off"`), with
+/// per-module `filters` on top, e.g. `"topk_bench=debug,aws=warn"`. Replaces `RUST_LOG`.
+#[pyfunction]
+#[pyo3(signature = (level="info", filters=None))]
+pub(crate) fn init_logging(level: &str, filters: Option<&str>) -> PyResult<()> {
+    telemetry::set_log_filter(level, filters).map_err(|e| PyValueError::new_err(format!("{e}")))
+}
+
+/// Call into Python from `threads` dedicated threads that hold the GIL across queued
+/// calls, or from the runtime's blocking thread pool with `None` (the default).
+#[pyfunction]
+#[pyo3(signature = (threads=None))]
+pub(crate) fn set_python_threads(threads: Option<usize>) -> PyResult<()> {
+    dispatch::configure(threads).map_err(|e| PyValueError::new_err(format!("{e}")))
 }
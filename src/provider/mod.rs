@@ -0,0 +1,1213 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::OnceCell;
+use pyo3::{
+    exceptions::{PyConnectionError, PyTimeoutError, PyValueError},
+    prelude::*,
+    types::{PyDict, PyList, PyTuple},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::data::{Distance, Document, Namespaces};
+use crate::dispatch::run_py_abortable;
+use crate::telemetry::metrics::Recorder;
+
+mod simulated;
+pub use simulated::SimulatedProvider;
+
+#[pyclass(subclass)]
+#[derive(Debug, Clone)]
+pub struct Provider {}
+
+#[pymethods]
+impl Provider {
+    #[new]
+    #[pyo3(signature = (*args, **kwargs))]
+    fn new(args: &Bound<'_, PyTuple>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let _ = (args, kwargs); // Suppress unused variable warnings
+        Ok(Self {})
+    }
+}
+
+/// Collection configuration handed to `Provider.setup`.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct SetupConfig {
+    /// Dense embedding dimension, if known.
+    #[pyo3(get)]
+    pub dimension: Option<usize>,
+    /// Distance metric.
+    #[pyo3(get)]
+    pub metric: String,
+    /// Fields the harness filters on.
+    #[pyo3(get)]
+    pub filterable_fields: Vec<String>,
+    /// Provider-specific index parameters.
+    pub index_params: Option<Arc<Py<PyDict>>>,
+}
+
+#[pymethods]
+impl SetupConfig {
+    #[getter]
+    fn index_params(&self, py: Python<'_>) -> Py<PyDict> {
+        match &self.index_params {
+            Some(params) => params.clone_ref(py),
+            None => PyDict::new(py).unbind(),
+        }
+    }
+}
+
+/// Provider-specific search parameters, e.g. `{"ef_search": 128}`.
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    pub params: Arc<Py<PyDict>>,
+    /// Canonical JSON form, used as a metric label.
+    pub label: String,
+}
+
+impl SearchParams {
+    pub fn new(params: &Bound<'_, PyDict>) -> PyResult<Self> {
+        Ok(Self {
+            params: Arc::new(params.clone().unbind()),
+            label: json_label(params)?,
+        })
+    }
+
+    /// Parse a single dict or a list of dicts (a sweep).
+    pub fn extract_all(obj: &Bound<'_, PyAny>) -> PyResult<Vec<Self>> {
+        if let Ok(params) = obj.downcast::<PyDict>() {
+            return Ok(vec![Self::new(params)?]);
+        }
+
+        obj.downcast::<PyList>()?
+            .iter()
+            .map(|params| Self::new(params.downcast::<PyDict>()?))
+            .collect()
+    }
+}
+
+/// Provider-specific keyword arguments passed to `setup`, `upsert` and `query`, e.g.
+/// `{"namespace": "bench"}`.
+#[derive(Debug, Clone)]
+pub struct ProviderParams {
+    pub params: Arc<Py<PyDict>>,
+    /// Canonical JSON form, used as a metric label.
+    pub label: String,
+}
+
+impl ProviderParams {
+    pub fn new(params: &Bound<'_, PyDict>) -> PyResult<Self> {
+        Ok(Self {
+            params: Arc::new(params.clone().unbind()),
+            label: json_label(params)?,
+        })
+    }
+}
+
+/// Canonical JSON form of `params`, with sorted keys.
+fn json_label(params: &Bound<'_, PyDict>) -> PyResult<String> {
+    let py = params.py();
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("sort_keys", true)?;
+
+    py.import("json")?
+        .call_method("dumps", (params,), Some(&kwargs))?
+        .extract()
+}
+
+/// Documents passed to `Provider.upsert`, converted to Python objects on the first
+/// attempt and reused on retries instead of being copied again.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub docs: Arc<Vec<Document>>,
+    /// The converted list, and how long converting it took
+    py: Arc<OnceCell<(Py<PyList>, Duration)>>,
+}
+
+impl Batch {
+    pub fn new(docs: Vec<Document>) -> Self {
+        Self {
+            docs: Arc::new(docs),
+            py: Arc::new(OnceCell::new()),
+        }
+    }
+
+    fn to_py<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let (list, _) = self.py.get_or_try_init(|| {
+            let start = Instant::now();
+            let list = PyList::new(py, self.docs.iter().cloned())?;
+            Ok::<_, PyErr>((list.unbind(), start.elapsed()))
+        })?;
+
+        Ok(list.bind(py).clone())
+    }
+
+    /// Time spent converting the documents to Python objects, once converted.
+    pub fn conversion_time(&self) -> Option<Duration> {
+        self.py.get().map(|(_, duration)| *duration)
+    }
+
+    /// Estimated size of the batch encoded as a JSON array.
+    pub fn json_size(&self) -> usize {
+        // Brackets around the documents, and commas between them
+        1 + self
+            .docs
+            .iter()
+            .map(|doc| doc.json_size() + 1)
+            .sum::<usize>()
+    }
+}
+
+/// What `Provider.upsert` may return about an acknowledged batch, so providers that only
+/// queue writes aren't credited with applying them.
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct UpsertAck {
+    /// Documents the provider accepted, all of the batch if unset
+    #[pyo3(get)]
+    pub accepted: Option<u64>,
+    /// Whether the documents were only queued when `upsert` returned (`True`) or already
+    /// applied (`False`), unreported if unset
+    #[pyo3(get)]
+    pub queued: Option<bool>,
+    /// The provider's ID of the request
+    #[pyo3(get)]
+    pub request_id: Option<String>,
+    /// Serialized request size in bytes
+    #[pyo3(get)]
+    pub request_bytes: Option<u64>,
+}
+
+#[pymethods]
+impl UpsertAck {
+    #[new]
+    #[pyo3(signature = (accepted=None, queued=None, request_id=None, request_bytes=None))]
+    fn new(
+        accepted: Option<u64>,
+        queued: Option<bool>,
+        request_id: Option<String>,
+        request_bytes: Option<u64>,
+    ) -> Self {
+        Self {
+            accepted,
+            queued,
+            request_id,
+            request_bytes,
+        }
+    }
+}
+
+impl UpsertAck {
+    /// Documents of a batch of `docs` the provider accepted.
+    pub fn accepted(&self, docs: usize) -> usize {
+        self.accepted.map_or(docs, |n| (n as usize).min(docs))
+    }
+
+    /// The `ack` label: `"applied"`, `"queued"` or `"unreported"`.
+    pub fn durability(&self) -> &'static str {
+        match self.queued {
+            Some(false) => "applied",
+            Some(true) => "queued",
+            None => "unreported",
+        }
+    }
+}
+
+/// Keyword arguments passed to `Provider.query`.
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    /// Distance metric the collection was built with.
+    pub metric: Distance,
+    /// Return `dense_embedding` on results.
+    pub include_vectors: bool,
+    /// Return document fields on results, not just IDs.
+    pub include_payload: bool,
+    /// Provider-specific read consistency level, e.g. `"strong"` or `"eventual"`.
+    pub consistency: Option<String>,
+    /// Provider-specific search parameters.
+    pub search_params: Option<SearchParams>,
+    /// Namespace within the collection to query.
+    pub namespace: Option<String>,
+    /// Only return documents with this `tag`.
+    pub tag: Option<String>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            metric: Distance::default(),
+            include_vectors: false,
+            include_payload: true,
+            consistency: None,
+            search_params: None,
+            namespace: None,
+            tag: None,
+        }
+    }
+}
+
+impl QueryOptions {
+    fn into_kwargs(self, py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("metric", self.metric.to_string())?;
+        kwargs.set_item("include_vectors", self.include_vectors)?;
+        kwargs.set_item("include_payload", self.include_payload)?;
+        kwargs.set_item("consistency", self.consistency)?;
+        kwargs.set_item(
+            "search_params",
+            self.search_params.map(|p| p.params.clone_ref(py)),
+        )?;
+        // Only passed when the run uses namespaces, so providers without them don't need it
+        if let Some(namespace) = self.namespace {
+            kwargs.set_item("namespace", namespace)?;
+        }
+        // Only passed for read-your-writes checks
+        if let Some(tag) = self.tag {
+            kwargs.set_item("tag", tag)?;
+        }
+        Ok(kwargs)
+    }
+}
+
+/// Read consistency levels runs can ask for, mapped by providers to their own.
+pub const CONSISTENCY_LEVELS: [&str; 6] = [
+    "strong", "bounded", "session", "quorum", "majority", "eventual",
+];
+
+/// Check a requested read consistency level is one of [`CONSISTENCY_LEVELS`].
+pub fn check_consistency(level: Option<&str>) -> anyhow::Result<()> {
+    match level {
+        Some(level) if !CONSISTENCY_LEVELS.contains(&level) => {
+            anyhow::bail!("Invalid consistency: {level}, expected one of {CONSISTENCY_LEVELS:?}")
+        }
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PyProvider {
+    py: Arc<Py<PyAny>>,
+    params: Option<ProviderParams>,
+    /// Namespaces documents are spread over, to look up and update them in
+    namespaces: Option<Namespaces>,
+    /// Stops the run this provider's calls belong to
+    abort: CancellationToken,
+}
+
+impl FromPyObject<'_> for PyProvider {
+    fn extract_bound(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(PyProvider {
+            py: Arc::new(obj.as_any().clone().into()),
+            params: None,
+            namespaces: None,
+            abort: CancellationToken::new(),
+        })
+    }
+}
+
+impl PyProvider {
+    /// Pass `params` as extra keyword arguments to `setup`, `upsert` and `query`.
+    pub fn with_params(self, params: Option<ProviderParams>) -> Self {
+        Self { params, ..self }
+    }
+
+    /// Pass the namespace of the document to `query_by_id` and `update`, as the `namespace`
+    /// keyword argument.
+    pub fn with_namespaces(self, namespaces: Option<Namespaces>) -> Self {
+        Self { namespaces, ..self }
+    }
+
+    /// Make calls part of the run `abort` stops. Calls fail once it is cancelled, and a
+    /// `KeyboardInterrupt` raised by any call cancels it.
+    pub fn with_abort(self, abort: CancellationToken) -> Self {
+        Self { abort, ..self }
+    }
+
+    /// Whether the run the provider's calls belong to was aborted.
+    pub fn is_aborted(&self) -> bool {
+        self.abort.is_cancelled()
+    }
+
+    /// Wait until the run the provider's calls belong to is aborted.
+    pub async fn aborted(&self) {
+        self.abort.cancelled().await
+    }
+
+    async fn run_py<F, R>(&self, f: F) -> PyResult<R>
+    where
+        F: FnOnce(Python<'_>) -> PyResult<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        run_py_abortable(&self.abort, f).await
+    }
+
+    /// Provider params merged into `kwargs`, failing on keys the harness already passes.
+    fn kwargs<'py>(
+        &self,
+        py: Python<'py>,
+        kwargs: Option<Bound<'py, PyDict>>,
+    ) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some(params) = &self.params else {
+            return Ok(kwargs);
+        };
+        let Some(kwargs) = kwargs else {
+            return Ok(Some(params.params.bind(py).copy()?));
+        };
+
+        for (key, value) in params.params.bind(py).iter() {
+            if kwargs.contains(&key)? {
+                return Err(PyValueError::new_err(format!(
+                    "provider_params key {key} conflicts with a built-in argument"
+                )));
+            }
+            kwargs.set_item(key, value)?;
+        }
+
+        Ok(Some(kwargs))
+    }
+
+    pub async fn name(&self) -> PyResult<String> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| -> PyResult<String> {
+            let name = provider.call_method0(py, "name")?;
+            let name = name.extract(py)?;
+            Ok(name)
+        })
+        .await
+    }
+
+    pub async fn setup(&self, collection: String, config: SetupConfig) -> PyResult<()> {
+        let this = self.clone();
+
+        self.run_py(move |py| {
+            let kwargs = this.kwargs(py, None)?;
+            this.py
+                .call_method(py, "setup", (collection, config), kwargs.as_ref())
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Upsert the batch, returning what the provider reports about it: an [`UpsertAck`],
+    /// or just the serialized request size in bytes as an int.
+    pub async fn upsert(&self, collection: String, batch: Batch) -> PyResult<UpsertAck> {
+        let this = self.clone();
+
+        self.run_py(move |py| {
+            let kwargs = this.kwargs(py, None)?;
+            let docs = batch.to_py(py)?;
+            let result = this
+                .py
+                .call_method(py, "upsert", (collection, docs), kwargs.as_ref())?;
+
+            if let Ok(ack) = result.extract::<UpsertAck>(py) {
+                return Ok(ack);
+            }
+            // Anything but an ack or an int, e.g. the SDK's response, reports nothing
+            Ok(UpsertAck {
+                request_bytes: result.extract(py).ok(),
+                ..UpsertAck::default()
+            })
+        })
+        .await
+    }
+
+    /// Fetch a document by ID. `consistency` is only passed to the provider when set.
+    pub async fn query_by_id(
+        &self,
+        collection: String,
+        id: String,
+        consistency: Option<String>,
+    ) -> PyResult<Option<Document>> {
+        let provider = self.py.clone();
+        let namespace = self
+            .namespaces
+            .as_ref()
+            .map(|namespaces| namespaces.of(&id));
+
+        let document = self
+            .run_py(move |py| {
+                let kwargs = PyDict::new(py);
+                if let Some(consistency) = consistency {
+                    kwargs.set_item("consistency", consistency)?;
+                }
+                if let Some(namespace) = namespace {
+                    kwargs.set_item("namespace", namespace)?;
+                }
+                let result =
+                    provider.call_method(py, "query_by_id", (collection, id), Some(&kwargs))?;
+                let result = result.downcast_bound::<PyList>(py)?;
+                let result = Vec::<Document>::extract_bound(result)?;
+
+                match &result[..] {
+                    [] => Ok(None),
+                    [doc] => Ok(Some(doc.clone())),
+                    _ => Err(PyValueError::new_err(format!(
+                        "expected 1 document, got {}",
+                        result.len()
+                    ))),
+                }
+            })
+            .await?;
+
+        Ok(document)
+    }
+
+    /// Query the collection. Returns the results and where the time of the call went.
+    pub async fn query(
+        &self,
+        collection: String,
+        vector: Arc<Vec<f32>>,
+        top_k: u32,
+        int_filter: Option<u32>,
+        keyword_filter: Option<String>,
+        options: QueryOptions,
+    ) -> PyResult<(Vec<Document>, QueryTimings)> {
+        let this = self.clone();
+        let submitted = Instant::now();
+
+        let documents = self
+            .run_py(move |py| {
+                // Waiting for a Python thread and the GIL
+                let queue_wait = submitted.elapsed();
+                let kwargs = this.kwargs(py, Some(options.into_kwargs(py)?))?;
+                let called = Instant::now();
+                let result = this.py.call_method(
+                    py,
+                    "query",
+                    (
+                        collection,
+                        vector.as_slice(),
+                        top_k,
+                        int_filter,
+                        keyword_filter,
+                    ),
+                    kwargs.as_ref(),
+                )?;
+                let provider = called.elapsed();
+                let result = result.downcast_bound::<PyList>(py)?;
+                let timings = QueryTimings {
+                    queue_wait,
+                    provider,
+                };
+                Ok((Vec::<Document>::extract_bound(result)?, timings))
+            })
+            .await?;
+
+        Ok(documents)
+    }
+
+    /// Patch `fields` of a single document without rewriting it.
+    ///
+    /// Optional hook, only required by the `update` write mode.
+    pub async fn update(
+        &self,
+        collection: String,
+        id: String,
+        fields: HashMap<String, String>,
+    ) -> PyResult<()> {
+        let provider = self.py.clone();
+        let namespace = self
+            .namespaces
+            .as_ref()
+            .map(|namespaces| namespaces.of(&id));
+
+        self.run_py(move |py| {
+            if !provider.bind(py).hasattr("update")? {
+                return Err(PyValueError::new_err(
+                    "Provider does not implement `update`",
+                ));
+            }
+
+            let kwargs = PyDict::new(py);
+            if let Some(namespace) = namespace {
+                kwargs.set_item("namespace", namespace)?;
+            }
+            provider.call_method(py, "update", (collection, id, fields), Some(&kwargs))
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete all documents matching the filters, with the same semantics as `query`.
+    ///
+    /// Optional hook, only required by the delete benchmark.
+    pub async fn delete_by_filter(
+        &self,
+        collection: String,
+        int_filter: Option<u32>,
+        keyword_filter: Option<String>,
+    ) -> PyResult<()> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            if !provider.bind(py).hasattr("delete_by_filter")? {
+                return Err(PyValueError::new_err(
+                    "Provider does not implement `delete_by_filter`",
+                ));
+            }
+
+            provider.call_method1(
+                py,
+                "delete_by_filter",
+                (collection, int_filter, keyword_filter),
+            )
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Snapshot the collection, returning the provider's snapshot ID.
+    ///
+    /// Optional hook, only required by the backup benchmark.
+    pub async fn snapshot(&self, collection: String) -> PyResult<String> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            if !provider.bind(py).hasattr("snapshot")? {
+                return Err(PyValueError::new_err(
+                    "Provider does not implement `snapshot`",
+                ));
+            }
+
+            provider
+                .call_method1(py, "snapshot", (collection,))?
+                .extract(py)
+        })
+        .await
+    }
+
+    /// Restore `snapshot_id` into the collection, returning once the provider accepted it.
+    ///
+    /// Optional hook, only required by the backup benchmark.
+    pub async fn restore(&self, collection: String, snapshot_id: String) -> PyResult<()> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            if !provider.bind(py).hasattr("restore")? {
+                return Err(PyValueError::new_err(
+                    "Provider does not implement `restore`",
+                ));
+            }
+
+            provider.call_method1(py, "restore", (collection, snapshot_id))
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Restart the provider's server, returning once it serves requests again.
+    ///
+    /// Optional hook, only required by durability verification, and only implementable
+    /// for self-hosted targets.
+    pub async fn restart(&self) -> PyResult<()> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            if !provider.bind(py).hasattr("restart")? {
+                return Err(PyValueError::new_err(
+                    "Provider does not implement `restart`",
+                ));
+            }
+
+            provider.call_method0(py, "restart")
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Ask the provider to open `n` connections ahead of the timed window.
+    ///
+    /// Optional hook. Returns the connection establishment latencies (ms)
+    /// reported by the provider, or nothing if the hook isn't implemented.
+    pub async fn warm_connections(&self, n: usize) -> PyResult<Vec<f64>> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            if !provider.bind(py).hasattr("warm_connections")? {
+                return Ok(Vec::new());
+            }
+
+            let result = provider.call_method1(py, "warm_connections", (n,))?;
+            if result.is_none(py) {
+                return Ok(Vec::new());
+            }
+
+            result.extract(py)
+        })
+        .await
+    }
+
+    /// Number of documents the provider reports as indexed in the collection.
+    ///
+    /// Optional hook. Returns `None` if the provider doesn't implement it.
+    pub async fn num_indexed(&self, collection: String) -> PyResult<Option<u64>> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            if !provider.bind(py).hasattr("num_indexed")? {
+                return Ok(None);
+            }
+
+            let result = provider.call_method1(py, "num_indexed", (collection,))?;
+            result.extract(py)
+        })
+        .await
+    }
+
+    /// Total number of documents in the collection.
+    ///
+    /// Optional hook. Returns `None` if the provider doesn't implement it.
+    pub async fn count(&self, collection: String) -> PyResult<Option<u64>> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            if !provider.bind(py).hasattr("count")? {
+                return Ok(None);
+            }
+
+            let result = provider.call_method1(py, "count", (collection,))?;
+            result.extract(py)
+        })
+        .await
+    }
+
+    /// Versions of the provider's client SDK and server.
+    ///
+    /// Optional `version` and `server_version` hooks. Either is `None` if the provider
+    /// doesn't implement it.
+    pub async fn version(&self) -> PyResult<ProviderVersion> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            let provider = provider.bind(py);
+            let hook = |name: &str| -> PyResult<Option<String>> {
+                if !provider.hasattr(name)? {
+                    return Ok(None);
+                }
+                provider.call_method0(name)?.extract()
+            };
+
+            Ok(ProviderVersion {
+                sdk: hook("version")?,
+                server: hook("server_version")?,
+            })
+        })
+        .await
+    }
+
+    /// Fail unless the provider applies the read consistency `level`, as listed by its
+    /// optional `consistency_levels` hook. Levels are passed through unchecked to providers
+    /// without the hook.
+    pub async fn check_consistency(&self, level: Option<&str>) -> anyhow::Result<()> {
+        let Some(level) = level else {
+            return Ok(());
+        };
+        let provider = self.py.clone();
+        let levels = self
+            .run_py(move |py| -> PyResult<Option<Vec<String>>> {
+                let provider = provider.bind(py);
+                if !provider.hasattr("consistency_levels")? {
+                    return Ok(None);
+                }
+                provider
+                    .call_method0("consistency_levels")?
+                    .extract()
+                    .map(Some)
+            })
+            .await?;
+
+        match levels {
+            Some(levels) if !levels.iter().any(|l| l == level) => anyhow::bail!(
+                "Provider does not support consistency {level:?}, supported: {levels:?}"
+            ),
+            Some(_) => Ok(()),
+            None => {
+                warn!(
+                    level,
+                    "Provider doesn't list its consistency levels, passing it through unchecked"
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// What the provider supports. The optional `capabilities` hook returns a dict of
+    /// capability names to booleans, and capabilities it leaves out are supported if the
+    /// provider implements the optional hooks they need.
+    pub async fn capabilities(&self) -> PyResult<Capabilities> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| {
+            let provider = provider.bind(py);
+            let declared: HashMap<String, bool> = if provider.hasattr("capabilities")? {
+                provider.call_method0("capabilities")?.extract()?
+            } else {
+                HashMap::new()
+            };
+
+            let mut supported = BTreeMap::new();
+            for capability in Capability::ALL {
+                let supports = match declared.get(capability.as_str()) {
+                    Some(supports) => *supports,
+                    None => capability
+                        .hooks()
+                        .iter()
+                        .map(|hook| provider.hasattr(*hook))
+                        .collect::<PyResult<Vec<_>>>()?
+                        .into_iter()
+                        .all(|has| has),
+                };
+                supported.insert(capability, supports);
+            }
+            for name in declared.keys() {
+                if !Capability::ALL.iter().any(|c| c.as_str() == name) {
+                    debug!(name, "Ignoring unknown provider capability");
+                }
+            }
+
+            Ok(Capabilities {
+                supported,
+                notes: Vec::new(),
+            })
+        })
+        .await
+    }
+
+    pub async fn close(&self) -> PyResult<()> {
+        let provider = self.py.clone();
+
+        self.run_py(move |py| provider.call_method0(py, "close"))
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Where the time of a query call went, besides the harness's own dispatch overhead.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryTimings {
+    /// Waiting for a Python thread and the GIL
+    pub queue_wait: Duration,
+    /// Inside the provider's `query`
+    pub provider: Duration,
+}
+
+/// Versions reported by a provider, recorded in run labels and the manifest.
+#[derive(Debug, Clone, Default)]
+pub struct ProviderVersion {
+    /// Client SDK version
+    pub sdk: Option<String>,
+    /// Server version, if the provider can tell
+    pub server: Option<String>,
+}
+
+impl ProviderVersion {
+    /// `provider_version` and `server_version` labels, empty when unknown.
+    pub fn labels(&self) -> [(&'static str, String); 2] {
+        [
+            ("provider_version", self.sdk.clone().unwrap_or_default()),
+            ("server_version", self.server.clone().unwrap_or_default()),
+        ]
+    }
+}
+
+/// Feature a benchmark mode relies on that a provider may not support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Capability {
+    Filters,
+    QueryById,
+    Update,
+    DeleteByFilter,
+    Snapshot,
+    NumIndexed,
+    Count,
+    Restart,
+    StringIds,
+}
+
+impl Capability {
+    const ALL: [Capability; 9] = [
+        Capability::Filters,
+        Capability::QueryById,
+        Capability::Update,
+        Capability::DeleteByFilter,
+        Capability::Snapshot,
+        Capability::NumIndexed,
+        Capability::Count,
+        Capability::Restart,
+        Capability::StringIds,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::Filters => "filters",
+            Capability::QueryById => "query_by_id",
+            Capability::Update => "update",
+            Capability::DeleteByFilter => "delete_by_filter",
+            Capability::Snapshot => "snapshot",
+            Capability::NumIndexed => "num_indexed",
+            Capability::Count => "count",
+            Capability::Restart => "restart",
+            Capability::StringIds => "string_ids",
+        }
+    }
+
+    /// Optional hooks the capability needs, used when the provider doesn't declare it.
+    fn hooks(&self) -> &'static [&'static str] {
+        match self {
+            Capability::Filters | Capability::QueryById | Capability::StringIds => &[],
+            Capability::Update => &["update"],
+            Capability::DeleteByFilter => &["delete_by_filter"],
+            Capability::Snapshot => &["snapshot", "restore"],
+            Capability::NumIndexed => &["num_indexed"],
+            Capability::Count => &["count"],
+            Capability::Restart => &["restart"],
+        }
+    }
+}
+
+/// What a provider supports, and notes on the features a run skipped or downgraded as a
+/// result.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    supported: BTreeMap<Capability, bool>,
+    notes: Vec<(Capability, String)>,
+}
+
+impl Capabilities {
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.supported.get(&capability).copied().unwrap_or(true)
+    }
+
+    /// Whether `capability` is supported. If not, notes `consequence`, what the run does
+    /// without it, e.g. `"skipping freshness probes"`.
+    pub fn require(&mut self, capability: Capability, consequence: &str) -> bool {
+        if self.supports(capability) {
+            return true;
+        }
+
+        warn!(
+            capability = capability.as_str(),
+            "Provider does not support {}, {consequence}",
+            capability.as_str()
+        );
+        self.notes.push((capability, consequence.to_string()));
+        false
+    }
+
+    /// Notes for the run manifest, e.g. `"update unsupported: writing with upsert instead"`.
+    pub fn notes(&self) -> Vec<String> {
+        self.notes
+            .iter()
+            .map(|(capability, consequence)| {
+                format!("{} unsupported: {consequence}", capability.as_str())
+            })
+            .collect()
+    }
+
+    /// Record a `bench.capabilities.<capability>_unsupported` gauge per note.
+    pub fn record(&self, m: &Recorder) {
+        for (capability, _) in &self.notes {
+            m.gauge(
+                &format!("bench.capabilities.{}_unsupported", capability.as_str()),
+                1.0,
+            );
+        }
+    }
+}
+
+/// Attributes SDK exceptions carry the HTTP status of the failed request in.
+const STATUS_ATTRIBUTES: [&str; 3] = ["status_code", "status", "http_status"];
+
+/// Words a status code follows in error messages, e.g. "HTTP 503" or "status code: 429".
+const STATUS_WORDS: [&str; 4] = ["http", "status", "code", "error"];
+
+/// Coarse classification of a failed provider call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Timeout,
+    RateLimited,
+    Server5xx,
+    Client4xx,
+    Network,
+    PythonException,
+}
+
+impl ErrorClass {
+    /// Classify an error from the Python exception type, the HTTP status the SDK attached
+    /// to it, and failing both, its message.
+    pub fn of(error: &PyErr) -> Self {
+        Python::with_gil(|py| {
+            if error.is_instance_of::<PyTimeoutError>(py) {
+                return Some(ErrorClass::Timeout);
+            }
+            if error.is_instance_of::<PyConnectionError>(py) {
+                return Some(ErrorClass::Network);
+            }
+
+            // e.g. `status_code` of httpx and Qdrant errors, `status` of Pinecone's
+            let value = error.value(py);
+            STATUS_ATTRIBUTES
+                .iter()
+                .filter_map(|name| value.getattr(*name).ok()?.extract::<u16>().ok())
+                .find_map(Self::of_status)
+        })
+        .unwrap_or_else(|| Self::of_message(&error.to_string()))
+    }
+
+    fn of_status(status: u16) -> Option<Self> {
+        match status {
+            429 => Some(ErrorClass::RateLimited),
+            500..=599 => Some(ErrorClass::Server5xx),
+            400..=499 => Some(ErrorClass::Client4xx),
+            _ => None,
+        }
+    }
+
+    /// Classify an error by the words of its message. Status codes only count next to a
+    /// word like `status` or `HTTP`, so counts like "batch of 500 documents" don't.
+    fn of_message(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let tokens = message
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>();
+        let has_token = |candidates: &[&str]| tokens.iter().any(|t| candidates.contains(t));
+        let has_stem = |stems: &[&str]| {
+            tokens
+                .iter()
+                .any(|t| stems.iter().any(|stem| t.contains(stem)))
+        };
+        let has_phrase = |phrases: &[&str]| {
+            phrases.iter().any(|phrase| {
+                let words = phrase.split(' ').collect::<Vec<_>>();
+                tokens.windows(words.len()).any(|window| window == words)
+            })
+        };
+        let status = tokens
+            .windows(2)
+            .filter(|pair| STATUS_WORDS.contains(&pair[0]))
+            .find_map(|pair| pair[1].parse().ok().and_then(Self::of_status));
+
+        if has_stem(&["timeout", "deadline"]) || has_phrase(&["timed out"]) {
+            ErrorClass::Timeout
+        } else if let Some(class) = status {
+            class
+        } else if has_stem(&["ratelimit", "throttl"])
+            || has_phrase(&["rate limit", "rate limited", "too many requests"])
+            || has_token(&["resource_exhausted"])
+        {
+            ErrorClass::RateLimited
+        } else if has_phrase(&[
+            "internal server error",
+            "bad gateway",
+            "service unavailable",
+        ]) || has_token(&["unavailable"])
+        {
+            ErrorClass::Server5xx
+        } else if has_phrase(&[
+            "bad request",
+            "unauthorized",
+            "forbidden",
+            "not found",
+            "invalid argument",
+        ]) {
+            ErrorClass::Client4xx
+        } else if has_token(&["connection", "connect", "unreachable", "dns", "ssl", "tls"])
+            || has_phrase(&["reset by peer", "broken pipe"])
+        {
+            ErrorClass::Network
+        } else {
+            ErrorClass::PythonException
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorClass::Timeout => "timeout",
+            ErrorClass::RateLimited => "rate_limited",
+            ErrorClass::Server5xx => "server_5xx",
+            ErrorClass::Client4xx => "client_4xx",
+            ErrorClass::Network => "network",
+            ErrorClass::PythonException => "python_exception",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use pyo3::types::{IntoPyDict, PyModule};
+
+    use super::*;
+
+    /// Import the `topk_bench.providers.<adapter>` module after running `sdk`, Python code
+    /// stubbing the provider's client modules in `sys.modules`. Returns the adapter module.
+    fn import_adapter<'py>(
+        py: Python<'py>,
+        adapter: &str,
+        sdk: &str,
+    ) -> PyResult<Bound<'py, PyModule>> {
+        let sys_modules = py.import("sys")?.getattr("modules")?;
+        let types = py.import("types")?;
+        let package = |name: &str, path: String| -> PyResult<()> {
+            let module = types.call_method1("ModuleType", (name,))?;
+            module.setattr("__path__", vec![path])?;
+            sys_modules.call_method1("setdefault", (name, module))?;
+            Ok(())
+        };
+        let root = format!("{}/python/topk_bench", env!("CARGO_MANIFEST_DIR"));
+        package("topk_bench", root.clone())?;
+        package("topk_bench.providers", format!("{root}/providers"))?;
+
+        let native = PyModule::new(py, "topk_bench.topk_bench")?;
+        native.add_class::<Provider>()?;
+        native.add_class::<SetupConfig>()?;
+        native.add_class::<UpsertAck>()?;
+        native.add_class::<Document>()?;
+        sys_modules.set_item("topk_bench.topk_bench", native)?;
+
+        let globals = PyDict::new(py);
+        py.run(&CString::new(sdk)?, Some(&globals), None)?;
+
+        let name = format!("topk_bench.providers.{adapter}");
+        sys_modules.call_method1("pop", (&name, py.None()))?;
+        py.import(name.as_str())
+    }
+
+    #[test]
+    fn topk_ranks_nearest_first_under_l2() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| -> PyResult<()> {
+            let sdk = r#"
+import sys, types
+
+class Query:
+    def __init__(self):
+        self.asc = False
+    def filter(self, expr):
+        return self
+    def topk(self, expr, k, asc=False):
+        self.k, self.asc = k, asc
+        return self
+
+class Collection:
+    def query(self, query):
+        # The service orders by the `topk` expression, descending unless `asc`
+        rows = [{"_id": "far", "vector_distance": 9.0}, {"_id": "near", "vector_distance": 1.0}]
+        rows.sort(key=lambda row: row["vector_distance"], reverse=not query.asc)
+        return rows[: query.k]
+
+class Client:
+    def __init__(self, **kwargs):
+        pass
+    def collection(self, name):
+        return Collection()
+
+def module(name, **attrs):
+    module = sys.modules[name] = types.ModuleType(name)
+    module.__dict__.update(attrs)
+    parent, _, child = name.rpartition(".")
+    if parent:
+        setattr(sys.modules[parent], child, module)
+
+module("topk_sdk", Client=Client)
+module("topk_sdk.query", select=lambda *args, **kwargs: Query(), field=lambda name: name,
+       fn=types.SimpleNamespace(vector_distance=lambda field, vector: None))
+module("topk_sdk.error", CollectionAlreadyExistsError=Exception)
+module("topk_sdk.schema")
+"#;
+            let adapter = import_adapter(py, "topk", sdk)?;
+            let provider = adapter.getattr("TopKProvider")?.call(
+                (),
+                Some(&[("api_key", "key"), ("region", "region")].into_py_dict(py)?),
+            )?;
+
+            for (metric, expected) in [("l2", "near"), ("cosine", "far")] {
+                let kwargs = [("metric", metric)].into_py_dict(py)?;
+                let docs: Vec<Document> = provider
+                    .call_method(
+                        "query",
+                        ("c", vec![1.0f32, 0.0], 1, py.None(), py.None()),
+                        Some(&kwargs),
+                    )?
+                    .extract()?;
+                assert_eq!(docs.len(), 1);
+                assert_eq!(docs[0].id, expected, "{metric}");
+            }
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn errors_are_classified_by_type_and_status_before_message() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| -> PyResult<()> {
+            let globals = PyDict::new(py);
+            py.run(
+                &CString::new(
+                    "class ApiError(Exception):\n\
+                     \x20   def __init__(self, status_code, message):\n\
+                     \x20       super().__init__(message)\n\
+                     \x20       self.status_code = status_code\n",
+                )?,
+                Some(&globals),
+                None,
+            )?;
+            let api_error = globals.get_item("ApiError")?.unwrap();
+            let sdk_error = |status: u16, message: &str| -> PyResult<PyErr> {
+                Ok(PyErr::from_value(api_error.call1((status, message))?))
+            };
+
+            let cases = [
+                (
+                    PyTimeoutError::new_err("connection pool exhausted"),
+                    ErrorClass::Timeout,
+                ),
+                (
+                    PyConnectionError::new_err("peer closed"),
+                    ErrorClass::Network,
+                ),
+                (
+                    sdk_error(429, "slow down: batch of 500 documents")?,
+                    ErrorClass::RateLimited,
+                ),
+                (sdk_error(503, "try again")?, ErrorClass::Server5xx),
+                (sdk_error(404, "no such collection")?, ErrorClass::Client4xx),
+                (
+                    PyValueError::new_err("rejected a batch of 500 documents"),
+                    ErrorClass::PythonException,
+                ),
+                (
+                    PyValueError::new_err("client disconnected"),
+                    ErrorClass::PythonException,
+                ),
+                (
+                    PyValueError::new_err("HTTP 502 from upstream"),
+                    ErrorClass::Server5xx,
+                ),
+                (
+                    PyValueError::new_err("failed to connect to host"),
+                    ErrorClass::Network,
+                ),
+            ];
+            for (error, expected) in cases {
+                assert_eq!(ErrorClass::of(&error), expected, "{error}");
+            }
+            Ok(())
+        })
+        .unwrap();
+    }
+}
@@ -0,0 +1,562 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::data::{standard_normal, Distance, Document};
+use crate::provider::{Provider, SetupConfig, UpsertAck, CONSISTENCY_LEVELS};
+
+/// In-memory provider whose latency and recall are known, to check the harness's own
+/// measurements against.
+///
+/// Every call takes a latency drawn from a lognormal distribution with median
+/// `latency_ms` and shape `latency_sigma`, plus `spike_ms` with probability
+/// `spike_probability`. Queries run an exact search over the upserted documents, then
+/// swap each of the true top-k results for the next closest match with probability
+/// `1 - recall`. The GIL is released while a call waits, like a network client's.
+#[pyclass(extends = Provider)]
+pub struct SimulatedProvider {
+    latency: LatencyModel,
+    recall: f64,
+    rng: Mutex<StdRng>,
+    collections: RwLock<HashMap<String, BTreeMap<String, Document>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LatencyModel {
+    /// Natural log of the median latency in milliseconds
+    mu: f64,
+    sigma: f64,
+    spike_probability: f64,
+    spike_ms: f64,
+}
+
+impl LatencyModel {
+    fn sample(&self, rng: &mut impl Rng) -> Duration {
+        let mut ms = (self.mu + self.sigma * standard_normal(rng)).exp();
+        if rng.random_bool(self.spike_probability) {
+            ms += self.spike_ms;
+        }
+        Duration::from_secs_f64(ms / 1000.0)
+    }
+}
+
+impl SimulatedProvider {
+    /// Provider returning straight away, whose latency is the harness's own overhead.
+    pub fn noop() -> PyClassInitializer<Self> {
+        PyClassInitializer::from(Provider {}).add_subclass(Self {
+            latency: LatencyModel {
+                mu: f64::NEG_INFINITY,
+                sigma: 0.0,
+                spike_probability: 0.0,
+                spike_ms: 0.0,
+            },
+            recall: 1.0,
+            rng: Mutex::new(StdRng::from_rng(&mut rand::rng())),
+            collections: Default::default(),
+        })
+    }
+
+    /// Run `f` without the GIL, then wait out the rest of a sampled latency.
+    fn call<R: Send>(&self, py: Python<'_>, f: impl FnOnce() -> R + Send) -> R {
+        let start = Instant::now();
+        let latency = self.latency.sample(&mut *self.rng.lock().unwrap());
+
+        py.allow_threads(|| {
+            let result = f();
+            let remaining = latency.saturating_sub(start.elapsed());
+            if !remaining.is_zero() {
+                std::thread::sleep(remaining);
+            }
+            result
+        })
+    }
+}
+
+#[pymethods]
+impl SimulatedProvider {
+    #[new]
+    #[pyo3(signature = (latency_ms=5.0, latency_sigma=0.25, spike_probability=0.0, spike_ms=100.0, recall=1.0, seed=None))]
+    fn new(
+        latency_ms: f64,
+        latency_sigma: f64,
+        spike_probability: f64,
+        spike_ms: f64,
+        recall: f64,
+        seed: Option<u64>,
+    ) -> PyResult<PyClassInitializer<Self>> {
+        if latency_ms <= 0.0 {
+            return Err(PyValueError::new_err("latency_ms must be positive"));
+        }
+        if latency_sigma < 0.0 {
+            return Err(PyValueError::new_err("latency_sigma must not be negative"));
+        }
+        if !(0.0..=1.0).contains(&spike_probability) {
+            return Err(PyValueError::new_err(
+                "spike_probability must be between 0 and 1",
+            ));
+        }
+        if spike_ms < 0.0 {
+            return Err(PyValueError::new_err("spike_ms must not be negative"));
+        }
+        if !(0.0..=1.0).contains(&recall) {
+            return Err(PyValueError::new_err("recall must be between 0 and 1"));
+        }
+
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+
+        Ok(PyClassInitializer::from(Provider {}).add_subclass(Self {
+            latency: LatencyModel {
+                mu: latency_ms.ln(),
+                sigma: latency_sigma,
+                spike_probability,
+                spike_ms,
+            },
+            recall,
+            rng: Mutex::new(rng),
+            collections: Default::default(),
+        }))
+    }
+
+    fn name(&self) -> &'static str {
+        "simulated"
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    #[pyo3(signature = (collection, config=None, **kwargs))]
+    fn setup(
+        &self,
+        collection: String,
+        config: Option<SetupConfig>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) {
+        let _ = (config, kwargs);
+        self.collections
+            .write()
+            .unwrap()
+            .entry(collection)
+            .or_default();
+    }
+
+    #[pyo3(signature = (collection, docs, **kwargs))]
+    fn upsert(
+        &self,
+        py: Python<'_>,
+        collection: String,
+        docs: Vec<Document>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> UpsertAck {
+        let _ = kwargs;
+        self.call(py, || {
+            let mut collections = self.collections.write().unwrap();
+            let collection = collections.entry(collection).or_default();
+            let accepted = docs.len() as u64;
+            for doc in docs {
+                collection.insert(doc.id.clone(), doc);
+            }
+
+            // Visible to queries as soon as the call returns
+            UpsertAck {
+                accepted: Some(accepted),
+                queued: Some(false),
+                ..UpsertAck::default()
+            }
+        })
+    }
+
+    #[pyo3(signature = (collection, id, consistency=None, **kwargs))]
+    fn query_by_id(
+        &self,
+        py: Python<'_>,
+        collection: String,
+        id: String,
+        consistency: Option<String>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> Vec<Document> {
+        let _ = (consistency, kwargs);
+        self.call(py, || {
+            let collections = self.collections.read().unwrap();
+            collections
+                .get(&collection)
+                .and_then(|docs| docs.get(&id))
+                .map(|doc| Document {
+                    dense_embedding: None,
+                    ..doc.clone()
+                })
+                .into_iter()
+                .collect()
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (collection, vector, top_k, int_filter, keyword_filter, metric="cosine".to_string(), include_vectors=false, include_payload=true, consistency=None, search_params=None, namespace=None, tag=None, **kwargs))]
+    fn query(
+        &self,
+        py: Python<'_>,
+        collection: String,
+        vector: Vec<f32>,
+        top_k: u32,
+        int_filter: Option<u32>,
+        keyword_filter: Option<String>,
+        metric: String,
+        include_vectors: bool,
+        include_payload: bool,
+        consistency: Option<String>,
+        search_params: Option<&Bound<'_, PyDict>>,
+        namespace: Option<String>,
+        tag: Option<String>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Vec<Document>> {
+        let _ = (consistency, search_params, kwargs);
+        let metric: Distance = metric
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        let top_k = top_k as usize;
+        // Which of the true top-k results to swap for the next closest ones
+        let misses: Vec<bool> = {
+            let mut rng = self.rng.lock().unwrap();
+            (0..top_k).map(|_| !rng.random_bool(self.recall)).collect()
+        };
+
+        Ok(self.call(py, || {
+            let collections = self.collections.read().unwrap();
+            let Some(docs) = collections.get(&collection) else {
+                return Vec::new();
+            };
+
+            let mut scored: Vec<(f32, &Document)> = docs
+                .values()
+                .filter(|doc| {
+                    int_filter.is_none_or(|f| doc.int_filter <= f)
+                        && keyword_filter
+                            .as_ref()
+                            .is_none_or(|f| doc.keyword_filter.split_whitespace().any(|t| t == f))
+                        && namespace
+                            .as_ref()
+                            .is_none_or(|ns| doc.namespace.as_ref() == Some(ns))
+                        && tag.as_ref().is_none_or(|tag| doc.tag.as_ref() == Some(tag))
+                })
+                .filter_map(|doc| {
+                    let embedding = doc.dense_embedding.as_ref()?;
+                    Some((metric.score(&vector, embedding), doc))
+                })
+                .collect();
+            closest_first(&mut scored, metric);
+
+            let (exact, rest) = scored.split_at(top_k.min(scored.len()));
+            let mut replacements = rest.iter();
+            let mut results: Vec<(f32, &Document)> = exact
+                .iter()
+                .zip(&misses)
+                .map(
+                    |(hit, miss)| match (*miss).then(|| replacements.next()).flatten() {
+                        Some(replacement) => *replacement,
+                        None => *hit,
+                    },
+                )
+                .collect();
+            // Replacements rank below the true results they displaced
+            closest_first(&mut results, metric);
+
+            results
+                .into_iter()
+                .map(|(score, doc)| {
+                    let doc = doc.clone();
+                    let mut result = if include_payload {
+                        doc
+                    } else {
+                        Document {
+                            text: String::new(),
+                            keyword_filter: String::new(),
+                            ..doc
+                        }
+                    };
+                    if !include_vectors {
+                        result.dense_embedding = None;
+                    }
+                    result.score = Some(score);
+                    result
+                })
+                .collect()
+        }))
+    }
+
+    #[pyo3(signature = (collection, id, fields, **kwargs))]
+    fn update(
+        &self,
+        py: Python<'_>,
+        collection: String,
+        id: String,
+        fields: HashMap<String, String>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<()> {
+        let _ = kwargs;
+        self.call(py, || {
+            let mut collections = self.collections.write().unwrap();
+            let Some(doc) = collections
+                .get_mut(&collection)
+                .and_then(|docs| docs.get_mut(&id))
+            else {
+                return Ok(());
+            };
+
+            for (field, value) in fields {
+                match field.as_str() {
+                    "text" => doc.text = value,
+                    "keyword_filter" => doc.keyword_filter = value,
+                    "int_filter" => {
+                        doc.int_filter = value
+                            .parse()
+                            .map_err(|e| PyValueError::new_err(format!("{e}")))?
+                    }
+                    _ => return Err(PyValueError::new_err(format!("Unknown field: {field}"))),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn delete_by_id(&self, py: Python<'_>, collection: String, ids: Vec<String>) {
+        self.call(py, || {
+            if let Some(docs) = self.collections.write().unwrap().get_mut(&collection) {
+                for id in ids {
+                    docs.remove(&id);
+                }
+            }
+        })
+    }
+
+    #[pyo3(signature = (collection, int_filter=None, keyword_filter=None))]
+    fn delete_by_filter(
+        &self,
+        py: Python<'_>,
+        collection: String,
+        int_filter: Option<u32>,
+        keyword_filter: Option<String>,
+    ) {
+        self.call(py, || {
+            if let Some(docs) = self.collections.write().unwrap().get_mut(&collection) {
+                docs.retain(|_, doc| {
+                    !(int_filter.is_none_or(|f| doc.int_filter <= f)
+                        && keyword_filter
+                            .as_ref()
+                            .is_none_or(|f| doc.keyword_filter.split_whitespace().any(|t| t == f)))
+                });
+            }
+        })
+    }
+
+    fn delete_collection(&self, collection: String) {
+        self.collections.write().unwrap().remove(&collection);
+    }
+
+    fn count(&self, collection: String) -> u64 {
+        self.collections
+            .read()
+            .unwrap()
+            .get(&collection)
+            .map_or(0, |docs| docs.len() as u64)
+    }
+
+    fn num_indexed(&self, collection: String) -> u64 {
+        self.count(collection)
+    }
+
+    /// Reads always see the latest writes, which satisfies every level.
+    fn consistency_levels(&self) -> Vec<&'static str> {
+        CONSISTENCY_LEVELS.to_vec()
+    }
+
+    fn close(&self) {}
+}
+
+fn closest_first(scored: &mut [(f32, &Document)], metric: Distance) {
+    scored.sort_by(|(a, _), (b, _)| {
+        let ordering = a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal);
+        if metric.higher_is_closer() {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::types::{PyDict, PyList};
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::data::Query;
+    use crate::provider::PyProvider;
+    use crate::query::{self, QueryConfig};
+    use crate::telemetry::metrics::Registry;
+    use crate::telemetry::{quantile_of_sorted, RunIds};
+
+    const DIMENSION: usize = 16;
+
+    fn latency_model(latency_ms: f64, sigma: f64) -> LatencyModel {
+        LatencyModel {
+            mu: latency_ms.ln(),
+            sigma,
+            spike_probability: 0.0,
+            spike_ms: 0.0,
+        }
+    }
+
+    fn random_vector(rng: &mut StdRng) -> Vec<f32> {
+        (0..DIMENSION).map(|_| rng.random::<f32>() - 0.5).collect()
+    }
+
+    #[test]
+    fn latency_model_has_the_configured_median_and_tail() {
+        let model = latency_model(5.0, 0.25);
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut samples: Vec<f64> = (0..50_000)
+            .map(|_| model.sample(&mut rng).as_secs_f64() * 1000.0)
+            .collect();
+        samples.sort_by(f64::total_cmp);
+
+        // The p99 of a lognormal is exp(mu + 2.326 * sigma)
+        let p50 = quantile_of_sorted(&samples, 0.50);
+        let p99 = quantile_of_sorted(&samples, 0.99);
+        assert!((p50 - 5.0).abs() < 0.1, "p50 {p50}");
+        assert!(
+            (p99 - 5.0 * (2.326f64 * 0.25).exp()).abs() < 0.2,
+            "p99 {p99}"
+        );
+    }
+
+    #[test]
+    fn latency_model_adds_spikes_at_the_configured_rate() {
+        let model = LatencyModel {
+            spike_probability: 0.05,
+            spike_ms: 100.0,
+            ..latency_model(5.0, 0.25)
+        };
+        let mut rng = StdRng::seed_from_u64(42);
+        let spikes = (0..50_000)
+            .filter(|_| model.sample(&mut rng) > Duration::from_millis(100))
+            .count();
+
+        assert!(
+            (spikes as f64 / 50_000.0 - 0.05).abs() < 0.005,
+            "{spikes} spikes"
+        );
+    }
+
+    /// The harness measures the latency and recall the simulated provider is configured with.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn query_run_measures_simulated_latency_and_recall() {
+        pyo3::prepare_freethreaded_python();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let (latency_ms, sigma, recall, top_k) = (20.0, 0.25, 0.9, 10);
+
+        let (provider, config) = Python::with_gil(|py| -> PyResult<(PyProvider, QueryConfig)> {
+            let simulated = Bound::new(
+                py,
+                SimulatedProvider::new(latency_ms, sigma, 0.0, 0.0, recall, Some(42))?,
+            )?;
+
+            let mut rng = StdRng::seed_from_u64(7);
+            let docs: Vec<Document> = (0..1_000)
+                .map(|i| Document {
+                    id: i.to_string(),
+                    text: format!("doc {i}"),
+                    int_filter: 0,
+                    keyword_filter: String::new(),
+                    dense_embedding: Some(random_vector(&mut rng)),
+                    tag: None,
+                    namespace: None,
+                    score: None,
+                })
+                .collect();
+            simulated.call_method1("upsert", ("simulated", docs.clone()))?;
+
+            // Exact ground truth, closest first
+            let queries = (0..200)
+                .map(|_| {
+                    let vector = random_vector(&mut rng);
+                    let mut scored: Vec<(f32, &Document)> = docs
+                        .iter()
+                        .map(|doc| {
+                            let embedding = doc.dense_embedding.as_ref().unwrap();
+                            (Distance::Cosine.score(&vector, embedding), doc)
+                        })
+                        .collect();
+                    closest_first(&mut scored, Distance::Cosine);
+
+                    let mut query = Query::new(vector);
+                    query.ground_truth = Some(
+                        scored[..top_k]
+                            .iter()
+                            .map(|(_, doc)| doc.id.parse().unwrap())
+                            .collect(),
+                    );
+                    Bound::new(py, query)
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("collection", "simulated")?;
+            kwargs.set_item("queries", PyList::new(py, queries)?)?;
+            kwargs.set_item("top_k", top_k)?;
+            kwargs.set_item("concurrency", 8)?;
+            kwargs.set_item("size", "100k")?;
+            kwargs.set_item("timeout", 3)?;
+            kwargs.set_item("mode", "filter")?;
+            kwargs.set_item("cache_dir", cache_dir.path().to_str())?;
+            kwargs.set_item("dimension", DIMENSION)?;
+            let config = py
+                .get_type::<QueryConfig>()
+                .call((), Some(&kwargs))?
+                .extract()?;
+
+            Ok((simulated.into_any().extract()?, config))
+        })
+        .unwrap();
+
+        let registry = Registry::default();
+        let run_ids = query::start(
+            config,
+            provider,
+            registry.clone(),
+            None,
+            RunIds::new(None, None),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        // Latencies are recorded in whole milliseconds, on top of the harness's overhead
+        let load = registry.snapshot(&run_ids[0]).await.phase("load");
+        let p50 = load.quantile("bench.query.latency_ms", 0.50);
+        let p99 = load.quantile("bench.query.latency_ms", 0.99);
+        let expected_p99 = latency_ms * (2.326f64 * sigma).exp();
+        assert!((p50 - latency_ms).abs() < latency_ms * 0.1, "p50 {p50}");
+        assert!((p99 - expected_p99).abs() < expected_p99 * 0.1, "p99 {p99}");
+
+        let stats = registry.snapshot(&run_ids[0]).await;
+        let measured = stats.avg("bench.query.recall");
+        assert_eq!(stats.count("bench.query.recall"), 200);
+        assert!((measured - recall).abs() < 0.03, "recall {measured}");
+
+        // The interval is recorded from the last samples, right before the run finishes
+        assert_eq!(stats.avg("bench.query.recall_evaluated"), 200.0);
+        let (low, high) = (
+            stats.avg("bench.query.recall_ci_low"),
+            stats.avg("bench.query.recall_ci_high"),
+        );
+        assert!(low <= measured && measured <= high, "{low}..{high}");
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use colored::Colorize;
+
+use crate::telemetry::metrics::{Metric, Recorder};
+use crate::telemetry::output::report;
+use crate::telemetry::Snapshot;
+
+/// Report how evenly the workers shared the load: the busiest worker's queries over the
+/// average worker's (`bench.query.worker_imbalance`, 1 when perfectly even) and the worst
+/// per-worker p99 wait for a Python thread (`bench.query.worker_queue_wait_p99_ms`).
+///
+/// Workers pull queries from one shared queue as they become free, so a slow call only
+/// delays its own query. A high imbalance points at the harness rather than the provider.
+pub fn report_worker_balance(stats: &Snapshot, m: &Recorder, prefix: &str) {
+    let mut workers = BTreeMap::<&str, (usize, Vec<Metric>)>::new();
+    for metric in &stats.metrics {
+        let Some(worker_id) = metric.label("worker_id") else {
+            continue;
+        };
+        match metric.name.as_str() {
+            "bench.query.latency_ms" => workers.entry(worker_id).or_default().0 += 1,
+            "bench.query.queue_wait_ms" => {
+                workers.entry(worker_id).or_default().1.push(metric.clone())
+            }
+            _ => {}
+        }
+    }
+
+    let total: usize = workers.values().map(|(queries, _)| queries).sum();
+    if workers.len() < 2 || total == 0 {
+        return;
+    }
+
+    let busiest = workers
+        .values()
+        .map(|(queries, _)| *queries)
+        .max()
+        .unwrap_or(0);
+    let idlest = workers
+        .values()
+        .map(|(queries, _)| *queries)
+        .min()
+        .unwrap_or(0);
+    let imbalance = busiest as f64 / (total as f64 / workers.len() as f64);
+    let queue_wait_p99 = workers
+        .into_values()
+        .map(|(_, waits)| Snapshot::new(waits).quantile("bench.query.queue_wait_ms", 0.99))
+        .fold(0.0, f64::max);
+
+    m.gauge("bench.query.worker_imbalance", imbalance);
+    m.gauge("bench.query.worker_queue_wait_p99_ms", queue_wait_p99);
+
+    report(format!(
+        "{:>16}] {} {} to {} queries per worker, Imbalance: {}, Queue wait: {}",
+        prefix,
+        "Workers:".green().bold(),
+        idlest,
+        busiest,
+        match imbalance {
+            i if i > 1.5 => format!("{i:.2}x").red().bold(),
+            i if i > 1.1 => format!("{i:.2}x").yellow().bold(),
+            i => format!("{i:.2}x").green().bold(),
+        },
+        format!("worst p99={queue_wait_p99:.2}ms").bold(),
+    ));
+}
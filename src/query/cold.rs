@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use colored::Colorize;
+
+use crate::telemetry::metrics::{Phase, PhaseSwitch, Recorder};
+use crate::telemetry::output::report;
+use crate::telemetry::Snapshot;
+
+/// Labels the first queries of a run `cold`, then switches the run to its steady phase.
+#[derive(Debug, Clone)]
+pub struct ColdStart {
+    remaining: Arc<AtomicUsize>,
+    phase: PhaseSwitch,
+    steady: Phase,
+}
+
+impl ColdStart {
+    pub fn new(queries: usize, phase: &PhaseSwitch, steady: Phase) -> Self {
+        phase.set(Phase::Cold);
+
+        Self {
+            remaining: Arc::new(AtomicUsize::new(queries)),
+            phase: phase.clone(),
+            steady,
+        }
+    }
+
+    /// Count a successful query, leaving the cold phase after the last cold one.
+    pub fn completed(&self) {
+        let remaining = self
+            .remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        if remaining == Ok(1) {
+            self.phase.set(self.steady);
+        }
+    }
+}
+
+/// Compare the latency of the cold queries with the steady state that followed, recording
+/// `bench.query.cold_latency_p50_ms` and `bench.query.cold_latency_p99_ms`.
+pub fn report_cold_start(cold: &Snapshot, steady: &Snapshot, m: &Recorder, prefix: &str) {
+    if cold.count("bench.query.latency_ms") == 0 {
+        return;
+    }
+
+    let cold_p50 = cold.quantile("bench.query.latency_ms", 0.50);
+    let cold_p99 = cold.quantile("bench.query.latency_ms", 0.99);
+    m.gauge("bench.query.cold_latency_p50_ms", cold_p50);
+    m.gauge("bench.query.cold_latency_p99_ms", cold_p99);
+
+    let steady_p50 = steady.quantile("bench.query.latency_ms", 0.50);
+    report(format!(
+        "{:>16}] {} first {} queries p50={:.2}ms p99={:.2}ms, steady p50={:.2}ms p99={:.2}ms{}",
+        prefix,
+        "Cold start:".green().bold(),
+        cold.count("bench.query.latency_ms"),
+        cold_p50,
+        cold_p99,
+        steady_p50,
+        steady.quantile("bench.query.latency_ms", 0.99),
+        if steady_p50 > 0.0 {
+            format!(" ({:.2}x at p50)", cold_p50 / steady_p50)
+                .bold()
+                .to_string()
+        } else {
+            "".to_string()
+        },
+    ));
+}
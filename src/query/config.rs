@@ -1,29 +1,217 @@
-use pyo3::{exceptions::PyValueError, prelude::*};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+
+use crate::compare::Comparison;
+use crate::data::{
+    dataset, run_id_prefix, Dataset, Dimension, Distance, DocumentTransform, FilterCounts, IdCodec,
+    IdScheme, NamespaceDistribution, Namespaces, PyDocumentTransform, QuerySource,
+};
+use crate::provider::{check_consistency, ProviderParams, QueryOptions, SearchParams};
+use crate::query::traffic::{Profile, Schedule};
+use crate::telemetry::Reporting;
 
 #[pyclass]
 #[derive(Clone, Debug)]
 pub struct QueryConfig {
     pub collection: String,
-    pub queries: String,
+    pub queries: QuerySource,
     pub top_k: u32,
     pub int_filter: Option<u32>,
     pub keyword_filter: Option<String>,
     pub concurrency: usize,
+    /// Name of the registered dataset the collection holds.
     pub size: String,
+    pub dataset: Dataset,
     pub timeout: u64,
     pub warmup: bool,
+    /// Label the first this many queries `cold`, without warming up connections first.
+    pub cold_queries: Option<usize>,
     pub read_write: bool,
+    /// Documents written in read_write mode, instead of the dataset's.
+    pub docs_override: Option<String>,
     pub mode: String,
     pub cache_dir: String,
+    /// Pad each written document's `text` to this many bytes in read_write mode.
+    pub payload_size: Option<usize>,
+    /// ID scheme the collection was ingested with.
+    pub id_scheme: IdScheme,
+    /// Prefix written document IDs with a run-scoped namespace.
+    pub namespace_ids: bool,
+    /// Run ID of the ingest that wrote the collection with `namespace_ids`, whose prefix
+    /// the collection's document IDs carry.
+    pub ingest_run_id: Option<String>,
+    /// Number of namespaces the collection's documents are spread over. Each query goes to
+    /// one of them, unless it sets its own `namespace`.
+    pub namespaces: Option<usize>,
+    /// How queries are spread over `namespaces`, the same as documents were.
+    pub namespace_distribution: NamespaceDistribution,
+    /// Expected dense embedding dimension. Defaults to the first embedding's dimension.
+    pub dimension: Option<usize>,
+    /// Pad or truncate embeddings to `dimension` instead of failing on a mismatch.
+    pub resize_vectors: bool,
+    /// Distance metric the collection was built with.
+    pub metric: Distance,
+    /// Recompute exact scores for returned documents and record the deviation.
+    pub verify_scores: bool,
+    /// Search parameter sets. Each set is benchmarked as a separate run.
+    pub search_params: Vec<SearchParams>,
+    /// Capture queries slower than this to `slow_query_log`.
+    pub slow_query_threshold_ms: Option<u64>,
+    /// Parquet path (local or `s3://`) for slow queries. `{run_id}` is substituted.
+    pub slow_query_log: String,
+    /// Flag the seconds whose p99 latency exceeds the run's median by this factor.
+    pub incident_factor: Option<f64>,
+    /// Target queries per second across all workers. Unlimited by default.
+    pub qps: Option<f64>,
+    /// Halve the send rate when the provider rate limits, raising it again while it doesn't.
+    pub adaptive_qps: bool,
+    /// Most provider calls in flight across all workers. Defaults to `concurrency`.
+    pub max_in_flight: Option<usize>,
+    /// Send traffic for this many seconds, then pause for `burst_off_secs`, repeating.
+    pub burst_on_secs: Option<u64>,
+    /// Idle period between bursts.
+    pub burst_off_secs: Option<u64>,
+    /// CSV file (local or `s3://`) of `offset_secs,qps` points for the target rate to follow.
+    pub qps_profile: Option<String>,
+    /// Replay `qps_profile` this many times faster than its offsets.
+    pub qps_profile_speedup: f64,
+    /// Soak mode: checkpoint every this many seconds.
+    pub checkpoint_interval_secs: Option<u64>,
+    /// Parquet path (local or `s3://`) the run's metrics are rotated to at each checkpoint.
+    /// `{run_id}` and `{checkpoint}` are substituted.
+    pub checkpoint_metrics: Option<String>,
+    /// Python callable or iterable yielding query dicts, replacing the built-in query source.
+    pub query_generator: Option<Arc<Py<PyAny>>>,
+    /// Python callable applied to documents written in read_write mode, replacing the
+    /// default random `tag` update.
+    pub write_transform: Option<PyDocumentTransform>,
+    /// How documents are written in read_write mode.
+    pub write_mode: WriteMode,
+    /// Give up on an update after this many failed attempts, or retry forever with `None`.
+    pub max_update_attempts: Option<u32>,
+    /// Order read_write mode writes the docs file's documents in.
+    pub write_order: WriteOrder,
+    /// Only write documents with dataset IDs in `start..end` in read_write mode.
+    pub write_id_range: Option<(u64, u64)>,
+    /// Write documents under net-new IDs after the dataset's, growing the collection.
+    pub write_new_ids: bool,
+    /// Growth mode: write net-new IDs at this many documents per second in read_write mode,
+    /// sampling the collection size as it grows.
+    pub growth_rate: Option<f64>,
+    /// Return document fields on results. With `false` providers return IDs only.
+    pub include_payload: bool,
+    /// Read consistency level passed to the provider, e.g. `"strong"` or `"eventual"`.
+    pub consistency: Option<String>,
+    /// Fraction of queries in read_write mode followed by a read-your-writes check.
+    pub read_your_writes: f64,
+    /// Hold a random sample of this many queries instead of the whole query file.
+    pub query_sample_size: Option<usize>,
+    /// Parquet path (local or `s3://`) of ground truth keyed by query index, replacing the
+    /// ground truth in `queries`.
+    pub ground_truth: Option<String>,
+    /// Measure recall on a random sample of this many queries instead of all of them.
+    pub recall_sample_size: Option<usize>,
+    /// Stop measuring recall once the 95% confidence interval half-width of the recall
+    /// estimate is within this, e.g. `0.005`.
+    pub recall_target_ci: Option<f64>,
+    /// Record missing expected results and results violating the filters during recall.
+    pub recall_diagnostics: bool,
+    /// Evaluate each query for every filter pair its ground truth covers, and report recall
+    /// by selectivity.
+    pub stratify_recall: bool,
+    /// Fail the run before it starts when the ground truth covers a smaller share of the
+    /// queries at their filters and `top_k`.
+    pub min_ground_truth_coverage: f64,
+    /// Seconds between progress reports and stats callbacks.
+    pub report_interval_secs: f64,
+    /// Seconds of recent metrics throughput and latency percentiles are computed over.
+    /// Defaults to one second for throughput and the whole run for percentiles.
+    pub report_window_secs: Option<f64>,
+    /// Provider-specific keyword arguments passed to `setup`, `upsert` and `query`.
+    pub provider_params: Option<ProviderParams>,
+    /// Write the run's metrics, summary, manifest, slow queries and log under `{results_dir}/{run_id}/`
+    /// (local or `s3://`).
+    pub results_dir: Option<String>,
+    /// The target this run measures in a comparison, set by `compare()`.
+    pub comparison: Option<Comparison>,
+    /// Documents by filter value, loaded from the docs file for recall by selectivity.
+    pub filter_counts: Option<Arc<FilterCounts>>,
+}
+
+/// How read_write mode writes documents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Full document upserts.
+    #[default]
+    Upsert,
+    /// Partial updates patching a single field of existing documents.
+    Update,
+}
+
+impl FromStr for WriteMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upsert" => Ok(WriteMode::Upsert),
+            "update" => Ok(WriteMode::Update),
+            _ => anyhow::bail!("Invalid write mode: {s}"),
+        }
+    }
+}
+
+impl fmt::Display for WriteMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteMode::Upsert => write!(f, "upsert"),
+            WriteMode::Update => write!(f, "update"),
+        }
+    }
+}
+
+/// Order read_write mode writes documents in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteOrder {
+    /// The order of the docs file.
+    #[default]
+    Sequential,
+    /// Row groups in random order, and documents shuffled within a window.
+    Shuffled,
+}
+
+impl FromStr for WriteOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sequential" => Ok(WriteOrder::Sequential),
+            "shuffle" => Ok(WriteOrder::Shuffled),
+            _ => anyhow::bail!("Invalid write order: {s}"),
+        }
+    }
+}
+
+impl fmt::Display for WriteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteOrder::Sequential => write!(f, "sequential"),
+            WriteOrder::Shuffled => write!(f, "shuffle"),
+        }
+    }
 }
 
 #[pymethods]
 impl QueryConfig {
     #[new]
-    #[pyo3(signature = (collection, queries, top_k, concurrency, size, timeout, mode, cache_dir, int_filter=None, keyword_filter=None, read_write=false, warmup=false))]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (collection, queries, top_k, concurrency, size, timeout, mode, cache_dir, int_filter=None, keyword_filter=None, read_write=false, docs=None, warmup=false, cold_queries=None, payload_size=None, id_scheme="numeric".to_string(), namespace_ids=false, ingest_run_id=None, namespaces=None, namespace_distribution="uniform".to_string(), dimension=None, resize_vectors=false, metric="cosine".to_string(), verify_scores=false, search_params=None, slow_query_threshold_ms=None, slow_query_log="slow_queries-{run_id}.parquet".to_string(), incident_factor=None, qps=None, adaptive_qps=false, max_in_flight=None, burst_on_secs=None, burst_off_secs=None, qps_profile=None, qps_profile_speedup=1.0, checkpoint_interval_secs=None, checkpoint_metrics=None, query_generator=None, write_transform=None, write_mode="upsert".to_string(), max_update_attempts=Some(10), write_order="sequential".to_string(), write_id_range=None, write_new_ids=false, growth_rate=None, include_payload=true, consistency=None, read_your_writes=0.0, query_sample_size=None, ground_truth=None, recall_sample_size=None, recall_target_ci=None, recall_diagnostics=false, stratify_recall=false, min_ground_truth_coverage=1.0, report_interval_secs=1.0, report_window_secs=None, provider_params=None, results_dir=None))]
     fn new(
         collection: String,
-        queries: String,
+        queries: Bound<'_, PyAny>,
         top_k: u32,
         concurrency: usize,
         size: String,
@@ -33,25 +221,394 @@ impl QueryConfig {
         int_filter: Option<u32>,
         keyword_filter: Option<String>,
         read_write: bool,
+        docs: Option<String>,
         warmup: bool,
+        cold_queries: Option<usize>,
+        payload_size: Option<usize>,
+        id_scheme: String,
+        namespace_ids: bool,
+        ingest_run_id: Option<String>,
+        namespaces: Option<usize>,
+        namespace_distribution: String,
+        dimension: Option<usize>,
+        resize_vectors: bool,
+        metric: String,
+        verify_scores: bool,
+        search_params: Option<&Bound<'_, PyAny>>,
+        slow_query_threshold_ms: Option<u64>,
+        slow_query_log: String,
+        incident_factor: Option<f64>,
+        qps: Option<f64>,
+        adaptive_qps: bool,
+        max_in_flight: Option<usize>,
+        burst_on_secs: Option<u64>,
+        burst_off_secs: Option<u64>,
+        qps_profile: Option<String>,
+        qps_profile_speedup: f64,
+        checkpoint_interval_secs: Option<u64>,
+        checkpoint_metrics: Option<String>,
+        query_generator: Option<Py<PyAny>>,
+        write_transform: Option<Py<PyAny>>,
+        write_mode: String,
+        max_update_attempts: Option<u32>,
+        write_order: String,
+        write_id_range: Option<(u64, u64)>,
+        write_new_ids: bool,
+        growth_rate: Option<f64>,
+        include_payload: bool,
+        consistency: Option<String>,
+        read_your_writes: f64,
+        query_sample_size: Option<usize>,
+        ground_truth: Option<String>,
+        recall_sample_size: Option<usize>,
+        recall_target_ci: Option<f64>,
+        recall_diagnostics: bool,
+        stratify_recall: bool,
+        min_ground_truth_coverage: f64,
+        report_interval_secs: f64,
+        report_window_secs: Option<f64>,
+        provider_params: Option<Bound<'_, PyDict>>,
+        results_dir: Option<String>,
     ) -> PyResult<Self> {
-        if !["100k", "1m", "10m"].contains(&size.as_str()) {
-            return Err(PyValueError::new_err(format!("Invalid size: {}", size)));
+        let dataset = dataset(&size).map_err(|e| PyValueError::new_err(format!("{e}")))?;
+
+        let id_scheme = id_scheme
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        let metric = metric
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        let write_mode = write_mode
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        let write_order = write_order
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        let namespace_distribution = namespace_distribution
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        if namespaces == Some(0) {
+            return Err(PyValueError::new_err("namespaces must be positive"));
+        }
+        // Ground truth covers the whole collection, not a namespace
+        if mode == "filter" && namespaces.is_some() {
+            return Err(PyValueError::new_err(
+                "namespaces cannot be combined with filter mode",
+            ));
+        }
+        if write_id_range.is_some_and(|(start, end)| start >= end) {
+            return Err(PyValueError::new_err(
+                "write_id_range must be a (start, end) range with start < end",
+            ));
+        }
+        if growth_rate.is_some_and(|rate| rate <= 0.0) {
+            return Err(PyValueError::new_err("growth_rate must be positive"));
+        }
+        if docs.is_some() && !read_write {
+            return Err(PyValueError::new_err("docs requires read_write"));
+        }
+        if growth_rate.is_some() && !read_write {
+            return Err(PyValueError::new_err("growth_rate requires read_write"));
+        }
+        if (write_new_ids || growth_rate.is_some()) && write_mode == WriteMode::Update {
+            return Err(PyValueError::new_err(
+                "write_new_ids and growth_rate cannot be combined with write_mode=\"update\"",
+            ));
+        }
+
+        if qps.is_some_and(|qps| qps <= 0.0) {
+            return Err(PyValueError::new_err("qps must be positive"));
+        }
+        if max_update_attempts == Some(0) {
+            return Err(PyValueError::new_err(
+                "max_update_attempts must be positive",
+            ));
+        }
+        if max_in_flight == Some(0) {
+            return Err(PyValueError::new_err("max_in_flight must be positive"));
+        }
+        match (burst_on_secs, burst_off_secs) {
+            (None, None) => {}
+            (Some(on), Some(_)) if on > 0 => {}
+            _ => {
+                return Err(PyValueError::new_err(
+                    "burst_on_secs and burst_off_secs must be set together, with burst_on_secs > 0",
+                ))
+            }
+        }
+        if qps_profile.is_some() && (qps.is_some() || burst_on_secs.is_some()) {
+            return Err(PyValueError::new_err(
+                "qps_profile cannot be combined with qps or burst_on_secs",
+            ));
+        }
+        if mode == "lookup" && (read_write || query_generator.is_some()) {
+            return Err(PyValueError::new_err(
+                "lookup mode cannot be combined with read_write or query_generator",
+            ));
+        }
+        if cold_queries == Some(0) {
+            return Err(PyValueError::new_err("cold_queries must be positive"));
+        }
+        if mode == "lookup" && cold_queries.is_some() {
+            return Err(PyValueError::new_err(
+                "cold_queries cannot be combined with lookup mode",
+            ));
+        }
+        if !(0.0..=1.0).contains(&min_ground_truth_coverage) {
+            return Err(PyValueError::new_err(
+                "min_ground_truth_coverage must be between 0 and 1",
+            ));
+        }
+        if incident_factor.is_some_and(|factor| factor <= 1.0) {
+            return Err(PyValueError::new_err(
+                "incident_factor must be greater than 1",
+            ));
+        }
+        if !(0.0..=1.0).contains(&read_your_writes) {
+            return Err(PyValueError::new_err(
+                "read_your_writes must be between 0 and 1",
+            ));
+        }
+        if query_sample_size == Some(0) {
+            return Err(PyValueError::new_err("query_sample_size must be positive"));
+        }
+        if recall_sample_size == Some(0) {
+            return Err(PyValueError::new_err("recall_sample_size must be positive"));
+        }
+        if recall_target_ci.is_some_and(|ci| ci <= 0.0) {
+            return Err(PyValueError::new_err("recall_target_ci must be positive"));
+        }
+        check_consistency(consistency.as_deref())
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        Reporting::new(report_interval_secs, report_window_secs)
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        if qps_profile_speedup <= 0.0 {
+            return Err(PyValueError::new_err(
+                "qps_profile_speedup must be positive",
+            ));
         }
 
         Ok(Self {
             collection,
-            queries,
+            queries: QuerySource::extract(&queries)?,
             top_k,
             int_filter,
             keyword_filter,
             concurrency,
             size,
+            dataset,
             timeout,
             mode,
             cache_dir,
             read_write,
+            docs_override: docs,
             warmup,
+            cold_queries,
+            payload_size,
+            id_scheme,
+            namespace_ids,
+            ingest_run_id,
+            namespaces,
+            namespace_distribution,
+            dimension,
+            resize_vectors,
+            metric,
+            verify_scores,
+            search_params: search_params
+                .map(SearchParams::extract_all)
+                .transpose()?
+                .unwrap_or_default(),
+            slow_query_threshold_ms,
+            slow_query_log,
+            incident_factor,
+            qps,
+            adaptive_qps,
+            max_in_flight,
+            burst_on_secs,
+            burst_off_secs,
+            qps_profile,
+            qps_profile_speedup,
+            checkpoint_interval_secs,
+            // Checkpoints rotate metrics out of the registry, into the results by default
+            checkpoint_metrics: checkpoint_metrics.or_else(|| {
+                results_dir
+                    .as_ref()
+                    .filter(|_| checkpoint_interval_secs.is_some())
+                    .map(|dir| {
+                        format!(
+                            "{}/{{run_id}}/checkpoint-{{checkpoint}}.parquet",
+                            dir.trim_end_matches('/')
+                        )
+                    })
+            }),
+            query_generator: query_generator.map(Arc::new),
+            write_transform: write_transform.map(|callable| PyDocumentTransform {
+                callable: Arc::new(callable),
+            }),
+            write_mode,
+            max_update_attempts,
+            write_order,
+            write_id_range,
+            write_new_ids,
+            growth_rate,
+            include_payload,
+            consistency,
+            read_your_writes,
+            query_sample_size,
+            ground_truth,
+            recall_sample_size,
+            recall_target_ci,
+            recall_diagnostics,
+            stratify_recall,
+            min_ground_truth_coverage,
+            report_interval_secs,
+            report_window_secs,
+            provider_params: provider_params
+                .map(|params| ProviderParams::new(&params))
+                .transpose()?,
+            results_dir,
+            comparison: None,
+            filter_counts: None,
+        })
+    }
+}
+
+impl QueryConfig {
+    pub fn ids(&self, run_id: &str) -> IdCodec {
+        IdCodec {
+            scheme: self.id_scheme,
+            prefix: self.namespace_ids.then(|| run_id_prefix(run_id)),
+        }
+    }
+
+    /// IDs of the documents the collection was ingested with, as returned by queries.
+    pub fn collection_ids(&self) -> IdCodec {
+        IdCodec {
+            scheme: self.id_scheme,
+            prefix: self.ingest_run_id.as_deref().map(run_id_prefix),
+        }
+    }
+
+    pub fn namespaces(&self) -> Option<Namespaces> {
+        self.namespaces
+            .map(|count| Namespaces::new(count, self.namespace_distribution))
+    }
+
+    pub fn query_options(&self) -> QueryOptions {
+        QueryOptions {
+            metric: self.metric,
+            include_vectors: self.verify_scores,
+            include_payload: self.include_payload,
+            consistency: self.consistency.clone(),
+            search_params: self.search_params.first().cloned(),
+            namespace: None,
+            tag: None,
+        }
+    }
+
+    pub async fn schedule(&self) -> anyhow::Result<Schedule> {
+        if let Some(path) = &self.qps_profile {
+            let profile = Profile::load(path, &self.cache_dir, self.qps_profile_speedup).await?;
+            return Ok(Schedule::Profile(profile));
+        }
+
+        Ok(match (self.burst_on_secs, self.burst_off_secs) {
+            (Some(on), Some(off)) => Schedule::Burst {
+                on: Duration::from_secs(on),
+                off: Duration::from_secs(off),
+                qps: self.qps,
+            },
+            _ => Schedule::Constant(self.qps),
+        })
+    }
+
+    /// Prefix of the run's report lines: the comparison target or the provider, and the
+    /// dataset.
+    pub fn prefix(&self, provider_name: &str) -> String {
+        match &self.comparison {
+            Some(comparison) => format!("{}@{}", comparison.target, self.size),
+            None => format!("{}@{}", provider_name, self.size),
+        }
+    }
+
+    /// Documents written in `read_write` mode.
+    pub fn docs(&self) -> String {
+        self.docs_override
+            .clone()
+            .unwrap_or_else(|| self.dataset.docs.clone())
+    }
+
+    /// Files the run reads, for the run manifest.
+    pub fn datasets(&self) -> Vec<String> {
+        let mut datasets: Vec<String> = self
+            .queries
+            .path()
+            .map(str::to_string)
+            .into_iter()
+            .collect();
+        datasets.extend(self.ground_truth.clone());
+        datasets.extend(self.qps_profile.clone());
+        if self.read_write {
+            datasets.push(self.docs());
+        }
+        datasets
+    }
+
+    /// Number of documents in the dataset.
+    pub fn num_docs(&self) -> u64 {
+        self.dataset.num_docs
+    }
+
+    pub fn reporting(&self) -> Reporting {
+        Reporting::new(self.report_interval_secs, self.report_window_secs)
+            .expect("validated in constructor")
+    }
+
+    /// Expected dimension, defaulting to the dataset's when its own queries are used.
+    pub fn dimension(&self) -> Dimension {
+        let from_dataset = (self.queries.path() == Some(self.dataset.queries.as_str()))
+            .then_some(self.dataset.dimension);
+
+        Dimension {
+            expected: self.dimension.or(from_dataset),
+            resize: self.resize_vectors,
+        }
+    }
+
+    pub fn transform(&self, run_id: &str) -> DocumentTransform {
+        DocumentTransform {
+            payload_size: self.payload_size,
+            ids: self.ids(run_id),
+            dimension: self.dimension(),
+            namespaces: self.namespaces(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl QueryConfig {
+    /// Filter mode config of a single query with `top_k`, for unit tests.
+    pub(crate) fn for_test(top_k: u32) -> Self {
+        use pyo3::types::PyList;
+
+        use crate::data::Query;
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| -> PyResult<Self> {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("collection", "test")?;
+            let queries = [Bound::new(py, Query::new(vec![1.0, 0.0]))?];
+            kwargs.set_item("queries", PyList::new(py, queries)?)?;
+            kwargs.set_item("top_k", top_k)?;
+            kwargs.set_item("concurrency", 1)?;
+            kwargs.set_item("size", "100k")?;
+            kwargs.set_item("timeout", 1)?;
+            kwargs.set_item("mode", "filter")?;
+            kwargs.set_item("cache_dir", "/tmp")?;
+            py.get_type::<QueryConfig>()
+                .call((), Some(&kwargs))?
+                .extract()
         })
+        .unwrap()
     }
 }
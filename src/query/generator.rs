@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use async_channel::Sender;
+use pyo3::{prelude::*, types::PyIterator};
+use tracing::info;
+
+use crate::data::{Dimension, Query};
+
+/// Python object queries are pulled from.
+enum Source {
+    /// Called with no arguments for every query. Returning `None` ends the stream.
+    Callable(Py<PyAny>),
+    Iterator(Py<PyIterator>),
+}
+
+/// Feed queries from a Python callable or iterable of query dicts into the workers.
+///
+/// Runs on a blocking thread and only holds the GIL while pulling the next query.
+pub fn python_query_generator(
+    generator: Arc<Py<PyAny>>,
+    mut dimension: Dimension,
+    tx: Sender<Query>,
+) -> anyhow::Result<()> {
+    let source = Python::with_gil(|py| -> PyResult<Source> {
+        let generator = generator.bind(py);
+        if generator.is_callable() {
+            Ok(Source::Callable(generator.clone().unbind()))
+        } else {
+            Ok(Source::Iterator(generator.try_iter()?.unbind()))
+        }
+    })?;
+
+    loop {
+        let next = Python::with_gil(|py| -> PyResult<Option<Query>> {
+            let item = match &source {
+                Source::Callable(f) => f.bind(py).call0()?,
+                Source::Iterator(it) => match it.bind(py).clone().next() {
+                    Some(item) => item?,
+                    None => return Ok(None),
+                },
+            };
+            if item.is_none() {
+                return Ok(None);
+            }
+
+            Query::from_py(&item).map(Some)
+        })?;
+
+        let Some(mut query) = next else {
+            info!("Query generator exhausted");
+            return Ok(());
+        };
+        dimension.check(&mut query.dense)?;
+
+        tx.send_blocking(query)?;
+    }
+}
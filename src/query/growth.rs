@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::ingest::Tally;
+use crate::provider::PyProvider;
+use crate::telemetry::metrics::Recorder;
+
+/// Time between collection size samples.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Record the size of a collection growing during the run as `bench.query.collection_size`,
+/// every [`SAMPLE_INTERVAL`], so query latency can be plotted against it.
+///
+/// The size is the provider's `count()` when it can report one, otherwise the dataset size
+/// plus the net-new documents acknowledged so far.
+pub async fn sample_collection_size(
+    provider: PyProvider,
+    collection: String,
+    count: bool,
+    base: u64,
+    tally: Tally,
+    m: Recorder,
+) -> anyhow::Result<()> {
+    let mut ticker = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        ticker.tick().await;
+
+        let counted = if count {
+            match provider.count(collection.clone()).await {
+                Ok(size) => size,
+                Err(_) if provider.is_aborted() => return Ok(()),
+                Err(error) => {
+                    warn!(?error, "Failed to count documents");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let size = counted.unwrap_or(base + tally.acknowledged_docs());
+
+        m.gauge("bench.query.collection_size", size as f64);
+    }
+}
@@ -0,0 +1,159 @@
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use rand::prelude::*;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+use crate::provider::{ErrorClass, PyProvider};
+use crate::query::traffic::{InFlight, Pacer};
+use crate::query::QueryConfig;
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::output::report;
+use crate::telemetry::Snapshot;
+
+/// Spawn workers issuing `query_by_id` point lookups of uniformly sampled dataset IDs,
+/// paced by the traffic schedule. Runs until aborted.
+pub async fn spawn_lookup_workers(
+    config: QueryConfig,
+    provider: PyProvider,
+    m: Recorder,
+    pacer: Pacer,
+) -> anyhow::Result<()> {
+    // Documents are written by a separate ingest run, namespaced by its run ID if any
+    let ids = config.collection_ids();
+    let num_docs = config.num_docs();
+
+    let mut workers = JoinSet::new();
+    let in_flight = InFlight::new(config.max_in_flight);
+    for worker_id in 0..config.concurrency {
+        let worker_id = worker_id.to_string();
+        let config = config.clone();
+        let provider = provider.clone();
+        let in_flight = in_flight.clone();
+        let m = m.clone();
+        let ids = ids.clone();
+        let pacer = pacer.clone();
+
+        workers.spawn(async move {
+            while !provider.is_aborted() {
+                pacer.acquire().await;
+
+                let id = ids.encode(rand::rng().random_range(0..num_docs));
+                let (permit, in_flight_wait) = in_flight.acquire().await;
+                if let Some(wait) = in_flight_wait {
+                    m.observe(
+                        "bench.lookup.in_flight_wait_ms",
+                        wait.as_secs_f64() * 1000.0,
+                    );
+                }
+
+                let s = Instant::now();
+                let result = provider
+                    .query_by_id(
+                        config.collection.clone(),
+                        id.clone(),
+                        config.consistency.clone(),
+                    )
+                    .await;
+                drop(permit);
+
+                m.incr("bench.lookup.requests", 1.0);
+                match result {
+                    Ok(doc) => {
+                        pacer.succeeded();
+                        m.incr("bench.lookup.oks", 1.0);
+                        m.observe_with(
+                            "bench.lookup.latency_ms",
+                            s.elapsed().as_millis() as f64,
+                            &[("worker_id", &worker_id)],
+                        );
+                        m.observe("bench.lookup.misses", if doc.is_some() { 0.0 } else { 1.0 });
+                    }
+                    Err(_) if provider.is_aborted() => break,
+                    Err(error) => {
+                        let class = ErrorClass::of(&error);
+                        m.incr(&format!("bench.lookup.errors.{}", class.as_str()), 1.0);
+                        error!(?error, id, "Failed to look up document");
+                        if class == ErrorClass::RateLimited {
+                            if let Some(qps) = pacer.rate_limited() {
+                                info!(qps, "Rate limited, throttling");
+                                m.incr("bench.lookup.rate_limit_backoffs", 1.0);
+                            }
+                        }
+
+                        let jitter = rand::rng().random_range(10..100);
+                        tokio::time::sleep(Duration::from_millis(jitter)).await;
+                    }
+                }
+            }
+        });
+    }
+
+    while let Some(res) = workers.join_next().await {
+        res?;
+    }
+
+    Ok(())
+}
+
+fn availability(stats: &Snapshot) -> f64 {
+    let requests = stats.total("bench.lookup.requests");
+    let errors = stats.total_prefix("bench.lookup.errors.");
+    if requests > 0.0 {
+        (1.0 - errors / requests) * 100.0
+    } else {
+        100.0
+    }
+}
+
+pub fn print_lookup_stats(stats: &Snapshot, prefix: &str) {
+    report(format!(
+        "{:>16}] {}, Throughput: {}, Latency: {}, {}, Misses: {}",
+        prefix,
+        match availability(stats) {
+            100.0 => "100%".to_string().green().bold(),
+            a if a > 99.0 => format!("{:.2}%", a).yellow().bold(),
+            a => format!("{:.2}%", a).red().bold(),
+        },
+        format!(
+            "{:.2} lookups/s",
+            stats.instantaneous_rate("bench.lookup.oks")
+        )
+        .blue()
+        .bold(),
+        format!(
+            "p50={:.2}ms",
+            stats.quantile("bench.lookup.latency_ms", 0.50)
+        )
+        .yellow()
+        .bold(),
+        format!(
+            "p99={:.2}ms",
+            stats.quantile("bench.lookup.latency_ms", 0.99)
+        )
+        .magenta()
+        .bold(),
+        format!("{:.2}%", stats.avg("bench.lookup.misses") * 100.0).bold(),
+    ));
+}
+
+/// Lookup stats passed to the stats callback.
+pub fn lookup_stats(stats: &Snapshot) -> Vec<(&'static str, f64)> {
+    vec![
+        ("availability", availability(stats)),
+        ("oks", stats.total("bench.lookup.oks")),
+        ("errors", stats.total_prefix("bench.lookup.errors.")),
+        ("throughput", stats.instantaneous_rate("bench.lookup.oks")),
+        ("latency_avg_ms", stats.avg("bench.lookup.latency_ms")),
+        (
+            "latency_p50_ms",
+            stats.quantile("bench.lookup.latency_ms", 0.50),
+        ),
+        (
+            "latency_p99_ms",
+            stats.quantile("bench.lookup.latency_ms", 0.99),
+        ),
+        ("miss_rate", stats.avg("bench.lookup.misses")),
+    ]
+}
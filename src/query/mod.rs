@@ -1,45 +1,194 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use anyhow::Context;
 use async_channel::{Receiver, Sender};
 use colored::Colorize;
-use parquet::arrow::arrow_reader::ParquetRecordBatchReader;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
 use rand::prelude::*;
-use tokio::sync::mpsc;
 use tokio::{signal::ctrl_c, task::JoinSet};
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::data::{load_from_path, parse_from_batch, Document, Query};
-use crate::ingest::{print_writer_stats, spawn_writers};
-use crate::provider::PyProvider;
-use crate::query::recall::calculate_recall;
+use crate::data::{
+    load_ground_truth, parse_embeddings, Dimension, Document, FilterCounts, IdCodec, Namespaces,
+    Query,
+};
+use crate::dispatch;
+use crate::ingest::{
+    print_lookup_summary, print_update_stats, print_writer_stats, spawn_updaters, spawn_writers,
+    warm_connections, writer_stats, FreshnessProbes, RecentWrites, Tally,
+};
+use crate::provider::{Capability, ErrorClass, PyProvider, QueryOptions};
+use crate::query::balance::report_worker_balance;
+use crate::query::cold::{report_cold_start, ColdStart};
+use crate::query::generator::python_query_generator;
+use crate::query::growth::sample_collection_size;
+use crate::query::lookup::{lookup_stats, print_lookup_stats, spawn_lookup_workers};
+use crate::query::recall::{
+    calculate_ndcg, calculate_recall, check_ground_truth, diagnose_recall, report_recall,
+    report_recall_progress, stratify, RecallUnavailable, Selectivity,
+};
+use crate::query::results::{report_latency_by_results, Fill};
+use crate::query::ryw::ReadYourWrites;
+use crate::query::scores::{is_ordered, score_deviations};
+use crate::query::slow::{write_slow_queries, SlowQueryLog};
+use crate::query::smoothness::{report_smoothness, SmoothnessWindows, Stalls};
+use crate::query::soak::run_checkpoints;
+use crate::query::traffic::{InFlight, Pacer};
+use crate::query::writes::WriteSource;
 use crate::s3::ensure_file;
-use crate::telemetry::metrics::{consume_metrics, snapshot_metrics, Metric, Recorder};
+use crate::telemetry::metrics::{Phase, PhaseSwitch, Recorder, Registry};
+use crate::telemetry::output::report;
+use crate::telemetry::{
+    detect_incidents, report_incidents, Reporting, RunIds, RunInfo, RunResults, Snapshot,
+    StatsCallback,
+};
+
+mod balance;
+mod cold;
 
 mod config;
-pub use config::QueryConfig;
+pub use config::{QueryConfig, WriteMode, WriteOrder};
+
+mod generator;
+mod growth;
+mod lookup;
+pub(crate) mod recall;
+mod results;
+mod ryw;
+mod scores;
+mod slow;
+mod smoothness;
+mod soak;
+mod traffic;
+mod writes;
+
+pub async fn start(
+    config: QueryConfig,
+    provider: PyProvider,
+    registry: Registry,
+    on_stats: Option<StatsCallback>,
+    ids: RunIds,
+    abort: CancellationToken,
+) -> anyhow::Result<Vec<String>> {
+    let provider = provider
+        .with_params(config.provider_params.clone())
+        .with_namespaces(config.namespaces())
+        .with_abort(abort.clone());
+
+    if config.search_params.len() <= 1 {
+        return Ok(vec![
+            run(config, provider, registry, on_stats, ids, abort).await?,
+        ]);
+    }
+
+    // Sweep over search parameter sets, one run each
+    let mut run_ids = Vec::new();
+    for (i, search_params) in config.search_params.clone().into_iter().enumerate() {
+        if abort.is_cancelled() {
+            break;
+        }
+        info!(search_params = %search_params.label, "Benchmarking search params");
+
+        let mut config = config.clone();
+        config.search_params = vec![search_params];
+        run_ids.push(
+            run(
+                config,
+                provider.clone(),
+                registry.clone(),
+                on_stats.clone(),
+                ids.nth(i),
+                abort.clone(),
+            )
+            .await?,
+        );
+    }
 
-mod recall;
+    Ok(run_ids)
+}
 
-pub async fn start(config: QueryConfig, provider: PyProvider) -> anyhow::Result<()> {
+async fn run(
+    config: QueryConfig,
+    provider: PyProvider,
+    registry: Registry,
+    on_stats: Option<StatsCallback>,
+    ids: RunIds,
+    abort: CancellationToken,
+) -> anyhow::Result<String> {
     let provider_name = provider.name().await?;
+    let provider_version = provider.version().await?;
     info!(?config, ?provider_name, "Starting query bench");
 
-    let run_id = uuid::Uuid::new_v4().to_string();
+    let run_id = ids.generate();
+    let results = config
+        .results_dir
+        .as_deref()
+        .map(|dir| RunResults::create(dir, &run_id))
+        .transpose()?;
+
+    let (metrics_tx, metrics_rx) = registry.channel();
+    let metrics_task_handle = tokio::spawn(registry.clone().consume(metrics_rx));
 
-    let (metrics_tx, metrics_rx) = mpsc::unbounded_channel::<Metric>();
-    let metrics_task_handle = tokio::spawn(consume_metrics(metrics_rx));
+    // Skip or downgrade what the provider doesn't support, before labeling the run with
+    // its config
+    provider
+        .check_consistency(config.consistency.as_deref())
+        .await?;
+    let mut config = config;
+    let mut capabilities = provider.capabilities().await?;
+    if config.ids(&run_id).is_string() && !capabilities.supports(Capability::StringIds) {
+        anyhow::bail!(
+            "Provider does not support string IDs, use id_scheme=\"numeric\" or \"uuid\" without namespace_ids"
+        );
+    }
+    let filtered = config.int_filter.is_some() || config.keyword_filter.is_some();
+    let skip = (filtered && !capabilities.require(Capability::Filters, "skipping the run"))
+        || (config.mode == "lookup"
+            && !capabilities.require(Capability::QueryById, "skipping the run"));
+    if config.read_write
+        && config.write_mode == WriteMode::Update
+        && !capabilities.require(Capability::Update, "writing with upsert instead")
+    {
+        config.write_mode = WriteMode::Upsert;
+    }
+    let freshness_probes = !config.read_write
+        || capabilities.require(
+            Capability::QueryById,
+            "skipping freshness probes and read-your-writes checks",
+        );
+    if !freshness_probes {
+        config.read_your_writes = 0.0;
+    }
+    let count = config.growth_rate.is_none()
+        || capabilities.require(
+            Capability::Count,
+            "estimating the collection size from acknowledged writes",
+        );
 
+    // Labels every metric with the stage of the run
+    let phase = PhaseSwitch::new(Phase::Warmup);
     let m = Recorder::new(
         metrics_tx,
         [
             ("run_id", run_id.clone()),
+            ("parent_run_id", ids.parent_label()),
             ("provider", provider.name().await?),
             ("collection", config.collection.clone()),
-            ("queries", config.queries.clone()),
+            ("queries", config.queries.label()),
             ("top_k", config.top_k.to_string()),
             ("concurrency", config.concurrency.to_string()),
+            ("adaptive_qps", config.adaptive_qps.to_string()),
+            (
+                "max_in_flight",
+                config
+                    .max_in_flight
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
             ("size", config.size.clone()),
             ("timeout", config.timeout.to_string()),
             (
@@ -51,10 +200,231 @@ pub async fn start(config: QueryConfig, provider: PyProvider) -> anyhow::Result<
                 config.keyword_filter.clone().unwrap_or_default(),
             ),
             ("warmup", config.warmup.to_string()),
+            (
+                "cold_queries",
+                config
+                    .cold_queries
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "incident_factor",
+                config
+                    .incident_factor
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
             ("read_write", config.read_write.to_string()),
+            ("docs", config.docs_override.clone().unwrap_or_default()),
+            ("write_mode", config.write_mode.to_string()),
+            (
+                "max_update_attempts",
+                config
+                    .max_update_attempts
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("write_order", config.write_order.to_string()),
+            (
+                "write_id_range",
+                config
+                    .write_id_range
+                    .map(|(start, end)| format!("{start}..{end}"))
+                    .unwrap_or_default(),
+            ),
+            ("write_new_ids", config.write_new_ids.to_string()),
+            (
+                "growth_rate",
+                config
+                    .growth_rate
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("read_your_writes", config.read_your_writes.to_string()),
+            (
+                "ground_truth",
+                config.ground_truth.clone().unwrap_or_default(),
+            ),
+            (
+                "recall_sample_size",
+                config
+                    .recall_sample_size
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("recall_diagnostics", config.recall_diagnostics.to_string()),
+            ("stratify_recall", config.stratify_recall.to_string()),
+            (
+                "min_ground_truth_coverage",
+                config.min_ground_truth_coverage.to_string(),
+            ),
+            (
+                "report_interval_secs",
+                config.report_interval_secs.to_string(),
+            ),
+            (
+                "report_window_secs",
+                config
+                    .report_window_secs
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "recall_target_ci",
+                config
+                    .recall_target_ci
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "query_sample_size",
+                config
+                    .query_sample_size
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
             ("mode", config.mode.to_string()),
-        ],
-    );
+            (
+                "payload_size",
+                config
+                    .payload_size
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            ("id_scheme", config.id_scheme.to_string()),
+            ("namespace_ids", config.namespace_ids.to_string()),
+            (
+                "ingest_run_id",
+                config.ingest_run_id.clone().unwrap_or_default(),
+            ),
+            (
+                "namespaces",
+                config.namespaces.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+            (
+                "namespace_distribution",
+                config.namespace_distribution.to_string(),
+            ),
+            (
+                "dimension",
+                config.dimension.map(|v| v.to_string()).unwrap_or_default(),
+            ),
+            ("metric", config.metric.to_string()),
+            ("verify_scores", config.verify_scores.to_string()),
+            ("include_payload", config.include_payload.to_string()),
+            (
+                "consistency",
+                config.consistency.clone().unwrap_or_default(),
+            ),
+            (
+                "search_params",
+                config
+                    .search_params
+                    .first()
+                    .map(|p| p.label.clone())
+                    .unwrap_or_default(),
+            ),
+            (
+                "python_threads",
+                dispatch::threads()
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "results_dir",
+                config.results_dir.clone().unwrap_or_default(),
+            ),
+            (
+                "provider_params",
+                config
+                    .provider_params
+                    .as_ref()
+                    .map(|p| p.label.clone())
+                    .unwrap_or_default(),
+            ),
+            ("qps", config.qps.map(|v| v.to_string()).unwrap_or_default()),
+            (
+                "burst",
+                match (config.burst_on_secs, config.burst_off_secs) {
+                    (Some(on), Some(off)) => format!("{on}s/{off}s"),
+                    _ => String::new(),
+                },
+            ),
+            (
+                "qps_profile",
+                config.qps_profile.clone().unwrap_or_default(),
+            ),
+            (
+                "qps_profile_speedup",
+                config.qps_profile_speedup.to_string(),
+            ),
+            (
+                "checkpoint_interval_secs",
+                config
+                    .checkpoint_interval_secs
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            ),
+            (
+                "target",
+                config
+                    .comparison
+                    .as_ref()
+                    .map(|c| c.target.clone())
+                    .unwrap_or_default(),
+            ),
+        ]
+        .into_iter()
+        .chain(provider_version.labels()),
+    )
+    .with_phase(&phase);
+    capabilities.record(&m);
+
+    registry
+        .start_run(
+            RunInfo::new(
+                "query",
+                &m,
+                provider_version,
+                config.datasets(),
+                &config.cache_dir,
+            )
+            .with_notes(capabilities.notes()),
+        )
+        .await;
+
+    if skip {
+        // The other targets of a comparison wait for this one to start
+        if let Some(comparison) = &config.comparison {
+            comparison.ready(&abort).await;
+        }
+        drop(m);
+        metrics_task_handle.await??;
+        registry.finish_run(&run_id).await;
+        if let Some(results) = results {
+            results.write(&registry, &run_id).await?;
+        }
+        return Ok(run_id);
+    }
+
+    if config.mode == "filter" && !config.warmup {
+        check_ground_truth(&config, &m, &config.prefix(&provider_name)).await?;
+    }
+
+    let steady = if config.warmup {
+        Phase::Warmup
+    } else {
+        Phase::Load
+    };
+    let cold = match config.cold_queries {
+        // Connections are left for the first queries to open
+        Some(queries) => Some(ColdStart::new(queries, &phase, steady)),
+        None => {
+            warm_connections(&provider, config.concurrency, &m).await?;
+            phase.set(steady);
+            None
+        }
+    };
 
     let mut tasks = JoinSet::new();
 
@@ -62,124 +432,276 @@ pub async fn start(config: QueryConfig, provider: PyProvider) -> anyhow::Result<
     let (queries_tx, queries_rx) = async_channel::bounded::<Query>(1000);
     let qtx = queries_tx.clone();
 
-    // Run query workers
-    tasks.spawn(spawn_workers(
-        config.clone(),
-        provider.clone(),
-        m.clone(),
-        queries_rx,
-        false,
-    ));
-
-    let cancel_token = CancellationToken::new();
-    let cancel_token_clone = cancel_token.clone();
+    // Capture slow queries
+    let (slow_log, slow_log_handle) = match config.slow_query_threshold_ms {
+        Some(threshold) => {
+            let (log, handle) = SlowQueryLog::spawn(threshold);
+            (Some(log), Some(handle))
+        }
+        None => (None, None),
+    };
 
-    if config.read_write {
-        let (writes_tx, writes_rx) = async_channel::bounded::<Vec<Document>>(100);
-        let file_path = ensure_file(
-            format!("s3://topk-bench/docs-{}.parquet", config.size),
-            config.cache_dir.clone(),
+    // Track acknowledged writes for read-your-writes checks
+    let recent = (config.read_write
+        && config.write_mode == WriteMode::Upsert
+        && config.read_your_writes > 0.0)
+        .then(|| RecentWrites::new(1_000));
+    let ryw = recent.clone().map(|recent| {
+        ReadYourWrites::spawn(
+            &mut tasks,
+            provider.clone(),
+            config.collection.clone(),
+            config.query_options(),
+            config.read_your_writes,
+            recent,
+            m.clone(),
         )
-        .await?;
-        let fp = file_path.clone();
-
-        tasks.spawn_blocking(move || {
-            // Spawn continuous batch producer task that loops the file
-            let file = std::fs::File::open(&fp)?;
-            let mut batch_reader = ParquetRecordBatchReader::try_new(file, 1)?;
+    });
 
-            loop {
-                if cancel_token_clone.is_cancelled() {
-                    return anyhow::Ok(());
-                }
+    // Start together with the other targets of a comparison
+    if let Some(comparison) = &config.comparison {
+        comparison.ready(&abort).await;
+    }
 
-                while let Some(batch) = batch_reader.next() {
-                    if cancel_token_clone.is_cancelled() {
-                        break;
-                    }
+    // Run query workers, paced by the traffic schedule
+    let schedule = config.schedule().await?;
+    let lookups = config.mode == "lookup";
+    let pacer = Pacer::new(schedule).adaptive(config.adaptive_qps);
+    let stalls = Stalls::default();
+    if lookups {
+        tasks.spawn(spawn_lookup_workers(
+            config.clone(),
+            provider.clone(),
+            m.clone(),
+            pacer.clone(),
+        ));
+    } else {
+        tasks.spawn(spawn_workers(
+            config.clone(),
+            provider.clone(),
+            m.clone(),
+            queries_rx,
+            config.collection_ids(),
+            slow_log,
+            Some(pacer.clone()),
+            ryw,
+            cold.clone(),
+            Some(stalls.clone()),
+            false,
+        ));
+    }
 
-                    match &parse_from_batch(batch?)[..] {
-                        [] => anyhow::bail!("No documents in batch"),
-                        [document] => queries_tx.send_blocking(Query {
-                            dense: document
-                                .dense_embedding
-                                .clone()
-                                .expect("Dense embedding not found"),
-                            recall: HashMap::new(),
-                        })?,
-                        _ => anyhow::bail!("Multiple documents in batch"),
-                    }
-                }
-            }
-        });
+    let cancel_token = abort.child_token();
 
-        let cancel_token = cancel_token.clone();
-        tasks.spawn_blocking(move || {
-            let file = std::fs::File::open(&file_path)?;
-            let mut batch_reader = ParquetRecordBatchReader::try_new(file, 100)?;
+    // The targets of a comparison share one query stream
+    if let Some(comparison) = &config.comparison {
+        tasks.spawn(forward_queries(comparison.queries.clone(), queries_tx));
+    } else if !lookups {
+        spawn_query_source(&config, &mut tasks, queries_tx, cancel_token.clone()).await?;
+    }
 
-            loop {
-                if cancel_token.is_cancelled() {
-                    return anyhow::Ok(());
-                }
+    if config.read_write {
+        let (writes_tx, writes_rx) = async_channel::bounded::<Vec<Document>>(100);
+        let file_path = ensure_file(config.docs(), config.cache_dir.clone()).await?;
 
-                while let Some(batch) = batch_reader.next() {
-                    if cancel_token.is_cancelled() {
-                        break;
-                    }
+        let source = WriteSource {
+            path: file_path,
+            order: config.write_order,
+            id_range: config.write_id_range,
+            next_id: (config.write_new_ids || config.growth_rate.is_some())
+                .then(|| config.num_docs()),
+            rate: config.growth_rate,
+            transform: config.transform(&run_id),
+            write_transform: config.write_transform.clone(),
+        };
+        let cancel_token = cancel_token.clone();
+        tasks.spawn_blocking(move || source.run(writes_tx, cancel_token));
 
-                    let documents = parse_from_batch(batch?);
-                    let documents = documents
-                        .into_iter()
-                        .map(|mut doc| {
-                            doc.tag = Some(format!("tag-{}", rand::rng().random_range(0..1000)));
-                            doc
-                        })
-                        .collect();
+        let tally = Tally::default();
+        match config.write_mode {
+            WriteMode::Upsert => {
+                let writers = spawn_writers(
+                    provider.clone(),
+                    config.collection.clone(),
+                    1,
+                    config.ids(&run_id),
+                    if freshness_probes {
+                        FreshnessProbes::unbounded(config.consistency.clone())
+                    } else {
+                        FreshnessProbes::new(config.consistency.clone(), None, 0.0)
+                    },
+                    None,
+                    recent,
+                    tally.clone(),
+                    None,
+                    None,
+                    m.clone(),
+                    writes_rx,
+                );
+                tasks.spawn(async move { writers.await.map(|_| ()) });
 
-                    writes_tx.send_blocking(documents)?;
+                if config.growth_rate.is_some() {
+                    tasks.spawn(sample_collection_size(
+                        provider.clone(),
+                        config.collection.clone(),
+                        count,
+                        config.num_docs(),
+                        tally,
+                        m.clone(),
+                    ));
                 }
             }
-        });
-
-        tasks.spawn(spawn_writers(
-            provider.clone(),
-            config.collection.clone(),
-            1,
-            m.clone(),
-            writes_rx,
-        ));
-    } else {
-        let queries = load_from_path(&config.queries, &config.cache_dir).await?;
-        tasks.spawn(random_query_generator(queries, queries_tx));
+            WriteMode::Update => {
+                let updaters = spawn_updaters(
+                    provider.clone(),
+                    config.collection.clone(),
+                    1,
+                    config.consistency.clone(),
+                    config.max_update_attempts,
+                    m.clone(),
+                    writes_rx,
+                );
+                tasks.spawn(async move { updaters.await.map(|_| ()) });
+            }
+        }
     }
 
-    tasks.spawn(report_metrics(
+    // The reporter only returns early when the stats callback stops the run
+    let mut reporter = tokio::spawn(report_metrics(
+        registry.clone(),
+        m.clone(),
         run_id.clone(),
-        format!("{}@{}", provider_name, config.size),
+        config.prefix(&provider_name),
+        config.top_k,
         config.read_write,
+        lookups,
+        config.reporting(),
+        stalls,
+        on_stats,
     ));
 
+    // Soak mode checkpoints
+    if let Some(interval) = config.checkpoint_interval_secs {
+        tasks.spawn(run_checkpoints(
+            provider.clone(),
+            config.clone(),
+            m.clone(),
+            registry.clone(),
+            run_id.clone(),
+            config.prefix(&provider_name),
+            Duration::from_secs(interval),
+        ));
+    }
+
     let start = Instant::now();
-    tokio::select! {
+    let stopped = tokio::select! {
         _ = ctrl_c() => {
             info!("Ctrl-C received, aborting.");
-            return Ok(());
+            abort.cancel();
+            true
         }
         _ = tokio::time::sleep(Duration::from_secs(config.timeout)) => {
             info!("Queries completed in {:.2}s", start.elapsed().as_secs_f64());
+            false
         }
-    }
+        result = &mut reporter => {
+            result??;
+            info!("Stats callback requested stop, aborting.");
+            true
+        }
+        _ = abort.cancelled() => {
+            info!("Run aborted.");
+            true
+        }
+    };
 
+    phase.set(Phase::Drain);
     qtx.close();
     cancel_token.cancel();
 
+    reporter.abort();
     tasks.abort_all();
-    while let Some(_) = tasks.join_next().await {
+    while tasks.join_next().await.is_some() {
         //
     }
+    // The reports below read the last samples of the load phase from the registry
+    m.flushed().await;
 
-    if config.mode == "filter" && !config.warmup {
+    if !lookups {
+        report_worker_balance(
+            &registry.snapshot(&run_id).await,
+            &m,
+            &config.prefix(&provider_name),
+        );
+    }
+
+    if !lookups {
+        report_smoothness(
+            &registry.snapshot(&run_id).await,
+            &m,
+            &config.prefix(&provider_name),
+        );
+    }
+
+    if !lookups {
+        report_latency_by_results(
+            &registry.snapshot(&run_id).await.phase(steady.as_str()),
+            &m,
+            &config.prefix(&provider_name),
+        );
+    }
+
+    if cold.is_some() {
+        report_cold_start(
+            &registry.snapshot(&run_id).await.phase(Phase::Cold.as_str()),
+            &registry.snapshot(&run_id).await.phase(steady.as_str()),
+            &m,
+            &config.prefix(&provider_name),
+        );
+    }
+
+    if let Some(factor) = config.incident_factor {
+        let area = if lookups { "lookup" } else { "query" };
+        let incidents = detect_incidents(
+            &registry.snapshot(&run_id).await.phase(steady.as_str()),
+            &format!("bench.{area}.latency_ms"),
+            factor,
+        );
+        report_incidents(&incidents, factor, area, &m, &config.prefix(&provider_name));
+        registry.add_incidents(&run_id, incidents).await;
+    }
+
+    if let Some(qps) = pacer.limit() {
+        let area = if lookups { "lookup" } else { "query" };
+        m.gauge(&format!("bench.{area}.sustainable_qps"), qps);
+        report(format!(
+            "{:>16}] {} {}",
+            config.prefix(&provider_name),
+            "Rate limited, sustainable rate:".yellow().bold(),
+            format!("{qps:.2} queries/s").bold(),
+        ));
+    }
+
+    if let Some(handle) = slow_log_handle {
+        let slow_queries = handle.await?;
+        if !slow_queries.is_empty() {
+            let path = match &results {
+                Some(results) => results.path("slow_queries.parquet"),
+                None => config.slow_query_log.replace("{run_id}", &run_id),
+            };
+            write_slow_queries(slow_queries, &run_id, &path).await?;
+        }
+    }
+
+    if config.read_write {
+        print_lookup_summary(
+            &registry.snapshot(&run_id).await,
+            &config.prefix(&provider_name),
+        );
+    }
+
+    if config.mode == "filter" && !config.warmup && !stopped {
+        phase.set(Phase::Recall);
         measure_recall(
             provider.clone(),
             {
@@ -188,53 +710,236 @@ pub async fn start(config: QueryConfig, provider: PyProvider) -> anyhow::Result<
                 c
             },
             m.clone(),
+            registry.clone(),
             run_id.clone(),
         )
         .await?;
     }
-    metrics_task_handle.abort();
+    // Wait for the registry to take in the last samples, such as the gauges above
+    drop(m);
+    metrics_task_handle.await??;
+    registry.finish_run(&run_id).await;
+    if let Some(results) = results {
+        results.write(&registry, &run_id).await?;
+    }
 
-    Ok(())
+    Ok(run_id)
 }
 
 async fn measure_recall(
     provider: PyProvider,
     config: QueryConfig,
     m: Recorder,
+    registry: Registry,
     run_id: String,
 ) -> anyhow::Result<()> {
     info!("Measuring recall...");
+    let start = chrono::Utc::now();
 
-    let queries = load_from_path(&config.queries, &config.cache_dir).await?;
+    // Evaluate a random subset of queries, picked by index
+    let available = config.queries.count(&config.cache_dir).await?;
+    let sample = config
+        .recall_sample_size
+        .filter(|&n| n < available)
+        .map(|n| {
+            rand::seq::index::sample(&mut rand::rng(), available, n)
+                .into_iter()
+                .collect::<HashSet<_>>()
+        });
+    let total = sample.as_ref().map_or(available, HashSet::len);
 
     let (queries_tx, queries_rx) = async_channel::bounded::<Query>(1_000);
 
-    // Send queries to the workers
-    let generator = tokio::spawn(async move {
-        for query in queries {
-            queries_tx.send(query).await?;
+    // Selectivity is the share of the documents a filter pair matches
+    let mut config = config;
+    if config.stratify_recall {
+        match FilterCounts::load(&config.docs(), &config.cache_dir).await {
+            Ok(counts) => config.filter_counts = Some(Arc::new(counts)),
+            Err(error) => warn!(
+                ?error,
+                "Failed to count the documents matching each filter, recall isn't reported by selectivity"
+            ),
         }
-        anyhow::Ok(())
-    });
-    let reporter = report_metrics(
+    }
+
+    let mut ground_truth = match &config.ground_truth {
+        Some(path) => Some(load_ground_truth(path, &config.cache_dir).await?),
+        None => None,
+    };
+
+    // Stream queries from the file to the workers
+    let generator = {
+        let (queries, cache_dir) = (config.queries.clone(), config.cache_dir.clone());
+        let mut dimension = config.dimension();
+        let stratified = config.stratify_recall;
+        let mut i = 0;
+        tokio::spawn(async move {
+            queries
+                .stream(&cache_dir, move |mut query| {
+                    let index = i;
+                    i += 1;
+                    if sample.as_ref().is_some_and(|s| !s.contains(&index)) {
+                        return Ok(());
+                    }
+
+                    dimension
+                        .check(&mut query.dense)
+                        .with_context(|| format!("Invalid embedding for query {index}"))?;
+                    if let Some(ground_truth) = &mut ground_truth {
+                        query.recall = ground_truth.remove(&index).unwrap_or_default();
+                    }
+
+                    if stratified {
+                        for query in stratify(query) {
+                            queries_tx.send_blocking(query)?;
+                        }
+                        return Ok(());
+                    }
+                    Ok(queries_tx.send_blocking(query)?)
+                })
+                .await
+        })
+    };
+    let prefix = config.prefix(&provider.name().await?);
+    let reporter = report_recall_progress(
+        registry.clone(),
         run_id.clone(),
-        format!("{}@{}", provider.name().await?, config.size),
-        config.read_write,
+        prefix.clone(),
+        start,
+        // Queries are evaluated once per filter pair their ground truth covers
+        (!config.stratify_recall).then_some(total),
+        config.recall_target_ci,
+        config.reporting().interval,
     );
     let workers = spawn_workers(
         config.clone(),
         provider.clone(),
         m.clone(),
         queries_rx,
+        config.collection_ids(),
+        None,
+        None,
+        None,
+        None,
+        None,
         true,
     );
 
     tokio::select! {
-        _ = workers => {}
-        _ = reporter => {}
+        _ = workers => generator.await??,
+        result = reporter => {
+            // The estimate converged, remaining queries are skipped
+            result?;
+            generator.abort();
+        }
+    }
+
+    m.flushed().await;
+    report_recall(&registry.snapshot(&run_id).await.since(start), &m, &prefix);
+
+    Ok(())
+}
+
+/// Feed the run's queries to `tx` from a task of `tasks`: the query generator if set,
+/// the embeddings of the docs file in read_write mode, or the queries file.
+pub async fn spawn_query_source(
+    config: &QueryConfig,
+    tasks: &mut JoinSet<anyhow::Result<()>>,
+    tx: Sender<Query>,
+    cancel: CancellationToken,
+) -> anyhow::Result<()> {
+    let dimension = config.dimension();
+
+    // A custom query generator replaces the built-in query sources
+    if let Some(generator) = config.query_generator.clone() {
+        tasks.spawn_blocking(move || python_query_generator(generator, dimension, tx));
+    } else if config.read_write {
+        let path = ensure_file(config.docs(), config.cache_dir.clone()).await?;
+        tasks.spawn_blocking(move || document_query_generator(&path, dimension, tx, cancel));
+    } else {
+        let queries = load_queries(config).await?;
+        tasks.spawn(random_query_generator(queries, tx));
+    }
+
+    Ok(())
+}
+
+/// Pass a comparison target's copy of the shared query stream on to the workers.
+async fn forward_queries(queries: Receiver<Query>, tx: Sender<Query>) -> anyhow::Result<()> {
+    while let Ok(query) = queries.recv().await {
+        tx.send(query).await?;
+    }
+
+    Ok(())
+}
+
+/// Queries held in memory for the random query stream without a `query_sample_size`.
+const DEFAULT_QUERY_SAMPLE_SIZE: usize = 100_000;
+
+/// Load a sample of the queries, all of them for smaller query files, and validate their
+/// embedding dimensions.
+async fn load_queries(config: &QueryConfig) -> anyhow::Result<Vec<Query>> {
+    let sample_size = config
+        .query_sample_size
+        .unwrap_or(DEFAULT_QUERY_SAMPLE_SIZE);
+    let mut queries = config
+        .queries
+        .sample(&config.cache_dir, sample_size)
+        .await?;
+
+    let mut dimension = config.dimension();
+    for (i, query) in queries.iter_mut().enumerate() {
+        dimension
+            .check(&mut query.dense)
+            .with_context(|| format!("Invalid embedding for query {i}"))?;
+    }
+
+    Ok(queries)
+}
+
+/// Embeddings read from the docs file in read_write mode, after which queries are drawn from
+/// them at random.
+const DOCUMENT_QUERY_POOL: usize = 10_000;
+
+/// Query with the embeddings of the documents in `path`, for read_write mode. Reads only the
+/// `dense` column in regular batches until [`DOCUMENT_QUERY_POOL`] embeddings are in memory
+/// (or the file ends), then recycles them in random order.
+fn document_query_generator(
+    path: &std::path::Path,
+    mut dimension: Dimension,
+    tx: Sender<Query>,
+    cancel: CancellationToken,
+) -> anyhow::Result<()> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path)?)?;
+    let projection = ProjectionMask::columns(builder.parquet_schema(), ["dense"]);
+    let reader = builder
+        .with_projection(projection)
+        .with_batch_size(1024)
+        .build()?;
+
+    let mut pool = Vec::with_capacity(DOCUMENT_QUERY_POOL);
+    for batch in reader {
+        for mut dense in parse_embeddings(&batch?) {
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+            dimension.check(&mut dense)?;
+
+            tx.send_blocking(Query::new(dense.clone()))?;
+            pool.push(dense);
+        }
+        if pool.len() >= DOCUMENT_QUERY_POOL {
+            break;
+        }
+    }
+
+    if pool.is_empty() {
+        anyhow::bail!("No documents in {}", path.display());
+    }
+    while !cancel.is_cancelled() {
+        let dense = pool.choose(&mut rand::rng()).expect("pool is not empty");
+        tx.send_blocking(Query::new(dense.clone()))?;
     }
-    generator.abort();
-    generator.await??;
 
     Ok(())
 }
@@ -251,67 +956,268 @@ async fn random_query_generator(queries: Vec<Query>, tx: Sender<Query>) -> anyho
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn spawn_workers(
     config: QueryConfig,
     provider: PyProvider,
     m: Recorder,
     queries: Receiver<Query>,
+    ids: IdCodec,
+    slow_log: Option<SlowQueryLog>,
+    pacer: Option<Pacer>,
+    ryw: Option<ReadYourWrites>,
+    cold: Option<ColdStart>,
+    stalls: Option<Stalls>,
     recall: bool,
 ) -> anyhow::Result<()> {
     // Spawn worker tasks
     let mut workers = JoinSet::new();
+    let in_flight = InFlight::new(config.max_in_flight);
+    let namespaces = config.namespaces();
 
-    for _ in 0..config.concurrency {
+    for worker_id in 0..config.concurrency {
+        let worker_id = worker_id.to_string();
         let queries = queries.clone();
         let config = config.clone();
         let provider = provider.clone();
+        let in_flight = in_flight.clone();
         let m = m.clone();
+        let ids = ids.clone();
+        let slow_log = slow_log.clone();
+        let pacer = pacer.clone();
+        let ryw = ryw.clone();
+        let cold = cold.clone();
+        let stalls = stalls.clone();
+        let namespaces = namespaces.clone();
 
         workers.spawn(async move {
-            loop {
+            'worker: loop {
+                if let Some(pacer) = &pacer {
+                    pacer.acquire().await;
+                }
+
                 let ss = Instant::now();
-                let query = match queries.recv().await {
-                    Ok(query) => query,
-                    Err(_) => break,
+                let mut query = tokio::select! {
+                    biased;
+                    _ = provider.aborted() => break,
+                    query = queries.recv() => match query {
+                        Ok(query) => query,
+                        Err(_) => break,
+                    },
                 };
-                m.record(
+                m.observe(
                     "bench.query.recv_latency_ms",
-                    ss.elapsed().as_millis() as f64,
+                    ss.elapsed().as_secs_f64() * 1000.0,
                 );
 
+                // Shared across retries, so the vector isn't copied per attempt
+                let dense = Arc::new(std::mem::take(&mut query.dense));
+
+                let top_k = query.top_k.unwrap_or(config.top_k);
+                let int_filter = query.int_filter.or(config.int_filter);
+                let keyword_filter = query
+                    .keyword_filter
+                    .clone()
+                    .or_else(|| config.keyword_filter.clone());
+                let namespace = query
+                    .namespace
+                    .clone()
+                    .or_else(|| namespaces.as_ref().map(Namespaces::sample));
+
+                // Across retries, for the latency the caller sees
+                let first_attempt = Instant::now();
+                let mut attempts = 0;
+
                 loop {
-                    let start = Instant::now();
+                    attempts += 1;
+                    let (permit, in_flight_wait) = in_flight.acquire().await;
+                    if let Some(wait) = in_flight_wait {
+                        m.observe("bench.query.in_flight_wait_ms", wait.as_secs_f64() * 1000.0);
+                    }
 
-                    match provider
+                    let start = Instant::now();
+                    let result = provider
                         .query(
                             config.collection.clone(),
-                            query.dense.clone(),
-                            config.top_k,
-                            config.int_filter.clone(),
-                            config.keyword_filter.clone(),
+                            dense.clone(),
+                            top_k,
+                            int_filter,
+                            keyword_filter.clone(),
+                            QueryOptions {
+                                namespace: namespace.clone(),
+                                ..config.query_options()
+                            },
                         )
-                        .await
-                    {
-                        Ok(res) => {
+                        .await;
+                    drop(permit);
+
+                    match result {
+                        Ok((res, timings)) => {
+                            let elapsed = start.elapsed();
+                            let duration = elapsed.as_millis();
+                            if let Some(pacer) = &pacer {
+                                pacer.succeeded();
+                            }
+                            m.observe_with(
+                                "bench.query.queue_wait_ms",
+                                timings.queue_wait.as_secs_f64() * 1000.0,
+                                &[("worker_id", &worker_id)],
+                            );
+                            m.observe(
+                                "bench.query.provider_ms",
+                                timings.provider.as_secs_f64() * 1000.0,
+                            );
+                            // Everything else, e.g. converting arguments and results
+                            m.observe(
+                                "bench.query.dispatch_overhead_ms",
+                                elapsed.saturating_sub(timings.provider).as_secs_f64() * 1000.0,
+                            );
+                            let result_bytes: usize = res.iter().map(|doc| doc.approx_size()).sum();
+                            m.observe("bench.query.result_bytes", result_bytes as f64);
+                            // Fewer than `top_k` results, e.g. under selective filters
+                            m.observe("bench.query.result_count", res.len() as f64);
+                            let fill = Fill::of(res.len(), top_k);
+
+                            if let Some(slow_log) = &slow_log {
+                                slow_log.observe(
+                                    duration as f64,
+                                    &dense,
+                                    top_k,
+                                    int_filter,
+                                    keyword_filter.clone(),
+                                    &res,
+                                );
+                            }
+
+                            if config.verify_scores {
+                                for deviation in score_deviations(&res, &dense, config.metric)
+                                {
+                                    match deviation {
+                                        Some(d) => m.observe("bench.query.score_deviation", d),
+                                        None => m.incr("bench.query.score_unverified", 1.0),
+                                    }
+                                }
+                            }
+
+                            if let (Some(first), Some(last)) = (res.first(), res.last()) {
+                                if let (Some(top), Some(bottom)) = (first.score, last.score) {
+                                    m.observe("bench.query.score_top", top as f64);
+                                    m.observe("bench.query.score_last", bottom as f64);
+                                }
+                            }
+                            if is_ordered(&res, config.metric) == Some(false) {
+                                m.incr("bench.query.misordered", 1.0);
+                            }
+
                             if recall {
-                                let recall = calculate_recall(res, query.clone(), &config)
-                                    .expect("failed to calculate recall");
-                                m.record("bench.query.recall", recall as f64);
+                                match calculate_recall(&res, &query, &config, &ids) {
+                                    // Skip queries without ground truth, evaluate the rest
+                                    Err(e) if e.is::<RecallUnavailable>() => {
+                                        debug!(error = %e, "Recall unavailable");
+                                        m.incr("bench.query.recall_unavailable", 1.0);
+                                    }
+                                    Err(error) => {
+                                        m.incr("bench.query.recall_errors", 1.0);
+                                        error!(?error, "Failed to calculate recall");
+                                    }
+                                    Ok(recall) => {
+                                        m.observe("bench.query.recall", recall as f64);
+                                        if config.stratify_recall {
+                                            match Selectivity::of(&query, &config) {
+                                                Some(selectivity) => m.observe(
+                                                    selectivity.metric(),
+                                                    recall as f64,
+                                                ),
+                                                None => m.incr(
+                                                    "bench.query.recall_selectivity_unknown",
+                                                    1.0,
+                                                ),
+                                            }
+                                        }
+                                        match calculate_ndcg(&res, &query, &config, &ids) {
+                                            Ok(ndcg) => m.observe("bench.query.ndcg", ndcg as f64),
+                                            Err(error) => {
+                                                m.incr("bench.query.recall_errors", 1.0);
+                                                error!(?error, "Failed to calculate nDCG");
+                                            }
+                                        }
+
+                                        if config.recall_diagnostics {
+                                            match diagnose_recall(&res, &query, &config, &ids) {
+                                                Ok(diagnostics) => {
+                                                    if !diagnostics.missing.is_empty() {
+                                                        debug!(missing = ?diagnostics.missing, "Missing results");
+                                                    }
+                                                    m.observe(
+                                                        "bench.query.recall_missing",
+                                                        diagnostics.missing.len() as f64,
+                                                    );
+                                                    m.observe(
+                                                        "bench.query.recall_extraneous",
+                                                        diagnostics.extraneous as f64,
+                                                    );
+                                                    m.observe(
+                                                        "bench.query.recall_duplicates",
+                                                        diagnostics.duplicates as f64,
+                                                    );
+                                                }
+                                                Err(error) => {
+                                                    m.incr("bench.query.recall_errors", 1.0);
+                                                    error!(?error, "Failed to diagnose recall");
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
                             } else {
-                                let duration = start.elapsed().as_millis();
-                                m.record("bench.query.oks", 1.0);
-                                m.record("bench.query.latency_ms", duration as f64);
+                                m.incr("bench.query.oks", 1.0);
+                                m.observe_with(
+                                    "bench.query.latency_ms",
+                                    duration as f64,
+                                    &[("worker_id", &worker_id), ("results", fill.as_str())],
+                                );
+                                m.observe(
+                                    "bench.query.e2e_latency_ms",
+                                    first_attempt.elapsed().as_millis() as f64,
+                                );
+                                m.observe("bench.query.attempts", attempts as f64);
+                                if let Some(cold) = &cold {
+                                    cold.completed();
+                                }
+                                if let Some(stalls) = &stalls {
+                                    stalls.success();
+                                }
+                            }
+
+                            // Verify a fraction of recent writes are readable
+                            if let Some(ryw) = &ryw {
+                                ryw.after_query(top_k, &m);
                             }
 
                             break;
                         }
+                        Err(_) if provider.is_aborted() => break 'worker,
                         Err(error) => {
-                            m.record("bench.query.errors", 1.0);
+                            let class = ErrorClass::of(&error);
+                            m.incr(&format!("bench.query.errors.{}", class.as_str()), 1.0);
                             error!(?error, "Failed to query documents");
 
                             // Sleep & retry
                             let jitter = rand::rng().random_range(10..100);
                             tokio::time::sleep(Duration::from_millis(jitter)).await;
+
+                            // Retries of rate-limited queries count against the throttled rate
+                            if let Some(pacer) = pacer
+                                .as_ref()
+                                .filter(|pacer| pacer.is_adaptive())
+                                .filter(|_| class == ErrorClass::RateLimited)
+                            {
+                                if let Some(qps) = pacer.rate_limited() {
+                                    info!(qps, "Rate limited, throttling");
+                                    m.incr("bench.query.rate_limit_backoffs", 1.0);
+                                }
+                                pacer.acquire().await;
+                            }
                         }
                     }
                 }
@@ -328,23 +1234,55 @@ async fn spawn_workers(
 }
 
 // metrics reporter task
-async fn report_metrics(run_id: String, prefix: String, writes: bool) -> anyhow::Result<()> {
-    let mut ticker = tokio::time::interval(Duration::from_secs(1));
-    ticker.tick().await;
+#[allow(clippy::too_many_arguments)]
+async fn report_metrics(
+    registry: Registry,
+    m: Recorder,
+    run_id: String,
+    prefix: String,
+    top_k: u32,
+    writes: bool,
+    lookups: bool,
+    reporting: Reporting,
+    stalls: Stalls,
+    on_stats: Option<StatsCallback>,
+) -> anyhow::Result<()> {
+    let mut ticker = reporting.ticker().await;
+    let mut smoothness = SmoothnessWindows::new(stalls);
+    let mut window_start = chrono::Utc::now();
 
     loop {
         ticker.tick().await;
 
-        let stats = snapshot_metrics(&run_id).await;
+        let stats = reporting.snapshot(&registry, &run_id).await;
 
         // Check if metrics exist (not just if they're zero)
         if stats.is_empty() {
-            println!("{prefix}] Waiting for metrics...");
+            report(format!("{prefix}] Waiting for metrics..."));
+            continue;
+        }
+
+        if lookups {
+            print_lookup_stats(&stats, &prefix);
+            if let Some(on_stats) = &on_stats {
+                if !on_stats.call(&run_id, lookup_stats(&stats)).await? {
+                    return Ok(());
+                }
+            }
             continue;
         }
 
+        // Smoothness over the interval since the last report, regardless of the window
+        let window_end = chrono::Utc::now();
+        let window = smoothness.next(
+            &registry.snapshot(&run_id).await.since(window_start),
+            (window_end - window_start).as_seconds_f64(),
+            &m,
+        );
+        window_start = window_end;
+
         let oks_total = stats.total("bench.query.oks");
-        let errors_total = stats.total("bench.query.errors");
+        let errors_total = stats.total_prefix("bench.query.errors.");
         let requests_total = oks_total + errors_total;
 
         let availability = if requests_total > 0.0 {
@@ -353,19 +1291,22 @@ async fn report_metrics(run_id: String, prefix: String, writes: bool) -> anyhow:
             100.0
         };
 
-        println!(
-            "{:>16}] {}, Throughput: {}, Latency: {}, {}, Recall: {}{}",
+        report(format!(
+            "{:>16}] {}, Throughput: {}, Latency: {}, {}, Recall: {}{}{}{}{}{}{}{}{}",
             prefix,
             // Availability
             match availability {
-                a if a == 100.0 => format!("100%").green().bold(),
+                100.0 => "100%".to_string().green().bold(),
                 a if a > 99.0 => format!("{:.2}%", a).yellow().bold(),
                 a => format!("{:.2}%", a).red().bold(),
             },
             // Throughput
-            format!("{} queries/s", stats.instantaneous_rate("bench.query.oks"))
-                .blue()
-                .bold(),
+            format!(
+                "{:.2} queries/s",
+                stats.instantaneous_rate("bench.query.oks")
+            )
+            .blue()
+            .bold(),
             // Latency
             format!("avg={:.2}ms", stats.avg("bench.query.latency_ms"))
                 .yellow()
@@ -380,6 +1321,83 @@ async fn report_metrics(run_id: String, prefix: String, writes: bool) -> anyhow:
             format!("avg={:.2}", stats.avg("bench.query.recall"))
                 .yellow()
                 .bold(),
+            // Queries without ground truth
+            {
+                let unavailable = stats.total("bench.query.recall_unavailable");
+                if unavailable == 0.0 {
+                    "".to_string()
+                } else {
+                    format!(" ({unavailable} unavailable)")
+                        .red()
+                        .bold()
+                        .to_string()
+                }
+            },
+            // nDCG
+            {
+                let ndcg = stats.avg("bench.query.ndcg");
+                if ndcg == 0.0 {
+                    "".to_string()
+                } else {
+                    format!(", nDCG: avg={:.2}", ndcg).bold().to_string()
+                }
+            },
+            // Results under-filled, which makes latencies look better than they are
+            {
+                let result_count = stats.avg("bench.query.result_count");
+                if stats.count("bench.query.result_count") == 0 || result_count >= top_k as f64 {
+                    "".to_string()
+                } else {
+                    format!(", Results: avg={result_count:.2} < top_k={top_k}")
+                        .red()
+                        .bold()
+                        .to_string()
+                }
+            },
+            // Latency including retries, once a query needed more than one attempt
+            {
+                let attempts = stats.avg("bench.query.attempts");
+                if attempts <= 1.0 {
+                    "".to_string()
+                } else {
+                    format!(
+                        ", E2E: p99={:.2}ms ({attempts:.2} attempts/query)",
+                        stats.quantile("bench.query.e2e_latency_ms", 0.99)
+                    )
+                    .red()
+                    .bold()
+                    .to_string()
+                }
+            },
+            // Where the time of a query goes
+            {
+                if stats.count("bench.query.provider_ms") == 0 {
+                    "".to_string()
+                } else {
+                    let [(_, recv), (_, dispatch), (_, provider)] = latency_budget(&stats);
+                    let total = recv + dispatch + provider;
+                    let share = |ms: f64| ms / total.max(f64::MIN_POSITIVE) * 100.0;
+                    format!(
+                        ", Time: {} + {} + {}",
+                        format!("recv={recv:.2}ms ({:.0}%)", share(recv)).bold(),
+                        format!("dispatch={dispatch:.2}ms ({:.0}%)", share(dispatch))
+                            .yellow()
+                            .bold(),
+                        format!("provider={provider:.2}ms ({:.0}%)", share(provider))
+                            .blue()
+                            .bold(),
+                    )
+                }
+            },
+            // Growth mode
+            {
+                let size = stats.latest("bench.query.collection_size");
+                if size == 0.0 {
+                    "".to_string()
+                } else {
+                    format!(", Collection: {size} docs").bold().to_string()
+                }
+            },
             // Recv
             {
                 let recv_max = stats.quantile("bench.query.recv_latency_ms", 1.0);
@@ -389,10 +1407,89 @@ async fn report_metrics(run_id: String, prefix: String, writes: bool) -> anyhow:
                     format!(", Skew max={:.2}ms", recv_max).bold().to_string()
                 }
             },
-        );
+            // Read-your-writes
+            {
+                let checks = stats.total("bench.query.ryw_checks");
+                if checks == 0.0 {
+                    "".to_string()
+                } else {
+                    let violations = stats.total("bench.query.ryw_violations");
+                    format!(", RYW violations={:.2}%", violations / checks * 100.0)
+                        .bold()
+                        .to_string()
+                }
+            },
+        ));
 
         if writes {
-            print_writer_stats(&stats, prefix.clone())
+            if stats.total("bench.update.requests") > 0.0 {
+                print_update_stats(&stats, prefix.clone())
+            } else {
+                print_writer_stats(&stats, prefix.clone())
+            }
+        }
+
+        if let Some(on_stats) = &on_stats {
+            let mut values = vec![
+                ("availability", availability),
+                ("oks", oks_total),
+                ("errors", errors_total),
+                ("throughput", stats.instantaneous_rate("bench.query.oks")),
+                ("latency_avg_ms", stats.avg("bench.query.latency_ms")),
+                (
+                    "latency_p50_ms",
+                    stats.quantile("bench.query.latency_ms", 0.50),
+                ),
+                (
+                    "latency_p99_ms",
+                    stats.quantile("bench.query.latency_ms", 0.99),
+                ),
+                (
+                    "e2e_latency_p99_ms",
+                    stats.quantile("bench.query.e2e_latency_ms", 0.99),
+                ),
+                ("attempts_avg", stats.avg("bench.query.attempts")),
+                ("recall_avg", stats.avg("bench.query.recall")),
+                ("result_count_avg", stats.avg("bench.query.result_count")),
+                (
+                    "recall_unavailable",
+                    stats.total("bench.query.recall_unavailable"),
+                ),
+                ("recall_errors", stats.total("bench.query.recall_errors")),
+                ("throughput_jitter", window.throughput_jitter),
+                ("latency_cv", window.latency_cv),
+                ("max_stall_ms", window.max_stall_ms),
+            ];
+            if stats.count("bench.query.provider_ms") > 0 {
+                values.extend(latency_budget(&stats));
+            }
+            if writes {
+                values.extend(writer_stats(&stats));
+            }
+            if stats.count("bench.query.collection_size") > 0 {
+                values.push((
+                    "collection_size",
+                    stats.latest("bench.query.collection_size"),
+                ));
+            }
+
+            if !on_stats.call(&run_id, values).await? {
+                return Ok(());
+            }
         }
     }
 }
+
+/// Average time a query spends waiting for the query stream, in the harness's dispatch
+/// (including the wait for a Python thread) and in the provider, which add up to the time
+/// a worker spends per query.
+fn latency_budget(stats: &Snapshot) -> [(&'static str, f64); 3] {
+    [
+        ("recv_avg_ms", stats.avg("bench.query.recv_latency_ms")),
+        (
+            "dispatch_overhead_avg_ms",
+            stats.avg("bench.query.dispatch_overhead_ms"),
+        ),
+        ("provider_avg_ms", stats.avg("bench.query.provider_ms")),
+    ]
+}
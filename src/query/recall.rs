@@ -1,45 +1,638 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use crate::data::{Document, Query};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use rand::Rng;
+use tracing::info;
+
+use crate::data::{load_ground_truth, Document, IdCodec, Query, UNFILTERED};
 use crate::query::QueryConfig;
+use crate::telemetry::metrics::{Recorder, Registry};
+use crate::telemetry::output::report;
+use crate::telemetry::{quantile_of_sorted, Snapshot};
+
+/// Queries evaluated before the recall estimate is trusted for an early exit.
+const MIN_RECALL_SAMPLES: usize = 100;
+
+/// Report recall progress every `interval`, returning once the 95% confidence interval
+/// half-width of the recall estimate is within `target_ci`. Without a target this only
+/// returns on error.
+pub async fn report_recall_progress(
+    registry: Registry,
+    run_id: String,
+    prefix: String,
+    start: DateTime<Utc>,
+    total: Option<usize>,
+    target_ci: Option<f64>,
+    interval: Duration,
+) -> anyhow::Result<()> {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+
+        let stats = registry.snapshot(&run_id).await.since(start);
+        let evaluated = stats.count("bench.query.recall");
+        let unavailable = stats.count("bench.query.recall_unavailable")
+            + stats.count("bench.query.recall_errors");
+        let ci = if evaluated > 0 {
+            1.96 * stats.stddev("bench.query.recall") / (evaluated as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        report(format!(
+            "{:>16}] Recall: {} evaluated, {}, {}{}",
+            prefix,
+            match total {
+                Some(total) => format!("{}/{}", evaluated + unavailable, total),
+                None => (evaluated + unavailable).to_string(),
+            }
+            .blue()
+            .bold(),
+            format!("avg={:.4}", stats.avg("bench.query.recall"))
+                .yellow()
+                .bold(),
+            format!("95% CI=±{ci:.4}").magenta().bold(),
+            // Diagnostics
+            if stats.count("bench.query.recall_missing") == 0 {
+                "".to_string()
+            } else {
+                format!(
+                    ", Missing: avg={:.2}, Extraneous: {}, Duplicates: {}",
+                    stats.avg("bench.query.recall_missing"),
+                    stats.total("bench.query.recall_extraneous"),
+                    stats.total("bench.query.recall_duplicates"),
+                )
+                .bold()
+                .to_string()
+            },
+        ));
+
+        if target_ci.is_some_and(|target| evaluated >= MIN_RECALL_SAMPLES && ci <= target) {
+            info!(evaluated, ci, "Recall estimate converged, stopping early");
+            return Ok(());
+        }
+    }
+}
+
+/// Resamples of the recall estimate drawn for its bootstrap confidence interval.
+const BOOTSTRAP_RESAMPLES: usize = 1_000;
+
+/// Summarize the recall pass with the number of queries evaluated and a 95% bootstrap
+/// confidence interval of the mean recall, recorded as the gauges
+/// `bench.query.recall_evaluated`, `bench.query.recall_ci_low` and
+/// `bench.query.recall_ci_high`.
+pub fn report_recall(stats: &Snapshot, m: &Recorder, prefix: &str) {
+    let recalls: Vec<f64> = stats
+        .metrics
+        .iter()
+        .filter(|sample| sample.name == "bench.query.recall")
+        .map(|sample| sample.value)
+        .collect();
+    if recalls.is_empty() {
+        return;
+    }
+
+    let (low, high) = bootstrap_ci(&recalls, 0.95, &mut rand::rng());
+    m.gauge("bench.query.recall_evaluated", recalls.len() as f64);
+    m.gauge("bench.query.recall_ci_low", low);
+    m.gauge("bench.query.recall_ci_high", high);
+
+    report(format!(
+        "{:>16}] {} {} over {} queries, {}",
+        prefix,
+        "Recall:".green().bold(),
+        format!("avg={:.4}", stats.avg("bench.query.recall"))
+            .yellow()
+            .bold(),
+        recalls.len(),
+        format!("95% CI={low:.4}..{high:.4}").magenta().bold(),
+    ));
+
+    for selectivity in Selectivity::ALL {
+        let evaluated = stats.count(selectivity.metric());
+        if evaluated == 0 {
+            continue;
+        }
+        report(format!(
+            "{:>16}]   {} selectivity: {} over {} queries, p10={:.4}",
+            prefix,
+            selectivity,
+            format!("avg={:.4}", stats.avg(selectivity.metric()))
+                .yellow()
+                .bold(),
+            evaluated,
+            stats.quantile(selectivity.metric(), 0.10),
+        ));
+    }
+    let unknown = stats.total("bench.query.recall_selectivity_unknown");
+    if unknown > 0.0 {
+        report(format!(
+            "{:>16}]   {} queries of unknown selectivity",
+            prefix, unknown
+        ));
+    }
+}
+
+/// Percentile bootstrap confidence interval of the mean of `values`.
+fn bootstrap_ci(values: &[f64], confidence: f64, rng: &mut impl Rng) -> (f64, f64) {
+    let mut means: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| {
+            let sum: f64 = (0..values.len())
+                .map(|_| values[rng.random_range(0..values.len())])
+                .sum();
+            sum / values.len() as f64
+        })
+        .collect();
+    means.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let tail = (1.0 - confidence) / 2.0;
+    (
+        quantile_of_sorted(&means, tail),
+        quantile_of_sorted(&means, 1.0 - tail),
+    )
+}
+
+/// Queries whose ground truth covers the filters and `top_k` they are run with.
+#[derive(Debug, Default)]
+struct Coverage {
+    total: usize,
+    covered: usize,
+    /// Uncovered queries by why their recall is unavailable
+    uncovered: BTreeMap<String, usize>,
+}
+
+/// Check that the ground truth covers the run's queries before the run, reporting the share
+/// covered and recording it as the gauge `bench.query.ground_truth_coverage`. Fails when
+/// it is below `min_ground_truth_coverage`, instead of counting the queries as
+/// `bench.query.recall_unavailable` after the run.
+pub async fn check_ground_truth(
+    config: &QueryConfig,
+    m: &Recorder,
+    prefix: &str,
+) -> anyhow::Result<()> {
+    let mut separate = match &config.ground_truth {
+        Some(path) => Some(load_ground_truth(path, &config.cache_dir).await?),
+        None => None,
+    };
+
+    let coverage = Arc::new(Mutex::new(Coverage::default()));
+    {
+        let (coverage, checked) = (coverage.clone(), config.clone());
+        let mut i = 0;
+        config
+            .queries
+            .stream(&config.cache_dir, move |mut query| {
+                let index = i;
+                i += 1;
+                if let Some(separate) = &mut separate {
+                    query.recall = separate.remove(&index).unwrap_or_default();
+                }
+
+                let mut coverage = coverage.lock().unwrap();
+                coverage.total += 1;
+                match ground_truth(&query, &checked) {
+                    Ok(_) => coverage.covered += 1,
+                    Err(e) => *coverage.uncovered.entry(e.to_string()).or_default() += 1,
+                }
+                Ok(())
+            })
+            .await?;
+    }
+    let coverage = std::mem::take(&mut *coverage.lock().unwrap());
+    if coverage.total == 0 {
+        return Ok(());
+    }
+
+    let share = coverage.covered as f64 / coverage.total as f64;
+    m.gauge("bench.query.ground_truth_coverage", share);
+    report(format!(
+        "{:>16}] Ground truth: {} queries covered at top_k={}, {}",
+        prefix,
+        format!("{}/{}", coverage.covered, coverage.total)
+            .blue()
+            .bold(),
+        config.top_k,
+        format!("{:.2}%", share * 100.0).yellow().bold(),
+    ));
+    for (reason, queries) in &coverage.uncovered {
+        report(format!("{:>16}]   {reason}: {queries} queries", prefix));
+    }
+
+    if share < config.min_ground_truth_coverage {
+        anyhow::bail!(
+            "Ground truth covers {} of {} queries, below min_ground_truth_coverage={}: {}. \
+             Pass queries with ground truth for the configured filters and top_k, or lower \
+             min_ground_truth_coverage to skip the rest",
+            coverage.covered,
+            coverage.total,
+            config.min_ground_truth_coverage,
+            coverage
+                .uncovered
+                .iter()
+                .map(|(reason, queries)| format!("{reason} ({queries} queries)"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    }
+
+    Ok(())
+}
 
 pub fn calculate_recall(
-    results: Vec<Document>,
-    query: Query,
+    results: &[Document],
+    query: &Query,
     config: &QueryConfig,
+    ids: &IdCodec,
 ) -> anyhow::Result<f32> {
-    let actual_doc_ids = results
-        .iter()
-        .map(|x| Ok(x.id.parse()?))
-        .collect::<anyhow::Result<HashSet<u32>>>()?;
+    let actual_doc_ids = decode_ids(results, ids)?
+        .into_iter()
+        .collect::<HashSet<u32>>();
 
-    let expected_doc_ids = recall(&query, config)?;
+    let expected_doc_ids = recall(query, config)?.into_iter().collect::<HashSet<u32>>();
     let found_doc_ids = actual_doc_ids.intersection(&expected_doc_ids).count();
 
     Ok(found_doc_ids as f32 / expected_doc_ids.len() as f32)
 }
 
-fn recall(query: &Query, config: &QueryConfig) -> anyhow::Result<HashSet<u32>> {
-    assert!(
-        config.top_k <= 100,
-        "top_k must be less than or equal to 100"
-    );
+/// Why recall falls short of 1.0 for a query.
+pub struct RecallDiagnostics {
+    /// Expected doc IDs missing from the results, closest first.
+    pub missing: Vec<u32>,
+    /// Returned documents that don't match the query's filters. Only checked when results
+    /// include the payload.
+    pub extraneous: usize,
+    /// Results repeating a doc ID already returned for the query.
+    pub duplicates: usize,
+}
+
+/// Compare results against the ground truth and the query's filters, to tell filter bugs
+/// (extraneous results) apart from ANN approximation loss (missing results only).
+pub fn diagnose_recall(
+    results: &[Document],
+    query: &Query,
+    config: &QueryConfig,
+    ids: &IdCodec,
+) -> anyhow::Result<RecallDiagnostics> {
+    let actual_doc_ids = decode_ids(results, ids)?
+        .into_iter()
+        .collect::<HashSet<u32>>();
+    let duplicates = results.len() - actual_doc_ids.len();
+    let missing = recall(query, config)?
+        .into_iter()
+        .filter(|id| !actual_doc_ids.contains(id))
+        .collect();
+
+    let extraneous = if config.include_payload {
+        let int_filter = query.int_filter.or(config.int_filter);
+        let keyword_filter = query
+            .keyword_filter
+            .as_ref()
+            .or(config.keyword_filter.as_ref());
+
+        results
+            .iter()
+            .filter(|doc| {
+                int_filter.is_some_and(|f| doc.int_filter > f)
+                    || keyword_filter
+                        .is_some_and(|f| !doc.keyword_filter.split_whitespace().any(|t| t == f))
+            })
+            .count()
+    } else {
+        0
+    };
+
+    Ok(RecallDiagnostics {
+        missing,
+        extraneous,
+        duplicates,
+    })
+}
+
+/// nDCG@k with binary relevance against the ground truth.
+///
+/// Results are ranked by their provider-reported score when every result has one,
+/// otherwise by the order the provider returned them in.
+pub fn calculate_ndcg(
+    results: &[Document],
+    query: &Query,
+    config: &QueryConfig,
+    ids: &IdCodec,
+) -> anyhow::Result<f32> {
+    let mut ranked = results.iter().collect::<Vec<_>>();
+    if ranked.iter().all(|doc| doc.score.is_some()) {
+        let higher_is_closer = config.metric.higher_is_closer();
+        ranked.sort_by(|a, b| {
+            let ordering = a
+                .score
+                .partial_cmp(&b.score)
+                .unwrap_or(std::cmp::Ordering::Equal);
+            if higher_is_closer {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
 
-    let int_filter = config.int_filter.unwrap_or(10000);
-    let keyword_filter = config.keyword_filter.clone().unwrap_or("10000".to_string());
+    let actual_doc_ids = decode_ids(ranked, ids)?;
+    let expected_doc_ids = recall(query, config)?.into_iter().collect::<HashSet<u32>>();
 
-    let doc_ids = query
-        .recall
-        .get(&int_filter)
-        .expect("int_filter not found")
-        .get(&keyword_filter)
-        .expect("keyword_filter not found")
-        .clone()
+    let dcg: f32 = actual_doc_ids
+        .iter()
+        .take(query.top_k.unwrap_or(config.top_k) as usize)
+        .enumerate()
+        .filter(|(_, id)| expected_doc_ids.contains(id))
+        .map(|(rank, _)| 1.0 / (rank as f32 + 2.0).log2())
+        .sum();
+    let ideal: f32 = (0..expected_doc_ids.len())
+        .map(|rank| 1.0 / (rank as f32 + 2.0).log2())
+        .sum();
+
+    Ok(if ideal > 0.0 { dcg / ideal } else { 0.0 })
+}
+
+fn decode_ids<'a>(
+    results: impl IntoIterator<Item = &'a Document>,
+    ids: &IdCodec,
+) -> anyhow::Result<Vec<u32>> {
+    results
         .into_iter()
-        .filter(|x| x.is_positive())
+        .map(|x| {
+            let id = ids
+                .decode(&x.id)
+                .ok_or_else(|| anyhow::anyhow!("Failed to decode ID: {}", x.id))?;
+            Ok(id as u32)
+        })
+        .collect()
+}
+
+/// The query's ground truth can't be evaluated.
+#[derive(Debug)]
+pub enum RecallUnavailable {
+    /// No ground truth for the filters the query was run with
+    Missing {
+        int_filter: u32,
+        keyword_filter: String,
+    },
+    /// The query asks for more results than the ground truth has neighbors for
+    TooDeep { top_k: u32, depth: u32 },
+    /// No documents match the filters the query was run with, so there is nothing to find
+    Empty {
+        int_filter: u32,
+        keyword_filter: String,
+    },
+}
+
+impl fmt::Display for RecallUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecallUnavailable::Missing {
+                int_filter,
+                keyword_filter,
+            } => write!(
+                f,
+                "No ground truth for int_filter={int_filter} keyword_filter={keyword_filter}"
+            ),
+            RecallUnavailable::TooDeep { top_k, depth } => write!(
+                f,
+                "Ground truth covers top_k up to {depth}, query asks for {top_k}"
+            ),
+            RecallUnavailable::Empty {
+                int_filter,
+                keyword_filter,
+            } => write!(
+                f,
+                "Empty ground truth for int_filter={int_filter} keyword_filter={keyword_filter}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecallUnavailable {}
+
+/// Filter pair the query's ground truth is looked up under: its own filters, else the
+/// configured ones, else [`UNFILTERED`].
+fn filters(query: &Query, config: &QueryConfig) -> (u32, String) {
+    let (unfiltered_int, unfiltered_keyword) = UNFILTERED;
+    let int_filter = query
+        .int_filter
+        .or(config.int_filter)
+        .unwrap_or(unfiltered_int);
+    let keyword_filter = query
+        .keyword_filter
+        .clone()
+        .or_else(|| config.keyword_filter.clone())
+        .unwrap_or(unfiltered_keyword.to_string());
+
+    (int_filter, keyword_filter)
+}
+
+/// How few documents a query's filters match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selectivity {
+    /// At most 1% of documents
+    High,
+    /// At most 10% of documents
+    Medium,
+    /// More than 10% of documents
+    Low,
+}
+
+impl Selectivity {
+    pub const ALL: [Selectivity; 3] = [Selectivity::High, Selectivity::Medium, Selectivity::Low];
+
+    /// Selectivity of the query's filters, from the share of the docs file's documents they
+    /// match. `None` if the documents weren't counted.
+    pub fn of(query: &Query, config: &QueryConfig) -> Option<Self> {
+        let (int_filter, keyword_filter) = filters(query, config);
+        let counts = config.filter_counts.as_ref()?;
+
+        Some(match counts.share(int_filter, &keyword_filter) {
+            share if share <= 0.01 => Selectivity::High,
+            share if share <= 0.1 => Selectivity::Medium,
+            _ => Selectivity::Low,
+        })
+    }
+
+    /// Recall of the queries of this selectivity, as a histogram.
+    pub fn metric(&self) -> &'static str {
+        match self {
+            Selectivity::High => "bench.query.recall_selectivity_high",
+            Selectivity::Medium => "bench.query.recall_selectivity_medium",
+            Selectivity::Low => "bench.query.recall_selectivity_low",
+        }
+    }
+}
+
+impl fmt::Display for Selectivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Selectivity::High => write!(f, "high"),
+            Selectivity::Medium => write!(f, "medium"),
+            Selectivity::Low => write!(f, "low"),
+        }
+    }
+}
+
+/// Copies of the query for every filter pair its ground truth covers, for recall
+/// stratified by [`Selectivity`]. Pairs no document matches are skipped. Queries with their
+/// own ground truth stay as they are.
+pub fn stratify(query: Query) -> Vec<Query> {
+    if query.ground_truth.is_some() || query.recall.is_empty() {
+        return vec![query];
+    }
+
+    query
+        .recall
+        .iter()
+        .flat_map(|(int_filter, by_keyword)| {
+            by_keyword
+                .iter()
+                .filter(|(_, doc_ids)| !doc_ids.is_empty())
+                .map(|(keyword_filter, _)| Query {
+                    int_filter: Some(*int_filter),
+                    keyword_filter: Some(keyword_filter.clone()),
+                    ..query.clone()
+                })
+        })
+        .collect()
+}
+
+/// Ground truth of the query for the filters and `top_k` it is run with.
+///
+/// Per-query filters and `top_k` take precedence over the configured ones.
+fn ground_truth<'a>(
+    query: &'a Query,
+    config: &QueryConfig,
+) -> Result<&'a [i64], RecallUnavailable> {
+    let top_k = query.top_k.unwrap_or(config.top_k);
+    let depth = config.dataset.ground_truth_top_k;
+    if top_k > depth {
+        return Err(RecallUnavailable::TooDeep { top_k, depth });
+    }
+
+    let (int_filter, keyword_filter) = filters(query, config);
+
+    query
+        .ground_truth
+        .as_ref()
+        .or_else(|| {
+            query
+                .recall
+                .get(&int_filter)
+                .and_then(|recall| recall.get(&keyword_filter))
+        })
+        .map(Vec::as_slice)
+        .ok_or(RecallUnavailable::Missing {
+            int_filter,
+            keyword_filter,
+        })
+}
+
+/// Ground truth doc IDs for the query's filters, closest first. Unavailable when no document
+/// matches the filters.
+fn recall(query: &Query, config: &QueryConfig) -> anyhow::Result<Vec<u32>> {
+    let top_k = query.top_k.unwrap_or(config.top_k);
+    let doc_ids = ground_truth(query, config)?;
+
+    let doc_ids = doc_ids
+        .iter()
+        .copied()
+        // Negative IDs pad lists shorter than the ground truth depth, doc ID 0 is a document
+        .filter(|x| !x.is_negative())
         .map(|x| x as u32)
-        .take(config.top_k as usize)
-        .collect();
+        .take(top_k as usize)
+        .collect::<Vec<_>>();
+    if doc_ids.is_empty() {
+        let (int_filter, keyword_filter) = filters(query, config);
+        return Err(RecallUnavailable::Empty {
+            int_filter,
+            keyword_filter,
+        }
+        .into());
+    }
 
     Ok(doc_ids)
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn empty_ground_truth_is_unavailable() {
+        let config = QueryConfig::for_test(10);
+
+        // No document matches "a"
+        let (unfiltered_int, unfiltered_keyword) = UNFILTERED;
+        let mut query = Query::new(vec![1.0, 0.0]);
+        query.recall = [(
+            unfiltered_int,
+            [
+                ("a".to_string(), vec![]),
+                (unfiltered_keyword.to_string(), vec![1, 2]),
+            ]
+            .into(),
+        )]
+        .into();
+
+        let stratified = stratify(query.clone());
+        assert_eq!(stratified.len(), 1);
+        assert_eq!(
+            stratified[0].keyword_filter.as_deref(),
+            Some(unfiltered_keyword)
+        );
+
+        query.keyword_filter = Some("a".to_string());
+        let error = calculate_recall(&[], &query, &config, &IdCodec::default()).unwrap_err();
+        assert!(
+            matches!(
+                error.downcast_ref::<RecallUnavailable>(),
+                Some(RecallUnavailable::Empty { .. })
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn bootstrap_ci_of_constant_values_is_the_value() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let (low, high) = bootstrap_ci(&[0.8; 50], 0.95, &mut rng);
+        assert!(
+            (low - 0.8).abs() < 1e-9 && (high - 0.8).abs() < 1e-9,
+            "{low}..{high}"
+        );
+    }
+
+    #[test]
+    fn bootstrap_ci_matches_the_normal_approximation() {
+        // Half the queries with perfect recall, half with none
+        let values: Vec<f64> = (0..1_000).map(|i| (i % 2) as f64).collect();
+        let mut rng = StdRng::seed_from_u64(42);
+        let (low, high) = bootstrap_ci(&values, 0.95, &mut rng);
+
+        // 1.96 standard errors of the mean either side
+        let half_width = 1.96 * (0.25f64 / 1_000.0).sqrt();
+        assert!(low < 0.5 && 0.5 < high, "{low}..{high}");
+        assert!((0.5 - low - half_width).abs() < 0.005, "low {low}");
+        assert!((high - 0.5 - half_width).abs() < 0.005, "high {high}");
+    }
+
+    #[test]
+    fn narrower_confidence_gives_a_narrower_interval() {
+        let values: Vec<f64> = (0..200).map(|i| (i % 10) as f64 / 10.0).collect();
+        let (low_95, high_95) = bootstrap_ci(&values, 0.95, &mut StdRng::seed_from_u64(1));
+        let (low_50, high_50) = bootstrap_ci(&values, 0.50, &mut StdRng::seed_from_u64(1));
+
+        assert!(low_95 < low_50 && high_50 < high_95);
+    }
+}
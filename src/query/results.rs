@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use colored::Colorize;
+
+use crate::telemetry::metrics::{Metric, Recorder};
+use crate::telemetry::output::report;
+use crate::telemetry::Snapshot;
+
+/// How full a query's results were, the `results` label of `bench.query.latency_ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Fill {
+    /// No results
+    Empty,
+    /// Fewer than `top_k` results, e.g. under a selective filter
+    Partial,
+    /// `top_k` results
+    Full,
+}
+
+impl Fill {
+    pub fn of(results: usize, top_k: u32) -> Self {
+        match results {
+            0 => Fill::Empty,
+            n if n < top_k as usize => Fill::Partial,
+            _ => Fill::Full,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Fill::Empty => "empty",
+            Fill::Partial => "partial",
+            Fill::Full => "full",
+        }
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "empty" => Some(Fill::Empty),
+            "partial" => Some(Fill::Partial),
+            "full" => Some(Fill::Full),
+            _ => None,
+        }
+    }
+}
+
+/// Report latency percentiles separately for queries returning no results, fewer than
+/// `top_k` and `top_k`, recorded as the gauges `bench.query.{fill}_results_queries`,
+/// `bench.query.{fill}_results_p50_ms` and `bench.query.{fill}_results_p99_ms`.
+///
+/// Queries cut short by a selective filter often take a different path through the
+/// provider than full ones, which the run's percentiles blur together.
+pub fn report_latency_by_results(stats: &Snapshot, m: &Recorder, prefix: &str) {
+    let mut fills = BTreeMap::<Fill, Vec<Metric>>::new();
+    for metric in stats
+        .metrics
+        .iter()
+        .filter(|metric| metric.name == "bench.query.latency_ms")
+    {
+        if let Some(fill) = metric.label("results").and_then(Fill::from_label) {
+            fills.entry(fill).or_default().push(metric.clone());
+        }
+    }
+
+    let total: usize = fills.values().map(Vec::len).sum();
+    let mut segments = Vec::with_capacity(fills.len());
+    for (fill, latencies) in &fills {
+        let latencies = Snapshot::new(latencies.clone());
+        let p50 = latencies.quantile("bench.query.latency_ms", 0.50);
+        let p99 = latencies.quantile("bench.query.latency_ms", 0.99);
+
+        let name = fill.as_str();
+        let queries = latencies.count("bench.query.latency_ms");
+        m.gauge(
+            &format!("bench.query.{name}_results_queries"),
+            queries as f64,
+        );
+        m.gauge(&format!("bench.query.{name}_results_p50_ms"), p50);
+        m.gauge(&format!("bench.query.{name}_results_p99_ms"), p99);
+
+        segments.push(format!(
+            "{name} {} of queries, {}, {}",
+            format!("{:.1}%", queries as f64 / total as f64 * 100.0).bold(),
+            format!("p50={p50:.2}ms").yellow().bold(),
+            format!("p99={p99:.2}ms").magenta().bold(),
+        ));
+    }
+
+    // A single kind of result count is already what the run's percentiles show
+    if fills.len() < 2 {
+        return;
+    }
+    report(format!(
+        "{:>16}] {} {}",
+        prefix,
+        "Latency by results:".green().bold(),
+        segments.join("; "),
+    ));
+}
@@ -0,0 +1,130 @@
+use rand::Rng;
+use tokio::task::JoinSet;
+use tracing::error;
+
+use crate::ingest::{RecentWrite, RecentWrites};
+use crate::provider::{PyProvider, QueryOptions};
+use crate::telemetry::metrics::Recorder;
+
+/// Read-your-writes checks requested by the query workers, run one at a time by a task of
+/// their own so that they don't delay the workers' paced queries.
+#[derive(Clone)]
+pub struct ReadYourWrites {
+    /// Fraction of queries followed by a check
+    fraction: f64,
+    /// `top_k` of the queries requesting a check
+    tx: async_channel::Sender<u32>,
+}
+
+impl ReadYourWrites {
+    /// Spawn the task checking a random write of `recent` per request on `tasks`.
+    pub fn spawn(
+        tasks: &mut JoinSet<anyhow::Result<()>>,
+        provider: PyProvider,
+        collection: String,
+        options: QueryOptions,
+        fraction: f64,
+        recent: RecentWrites,
+        m: Recorder,
+    ) -> Self {
+        let (tx, rx) = async_channel::bounded(1);
+        tasks.spawn(async move {
+            while let Ok(top_k) = rx.recv().await {
+                let Some(write) = recent.sample() else {
+                    continue;
+                };
+                let checked = check_read_your_writes(
+                    &provider,
+                    &collection,
+                    options.clone(),
+                    top_k,
+                    write,
+                    &m,
+                )
+                .await;
+                if let Err(error) = checked {
+                    error!(?error, "Failed to check read-your-writes");
+                }
+            }
+
+            Ok(())
+        });
+
+        Self { fraction, tx }
+    }
+
+    /// Request a check after a query with `top_k`, with the configured probability. While
+    /// the previous check is still running the request is counted in
+    /// `bench.query.ryw_skipped` instead.
+    pub fn after_query(&self, top_k: u32, m: &Recorder) {
+        if !rand::rng().random_bool(self.fraction) {
+            return;
+        }
+        if self.tx.try_send(top_k).is_err() {
+            m.incr("bench.query.ryw_skipped", 1.0);
+        }
+    }
+}
+
+/// Check that a recently acknowledged write is visible with the written contents: by ID,
+/// and among the results of a query for its embedding filtered to its `tag`.
+///
+/// Each read is labeled `read`. Violations record the age of the write when it was read
+/// stale.
+pub async fn check_read_your_writes(
+    provider: &PyProvider,
+    collection: &str,
+    options: QueryOptions,
+    top_k: u32,
+    write: RecentWrite,
+    m: &Recorder,
+) -> anyhow::Result<()> {
+    let doc = provider
+        .query_by_id(
+            collection.to_string(),
+            write.id.clone(),
+            options.consistency.clone(),
+        )
+        .await?;
+    let stale = doc
+        .is_none_or(|doc| doc.text != write.text || (write.tag.is_some() && doc.tag != write.tag));
+    record(m, "id", stale, &write);
+
+    // Untagged writes, e.g. from a write_transform, can only be looked up by ID
+    let (Some(tag), Some(dense)) = (&write.tag, &write.dense) else {
+        return Ok(());
+    };
+    let (results, _) = provider
+        .query(
+            collection.to_string(),
+            dense.clone(),
+            top_k,
+            None,
+            None,
+            QueryOptions {
+                include_vectors: false,
+                include_payload: false,
+                namespace: write.namespace.clone(),
+                tag: Some(tag.clone()),
+                ..options
+            },
+        )
+        .await?;
+    let stale = !results.iter().any(|doc| doc.id == write.id);
+    record(m, "tag", stale, &write);
+
+    Ok(())
+}
+
+fn record(m: &Recorder, read: &str, stale: bool, write: &RecentWrite) {
+    let labels = [("read", read)];
+    m.incr_with("bench.query.ryw_checks", 1.0, &labels);
+    if stale {
+        m.incr_with("bench.query.ryw_violations", 1.0, &labels);
+        m.observe_with(
+            "bench.query.ryw_violation_age_ms",
+            write.acked.elapsed().as_millis() as f64,
+            &labels,
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use crate::data::{Distance, Document};
+
+/// Deviation between the provider-reported score and the exact score of each result.
+///
+/// Returns `None` for results missing either a score or an embedding.
+pub fn score_deviations(
+    results: &[Document],
+    vector: &[f32],
+    metric: Distance,
+) -> Vec<Option<f64>> {
+    results
+        .iter()
+        .map(|doc| {
+            let score = doc.score?;
+            let embedding = doc.dense_embedding.as_ref()?;
+            let exact = metric.score(vector, embedding);
+
+            Some((exact - score).abs() as f64)
+        })
+        .collect()
+}
+
+/// Whether results are ordered from closest to farthest by their reported score.
+///
+/// Returns `None` if any result is missing a score.
+pub fn is_ordered(results: &[Document], metric: Distance) -> Option<bool> {
+    let scores = results
+        .iter()
+        .map(|doc| doc.score)
+        .collect::<Option<Vec<f32>>>()?;
+
+    Some(scores.windows(2).all(|w| {
+        if metric.higher_is_closer() {
+            w[0] >= w[1]
+        } else {
+            w[0] <= w[1]
+        }
+    }))
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::{
+    ArrayRef, Float64Array, RecordBatch, StringArray, TimestampMicrosecondArray, UInt32Array,
+};
+use arrow_schema::TimeUnit;
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
+use tracing::info;
+
+use crate::data::{stable_hash, Document};
+use crate::telemetry::write_batch;
+
+/// A query that exceeded the slow query threshold.
+#[derive(Debug, Clone)]
+pub struct SlowQuery {
+    pub timestamp: DateTime<Utc>,
+    pub latency_ms: f64,
+    /// Hash of the query vector, to find the query in the queries file.
+    pub vector_hash: String,
+    pub top_k: u32,
+    pub int_filter: Option<u32>,
+    pub keyword_filter: Option<String>,
+    /// IDs returned by the provider.
+    pub result_ids: Vec<String>,
+}
+
+/// Collects queries slower than a threshold.
+#[derive(Debug, Clone)]
+pub struct SlowQueryLog {
+    threshold_ms: f64,
+    tx: UnboundedSender<SlowQuery>,
+}
+
+impl SlowQueryLog {
+    /// Spawn a collector task. The task returns all captured queries once every log is dropped.
+    pub fn spawn(threshold_ms: u64) -> (Self, JoinHandle<Vec<SlowQuery>>) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let handle = tokio::spawn(async move {
+            let mut queries = Vec::new();
+            while let Some(query) = rx.recv().await {
+                queries.push(query);
+            }
+            queries
+        });
+
+        let log = Self {
+            threshold_ms: threshold_ms as f64,
+            tx,
+        };
+
+        (log, handle)
+    }
+
+    pub fn observe(
+        &self,
+        latency_ms: f64,
+        vector: &[f32],
+        top_k: u32,
+        int_filter: Option<u32>,
+        keyword_filter: Option<String>,
+        results: &[Document],
+    ) {
+        if latency_ms < self.threshold_ms {
+            return;
+        }
+
+        // The collector only stops once all logs are dropped
+        let _ = self.tx.send(SlowQuery {
+            timestamp: Utc::now(),
+            latency_ms,
+            vector_hash: vector_hash(vector),
+            top_k,
+            int_filter,
+            keyword_filter,
+            result_ids: results.iter().map(|doc| doc.id.clone()).collect(),
+        });
+    }
+}
+
+/// Hash of the little-endian bytes of `vector`, stable across builds and Rust versions so
+/// slow query logs can be matched against the queries file later.
+pub fn vector_hash(vector: &[f32]) -> String {
+    let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+    format!("{:016x}", stable_hash(&bytes))
+}
+
+pub async fn write_slow_queries(
+    queries: Vec<SlowQuery>,
+    run_id: &str,
+    path: &str,
+) -> anyhow::Result<()> {
+    let count = queries.len();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            false,
+        ),
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("latency_ms", DataType::Float64, false),
+        Field::new("vector_hash", DataType::Utf8, false),
+        Field::new("top_k", DataType::UInt32, false),
+        Field::new("int_filter", DataType::UInt32, true),
+        Field::new("keyword_filter", DataType::Utf8, true),
+        Field::new(
+            "result_ids",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            false,
+        ),
+    ]));
+
+    let mut result_ids = ListBuilder::new(StringBuilder::new());
+    for query in &queries {
+        for id in &query.result_ids {
+            result_ids.values().append_value(id);
+        }
+        result_ids.append(true);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(TimestampMicrosecondArray::from_iter_values(
+            queries.iter().map(|q| q.timestamp.timestamp_micros()),
+        )),
+        Arc::new(StringArray::from(vec![run_id; count])),
+        Arc::new(Float64Array::from_iter_values(
+            queries.iter().map(|q| q.latency_ms),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            queries.iter().map(|q| q.vector_hash.as_str()),
+        )),
+        Arc::new(UInt32Array::from_iter_values(
+            queries.iter().map(|q| q.top_k),
+        )),
+        Arc::new(UInt32Array::from_iter(queries.iter().map(|q| q.int_filter))),
+        Arc::new(StringArray::from_iter(
+            queries.iter().map(|q| q.keyword_filter.as_deref()),
+        )),
+        Arc::new(result_ids.finish()),
+    ];
+
+    write_batch(RecordBatch::try_new(schema, arrays)?, path).await?;
+    info!("{count} slow queries written to {path}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vector_hash_is_stable() {
+        // Pinned, so logs written by earlier builds still match
+        assert_eq!(vector_hash(&[1.0, -0.5, 0.25]), "ccb7dcfe86f07c3e");
+        assert_ne!(vector_hash(&[1.0, 2.0]), vector_hash(&[2.0, 1.0]));
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use colored::Colorize;
+
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::output::report;
+use crate::telemetry::Snapshot;
+
+/// Tracks the gaps between successful queries, across all workers.
+#[derive(Debug, Clone)]
+pub struct Stalls {
+    start: Instant,
+    /// Microseconds since `start` of the last success, `u64::MAX` before the first
+    last_success_us: Arc<AtomicU64>,
+    /// Longest gap ended within the current reporting window, in microseconds
+    window_max_us: Arc<AtomicU64>,
+}
+
+impl Default for Stalls {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            last_success_us: Arc::new(AtomicU64::new(u64::MAX)),
+            window_max_us: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Stalls {
+    /// Count a successful query, ending the gap since the previous one.
+    pub fn success(&self) {
+        let now = self.start.elapsed().as_micros() as u64;
+        let last = self.last_success_us.swap(now, Ordering::Relaxed);
+        if last != u64::MAX {
+            self.window_max_us
+                .fetch_max(now.saturating_sub(last), Ordering::Relaxed);
+        }
+    }
+
+    /// Longest gap between successes within the window ending now, including a stall still
+    /// ongoing, in milliseconds. Starts the next window.
+    fn take_window_max_ms(&self) -> f64 {
+        let now = self.start.elapsed().as_micros() as u64;
+        let ended = self.window_max_us.swap(0, Ordering::Relaxed);
+        let ongoing = match self.last_success_us.load(Ordering::Relaxed) {
+            u64::MAX => 0,
+            last => now.saturating_sub(last),
+        };
+        ended.max(ongoing) as f64 / 1000.0
+    }
+}
+
+/// Derived metrics of one reporting window, telling apart providers with the same
+/// percentiles but different behavior over time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Smoothness {
+    /// Change in throughput from the previous window, in queries/s
+    pub throughput_jitter: f64,
+    /// Standard deviation of the window's latencies over their mean
+    pub latency_cv: f64,
+    /// Longest gap between successful queries, in milliseconds
+    pub max_stall_ms: f64,
+}
+
+/// Computes [`Smoothness`] for consecutive reporting windows.
+#[derive(Debug)]
+pub struct SmoothnessWindows {
+    stalls: Stalls,
+    previous_throughput: Option<f64>,
+}
+
+impl SmoothnessWindows {
+    pub fn new(stalls: Stalls) -> Self {
+        Self {
+            stalls,
+            previous_throughput: None,
+        }
+    }
+
+    /// Smoothness of the window covered by `window`, lasting `secs`, recorded as
+    /// `bench.query.throughput_jitter`, `bench.query.latency_cv` and
+    /// `bench.query.max_stall_ms`.
+    pub fn next(&mut self, window: &Snapshot, secs: f64, m: &Recorder) -> Smoothness {
+        let throughput = window.total("bench.query.oks") / secs;
+        let previous = self.previous_throughput.replace(throughput);
+        let throughput_jitter = previous.map_or(0.0, |previous| (throughput - previous).abs());
+
+        let latency_avg = window.avg("bench.query.latency_ms");
+        let latency_cv = if latency_avg > 0.0 {
+            window.stddev("bench.query.latency_ms") / latency_avg
+        } else {
+            0.0
+        };
+
+        let smoothness = Smoothness {
+            throughput_jitter,
+            latency_cv,
+            max_stall_ms: self.stalls.take_window_max_ms(),
+        };
+        // The first window has no previous one to differ from
+        if previous.is_some() {
+            m.observe(
+                "bench.query.throughput_jitter",
+                smoothness.throughput_jitter,
+            );
+        }
+        if window.count("bench.query.latency_ms") > 1 {
+            m.observe("bench.query.latency_cv", smoothness.latency_cv);
+        }
+        m.observe("bench.query.max_stall_ms", smoothness.max_stall_ms);
+
+        smoothness
+    }
+}
+
+/// Summarize the per-window smoothness of the run, recording the longest stall of the run
+/// as `bench.query.longest_stall_ms`.
+pub fn report_smoothness(stats: &Snapshot, m: &Recorder, prefix: &str) {
+    if stats.count("bench.query.max_stall_ms") == 0 {
+        return;
+    }
+
+    let longest_stall = stats.quantile("bench.query.max_stall_ms", 1.0);
+    m.gauge("bench.query.longest_stall_ms", longest_stall);
+
+    report(format!(
+        "{:>16}] {} Throughput jitter: {}, Latency CV: {}, Longest stall: {}",
+        prefix,
+        "Smoothness:".green().bold(),
+        format!(
+            "avg={:.2} queries/s",
+            stats.avg("bench.query.throughput_jitter")
+        )
+        .blue()
+        .bold(),
+        format!(
+            "avg={:.2} max={:.2}",
+            stats.avg("bench.query.latency_cv"),
+            stats.quantile("bench.query.latency_cv", 1.0)
+        )
+        .yellow()
+        .bold(),
+        format!("{longest_stall:.2}ms").magenta().bold(),
+    ));
+}
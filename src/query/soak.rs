@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use colored::Colorize;
+
+use crate::provider::PyProvider;
+use crate::query::{measure_recall, QueryConfig};
+use crate::telemetry::metrics::{Recorder, Registry};
+use crate::telemetry::output::report;
+use crate::telemetry::{export_run, Snapshot};
+
+/// Periodic checkpoints for long-running soak tests.
+///
+/// Every `interval` this re-measures recall (in filter mode), prints a summary of the
+/// interval, and rotates the run's metrics out to `checkpoint_metrics` if set.
+pub async fn run_checkpoints(
+    provider: PyProvider,
+    config: QueryConfig,
+    m: Recorder,
+    registry: Registry,
+    run_id: String,
+    prefix: String,
+    interval: Duration,
+) -> anyhow::Result<()> {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    let mut start = Utc::now();
+    let mut checkpoint = 0u64;
+    loop {
+        ticker.tick().await;
+        checkpoint += 1;
+
+        if config.mode == "filter" {
+            let mut config = config.clone();
+            config.concurrency = 8; // For recall, it doesn't matter how many workers are used
+            measure_recall(
+                provider.clone(),
+                config,
+                m.with_label("checkpoint", checkpoint.to_string()),
+                registry.clone(),
+                run_id.clone(),
+            )
+            .await?;
+        }
+
+        let end = Utc::now();
+        let stats = registry.snapshot(&run_id).await.since(start);
+        print_summary(&prefix, checkpoint, &stats, (end - start).as_seconds_f64());
+
+        if let Some(path) = &config.checkpoint_metrics {
+            let path = path
+                .replace("{run_id}", &run_id)
+                .replace("{checkpoint}", &checkpoint.to_string());
+            export_run(&registry, &run_id, &path).await?;
+        }
+
+        start = end;
+    }
+}
+
+fn print_summary(prefix: &str, checkpoint: u64, stats: &Snapshot, secs: f64) {
+    let oks = stats.total("bench.query.oks");
+    let errors = stats.total_prefix("bench.query.errors.");
+
+    report(format!(
+        "{:>16}] {} over {:.0}s, Throughput: {}, Latency: {}, {}, Errors: {}, Recall: {}",
+        prefix,
+        format!("Checkpoint {checkpoint}").green().bold(),
+        secs,
+        format!("{:.2} queries/s", oks / secs).blue().bold(),
+        format!("avg={:.2}ms", stats.avg("bench.query.latency_ms"))
+            .yellow()
+            .bold(),
+        format!(
+            "p99={:.2}ms",
+            stats.quantile("bench.query.latency_ms", 0.99)
+        )
+        .magenta()
+        .bold(),
+        format!("{errors}").red().bold(),
+        format!("avg={:.2}", stats.avg("bench.query.recall"))
+            .yellow()
+            .bold(),
+    ));
+}
@@ -0,0 +1,416 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+use crate::s3::ensure_file;
+
+/// Target query rate over the course of a run.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Constant rate. `None` runs closed-loop, as fast as the workers can go.
+    Constant(Option<f64>),
+    /// Alternate between `on` at `qps` and `off` with no traffic.
+    Burst {
+        on: Duration,
+        off: Duration,
+        qps: Option<f64>,
+    },
+    /// Follow a load profile, interpolating linearly between points.
+    Profile(Profile),
+}
+
+/// Load profile as `(offset, qps)` points, sorted by offset.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    points: Vec<(Duration, f64)>,
+}
+
+impl Profile {
+    /// Load a profile from a local or `s3://` CSV file of `offset_secs,qps` lines.
+    ///
+    /// Offsets are divided by `speedup`, so a 24-hour profile replays in one hour with
+    /// `speedup=24`. Blank lines, `#` comments and a header line are ignored.
+    pub async fn load(path: &str, cache_dir: &str, speedup: f64) -> anyhow::Result<Self> {
+        let file = ensure_file(path, cache_dir).await?;
+        let contents = tokio::fs::read_to_string(&file)
+            .await
+            .with_context(|| format!("Failed to read qps profile {path}"))?;
+
+        Self::parse(&contents, speedup).with_context(|| format!("Invalid qps profile {path}"))
+    }
+
+    fn parse(contents: &str, speedup: f64) -> anyhow::Result<Self> {
+        let mut points: Vec<(Duration, f64)> = Vec::new();
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((offset, qps)) = line.split_once(',') else {
+                anyhow::bail!("Line {}: expected `offset_secs,qps`", i + 1);
+            };
+            let (offset, qps) = match (offset.trim().parse::<f64>(), qps.trim().parse::<f64>()) {
+                (Ok(offset), Ok(qps)) => (offset, qps),
+                // Header line
+                _ if points.is_empty() && i == 0 => continue,
+                _ => anyhow::bail!("Line {}: expected `offset_secs,qps`", i + 1),
+            };
+
+            if offset < 0.0 || qps < 0.0 {
+                anyhow::bail!("Line {}: offset and qps must not be negative", i + 1);
+            }
+            let offset = Duration::from_secs_f64(offset / speedup);
+            if points.last().is_some_and(|(prev, _)| *prev > offset) {
+                anyhow::bail!("Line {}: offsets must be increasing", i + 1);
+            }
+
+            points.push((offset, qps));
+        }
+
+        if points.is_empty() {
+            anyhow::bail!("No points in profile");
+        }
+
+        Ok(Self { points })
+    }
+
+    /// Target qps at `elapsed`, holding the first and last points outside the profile.
+    fn qps_at(&self, elapsed: Duration) -> f64 {
+        let i = self
+            .points
+            .partition_point(|(offset, _)| *offset <= elapsed);
+
+        match (
+            i.checked_sub(1).map(|i| self.points[i]),
+            self.points.get(i).copied(),
+        ) {
+            (Some((t0, q0)), Some((t1, q1))) => {
+                let span = (t1 - t0).as_secs_f64();
+                let frac = (elapsed - t0).as_secs_f64() / span;
+                q0 + (q1 - q0) * frac
+            }
+            (Some((_, q)), None) | (None, Some((_, q))) => q,
+            (None, None) => unreachable!("profile has at least one point"),
+        }
+    }
+}
+
+/// Target at a point in time.
+enum Target {
+    Unlimited,
+    Rate(f64),
+    /// No traffic for the given duration.
+    Idle(Duration),
+}
+
+impl Schedule {
+    fn target(&self, elapsed: Duration) -> Target {
+        let rate = |qps: Option<f64>| qps.map(Target::Rate).unwrap_or(Target::Unlimited);
+
+        match self {
+            Schedule::Constant(qps) => rate(*qps),
+            Schedule::Burst { on, off, qps } => {
+                let period = (*on + *off).as_secs_f64();
+                let phase = Duration::from_secs_f64(elapsed.as_secs_f64() % period);
+                if phase < *on {
+                    rate(*qps)
+                } else {
+                    Target::Idle(*on + *off - phase)
+                }
+            }
+            Schedule::Profile(profile) => match profile.qps_at(elapsed) {
+                // Re-check periodically until the profile picks up again
+                qps if qps <= 0.0 => Target::Idle(MAX_WAIT),
+                qps => Target::Rate(qps),
+            },
+        }
+    }
+}
+
+/// Longest a worker sleeps before re-checking the schedule.
+const MAX_WAIT: Duration = Duration::from_millis(100);
+
+/// Rate ceiling gained per second without rate limiting, in queries/s.
+const ADDITIVE_INCREASE: f64 = 10.0;
+
+/// Shortest time between two decreases, so the errors of calls already in flight when the
+/// provider starts rate limiting only halve the rate once.
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// Lowest rate ceiling, in queries/s.
+const MIN_RATE: f64 = 1.0;
+
+/// Paces query workers according to a [`Schedule`]. Shared by all workers of a run.
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    schedule: Schedule,
+    start: Instant,
+    bucket: Arc<Mutex<Bucket>>,
+    /// Adaptive rate ceiling, with `adaptive`.
+    aimd: Option<Arc<Mutex<Aimd>>>,
+}
+
+/// Rate ceiling below the schedule, adapted to rate limiting by additive increase and
+/// multiplicative decrease.
+#[derive(Debug)]
+struct Aimd {
+    /// `None` until the provider first rate limits.
+    limit: Option<f64>,
+    /// Last time `limit` was changed.
+    adjusted: Instant,
+    decreased: Option<Instant>,
+    /// Queries sent in the current one-second window, and the rate of the previous one.
+    window: Instant,
+    sent: u64,
+    rate: Option<f64>,
+}
+
+impl Aimd {
+    /// Rate queries were sent at recently.
+    fn send_rate(&self, now: Instant) -> f64 {
+        self.rate
+            .unwrap_or_else(|| self.sent as f64 / (now - self.window).as_secs_f64().max(1e-3))
+    }
+}
+
+/// Token bucket refilled at the current target rate.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Pacer {
+    pub fn new(schedule: Schedule) -> Self {
+        let start = Instant::now();
+
+        Self {
+            schedule,
+            start,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: 0.0,
+                updated: start,
+            })),
+            aimd: None,
+        }
+    }
+
+    /// Throttle below the schedule when the provider rate limits, see [`Pacer::rate_limited`].
+    pub fn adaptive(mut self, adaptive: bool) -> Self {
+        let now = Instant::now();
+        self.aimd = adaptive.then(|| {
+            Arc::new(Mutex::new(Aimd {
+                limit: None,
+                adjusted: now,
+                decreased: None,
+                window: now,
+                sent: 0,
+                rate: None,
+            }))
+        });
+        self
+    }
+
+    /// Halve the send rate after a rate-limit error, starting from the rate queries were
+    /// sent at. Returns the new ceiling, or `None` if not adaptive or within the cooldown of
+    /// the previous decrease.
+    pub fn rate_limited(&self) -> Option<f64> {
+        let mut aimd = self.aimd.as_ref()?.lock().expect("pacer lock poisoned");
+        let now = Instant::now();
+        if aimd
+            .decreased
+            .is_some_and(|decreased| now - decreased < DECREASE_COOLDOWN)
+        {
+            return None;
+        }
+
+        let rate = aimd.send_rate(now);
+        let limit = (aimd.limit.map_or(rate, |limit| limit.min(rate)) / 2.0).max(MIN_RATE);
+        aimd.limit = Some(limit);
+        aimd.adjusted = now;
+        aimd.decreased = Some(now);
+        Some(limit)
+    }
+
+    /// Raise the rate ceiling after a successful call, by [`ADDITIVE_INCREASE`] per second.
+    pub fn succeeded(&self) {
+        let Some(aimd) = &self.aimd else {
+            return;
+        };
+        let mut aimd = aimd.lock().expect("pacer lock poisoned");
+        let now = Instant::now();
+        if let Some(limit) = aimd.limit {
+            let increase = (now - aimd.adjusted).as_secs_f64() * ADDITIVE_INCREASE;
+            // Never above the scheduled rate
+            let ceiling = match self.schedule.target(self.start.elapsed()) {
+                Target::Rate(qps) => qps.max(MIN_RATE),
+                _ => f64::INFINITY,
+            };
+            aimd.limit = Some((limit + increase).min(ceiling));
+            aimd.adjusted = now;
+        }
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        self.aimd.is_some()
+    }
+
+    /// Current adaptive rate ceiling, once the provider has rate limited.
+    pub fn limit(&self) -> Option<f64> {
+        self.aimd
+            .as_ref()?
+            .lock()
+            .expect("pacer lock poisoned")
+            .limit
+    }
+
+    /// Wait until the schedule allows sending the next query.
+    pub async fn acquire(&self) {
+        self.wait().await;
+
+        if let Some(aimd) = &self.aimd {
+            let mut aimd = aimd.lock().expect("pacer lock poisoned");
+            let now = Instant::now();
+            aimd.sent += 1;
+            let elapsed = (now - aimd.window).as_secs_f64();
+            if elapsed >= 1.0 {
+                aimd.rate = Some(aimd.sent as f64 / elapsed);
+                aimd.window = now;
+                aimd.sent = 0;
+            }
+        }
+    }
+
+    async fn wait(&self) {
+        loop {
+            let limit = self.limit();
+            let target = match (self.schedule.target(self.start.elapsed()), limit) {
+                (Target::Unlimited, Some(limit)) => Target::Rate(limit),
+                (Target::Rate(qps), Some(limit)) => Target::Rate(qps.min(limit)),
+                (target, _) => target,
+            };
+
+            let wait = match target {
+                Target::Unlimited => return,
+                Target::Idle(remaining) => remaining,
+                Target::Rate(qps) => {
+                    let mut bucket = self.bucket.lock().expect("pacer lock poisoned");
+                    let now = Instant::now();
+                    let refill = (now - bucket.updated).as_secs_f64() * qps;
+                    // Cap the burst so slots missed while idle or saturated aren't caught up on
+                    bucket.tokens = (bucket.tokens + refill).min((qps * 0.01).max(1.0));
+                    bucket.updated = now;
+
+                    if bucket.tokens >= 1.0 {
+                        bucket.tokens -= 1.0;
+                        return;
+                    }
+
+                    Duration::from_secs_f64((1.0 - bucket.tokens) / qps).min(MAX_WAIT)
+                }
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Caps the provider calls in flight across all workers of a run, independently of the
+/// number of workers. Unlimited with `None`.
+#[derive(Debug, Clone)]
+pub struct InFlight {
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl InFlight {
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            permits: max.map(|max| Arc::new(Semaphore::new(max))),
+        }
+    }
+
+    /// Wait for a free slot, held until the permit is dropped, and how long that took.
+    pub async fn acquire(&self) -> (Option<OwnedSemaphorePermit>, Option<Duration>) {
+        let Some(permits) = &self.permits else {
+            return (None, None);
+        };
+
+        let start = Instant::now();
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("in-flight semaphore is never closed");
+        (Some(permit), Some(start.elapsed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send `count` queries evenly over one second, closing the send-rate window.
+    async fn send(pacer: &Pacer, count: u32) {
+        for _ in 0..count {
+            tokio::time::advance(Duration::from_secs(1) / count).await;
+            pacer.acquire().await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limits_halve_the_send_rate() {
+        let pacer = Pacer::new(Schedule::Constant(None)).adaptive(true);
+        assert_eq!(pacer.limit(), None);
+
+        send(&pacer, 100).await;
+        assert_eq!(pacer.rate_limited(), Some(50.0));
+        // Errors of calls already in flight don't halve it again
+        assert_eq!(pacer.rate_limited(), None);
+
+        tokio::time::advance(DECREASE_COOLDOWN).await;
+        assert_eq!(pacer.rate_limited(), Some(25.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limits_never_drop_below_the_minimum_rate() {
+        let pacer = Pacer::new(Schedule::Constant(None)).adaptive(true);
+
+        send(&pacer, 2).await;
+        for _ in 0..5 {
+            tokio::time::advance(DECREASE_COOLDOWN).await;
+            pacer.rate_limited();
+        }
+        assert_eq!(pacer.limit(), Some(MIN_RATE));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn successes_raise_the_limit_up_to_the_schedule() {
+        let pacer = Pacer::new(Schedule::Constant(Some(100.0))).adaptive(true);
+
+        send(&pacer, 100).await;
+        assert_eq!(pacer.rate_limited(), Some(50.0));
+
+        tokio::time::advance(Duration::from_secs(2)).await;
+        pacer.succeeded();
+        assert_eq!(pacer.limit(), Some(50.0 + 2.0 * ADDITIVE_INCREASE));
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        pacer.succeeded();
+        assert_eq!(pacer.limit(), Some(100.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pacing_is_not_adaptive_by_default() {
+        let pacer = Pacer::new(Schedule::Constant(Some(100.0)));
+
+        send(&pacer, 100).await;
+        assert_eq!(pacer.rate_limited(), None);
+        pacer.succeeded();
+        assert_eq!(pacer.limit(), None);
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use async_channel::Sender;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use rand::prelude::*;
+use tokio_util::sync::CancellationToken;
+
+use crate::data::{parse_from_batch, Document, DocumentTransform, PyDocumentTransform};
+use crate::query::WriteOrder;
+
+/// Documents per write.
+const WRITE_BATCH: usize = 100;
+
+/// Documents shuffled together with `WriteOrder::Shuffled`, on top of the row group order.
+const SHUFFLE_WINDOW: usize = 10_000;
+
+/// Produces the documents read_write mode writes, looping over the docs file until
+/// cancelled.
+pub struct WriteSource {
+    pub path: PathBuf,
+    pub order: WriteOrder,
+    /// Only write documents with dataset IDs in `start..end`.
+    pub id_range: Option<(u64, u64)>,
+    /// Next net-new ID, when writing IDs beyond the dataset instead of rewriting its own.
+    pub next_id: Option<u64>,
+    /// Documents written per second, as fast as the writer goes by default.
+    pub rate: Option<f64>,
+    pub transform: DocumentTransform,
+    pub write_transform: Option<PyDocumentTransform>,
+}
+
+impl WriteSource {
+    pub fn run(
+        mut self,
+        tx: Sender<Vec<Document>>,
+        cancel: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let window = match self.order {
+            WriteOrder::Sequential => WRITE_BATCH,
+            WriteOrder::Shuffled => SHUFFLE_WINDOW,
+        };
+        let mut pace = self.rate.map(Pace::new);
+
+        while !cancel.is_cancelled() {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&self.path)?)?;
+            let mut row_groups = (0..builder.metadata().num_row_groups()).collect::<Vec<_>>();
+            if self.order == WriteOrder::Shuffled {
+                row_groups.shuffle(&mut rand::rng());
+            }
+            let reader = builder
+                .with_row_groups(row_groups)
+                .with_batch_size(WRITE_BATCH)
+                .build()?;
+
+            let mut pending = Vec::new();
+            let mut any = false;
+            for batch in reader {
+                if cancel.is_cancelled() {
+                    return Ok(());
+                }
+
+                let mut documents = parse_from_batch(batch?);
+                if let Some((start, end)) = self.id_range {
+                    documents.retain(|doc| {
+                        doc.id
+                            .parse::<u64>()
+                            .is_ok_and(|id| (start..end).contains(&id))
+                    });
+                }
+                any |= !documents.is_empty();
+                pending.extend(documents);
+
+                if pending.len() >= window {
+                    self.flush(&mut pending, &mut pace, &tx)?;
+                }
+            }
+            self.flush(&mut pending, &mut pace, &tx)?;
+
+            if !any {
+                anyhow::bail!("No documents to write in {}", self.path.display());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flush(
+        &mut self,
+        pending: &mut Vec<Document>,
+        pace: &mut Option<Pace>,
+        tx: &Sender<Vec<Document>>,
+    ) -> anyhow::Result<()> {
+        if self.order == WriteOrder::Shuffled {
+            pending.shuffle(&mut rand::rng());
+        }
+
+        let batch_size = pace.as_ref().map_or(WRITE_BATCH, Pace::batch_size);
+        while !pending.is_empty() {
+            let batch: Vec<_> = pending.drain(..batch_size.min(pending.len())).collect();
+            if let Some(pace) = pace {
+                pace.wait(batch.len());
+            }
+            tx.send_blocking(self.prepare(batch)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn prepare(&mut self, mut documents: Vec<Document>) -> anyhow::Result<Vec<Document>> {
+        if let Some(next_id) = self.next_id.as_mut() {
+            for doc in documents.iter_mut() {
+                doc.id = next_id.to_string();
+                *next_id += 1;
+            }
+        }
+
+        match &self.write_transform {
+            Some(write_transform) => {
+                documents = write_transform.apply_batch(documents)?;
+            }
+            None => {
+                for doc in documents.iter_mut() {
+                    doc.tag = Some(format!("tag-{}", rand::rng().random_range(0..1000)));
+                }
+            }
+        }
+        for doc in documents.iter_mut() {
+            self.transform.apply(doc)?;
+        }
+
+        Ok(documents)
+    }
+}
+
+/// Spaces out writes to a target rate of documents per second.
+struct Pace {
+    rate: f64,
+    start: Instant,
+    sent: u64,
+}
+
+impl Pace {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            start: Instant::now(),
+            sent: 0,
+        }
+    }
+
+    /// Roughly ten batches per second, so slow rates don't write in bursts.
+    fn batch_size(&self) -> usize {
+        ((self.rate / 10.0).ceil() as usize).clamp(1, WRITE_BATCH)
+    }
+
+    /// Block until `n` more documents can be sent.
+    fn wait(&mut self, n: usize) {
+        let due = self.start + Duration::from_secs_f64(self.sent as f64 / self.rate);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+        self.sent += n as u64;
+    }
+}
@@ -1,32 +1,53 @@
 use std::env;
+use std::future::Future;
+use std::time::{Duration, SystemTime};
 use std::{
     fs::File,
     path::{Path, PathBuf},
     time::Instant,
 };
 
+use anyhow::Context;
 use aws_config::Region;
 use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::{config::Credentials, Client, Config};
+use aws_sdk_s3::{config::Credentials, error::DisplayErrorContext, Client, Config};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper_util::{client::legacy::Client as HttpClient, rt::TokioExecutor};
+use rand::Rng;
 use tokio::fs::File as TokioFile;
-use tracing::{debug, info};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
 
+use crate::cache;
+
+/// Region of the public benchmark datasets, used when `AWS_REGION` is not set.
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// S3 client. Without `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY` requests are sent unsigned,
+/// which is enough to read public buckets such as the benchmark datasets.
 pub(crate) fn new_client() -> anyhow::Result<Client> {
-    let creds = Credentials::new(
-        env::var("AWS_ACCESS_KEY_ID")?,
-        env::var("AWS_SECRET_ACCESS_KEY")?,
-        None,
-        None,
-        "topk-bench",
-    );
+    let region = env::var("AWS_REGION").unwrap_or_else(|_| DEFAULT_REGION.to_string());
 
     let mut builder = Config::builder()
-        .region(Region::new(env::var("AWS_REGION")?))
-        .credentials_provider(creds)
-        .endpoint_url(format!(
-            "https://s3.{}.amazonaws.com",
-            env::var("AWS_REGION")?
-        ));
+        .region(Region::new(region.clone()))
+        .endpoint_url(format!("https://s3.{region}.amazonaws.com"));
+
+    match (
+        env::var("AWS_ACCESS_KEY_ID"),
+        env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        (Ok(access_key_id), Ok(secret_access_key)) => {
+            builder = builder.credentials_provider(Credentials::new(
+                access_key_id,
+                secret_access_key,
+                None,
+                None,
+                "topk-bench",
+            ));
+        }
+        _ => debug!("No AWS credentials set, sending unsigned S3 requests"),
+    }
 
     // Disable the following warning: This checksum is a part-level checksum which can't be validated by the Rust SDK. Disable checksum validation for this request to fix this warning. more_info="See https://docs.aws.amazon.com/AmazonS3/latest/userguide/checking-object-integrity.html#large-object-checksums for more information."
     builder.set_request_checksum_calculation(None);
@@ -42,6 +63,8 @@ pub async fn ensure_file(
 
     let path = if path.starts_with("s3://") {
         pull_file(path, out_dir).await?
+    } else if path.starts_with("https://") || path.starts_with("http://") {
+        download_file(path, out_dir).await?
     } else {
         PathBuf::from(path)
     };
@@ -49,23 +72,104 @@ pub async fn ensure_file(
     Ok(path)
 }
 
+/// Local path `ensure_file` stores `path` at, whether or not it was downloaded yet.
+pub fn cached_path(path: &str, cache_dir: &str) -> Option<PathBuf> {
+    if let Some(url) = path.strip_prefix("s3://") {
+        let (_, key) = url.split_once("/")?;
+        Some(PathBuf::from(format!("{cache_dir}/{key}")))
+    } else if path.starts_with("https://") || path.starts_with("http://") {
+        let uri: hyper::Uri = path.parse().ok()?;
+        Some(PathBuf::from(format!(
+            "{cache_dir}/{}{}",
+            uri.host()?,
+            uri.path()
+        )))
+    } else {
+        Some(PathBuf::from(path))
+    }
+}
+
 pub async fn upload_file(bucket: &str, key: &str, file: PathBuf) -> anyhow::Result<()> {
     let s3 = new_client()?;
 
-    let body = ByteStream::from_path(file).await?;
+    let response = with_retries("upload", bucket, key, || async {
+        let body = ByteStream::from_path(&file).await?;
 
-    let response = s3
-        .put_object()
-        .bucket(bucket)
-        .key(key)
-        .body(body)
-        .send()
-        .await?;
+        s3.put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(body)
+            .send()
+            .await
+            .map_err(sdk_error)
+    })
+    .await?;
     debug!(?response, "File written to S3");
 
     Ok(())
 }
 
+/// Environment variable setting how many times failed S3 requests are retried.
+const RETRIES_VAR: &str = "TOPK_BENCH_S3_RETRIES";
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Delay before the first retry, doubled on every further attempt.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Run an S3 operation, retrying failures with jittered exponential backoff.
+async fn with_retries<T, Fut>(
+    op: &str,
+    bucket: &str,
+    key: &str,
+    mut f: impl FnMut() -> Fut,
+) -> anyhow::Result<T>
+where
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let retries = match env::var(RETRIES_VAR) {
+        Ok(value) => value
+            .parse()
+            .with_context(|| format!("Invalid {RETRIES_VAR}: {value}"))?,
+        Err(_) => DEFAULT_RETRIES,
+    };
+
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(error) if attempt <= retries => {
+                let backoff = BASE_BACKOFF
+                    .saturating_mul(1 << (attempt - 1).min(16))
+                    .min(MAX_BACKOFF)
+                    .mul_f64(rand::rng().random_range(0.5..1.0));
+                warn!(
+                    op,
+                    bucket,
+                    key,
+                    attempt,
+                    error = format!("{error:#}"),
+                    ?backoff,
+                    "S3 request failed, retrying"
+                );
+
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+            Err(error) => {
+                return Err(error.context(format!(
+                    "S3 {op} of s3://{bucket}/{key} failed after {attempt} attempts"
+                )))
+            }
+        }
+    }
+}
+
+/// Flatten an SDK error with its full source chain, which its `Display` omits.
+fn sdk_error(error: impl std::error::Error + Send + Sync + 'static) -> anyhow::Error {
+    anyhow::anyhow!("{}", DisplayErrorContext(error))
+}
+
 pub async fn open_file(
     path: impl Into<String>,
     cache_dir: impl Into<String>,
@@ -93,27 +197,281 @@ async fn pull_file(url: String, out_dir: impl Into<String>) -> anyhow::Result<Pa
         std::fs::create_dir_all(dir)?;
     }
 
-    let out = format!("{cache_dir}/{key}");
-    if Path::new(&out).exists() {
-        debug!(?out, "Dataset already downloaded");
-        return Ok(PathBuf::from(out));
+    let out = PathBuf::from(format!("{cache_dir}/{key}"));
+    let s3 = new_client()?;
+
+    if out.exists() {
+        // Check the cached file against the remote ETag it was downloaded with
+        let stale = match cache::etag(&out) {
+            Some(etag) => match s3.head_object().bucket(bucket).key(key).send().await {
+                Ok(head) => head.e_tag() != Some(etag.as_str()),
+                Err(error) => {
+                    warn!(?out, error = %DisplayErrorContext(error), "Failed to check cached dataset");
+                    false
+                }
+            },
+            // Downloaded before ETags were recorded, so check its size and adopt it
+            None if cache::is_legacy(&out) => {
+                match s3.head_object().bucket(bucket).key(key).send().await {
+                    Ok(head) => {
+                        let len = std::fs::metadata(&out)?.len();
+                        let matches = head.content_length() == Some(len as i64);
+                        if matches {
+                            cache::adopt(&out, head.e_tag())?;
+                        }
+                        !matches
+                    }
+                    Err(error) => {
+                        warn!(?out, error = %DisplayErrorContext(error), "Failed to check cached dataset");
+                        false
+                    }
+                }
+            }
+            None => false,
+        };
+
+        if !stale {
+            debug!(?out, "Dataset already downloaded");
+            cache::touch(&out);
+            return Ok(out);
+        }
+        info!(?out, "Cached dataset changed remotely");
     }
 
     info!(?bucket, ?key, "Downloading dataset");
 
     // Download dataset
-    let s3 = new_client()?;
-
     let start = Instant::now();
-    let resp = s3.get_object().bucket(bucket).key(key).send().await?;
-    let mut data = resp.body.into_async_read();
+    let head = with_retries("head", bucket, key, || async {
+        s3.head_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(sdk_error)
+    })
+    .await?;
+    let etag = head.e_tag().map(str::to_string);
+    let len = head.content_length().map(|len| len as u64);
+
     // Ensure the directory exists
-    std::fs::create_dir_all(Path::new(&out).parent().unwrap())?;
-    let mut file = tokio::fs::File::create(&out).await?;
+    std::fs::create_dir_all(out.parent().unwrap())?;
+    let part = PathBuf::from(format!("{}.part", out.display()));
+    // Resume a partial download of an earlier invocation, unless the object changed since
+    if let Ok(metadata) = tokio::fs::metadata(&part).await {
+        let changed = head
+            .last_modified()
+            .and_then(|changed| SystemTime::try_from(*changed).ok());
+        let stale = len.is_some_and(|len| metadata.len() > len)
+            || changed
+                .zip(metadata.modified().ok())
+                .is_some_and(|(changed, written)| changed > written);
+        if stale {
+            debug!(?part, "Discarding stale partial download");
+            tokio::fs::remove_file(&part).await?;
+        }
+    }
+
+    // Retries resume from what was already written
+    with_retries("download", bucket, key, || {
+        download_part(&s3, bucket, key, &part, etag.as_deref(), len)
+    })
+    .await?;
+    tokio::fs::rename(&part, &out).await?;
+    let duration = start.elapsed();
+
+    info!(?out, ?duration, "Dataset downloaded");
+    cache::insert(&cache_dir, &out, etag.as_deref())?;
+
+    Ok(out)
+}
+
+/// Download an S3 object of `len` bytes into `part`, continuing after whatever `part`
+/// already holds. With `etag`, fails instead of mixing in a different version of the object.
+async fn download_part(
+    s3: &Client,
+    bucket: &str,
+    key: &str,
+    part: &Path,
+    etag: Option<&str>,
+    len: Option<u64>,
+) -> anyhow::Result<()> {
+    let offset = tokio::fs::metadata(part)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    // A range past the end of the object is rejected
+    if offset > 0 && len == Some(offset) {
+        return Ok(());
+    }
+
+    let mut request = s3.get_object().bucket(bucket).key(key);
+    if let Some(etag) = etag {
+        request = request.if_match(etag);
+    }
+    if offset > 0 {
+        request = request.range(format!("bytes={offset}-"));
+    }
+    let resp = request.send().await.map_err(sdk_error)?;
+
+    let mut file = if offset > 0 && resp.content_range().is_some() {
+        debug!(?part, offset, "Resuming download");
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(part)
+            .await?
+    } else {
+        tokio::fs::File::create(part).await?
+    };
+    let mut data = resp.body.into_async_read();
     tokio::io::copy(&mut data, &mut file).await?;
+    file.flush().await?;
+
+    Ok(())
+}
+
+/// Redirects followed before giving up, e.g. HuggingFace `resolve` URLs redirect to a CDN.
+const MAX_REDIRECTS: usize = 10;
+
+type Https = hyper_rustls::HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>;
+
+/// Download an HTTP(S) URL, e.g. a presigned S3 URL or a HuggingFace file, into the cache
+/// directory.
+///
+/// The file is cached by host and path, ignoring the query string, so presigned URLs
+/// with fresh signatures reuse the same download. Cached files are revalidated against
+/// their ETag, and interrupted downloads are resumed from the partial `.part` file when
+/// the server supports range requests.
+async fn download_file(url: String, out_dir: impl Into<String>) -> anyhow::Result<PathBuf> {
+    let cache_dir = out_dir.into();
+
+    let uri: hyper::Uri = url.parse()?;
+    let host = uri
+        .host()
+        .ok_or_else(|| anyhow::anyhow!("URL has no host: {url}"))?;
+
+    let out = PathBuf::from(format!("{cache_dir}/{host}{}", uri.path()));
+    let cached_etag = match (out.exists(), cache::etag(&out)) {
+        (false, _) => None,
+        (true, Some(etag)) => Some(etag),
+        // Nothing to revalidate against
+        (true, None) => {
+            if cache::is_legacy(&out) {
+                cache::adopt(&out, None)?;
+            }
+            debug!(?out, "Dataset already downloaded");
+            cache::touch(&out);
+            return Ok(out);
+        }
+    };
+
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()?
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = HttpClient::builder(TokioExecutor::new()).build::<_, Empty<Bytes>>(https);
+
+    std::fs::create_dir_all(out.parent().unwrap())?;
+    let part = PathBuf::from(format!("{}.part", out.display()));
+    let offset = match cached_etag {
+        Some(_) => 0,
+        None => tokio::fs::metadata(&part)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0),
+    };
+
+    let start = Instant::now();
+    let mut resp = match (
+        get(&client, uri.clone(), cached_etag.as_deref(), offset).await,
+        &cached_etag,
+    ) {
+        (Ok(resp), Some(_)) if resp.status() == hyper::StatusCode::NOT_MODIFIED => {
+            debug!(?out, "Dataset already downloaded");
+            cache::touch(&out);
+            return Ok(out);
+        }
+        (Err(error), Some(_)) => {
+            warn!(?out, %error, "Failed to check cached dataset");
+            cache::touch(&out);
+            return Ok(out);
+        }
+        (resp, _) => resp?,
+    };
+
+    info!(?host, path = uri.path(), "Downloading dataset");
+
+    let mut file = match resp.status() {
+        hyper::StatusCode::PARTIAL_CONTENT => {
+            info!(?out, offset, "Resuming download");
+            tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(&part)
+                .await?
+        }
+        // The server ignored the range, start over
+        status if status.is_success() => tokio::fs::File::create(&part).await?,
+        status => anyhow::bail!("Failed to download {url}: {status}"),
+    };
+    let etag = resp
+        .headers()
+        .get(hyper::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    while let Some(frame) = resp.body_mut().frame().await {
+        if let Some(data) = frame?.data_ref() {
+            file.write_all(data).await?;
+        }
+    }
+    file.flush().await?;
+    tokio::fs::rename(&part, &out).await?;
     let duration = start.elapsed();
 
     info!(?out, ?duration, "Dataset downloaded");
+    cache::insert(&cache_dir, &out, etag.as_deref())?;
+
+    Ok(out)
+}
+
+/// GET `uri`, following redirects, conditional on `etag` and starting at `offset`.
+async fn get(
+    client: &HttpClient<Https, Empty<Bytes>>,
+    mut uri: hyper::Uri,
+    etag: Option<&str>,
+    offset: u64,
+) -> anyhow::Result<hyper::Response<hyper::body::Incoming>> {
+    for _ in 0..=MAX_REDIRECTS {
+        let mut request = hyper::Request::get(uri.clone());
+        if let Some(etag) = etag {
+            request = request.header(hyper::header::IF_NONE_MATCH, etag);
+        }
+        if offset > 0 {
+            request = request.header(hyper::header::RANGE, format!("bytes={offset}-"));
+        }
+        let resp = client.request(request.body(Empty::new())?).await?;
+
+        if !resp.status().is_redirection() || resp.status() == hyper::StatusCode::NOT_MODIFIED {
+            return Ok(resp);
+        }
+
+        let location = resp
+            .headers()
+            .get(hyper::header::LOCATION)
+            .ok_or_else(|| anyhow::anyhow!("Redirect without location from {uri}"))?
+            .to_str()?
+            .parse::<hyper::Uri>()?;
+        // Relative redirects keep the scheme and host
+        uri = if location.host().is_some() {
+            location
+        } else {
+            let mut parts = location.into_parts();
+            parts.scheme = uri.scheme().cloned();
+            parts.authority = uri.authority().cloned();
+            hyper::Uri::from_parts(parts)?
+        };
+    }
 
-    Ok(PathBuf::from(out))
+    anyhow::bail!("Too many redirects downloading {uri}")
 }
@@ -0,0 +1,36 @@
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::telemetry::metrics::{Buffer, Registry};
+
+/// Handle to an isolated set of metrics.
+///
+/// Runs started with the same session share a registry, so several sessions can
+/// benchmark different providers side by side and export their metrics separately.
+///
+/// Up to `metrics_buffer` samples are buffered per run before the registry stores them.
+/// When the buffer is full, `metrics_overflow` either blocks the recording worker
+/// (`"block"`) or discards a sample (`"drop-oldest"`, `"drop-newest"`).
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub registry: Registry,
+}
+
+#[pymethods]
+impl Session {
+    #[new]
+    #[pyo3(signature = (metrics_buffer=100_000, metrics_overflow="block"))]
+    fn new(metrics_buffer: usize, metrics_overflow: &str) -> PyResult<Self> {
+        let buffer = Buffer::new(metrics_buffer, metrics_overflow)
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+
+        Ok(Self {
+            registry: Registry::with_buffer(buffer),
+        })
+    }
+}
+
+/// Resolve the registry for an optional session, falling back to the global one.
+pub fn registry(session: Option<Session>) -> Registry {
+    session.map(|s| s.registry).unwrap_or_else(Registry::global)
+}
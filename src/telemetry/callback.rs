@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use pyo3::{
+    prelude::*,
+    types::{PyBool, PyDict},
+};
+
+use crate::dispatch::run_py;
+
+/// Python callable invoked with a stats dict every reporting tick.
+///
+/// Returning `False` from the callable stops the run. Exceptions are raised as errors.
+#[derive(Debug, Clone)]
+pub struct StatsCallback {
+    callable: Arc<Py<PyAny>>,
+}
+
+impl StatsCallback {
+    pub fn new(callable: Py<PyAny>) -> Self {
+        Self {
+            callable: Arc::new(callable),
+        }
+    }
+
+    /// Call with the run ID and stats. Returns `false` if the run should stop.
+    pub async fn call(&self, run_id: &str, stats: Vec<(&'static str, f64)>) -> PyResult<bool> {
+        let callable = self.callable.clone();
+        let run_id = run_id.to_string();
+
+        run_py(move |py| {
+            let dict = PyDict::new(py);
+            dict.set_item("run_id", run_id)?;
+            for (key, value) in stats {
+                dict.set_item(key, value)?;
+            }
+
+            let result = callable.call1(py, (dict,))?;
+            let stop = result.bind(py).is_instance_of::<PyBool>() && !result.extract::<bool>(py)?;
+
+            Ok(!stop)
+        })
+        .await
+    }
+}
@@ -0,0 +1,61 @@
+use arrow::json::ArrayWriter;
+use arrow_array::RecordBatch;
+use pyo3::prelude::*;
+use serde_json::{json, Map, Value};
+
+use crate::dispatch::run_py;
+use crate::telemetry::manifest::{manifest, RunInfo};
+
+/// Record `runs`, exported to `path` with `summary`, in the local run catalog kept by
+/// `topk_bench.catalog`, if one was set with `set_catalog()` or `TOPK_BENCH_CATALOG`.
+///
+/// Each run is one entry with its manifest and summary rows, replacing an earlier export
+/// of the same run.
+pub(super) async fn record(
+    runs: &[RunInfo],
+    path: &str,
+    summary: &RecordBatch,
+) -> anyhow::Result<()> {
+    if runs.is_empty() {
+        return Ok(());
+    }
+
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write(summary)?;
+    writer.finish()?;
+    let rows = writer.into_inner();
+    let rows: Vec<Map<String, Value>> = if rows.is_empty() {
+        Vec::new()
+    } else {
+        serde_json::from_slice(&rows)?
+    };
+
+    let entries = runs
+        .iter()
+        .map(|run| {
+            json!({
+                "run_id": run.run_id,
+                "kind": run.kind,
+                "provider": run.provider,
+                "started_at": run.started_at.to_rfc3339(),
+                "ended_at": run.ended_at.map(|t| t.to_rfc3339()),
+                "metrics": path,
+                "manifest": manifest(std::slice::from_ref(run), path),
+                "summary": rows
+                    .iter()
+                    .filter(|row| row.get("run_id").and_then(Value::as_str) == Some(&run.run_id))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+    let entries = serde_json::to_string(&entries)?;
+
+    run_py(move |py| {
+        py.import("topk_bench.catalog")?
+            .call_method1("_record", (entries,))?;
+        Ok(())
+    })
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow_array::{
+    ArrayRef, Float64Array, Int32Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+    UInt64Array,
+};
+use arrow_schema::TimeUnit;
+use once_cell::sync::Lazy;
+
+use crate::telemetry::manifest::RunInfo;
+use crate::telemetry::metrics::{Metric, MetricKind};
+use crate::telemetry::persist::{label_column, SCHEMA_VERSION};
+use crate::telemetry::summary::{run_phase, RunLabels};
+
+/// Leading columns of the histograms, which labels must not shadow.
+const COLUMNS: [&str; 5] = ["schema_version", "ts", "metric", "le", "count"];
+
+/// Length of the intervals samples are bucketed over, aligned to the Unix epoch so that
+/// intervals of concurrent runs line up.
+const INTERVAL_SECS: i64 = 10;
+
+/// Upper bounds of the latency buckets in ms, 10 per decade from 0.1ms to 100s. Samples
+/// above the last bound fall into a final `+inf` bucket.
+static BOUNDS: Lazy<Vec<f64>> =
+    Lazy::new(|| (-10..=50).map(|k| 10f64.powf(k as f64 / 10.0)).collect());
+
+/// Whether `metric` is exported as a histogram: latencies, i.e. histograms in ms.
+fn is_latency(metric: &Metric) -> bool {
+    metric.kind == MetricKind::Histogram && metric.name.ends_with("_ms")
+}
+
+/// Latency samples as fixed-bucket histograms: one row per run, phase, metric, interval
+/// and non-empty bucket with the number of samples above the previous bound and at most
+/// `le`. Unlike percentiles, bucket counts from several runs or load generators can be
+/// summed exactly.
+pub fn to_histogram_batch(metrics: &[Metric], runs: &[RunInfo]) -> anyhow::Result<RecordBatch> {
+    let mut counts = BTreeMap::<((&str, &str), &str, i64, usize), u64>::new();
+    let mut samples = HashMap::<(&str, &str), &HashMap<String, String>>::new();
+    for metric in metrics.iter().filter(|m| is_latency(m)) {
+        let run_phase = run_phase(metric);
+        let ts = metric.timestamp.timestamp();
+        let interval = ts - ts.rem_euclid(INTERVAL_SECS);
+        let bucket = BOUNDS.partition_point(|le| *le < metric.value);
+
+        *counts
+            .entry((run_phase, &metric.name, interval, bucket))
+            .or_default() += 1;
+        samples.entry(run_phase).or_insert(&metric.metadata);
+    }
+
+    let runs = RunLabels::new(runs);
+    let labels: HashMap<(&str, &str), BTreeMap<String, String>> = samples
+        .into_iter()
+        .map(|(run_phase, sample)| (run_phase, runs.get(run_phase.0, sample)))
+        .collect();
+    let label_keys: BTreeSet<&String> = labels.values().flat_map(|l| l.keys()).collect();
+
+    let mut fields = vec![
+        Field::new("schema_version", DataType::Int32, false),
+        Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            false,
+        ),
+        Field::new("metric", DataType::Utf8, false),
+        Field::new("le", DataType::Float64, false),
+        Field::new("count", DataType::UInt64, false),
+    ];
+    for key in &label_keys {
+        fields.push(Field::new(
+            label_column(key, &COLUMNS),
+            DataType::Utf8,
+            false,
+        ));
+    }
+
+    let mut timestamps = Vec::with_capacity(counts.len());
+    let mut names = Vec::with_capacity(counts.len());
+    let mut les = Vec::with_capacity(counts.len());
+    let mut values = Vec::with_capacity(counts.len());
+    let mut labels_vecs: Vec<Vec<String>> = (0..label_keys.len())
+        .map(|_| Vec::with_capacity(counts.len()))
+        .collect();
+
+    for ((run_phase, name, interval, bucket), count) in counts {
+        timestamps.push(interval * 1_000_000);
+        names.push(name.to_string());
+        les.push(BOUNDS.get(bucket).copied().unwrap_or(f64::INFINITY));
+        values.push(count);
+        for (i, key) in label_keys.iter().enumerate() {
+            labels_vecs[i].push(labels[&run_phase].get(*key).cloned().unwrap_or_default());
+        }
+    }
+
+    let mut arrays: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![SCHEMA_VERSION; names.len()])) as ArrayRef,
+        Arc::new(TimestampMicrosecondArray::from(timestamps)) as ArrayRef,
+        Arc::new(StringArray::from(names)) as ArrayRef,
+        Arc::new(Float64Array::from(les)) as ArrayRef,
+        Arc::new(UInt64Array::from(values)) as ArrayRef,
+    ];
+    for values in labels_vecs {
+        arrays.push(Arc::new(StringArray::from(values)) as ArrayRef);
+    }
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::AsArray;
+    use arrow::datatypes::{Float64Type, UInt64Type};
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn sample(name: &str, kind: MetricKind, value: f64, secs: i64) -> Metric {
+        Metric {
+            name: name.to_string(),
+            kind,
+            value,
+            timestamp: Utc.timestamp_opt(secs, 0).unwrap(),
+            metadata: Arc::new(HashMap::from([
+                ("run_id".to_string(), "run".to_string()),
+                ("phase".to_string(), "load".to_string()),
+            ])),
+            labels: Vec::new(),
+        }
+    }
+
+    /// `(le, count)` of each row of the histograms.
+    fn buckets(metrics: &[Metric]) -> Vec<(f64, u64)> {
+        let batch = to_histogram_batch(metrics, &[]).unwrap();
+        let les = batch
+            .column_by_name("le")
+            .unwrap()
+            .as_primitive::<Float64Type>();
+        let counts = batch
+            .column_by_name("count")
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        les.values()
+            .iter()
+            .copied()
+            .zip(counts.values().iter().copied())
+            .collect()
+    }
+
+    #[test]
+    fn bounds_are_ten_per_decade_from_a_tenth_of_a_ms_to_100s() {
+        assert_eq!(BOUNDS.len(), 61);
+        assert!((BOUNDS[0] - 0.1).abs() < 1e-12);
+        assert_eq!(BOUNDS[10], 1.0);
+        assert!((BOUNDS[60] - 100_000.0).abs() < 1e-6);
+        assert!(BOUNDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn samples_fall_into_the_bucket_of_the_next_bound() {
+        let latency = |value| sample("bench.query.latency_ms", MetricKind::Histogram, value, 5);
+        let rows = buckets(&[
+            latency(0.05),
+            latency(1.0),
+            latency(1.0),
+            latency(1.1),
+            latency(200_000.0),
+        ]);
+
+        assert_eq!(rows.len(), 4);
+        assert!((rows[0].0 - 0.1).abs() < 1e-12 && rows[0].1 == 1);
+        // A sample equal to a bound falls into its bucket
+        assert_eq!(rows[1], (1.0, 2));
+        assert!((rows[2].0 - 10f64.powf(0.1)).abs() < 1e-12 && rows[2].1 == 1);
+        assert_eq!(rows[3], (f64::INFINITY, 1));
+    }
+
+    #[test]
+    fn samples_are_bucketed_per_interval() {
+        let rows = buckets(&[
+            sample("bench.query.latency_ms", MetricKind::Histogram, 1.0, 1),
+            sample("bench.query.latency_ms", MetricKind::Histogram, 1.0, 9),
+            sample("bench.query.latency_ms", MetricKind::Histogram, 1.0, 10),
+        ]);
+
+        assert_eq!(rows, vec![(1.0, 2), (1.0, 1)]);
+    }
+
+    #[test]
+    fn only_latencies_are_exported() {
+        let rows = buckets(&[
+            sample("bench.query.recall", MetricKind::Histogram, 0.9, 5),
+            sample("bench.query.oks", MetricKind::Counter, 1.0, 5),
+        ]);
+
+        assert!(rows.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use colored::Colorize;
+use serde_json::{json, Value};
+
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::output::report;
+use crate::telemetry::snapshot::quantile_of_sorted;
+use crate::telemetry::Snapshot;
+
+/// Consecutive seconds of a run whose p99 latency exceeded the run's median by the
+/// configured factor, e.g. while the provider was compacting or collecting garbage.
+#[derive(Debug, Clone)]
+pub struct Incident {
+    pub start: DateTime<Utc>,
+    /// End of the last second flagged
+    pub end: DateTime<Utc>,
+    /// Highest per-second p99 within the incident
+    pub peak_p99_ms: f64,
+    /// The run's median latency the incident is compared to
+    pub median_ms: f64,
+    pub samples: usize,
+}
+
+impl Incident {
+    pub(super) fn to_json(&self) -> Value {
+        json!({
+            "start": self.start.to_rfc3339(),
+            "end": self.end.to_rfc3339(),
+            "peak_p99_ms": self.peak_p99_ms,
+            "median_ms": self.median_ms,
+            "samples": self.samples,
+        })
+    }
+}
+
+/// Scan the per-second p99 of `metric` and flag the seconds above `factor` times its
+/// median over the whole snapshot, merging consecutive ones into incidents.
+pub fn detect_incidents(stats: &Snapshot, metric: &str, factor: f64) -> Vec<Incident> {
+    let mut seconds = BTreeMap::<DateTime<Utc>, Vec<f64>>::new();
+    for sample in stats.metrics.iter().filter(|sample| sample.name == metric) {
+        let second = sample
+            .timestamp
+            .duration_trunc(Duration::seconds(1))
+            .unwrap_or(sample.timestamp);
+        seconds.entry(second).or_default().push(sample.value);
+    }
+    if seconds.is_empty() {
+        return Vec::new();
+    }
+
+    let median_ms = stats.quantile(metric, 0.50);
+    let threshold = median_ms * factor;
+
+    let mut incidents: Vec<Incident> = Vec::new();
+    for (second, mut values) in seconds {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let p99 = quantile_of_sorted(&values, 0.99);
+        if p99 <= threshold {
+            continue;
+        }
+
+        let end = second + Duration::seconds(1);
+        match incidents.last_mut() {
+            Some(incident) if incident.end == second => {
+                incident.end = end;
+                incident.peak_p99_ms = incident.peak_p99_ms.max(p99);
+                incident.samples += values.len();
+            }
+            _ => incidents.push(Incident {
+                start: second,
+                end,
+                peak_p99_ms: p99,
+                median_ms,
+                samples: values.len(),
+            }),
+        }
+    }
+
+    incidents
+}
+
+/// Print the incidents of a run with their timestamps, to correlate with provider logs, and
+/// record their count and total duration as `bench.{area}.incidents` and
+/// `bench.{area}.incident_secs`.
+pub fn report_incidents(
+    incidents: &[Incident],
+    factor: f64,
+    area: &str,
+    m: &Recorder,
+    prefix: &str,
+) {
+    let secs: i64 = incidents
+        .iter()
+        .map(|incident| (incident.end - incident.start).num_seconds())
+        .sum();
+    m.gauge(&format!("bench.{area}.incidents"), incidents.len() as f64);
+    m.gauge(&format!("bench.{area}.incident_secs"), secs as f64);
+
+    let Some(first) = incidents.first() else {
+        return;
+    };
+    report(format!(
+        "{:>16}] {} {} over {}s, p99 above {:.2}x the median of {:.2}ms",
+        prefix,
+        "Incidents:".red().bold(),
+        incidents.len(),
+        secs,
+        factor,
+        first.median_ms,
+    ));
+    for incident in incidents {
+        report(format!(
+            "{:>16}]   {} to {}: p99={} ({:.2}x median) over {} requests",
+            prefix,
+            incident.start.to_rfc3339(),
+            incident.end.to_rfc3339(),
+            format!("{:.2}ms", incident.peak_p99_ms).magenta().bold(),
+            incident.peak_p99_ms / incident.median_ms,
+            incident.samples,
+        ));
+    }
+}
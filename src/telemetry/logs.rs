@@ -1,15 +1,50 @@
+use std::sync::OnceLock;
+
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter};
+
+use crate::telemetry::output::{self, Output, OUTPUT_VAR};
+use crate::telemetry::results;
+
+type Reload = Box<dyn Fn(EnvFilter) -> anyhow::Result<()> + Send + Sync>;
+
+/// Swaps the filter installed by [`install`], whose subscriber type can't be named.
+static RELOAD: OnceLock<Reload> = OnceLock::new();
 
 pub fn install() -> anyhow::Result<()> {
+    let output = match std::env::var(OUTPUT_VAR) {
+        Ok(value) => value.parse()?,
+        Err(_) => Output::default(),
+    };
+
+    let (filter, handle) =
+        reload::Layer::new(EnvFilter::builder().try_from_env().unwrap_or("info".into()));
+    let _ = RELOAD.set(Box::new(move |filter| Ok(handle.reload(filter)?)));
+
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .with(
-            tracing_subscriber::EnvFilter::builder()
-                .try_from_env()
-                .unwrap_or("info".into()),
-        )
+        .with(output::layer(output))
+        .with(results::layer())
+        .with(filter)
         .try_init()?;
 
     Ok(())
 }
+
+/// Replace the log filter, `RUST_LOG` at import, with `level` for every module and
+/// `filters` directives such as `topk_bench=debug,aws=warn` on top.
+pub fn set_filter(level: &str, filters: Option<&str>) -> anyhow::Result<()> {
+    let level: tracing_subscriber::filter::LevelFilter = level
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid log level: {level}"))?;
+    let directives = match filters {
+        Some(filters) => format!("{level},{filters}"),
+        None => level.to_string(),
+    };
+    let filter = EnvFilter::builder().parse(&directives)?;
+
+    match RELOAD.get() {
+        Some(reload) => reload(filter),
+        None => anyhow::bail!("Logging is not installed"),
+    }
+}
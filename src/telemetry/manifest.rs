@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use pyo3::Python;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::cache;
+use crate::provider::ProviderVersion;
+use crate::s3::cached_path;
+use crate::telemetry::incidents::Incident;
+use crate::telemetry::metrics::Recorder;
+use crate::telemetry::persist::{sibling, write_bytes};
+
+/// What a run was started with, written to the manifest next to its exported metrics.
+#[derive(Debug, Clone)]
+pub struct RunInfo {
+    pub run_id: String,
+    /// `ingest`, `query`, `delete`, `backup` or `backfill`
+    pub kind: &'static str,
+    pub provider: String,
+    pub provider_version: ProviderVersion,
+    /// The run's labels, which cover its config
+    pub config: BTreeMap<String, String>,
+    /// Local or remote files the run read
+    pub datasets: Vec<String>,
+    pub cache_dir: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Features skipped or downgraded because the provider doesn't support them
+    pub notes: Vec<String>,
+    /// Latency spikes flagged at the end of the run
+    pub incidents: Vec<Incident>,
+}
+
+impl RunInfo {
+    /// Run recording through `m`, starting now.
+    pub fn new(
+        kind: &'static str,
+        m: &Recorder,
+        provider_version: ProviderVersion,
+        datasets: impl IntoIterator<Item = String>,
+        cache_dir: &str,
+    ) -> Self {
+        let config: BTreeMap<String, String> = m
+            .labels()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        Self {
+            run_id: config.get("run_id").cloned().expect("run_id is required"),
+            kind,
+            provider: config.get("provider").cloned().unwrap_or_default(),
+            provider_version,
+            config,
+            datasets: datasets.into_iter().collect(),
+            cache_dir: cache_dir.to_string(),
+            started_at: Utc::now(),
+            ended_at: None,
+            notes: Vec::new(),
+            incidents: Vec::new(),
+        }
+    }
+
+    pub fn with_notes(self, notes: Vec<String>) -> Self {
+        Self { notes, ..self }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "run_id": self.run_id,
+            "kind": self.kind,
+            "provider": {
+                "name": self.provider,
+                "version": self.provider_version.sdk,
+                "server_version": self.provider_version.server,
+            },
+            "config": self.config,
+            "datasets": self.datasets.iter().map(|uri| dataset(uri, &self.cache_dir)).collect::<Vec<_>>(),
+            "started_at": self.started_at.to_rfc3339(),
+            "ended_at": self.ended_at.map(|t| t.to_rfc3339()),
+            "notes": self.notes,
+            "incidents": self.incidents.iter().map(Incident::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Dataset URI with the ETag and size of its cached copy, if downloaded.
+fn dataset(uri: &str, cache_dir: &str) -> Value {
+    let path = cached_path(uri, cache_dir).filter(|path| path.exists());
+
+    json!({
+        "uri": uri,
+        "etag": path.as_deref().and_then(cache::etag),
+        "size": path.and_then(|path| std::fs::metadata(path).ok()).map(|m| m.len()),
+    })
+}
+
+/// Write the manifest of `runs` next to the metrics exported to `path`.
+pub async fn write_manifest(runs: &[RunInfo], path: &str) -> anyhow::Result<()> {
+    let contents = serde_json::to_vec_pretty(&manifest(runs, path))?;
+
+    let path = sibling(path, "manifest.json");
+    write_bytes(&contents, &path).await?;
+    info!("Manifest written to {path}");
+
+    Ok(())
+}
+
+/// Manifest of `runs` exported to `path`.
+pub(super) fn manifest(runs: &[RunInfo], path: &str) -> Value {
+    json!({
+        "bench_version": env!("CARGO_PKG_VERSION"),
+        "metrics": path,
+        "exported_at": Utc::now().to_rfc3339(),
+        "host": host(),
+        "runs": runs.iter().map(RunInfo::to_json).collect::<Vec<_>>(),
+    })
+}
+
+fn host() -> Value {
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok());
+
+    json!({
+        "hostname": hostname,
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "cpus": std::thread::available_parallelism().map(|n| n.get()).ok(),
+        "python": Python::with_gil(|py| py.version().to_string()),
+    })
+}
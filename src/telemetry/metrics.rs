@@ -1,66 +1,504 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::runtime::{Handle, RuntimeFlavor};
 use tokio::sync::RwLock;
+use tracing::warn;
 
+use crate::telemetry::incidents::Incident;
+use crate::telemetry::manifest::RunInfo;
 use crate::telemetry::snapshot::Snapshot;
 
-static METRICS: Lazy<RwLock<Vec<Metric>>> = Lazy::new(|| RwLock::new(Vec::new()));
+static DEFAULT_REGISTRY: Lazy<Registry> = Lazy::new(Registry::default);
 
-pub async fn snapshot_metrics(run_id: &str) -> Snapshot {
-    let guard = METRICS.read().await;
-    let metrics = guard
-        .iter()
-        .filter(|m| m.metadata.get("run_id").expect("run_id is required") == run_id)
-        .cloned()
-        .collect();
+/// In-memory store of metrics collected by one benchmark session.
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    metrics: Arc<RwLock<Vec<Metric>>>,
+    runs: Arc<RwLock<HashMap<String, RunInfo>>>,
+    buffer: Buffer,
+}
+
+/// Capacity of the channel between a run's recorders and the registry, and what
+/// happens to samples recorded while it is full.
+#[derive(Debug, Clone, Copy)]
+pub struct Buffer {
+    pub capacity: usize,
+    pub overflow: Overflow,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self {
+            capacity: 100_000,
+            overflow: Overflow::Block,
+        }
+    }
+}
+
+impl Buffer {
+    pub fn new(capacity: usize, overflow: &str) -> anyhow::Result<Self> {
+        if capacity == 0 {
+            anyhow::bail!("Metrics buffer capacity must be positive");
+        }
+
+        Ok(Self {
+            capacity,
+            overflow: overflow.parse()?,
+        })
+    }
+}
+
+impl Registry {
+    /// Registry used when no session is passed from Python.
+    pub fn global() -> Registry {
+        DEFAULT_REGISTRY.clone()
+    }
+
+    pub fn with_buffer(buffer: Buffer) -> Registry {
+        Registry {
+            metrics: Default::default(),
+            runs: Default::default(),
+            buffer,
+        }
+    }
+
+    /// Channel for a run's recorders to send metrics to [`Registry::consume`].
+    pub fn channel(&self) -> (MetricSender, MetricReceiver) {
+        let (tx, rx) = async_channel::bounded(self.buffer.capacity);
+        let dropped = Arc::new(Dropped::default());
+        let queued = Arc::new(AtomicU64::new(0));
+
+        (
+            MetricSender {
+                tx,
+                rx: rx.clone(),
+                overflow: self.buffer.overflow,
+                dropped: dropped.clone(),
+                queued: queued.clone(),
+            },
+            MetricReceiver {
+                rx,
+                dropped,
+                queued,
+            },
+        )
+    }
+
+    pub async fn start_run(&self, run: RunInfo) {
+        self.runs.write().await.insert(run.run_id.clone(), run);
+    }
+
+    pub async fn finish_run(&self, run_id: &str) {
+        if let Some(run) = self.runs.write().await.get_mut(run_id) {
+            run.ended_at = Some(Utc::now());
+        }
+    }
+
+    pub async fn add_incidents(&self, run_id: &str, incidents: Vec<Incident>) {
+        if let Some(run) = self.runs.write().await.get_mut(run_id) {
+            run.incidents.extend(incidents);
+        }
+    }
+
+    /// Runs started in this registry among `run_ids`.
+    pub async fn runs(&self, run_ids: &BTreeSet<String>) -> Vec<RunInfo> {
+        let runs = self.runs.read().await;
+        run_ids
+            .iter()
+            .filter_map(|id| runs.get(id).cloned())
+            .collect()
+    }
+
+    pub async fn snapshot(&self, run_id: &str) -> Snapshot {
+        Snapshot::new(self.run_metrics(run_id).await)
+    }
+
+    /// Copy of the metrics of a single run, leaving them in the registry.
+    pub async fn run_metrics(&self, run_id: &str) -> Vec<Metric> {
+        let guard = self.metrics.read().await;
+        guard
+            .iter()
+            .filter(|m| m.metadata.get("run_id").expect("run_id is required") == run_id)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn snapshot_all(&self) -> Snapshot {
+        Snapshot::new(self.metrics.read().await.clone())
+    }
+
+    pub async fn consume(self, rx: MetricReceiver) -> anyhow::Result<()> {
+        // Kind each metric name was first recorded with, to catch misuse
+        let mut kinds = HashMap::<String, Option<MetricKind>>::new();
+
+        while let Ok(metric) = rx.rx.recv().await {
+            match kinds.get_mut(&metric.name) {
+                None => {
+                    kinds.insert(metric.name.clone(), Some(metric.kind));
+                }
+                Some(kind) => {
+                    if let Some(expected) = kind.filter(|kind| *kind != metric.kind) {
+                        warn!(
+                            name = metric.name,
+                            %expected,
+                            actual = %metric.kind,
+                            "Metric recorded with conflicting kinds"
+                        );
+                        // Only warn once per metric
+                        *kind = None;
+                    }
+                }
+            }
+
+            let mut metrics = self.metrics.write().await;
+            metrics.push(metric);
+            // Explicitly drop the guard
+            drop(metrics);
+            rx.queued.fetch_sub(1, Ordering::AcqRel);
+        }
 
-    Snapshot { metrics }
+        // Samples dropped after the last one sent are only counted here
+        let pending = rx.dropped.pending.swap(0, Ordering::Relaxed);
+        if pending > 0 {
+            let metadata = rx.dropped.labels.lock().unwrap().clone();
+            self.metrics.write().await.push(Metric {
+                name: "bench.telemetry.dropped".to_string(),
+                kind: MetricKind::Counter,
+                value: pending as f64,
+                timestamp: Utc::now(),
+                metadata: metadata.unwrap_or_default(),
+                labels: Vec::new(),
+            });
+        }
+
+        let dropped = rx.dropped.total.load(Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(
+                dropped,
+                "Dropped metric samples, the metrics buffer was full"
+            );
+        }
+
+        Ok(())
+    }
+
+    pub async fn flush(&self) -> Vec<Metric> {
+        let mut guard = self.metrics.write().await;
+        let metrics = guard.drain(..).collect();
+        metrics
+    }
+
+    /// Remove and return the metrics of a single run.
+    pub async fn flush_run(&self, run_id: &str) -> Vec<Metric> {
+        let mut guard = self.metrics.write().await;
+        let (flushed, kept) = guard
+            .drain(..)
+            .partition(|m| m.metadata.get("run_id").map(String::as_str) == Some(run_id));
+        *guard = kept;
+        flushed
+    }
 }
 
-pub async fn consume_metrics(mut rx: UnboundedReceiver<Metric>) -> anyhow::Result<()> {
-    while let Some(metric) = rx.recv().await {
-        let mut metrics = METRICS.write().await;
-        metrics.push(metric);
-        // Explicitly drop the guard
-        drop(metrics);
+/// How the samples of a metric aggregate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// Increments summed over time, e.g. requests.
+    Counter,
+    /// Point-in-time values where the latest wins, e.g. a document count.
+    Gauge,
+    /// Observations summarized by their distribution, e.g. latencies.
+    Histogram,
+}
+
+impl FromStr for MetricKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "counter" => Ok(MetricKind::Counter),
+            "gauge" => Ok(MetricKind::Gauge),
+            "histogram" => Ok(MetricKind::Histogram),
+            _ => anyhow::bail!("Invalid metric kind: {s}"),
+        }
+    }
+}
+
+/// What to do with a sample recorded while the metrics buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait for the registry to catch up, slowing down the run.
+    Block,
+    /// Discard the oldest buffered sample to make room.
+    DropOldest,
+    /// Discard the sample being recorded.
+    DropNewest,
+}
+
+impl FromStr for Overflow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(Overflow::Block),
+            "drop-oldest" => Ok(Overflow::DropOldest),
+            "drop-newest" => Ok(Overflow::DropNewest),
+            _ => anyhow::bail!("Invalid metrics overflow: {s}"),
+        }
+    }
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Overflow::Block => write!(f, "block"),
+            Overflow::DropOldest => write!(f, "drop-oldest"),
+            Overflow::DropNewest => write!(f, "drop-newest"),
+        }
+    }
+}
+
+impl fmt::Display for MetricKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricKind::Counter => write!(f, "counter"),
+            MetricKind::Gauge => write!(f, "gauge"),
+            MetricKind::Histogram => write!(f, "histogram"),
+        }
+    }
+}
+
+/// Stage of a run, recorded as the `phase` label of its metrics so that e.g. the recall
+/// pass doesn't blend into the load aggregates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Before the timed window, or all of a warmup run
+    Warmup,
+    /// The first queries of a run started with cold connections
+    Cold,
+    /// The timed window
+    Load,
+    /// Measuring recall after the timed window
+    Recall,
+    /// Winding down in-flight requests once the timed window ends
+    Drain,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::Warmup,
+        Phase::Cold,
+        Phase::Load,
+        Phase::Recall,
+        Phase::Drain,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Warmup => "warmup",
+            Phase::Cold => "cold",
+            Phase::Load => "load",
+            Phase::Recall => "recall",
+            Phase::Drain => "drain",
+        }
+    }
+}
+
+/// Current phase of a run, shared by its recorders and switched as the run progresses.
+#[derive(Debug, Clone)]
+pub struct PhaseSwitch(Arc<AtomicU8>);
+
+impl PhaseSwitch {
+    pub fn new(phase: Phase) -> Self {
+        Self(Arc::new(AtomicU8::new(phase as u8)))
     }
 
-    Ok(())
+    pub fn set(&self, phase: Phase) {
+        self.0.store(phase as u8, Ordering::Relaxed);
+    }
+
+    fn index(&self) -> usize {
+        self.0.load(Ordering::Relaxed) as usize
+    }
 }
 
-pub async fn flush_metrics() -> Vec<Metric> {
-    let mut guard = METRICS.write().await;
-    let metrics = guard.drain(..).collect();
-    metrics
+/// Labels of a recorder for each phase, so that switching phases doesn't copy them per
+/// sample.
+#[derive(Debug, Clone)]
+struct Phased {
+    switch: PhaseSwitch,
+    metadata: Arc<[Arc<HashMap<String, String>>; Phase::ALL.len()]>,
+}
+
+impl Phased {
+    fn new(switch: &PhaseSwitch, metadata: &HashMap<String, String>) -> Self {
+        let labeled = |phase: Phase| {
+            let mut metadata = metadata.clone();
+            metadata.insert("phase".to_string(), phase.as_str().to_string());
+            Arc::new(metadata)
+        };
+
+        Self {
+            switch: switch.clone(),
+            metadata: Arc::new(Phase::ALL.map(labeled)),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Metric {
     /// Name of the metric
     pub name: String,
+    /// How samples of the metric aggregate
+    pub kind: MetricKind,
     /// Value of the metric
     pub value: f64,
     /// Timestamp of the metric
     pub timestamp: DateTime<Utc>,
-    /// Metadata for the metric
+    /// Labels of the recorder, shared by its samples
     pub metadata: Arc<HashMap<String, String>>,
+    /// Labels of this sample alone, taking precedence over `metadata`
+    pub labels: Vec<(Arc<str>, Arc<str>)>,
+}
+
+impl Metric {
+    /// Value of the label `key`, from the sample's own labels first.
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(k, _)| &**k == key)
+            .map(|(_, v)| &**v)
+            .or_else(|| self.metadata.get(key).map(String::as_str))
+    }
+
+    /// Keys of all the sample's labels, possibly repeated.
+    pub fn label_keys(&self) -> impl Iterator<Item = &str> {
+        self.metadata
+            .keys()
+            .map(String::as_str)
+            .chain(self.labels.iter().map(|(k, _)| &**k))
+    }
+}
+
+/// Samples discarded on overflow.
+#[derive(Debug, Default)]
+struct Dropped {
+    /// Not yet recorded as `bench.telemetry.dropped`
+    pending: AtomicU64,
+    total: AtomicU64,
+    /// Labels of the recorder that first saw samples pending, for the count flushed once
+    /// the channel closes
+    labels: Mutex<Option<Arc<HashMap<String, String>>>>,
+}
+
+impl Dropped {
+    fn add(&self, n: u64) {
+        self.pending.fetch_add(n, Ordering::Relaxed);
+        self.total.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+/// Sending half of [`Registry::channel`].
+#[derive(Debug, Clone)]
+pub struct MetricSender {
+    tx: async_channel::Sender<Metric>,
+    /// Used to discard the oldest sample on overflow
+    rx: async_channel::Receiver<Metric>,
+    overflow: Overflow,
+    dropped: Arc<Dropped>,
+    /// Samples sent but not yet taken in by the registry
+    queued: Arc<AtomicU64>,
+}
+
+impl MetricSender {
+    /// Send a sample, applying the overflow policy if the buffer is full. Returns
+    /// whether the sample was buffered.
+    fn send(&self, mut metric: Metric) -> bool {
+        loop {
+            metric = match self.try_send(metric) {
+                Ok(()) => return true,
+                Err(async_channel::TrySendError::Full(metric)) => metric,
+                Err(async_channel::TrySendError::Closed(_)) => {
+                    self.dropped.add(1);
+                    return false;
+                }
+            };
+
+            match self.overflow {
+                Overflow::Block => {
+                    let send = || {
+                        self.queued.fetch_add(1, Ordering::AcqRel);
+                        let sent = self.tx.send_blocking(metric).is_ok();
+                        if !sent {
+                            self.queued.fetch_sub(1, Ordering::AcqRel);
+                        }
+                        sent
+                    };
+                    // Blocking this worker is fine while the others drain the channel
+                    let sent = match Handle::try_current() {
+                        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                            tokio::task::block_in_place(send)
+                        }
+                        _ => send(),
+                    };
+                    if !sent {
+                        self.dropped.add(1);
+                    }
+                    return sent;
+                }
+                Overflow::DropOldest => {
+                    if self.rx.try_recv().is_ok() {
+                        self.queued.fetch_sub(1, Ordering::AcqRel);
+                        self.dropped.add(1);
+                    }
+                }
+                Overflow::DropNewest => {
+                    self.dropped.add(1);
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Buffer a sample if there is room, counting it as queued until it is consumed.
+    fn try_send(&self, metric: Metric) -> Result<(), async_channel::TrySendError<Metric>> {
+        // Counted before it can be consumed, so the count never underflows
+        self.queued.fetch_add(1, Ordering::AcqRel);
+        let sent = self.tx.try_send(metric);
+        if sent.is_err() {
+            self.queued.fetch_sub(1, Ordering::AcqRel);
+        }
+        sent
+    }
+}
+
+/// Receiving half of [`Registry::channel`].
+#[derive(Debug)]
+pub struct MetricReceiver {
+    rx: async_channel::Receiver<Metric>,
+    dropped: Arc<Dropped>,
+    queued: Arc<AtomicU64>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Recorder {
     /// Sender for the metrics
-    tx: UnboundedSender<Metric>,
+    tx: MetricSender,
     /// Metadata for the metrics
     metadata: Arc<HashMap<String, String>>,
+    /// Set when labeling the metrics with the run's current phase
+    phased: Option<Phased>,
 }
 
 impl Recorder {
     pub fn new(
-        tx: UnboundedSender<Metric>,
+        tx: MetricSender,
         metadata: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
     ) -> Self {
         Self {
@@ -71,17 +509,178 @@ impl Recorder {
                     .map(|(k, v)| (k.into(), v.into()))
                     .collect(),
             ),
+            phased: None,
+        }
+    }
+
+    /// Recorder labeling its metrics with the current phase of `switch`.
+    pub fn with_phase(&self, switch: &PhaseSwitch) -> Self {
+        Self {
+            phased: Some(Phased::new(switch, &self.metadata)),
+            ..self.clone()
+        }
+    }
+
+    /// Recorder with an additional label.
+    pub fn with_label(&self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut metadata = (*self.metadata).clone();
+        metadata.insert(key.into(), value.into());
+
+        Self {
+            tx: self.tx.clone(),
+            phased: self
+                .phased
+                .as_ref()
+                .map(|phased| Phased::new(&phased.switch, &metadata)),
+            metadata: Arc::new(metadata),
+        }
+    }
+
+    /// Labels of the recorder, without the current phase.
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// Wait for the registry to take in every sample sent so far, so that its snapshots
+    /// include them. Only returns while the channel is being consumed.
+    pub async fn flushed(&self) {
+        while self.tx.queued.load(Ordering::Acquire) > 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    /// Add `value` to a counter.
+    pub fn incr(&self, name: &str, value: f64) {
+        self.record(name, MetricKind::Counter, value);
+    }
+
+    /// Set a gauge to `value`.
+    pub fn gauge(&self, name: &str, value: f64) {
+        self.record(name, MetricKind::Gauge, value);
+    }
+
+    /// Add an observation to a histogram.
+    pub fn observe(&self, name: &str, value: f64) {
+        self.record(name, MetricKind::Histogram, value);
+    }
+
+    /// Add `value` to a counter, labeling this sample with `labels` on top of the
+    /// recorder's own labels.
+    pub fn incr_with(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        self.record_with(name, MetricKind::Counter, value, labels);
+    }
+
+    /// Set a gauge to `value`, labeling this sample with `labels`.
+    pub fn gauge_with(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        self.record_with(name, MetricKind::Gauge, value, labels);
+    }
+
+    /// Add an observation to a histogram, labeling this sample with `labels`.
+    pub fn observe_with(&self, name: &str, value: f64, labels: &[(&str, &str)]) {
+        self.record_with(name, MetricKind::Histogram, value, labels);
+    }
+
+    /// Labels of the next sample, including the current phase.
+    fn metadata(&self) -> &Arc<HashMap<String, String>> {
+        match &self.phased {
+            Some(phased) => &phased.metadata[phased.switch.index()],
+            None => &self.metadata,
         }
     }
 
-    pub fn record(&self, name: &str, value: f64) {
-        self.tx
-            .send(Metric {
-                name: name.to_string(),
-                value,
+    fn record(&self, name: &str, kind: MetricKind, value: f64) {
+        self.send(name, kind, value, Vec::new());
+    }
+
+    fn record_with(&self, name: &str, kind: MetricKind, value: f64, labels: &[(&str, &str)]) {
+        // Kept apart from the recorder's labels, which are shared, until export
+        let labels = labels
+            .iter()
+            .map(|(k, v)| (Arc::from(*k), Arc::from(*v)))
+            .collect();
+
+        self.send(name, kind, value, labels);
+    }
+
+    fn send(&self, name: &str, kind: MetricKind, value: f64, labels: Vec<(Arc<str>, Arc<str>)>) {
+        let sent = self.tx.send(Metric {
+            name: name.to_string(),
+            kind,
+            value,
+            timestamp: Utc::now(),
+            metadata: self.metadata().clone(),
+            labels,
+        });
+
+        // Account for samples dropped on overflow once there is room again
+        let dropped = &self.tx.dropped;
+        if dropped.pending.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        dropped
+            .labels
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| self.metadata().clone());
+        if sent {
+            let pending = dropped.pending.swap(0, Ordering::Relaxed);
+            let metric = Metric {
+                name: "bench.telemetry.dropped".to_string(),
+                kind: MetricKind::Counter,
+                value: pending as f64,
                 timestamp: Utc::now(),
-                metadata: self.metadata.clone(),
-            })
-            .unwrap();
+                metadata: self.metadata().clone(),
+                labels: Vec::new(),
+            };
+            if self.tx.try_send(metric).is_err() {
+                dropped.pending.fetch_add(pending, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn samples_dropped_after_the_last_send_are_counted() {
+        let registry = Registry::with_buffer(Buffer {
+            capacity: 1,
+            overflow: Overflow::DropNewest,
+        });
+        let (tx, rx) = registry.channel();
+        let m = Recorder::new(tx, [("run_id", "run")]);
+
+        // Only the first fits the buffer, and nothing is sent after the others are dropped
+        for _ in 0..3 {
+            m.incr("bench.query.oks", 1.0);
+        }
+        drop(m);
+        registry.clone().consume(rx).await.unwrap();
+
+        let stats = registry.snapshot("run").await;
+        assert_eq!(stats.total("bench.query.oks"), 1.0);
+        assert_eq!(stats.total("bench.telemetry.dropped"), 2.0);
+    }
+
+    #[tokio::test]
+    async fn flushed_waits_for_the_registry_to_take_in_samples() {
+        let registry = Registry::default();
+        let (tx, rx) = registry.channel();
+        let m = Recorder::new(tx, [("run_id", "run")]);
+        for _ in 0..1_000 {
+            m.observe("bench.query.recall", 1.0);
+        }
+
+        let consumer = tokio::spawn(registry.clone().consume(rx));
+        m.flushed().await;
+        assert_eq!(
+            registry.snapshot("run").await.count("bench.query.recall"),
+            1_000
+        );
+
+        drop(m);
+        consumer.await.unwrap().unwrap();
     }
 }
@@ -1,10 +1,38 @@
 mod logs;
+pub use logs::set_filter as set_log_filter;
+
+mod catalog;
+
+mod callback;
+pub use callback::StatsCallback;
+
+pub mod output;
+
+mod histograms;
+
+mod incidents;
+pub use incidents::{detect_incidents, report_incidents};
+
+mod manifest;
+pub use manifest::RunInfo;
 
 mod persist;
-pub use persist::export;
+pub use persist::{export, export_run, write_batch, SCHEMA_VERSION};
+
+mod reporting;
+pub use reporting::Reporting;
+
+mod results;
+pub use results::RunResults;
+
+mod run;
+pub use run::RunIds;
+
+mod summary;
 
 mod snapshot;
-pub use snapshot::Snapshot;
+pub(crate) use snapshot::quantile_of_sorted;
+pub use snapshot::{PySnapshot, Snapshot};
 
 pub mod metrics;
 
@@ -0,0 +1,259 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+use colored::control;
+use pyo3::prelude::*;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{reload, Layer, Registry};
+
+use crate::telemetry::results;
+
+/// How progress reports and logs are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Output {
+    /// Colors when stdout is a terminal.
+    #[default]
+    Auto,
+    Color,
+    Plain,
+    /// Warnings and errors only, no progress reports.
+    Quiet,
+    /// One JSON object per log line and progress report.
+    JsonLines,
+    /// Log lines and progress reports forwarded to Python's `logging`.
+    Python,
+}
+
+impl FromStr for Output {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Output::Auto),
+            "color" => Ok(Output::Color),
+            "plain" => Ok(Output::Plain),
+            "quiet" => Ok(Output::Quiet),
+            "json-lines" => Ok(Output::JsonLines),
+            "python" => Ok(Output::Python),
+            _ => anyhow::bail!("Invalid output: {s}"),
+        }
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Auto => write!(f, "auto"),
+            Output::Color => write!(f, "color"),
+            Output::Plain => write!(f, "plain"),
+            Output::Quiet => write!(f, "quiet"),
+            Output::JsonLines => write!(f, "json-lines"),
+            Output::Python => write!(f, "python"),
+        }
+    }
+}
+
+/// Environment variable selecting the output mode at import.
+pub const OUTPUT_VAR: &str = "TOPK_BENCH_OUTPUT";
+
+/// Target of progress reports logged in `json-lines` and `python` mode.
+const REPORT_TARGET: &str = "topk_bench::report";
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+static RELOAD: OnceLock<reload::Handle<BoxedLayer, Registry>> = OnceLock::new();
+
+/// Logging layer for the initial output mode, whose formatting [`set`] can later swap.
+pub(super) fn layer(output: Output) -> reload::Layer<BoxedLayer, Registry> {
+    let (layer, handle) = reload::Layer::new(fmt_layer(output));
+    let _ = RELOAD.set(handle);
+    apply(output);
+
+    layer
+}
+
+/// Switch the output mode of progress reports and logs.
+pub fn set(output: Output) -> anyhow::Result<()> {
+    if let Some(handle) = RELOAD.get() {
+        handle.reload(fmt_layer(output))?;
+    }
+    apply(output);
+
+    Ok(())
+}
+
+pub fn current() -> Output {
+    match CURRENT.load(Ordering::Relaxed) {
+        1 => Output::Color,
+        2 => Output::Plain,
+        3 => Output::Quiet,
+        4 => Output::JsonLines,
+        5 => Output::Python,
+        _ => Output::Auto,
+    }
+}
+
+/// Print a progress report line in the current output mode.
+pub fn report(line: String) {
+    results::capture(&line);
+
+    match current() {
+        Output::Quiet => {}
+        Output::JsonLines | Output::Python => tracing::info!(target: REPORT_TARGET, "{line}"),
+        _ => println!("{line}"),
+    }
+}
+
+fn apply(output: Output) {
+    CURRENT.store(output as u8, Ordering::Relaxed);
+
+    match output {
+        Output::Auto => control::unset_override(),
+        Output::Color => control::set_override(true),
+        Output::Plain | Output::Quiet | Output::JsonLines | Output::Python => {
+            control::set_override(false)
+        }
+    }
+}
+
+fn fmt_layer(output: Output) -> BoxedLayer {
+    let layer = tracing_subscriber::fmt::layer();
+
+    match output {
+        Output::Auto => layer.with_ansi(std::io::stdout().is_terminal()).boxed(),
+        Output::Color => layer.with_ansi(true).boxed(),
+        Output::Plain => layer.with_ansi(false).boxed(),
+        Output::Quiet => layer
+            .with_ansi(false)
+            .with_filter(LevelFilter::WARN)
+            .boxed(),
+        Output::JsonLines => layer.with_ansi(false).event_format(JsonLines).boxed(),
+        Output::Python => PythonLogging.boxed(),
+    }
+}
+
+/// Forwards events to the Python logger named after their target, e.g. `topk_bench.query`
+/// for `topk_bench::query`, so Python's logging configuration decides what is shown.
+struct PythonLogging;
+
+impl<S: Subscriber> Layer<S> for PythonLogging {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = match *metadata.level() {
+            Level::ERROR => 40,
+            Level::WARN => 30,
+            Level::INFO => 20,
+            Level::DEBUG => 10,
+            Level::TRACE => 5,
+        };
+
+        let mut message = MessageFields(String::new());
+        event.record(&mut message);
+
+        Python::with_gil(|py| {
+            let log = || -> PyResult<()> {
+                let logger = py
+                    .import("logging")?
+                    .call_method1("getLogger", (metadata.target().replace("::", "."),))?;
+                if logger.call_method1("isEnabledFor", (level,))?.is_truthy()? {
+                    logger.call_method1("log", (level, "%s", message.0))?;
+                }
+                Ok(())
+            };
+            // Logging must not fail the code that logged
+            if let Err(e) = log() {
+                e.print(py);
+            }
+        });
+    }
+}
+
+/// The event's message followed by its other fields as `key=value`.
+struct MessageFields(String);
+
+impl Visit for MessageFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        use std::fmt::Write;
+
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        let _ = match field.name() {
+            "message" => write!(self.0, "{value:?}"),
+            name => write!(self.0, "{name}={value:?}"),
+        };
+    }
+}
+
+/// Formats events as JSON objects with `timestamp`, `level`, `target` and the event fields.
+struct JsonLines;
+
+impl<S, N> FormatEvent<S, N> for JsonLines
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+
+        let mut fields = JsonFields(serde_json::Map::new());
+        fields.0.insert(
+            "timestamp".to_string(),
+            chrono::Utc::now().to_rfc3339().into(),
+        );
+        fields
+            .0
+            .insert("level".to_string(), metadata.level().as_str().into());
+        fields
+            .0
+            .insert("target".to_string(), metadata.target().into());
+        event.record(&mut fields);
+
+        writeln!(writer, "{}", serde_json::Value::Object(fields.0))
+    }
+}
+
+struct JsonFields(serde_json::Map<String, serde_json::Value>);
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}").into());
+    }
+}
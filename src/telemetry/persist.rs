@@ -7,35 +7,176 @@ use arrow::datatypes::Field;
 use arrow::datatypes::Schema;
 use arrow_array::ArrayRef;
 use arrow_array::Float64Array;
+use arrow_array::Int32Array;
 use arrow_array::RecordBatch;
 use arrow_array::StringArray;
 use arrow_array::TimestampMicrosecondArray;
 use arrow_schema::TimeUnit;
 use parquet::arrow::ArrowWriter;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::s3::upload_file;
-use crate::telemetry::metrics::flush_metrics;
+use crate::telemetry::catalog;
+use crate::telemetry::histograms::to_histogram_batch;
+use crate::telemetry::manifest::write_manifest;
 use crate::telemetry::metrics::Metric;
+use crate::telemetry::metrics::Registry;
+use crate::telemetry::summary::to_summary_batch;
 
-pub async fn export(path: &str) -> anyhow::Result<()> {
-    let metrics = flush_metrics().await;
+pub async fn export(registry: &Registry, path: &str) -> anyhow::Result<()> {
+    let metrics = registry.flush().await;
+    write_all(registry, metrics, path).await?;
+    Ok(())
+}
+
+/// Export the metrics of a single run, removing them from the registry.
+pub async fn export_run(registry: &Registry, run_id: &str, path: &str) -> anyhow::Result<()> {
+    let metrics = registry.flush_run(run_id).await;
+    write_all(registry, metrics, path).await?;
+    Ok(())
+}
+
+/// Write `metrics` to `path`, with their per-run summary, latency histograms and
+/// manifest next to it. Returns the summary.
+pub(super) async fn write_all(
+    registry: &Registry,
+    metrics: Vec<Metric>,
+    path: &str,
+) -> anyhow::Result<RecordBatch> {
+    let runs = registry.runs(&run_ids(&metrics)).await;
+    let summary = to_summary_batch(&metrics, &runs)?;
+    let histograms = to_histogram_batch(&metrics, &runs)?;
+
+    write_batch(to_batch(metrics)?, path).await?;
+    info!("Metrics written to {path}");
+
+    let extension = table_extension(path);
+    let summary_path = sibling(path, &format!("summary.{extension}"));
+    write_batch(summary.clone(), &summary_path).await?;
+    info!("Summary written to {summary_path}");
+
+    let histograms_path = sibling(path, &format!("histograms.{extension}"));
+    write_batch(histograms, &histograms_path).await?;
+    info!("Latency histograms written to {histograms_path}");
+
+    write_manifest(&runs, path).await?;
+
+    if let Err(e) = catalog::record(&runs, path, &summary).await {
+        warn!("Failed to record runs in the catalog: {e}");
+    }
+
+    Ok(summary)
+}
+
+/// Path next to the metrics at `path`, e.g. `results/topk.parquet` with `suffix`
+/// `manifest.json` is `results/topk.manifest.json`.
+pub(super) fn sibling(path: &str, suffix: &str) -> String {
+    let stem = Format::EXTENSIONS
+        .iter()
+        .find_map(|extension| path.strip_suffix(&format!(".{extension}")))
+        .unwrap_or(path);
+    format!("{stem}.{suffix}")
+}
+
+/// Extension of the table at `path`, for tables written next to it in the same format.
+fn table_extension(path: &str) -> &str {
+    Format::EXTENSIONS
+        .iter()
+        .find(|extension| path.ends_with(&format!(".{extension}")))
+        .copied()
+        .unwrap_or("parquet")
+}
+
+/// File format of a table, chosen by the extension of its path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Parquet,
+    Csv,
+    /// One JSON object per row
+    JsonLines,
+}
+
+impl Format {
+    const EXTENSIONS: [&str; 4] = ["parquet", "csv", "jsonl", "ndjson"];
+
+    /// Parquet unless `path` ends in `.csv`, `.jsonl` or `.ndjson`.
+    fn of(path: &str) -> Self {
+        if path.ends_with(".csv") {
+            Format::Csv
+        } else if path.ends_with(".jsonl") || path.ends_with(".ndjson") {
+            Format::JsonLines
+        } else {
+            Format::Parquet
+        }
+    }
+}
+
+fn run_ids(metrics: &[Metric]) -> BTreeSet<String> {
+    metrics
+        .iter()
+        .filter_map(|m| m.metadata.get("run_id").cloned())
+        .collect()
+}
+
+/// Version of the exported column contract:
+///
+/// - `schema_version`, `ts`, `metric`, `kind` and `value` come first, in that order.
+/// - Every label becomes a string column, sorted by name, empty where a sample doesn't
+///   carry it. Labels named like a leading column are prefixed with `label_`, e.g. the
+///   distance `metric` label is exported as `label_metric`.
+/// - Metric names are `bench.<area>.<name>`. Within a version a name keeps its kind and
+///   unit (`_ms` for milliseconds) and labels keep their meaning, while new metrics and
+///   labels may be added at any time. Renaming or removing any of them bumps the version.
+///
+/// Exports without a `schema_version` column are version 1, whose distance `metric`
+/// label clashed with the `metric` column.
+pub const SCHEMA_VERSION: i32 = 2;
+
+/// Leading columns, which labels must not shadow.
+const COLUMNS: [&str; 5] = ["schema_version", "ts", "metric", "kind", "value"];
 
+/// Column name of the label `key` in a file whose leading columns are `columns`.
+pub(super) fn label_column(key: &str, columns: &[&str]) -> String {
+    if columns.contains(&key) {
+        format!("label_{key}")
+    } else {
+        key.to_string()
+    }
+}
+
+/// Write a record batch to a local path or an `s3://` URI, as CSV or JSON Lines if the path
+/// ends in `.csv` or `.jsonl`, and as parquet otherwise.
+pub async fn write_batch(batch: RecordBatch, path: &str) -> anyhow::Result<()> {
+    let format = Format::of(path);
     if path.starts_with("s3://") {
         let (_, bucket_uri) = path.split_once("://").expect("Invalid S3 path");
         let (bucket, key) = bucket_uri.split_once("/").expect("Invalid S3 path");
 
         let tmp_dir = tempfile::tempdir()?;
-        let tmp_file = tmp_dir
-            .path()
-            .join(format!("{}.parquet", uuid::Uuid::new_v4()));
+        let tmp_file = tmp_dir.path().join(uuid::Uuid::new_v4().to_string());
 
-        write_to_file(metrics, tmp_file.clone())?;
+        write_to_file(batch, tmp_file.clone(), format)?;
         write_to_s3(bucket, key, tmp_file).await?;
-        info!("Metrics written to s3://{bucket}/{key}");
     } else {
-        write_to_file(metrics, PathBuf::from(path))?;
-        info!("Metrics written to {path}");
+        write_to_file(batch, PathBuf::from(path), format)?;
+    }
+
+    Ok(())
+}
+
+/// Write `contents` to a local path or an `s3://` URI.
+pub(super) async fn write_bytes(contents: &[u8], path: &str) -> anyhow::Result<()> {
+    if path.starts_with("s3://") {
+        let (_, bucket_uri) = path.split_once("://").expect("Invalid S3 path");
+        let (bucket, key) = bucket_uri.split_once("/").expect("Invalid S3 path");
+
+        let tmp_dir = tempfile::tempdir()?;
+        let tmp_file = tmp_dir.path().join(uuid::Uuid::new_v4().to_string());
+
+        std::fs::write(&tmp_file, contents)?;
+        upload_file(bucket, key, tmp_file).await?;
+    } else {
+        std::fs::write(PathBuf::from(path), contents)?;
     }
 
     Ok(())
@@ -45,33 +186,62 @@ async fn write_to_s3(bucket: &str, key: &str, path: PathBuf) -> anyhow::Result<(
     upload_file(bucket, key, path).await
 }
 
-fn write_to_file(metrics: Vec<Metric>, path: PathBuf) -> anyhow::Result<()> {
+fn write_to_file(batch: RecordBatch, path: PathBuf, format: Format) -> anyhow::Result<()> {
     let file = std::fs::File::create(path)?;
 
+    match format {
+        Format::Parquet => {
+            let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+            writer.write(&batch)?;
+            writer.close()?;
+        }
+        Format::Csv => {
+            let mut writer = arrow::csv::Writer::new(file);
+            writer.write(&batch)?;
+        }
+        Format::JsonLines => {
+            let mut writer = arrow::json::LineDelimitedWriter::new(file);
+            writer.write(&batch)?;
+            writer.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn to_batch(metrics: Vec<Metric>) -> anyhow::Result<RecordBatch> {
     // Find all unique label keys (union of all label sets in the metrics)
     let label_keys = {
         let mut set = BTreeSet::new();
         for metric in &metrics {
-            for k in metric.metadata.keys() {
-                set.insert(k.clone());
+            for k in metric.label_keys() {
+                if !set.contains(k) {
+                    set.insert(k.to_string());
+                }
             }
         }
         set.into_iter().collect::<Vec<String>>()
     };
 
-    // Compose the schema: timestamp, metric, value, ...label_keys
+    // Compose the schema: schema_version, timestamp, metric, kind, value, ...label_keys
     let schema = {
         let mut fields = vec![
+            Field::new("schema_version", DataType::Int32, false),
             Field::new(
                 "ts",
                 DataType::Timestamp(TimeUnit::Microsecond, None),
                 false,
             ),
             Field::new("metric", DataType::Utf8, false),
+            Field::new("kind", DataType::Utf8, false),
             Field::new("value", DataType::Float64, false),
         ];
         for key in &label_keys {
-            fields.push(Field::new(key, DataType::Utf8, false));
+            fields.push(Field::new(
+                label_column(key, &COLUMNS),
+                DataType::Utf8,
+                false,
+            ));
         }
         Arc::new(Schema::new(fields))
     };
@@ -79,6 +249,7 @@ fn write_to_file(metrics: Vec<Metric>, path: PathBuf) -> anyhow::Result<()> {
     // Collect data into column vectors, in schema order
     let mut timestamps = Vec::with_capacity(metrics.len());
     let mut names = Vec::with_capacity(metrics.len());
+    let mut kinds = Vec::with_capacity(metrics.len());
     let mut values = Vec::with_capacity(metrics.len());
     let mut labels_vecs: Vec<Vec<String>> = (0..label_keys.len())
         .map(|_| Vec::with_capacity(metrics.len()))
@@ -86,27 +257,25 @@ fn write_to_file(metrics: Vec<Metric>, path: PathBuf) -> anyhow::Result<()> {
 
     for metric in metrics {
         timestamps.push(metric.timestamp.timestamp_micros());
-        names.push(metric.name);
+        kinds.push(metric.kind.to_string());
         values.push(metric.value);
         for (i, key) in label_keys.iter().enumerate() {
-            labels_vecs[i].push(metric.metadata.get(key).cloned().unwrap_or_default());
+            labels_vecs[i].push(metric.label(key).unwrap_or_default().to_string());
         }
+        names.push(metric.name);
     }
 
-    // Build Arrow arrays in order: ts, metric, value, ...labels
+    // Build Arrow arrays in order: schema_version, ts, metric, kind, value, ...labels
     let mut arrays: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![SCHEMA_VERSION; timestamps.len()])) as ArrayRef,
         Arc::new(TimestampMicrosecondArray::from(timestamps)) as ArrayRef,
         Arc::new(StringArray::from(names)) as ArrayRef,
+        Arc::new(StringArray::from(kinds)) as ArrayRef,
         Arc::new(Float64Array::from(values)) as ArrayRef,
     ];
     for values in labels_vecs {
         arrays.push(Arc::new(StringArray::from(values)) as ArrayRef);
     }
 
-    let batch = RecordBatch::try_new(schema.clone(), arrays)?;
-    let mut writer = ArrowWriter::try_new(file, schema, None)?;
-    writer.write(&batch)?;
-    writer.close()?;
-
-    Ok(())
+    Ok(RecordBatch::try_new(schema, arrays)?)
 }
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use tokio::time::{Interval, MissedTickBehavior};
+
+use crate::telemetry::metrics::Registry;
+use crate::telemetry::Snapshot;
+
+/// How often a run reports progress, and over which window of recent metrics.
+#[derive(Debug, Clone, Copy)]
+pub struct Reporting {
+    pub interval: Duration,
+    pub window: Option<Duration>,
+}
+
+impl Reporting {
+    pub fn new(interval_secs: f64, window_secs: Option<f64>) -> anyhow::Result<Self> {
+        if interval_secs <= 0.0 {
+            anyhow::bail!("report_interval_secs must be positive");
+        }
+        if window_secs.is_some_and(|secs| secs <= 0.0) {
+            anyhow::bail!("report_window_secs must be positive");
+        }
+
+        Ok(Self {
+            interval: Duration::from_secs_f64(interval_secs),
+            window: window_secs.map(Duration::from_secs_f64),
+        })
+    }
+
+    /// Ticker for the reporter loop, with the immediate first tick already consumed.
+    pub async fn ticker(&self) -> Interval {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker.tick().await;
+
+        ticker
+    }
+
+    /// Snapshot of the run with rates and percentiles over the reporting window.
+    pub async fn snapshot(&self, registry: &Registry, run_id: &str) -> Snapshot {
+        registry.snapshot(run_id).await.with_window(self.window)
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use arrow::json::ArrayWriter;
+use once_cell::sync::Lazy;
+use tempfile::TempDir;
+use tracing::{info, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::telemetry::metrics::Registry;
+use crate::telemetry::persist::{write_all, write_bytes};
+
+/// Everything a run writes, under `{results_dir}/{run_id}/` locally or on S3:
+///
+/// - `metrics.parquet`, with the usual summary, histograms and manifest next to it
+/// - `summary.json`, the summary as JSON
+/// - `slow_queries.parquet`, for query runs that captured slow queries
+/// - `quarantine.parquet`, for ingests that gave up on batches
+/// - `log.txt`, the logs and progress reports printed while the run was active
+pub struct RunResults {
+    dir: String,
+    /// Local copy of `log.txt`, uploaded at the end for S3
+    log_path: PathBuf,
+    log: LogCapture,
+    _tmp_dir: Option<TempDir>,
+}
+
+impl RunResults {
+    /// Create the run's directory and start capturing its logs.
+    pub fn create(results_dir: &str, run_id: &str) -> anyhow::Result<Self> {
+        let dir = format!("{}/{run_id}", results_dir.trim_end_matches('/'));
+
+        let (log_path, tmp_dir) = if dir.starts_with("s3://") {
+            let tmp_dir = tempfile::tempdir()?;
+            (tmp_dir.path().join("log.txt"), Some(tmp_dir))
+        } else {
+            std::fs::create_dir_all(&dir)?;
+            (Path::new(&dir).join("log.txt"), None)
+        };
+
+        Ok(Self {
+            log: LogCapture::start(&log_path)?,
+            dir,
+            log_path,
+            _tmp_dir: tmp_dir,
+        })
+    }
+
+    /// Path of `name` within the run's directory.
+    pub fn path(&self, name: &str) -> String {
+        format!("{}/{name}", self.dir)
+    }
+
+    /// Write the run's metrics, leaving them in the registry, and close the log.
+    pub async fn write(self, registry: &Registry, run_id: &str) -> anyhow::Result<()> {
+        let metrics = registry.run_metrics(run_id).await;
+        let summary = write_all(registry, metrics, &self.path("metrics.parquet")).await?;
+
+        let mut writer = ArrayWriter::new(Vec::new());
+        writer.write(&summary)?;
+        writer.finish()?;
+        write_bytes(&writer.into_inner(), &self.path("summary.json")).await?;
+
+        info!("Results written to {}", self.dir);
+
+        let log_uri = self.path("log.txt");
+        drop(self.log);
+        if log_uri.starts_with("s3://") {
+            write_bytes(&std::fs::read(&self.log_path)?, &log_uri).await?;
+        }
+
+        Ok(())
+    }
+}
+
+static CAPTURES: Lazy<Mutex<HashMap<u64, LineWriter<File>>>> = Lazy::new(Default::default);
+static NEXT_CAPTURE: AtomicU64 = AtomicU64::new(0);
+/// Number of active captures, checked for every event without taking the lock
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Copies logs and progress reports to a file until dropped.
+struct LogCapture(u64);
+
+impl LogCapture {
+    fn start(path: &Path) -> io::Result<Self> {
+        let file = LineWriter::new(File::create(path)?);
+        let id = NEXT_CAPTURE.fetch_add(1, Ordering::Relaxed);
+        CAPTURES.lock().unwrap().insert(id, file);
+        ACTIVE.fetch_add(1, Ordering::Relaxed);
+
+        Ok(Self(id))
+    }
+}
+
+impl Drop for LogCapture {
+    fn drop(&mut self) {
+        if let Some(mut file) = CAPTURES.lock().unwrap().remove(&self.0) {
+            let _ = file.flush();
+        }
+        ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Logging layer writing plain text to the active captures, only formatting events while
+/// there are any.
+pub(super) fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(|| Captures)
+        .with_filter(filter_fn(|_| ACTIVE.load(Ordering::Relaxed) > 0))
+}
+
+/// Copy a progress report line to the active captures, without colors.
+pub(super) fn capture(line: &str) {
+    if ACTIVE.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+
+    let line = strip_ansi(line);
+    for file in CAPTURES.lock().unwrap().values_mut() {
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+struct Captures;
+
+impl Write for Captures {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for file in CAPTURES.lock().unwrap().values_mut() {
+            let _ = file.write_all(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `line` without the color escape sequences of `colored`.
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip to the end of `ESC [ ... m`
+            chars.by_ref().find(|c| *c == 'm');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,36 @@
+/// Caller-supplied identity of a run, linking it to the other runs of a benchmark session.
+#[derive(Debug, Clone, Default)]
+pub struct RunIds {
+    /// Run ID to record metrics under. A random one is generated when unset.
+    pub run_id: Option<String>,
+    /// Groups runs, e.g. the ingest, warmup and query sweeps of one benchmark.
+    pub parent_run_id: Option<String>,
+}
+
+impl RunIds {
+    pub fn new(run_id: Option<String>, parent_run_id: Option<String>) -> Self {
+        Self {
+            run_id,
+            parent_run_id,
+        }
+    }
+
+    /// The supplied run ID, or a new random one.
+    pub fn generate(&self) -> String {
+        self.run_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+    }
+
+    /// IDs of the `i`th run of a sweep, suffixing the supplied run ID.
+    pub fn nth(&self, i: usize) -> Self {
+        Self {
+            run_id: self.run_id.as_ref().map(|id| format!("{id}-{i}")),
+            parent_run_id: self.parent_run_id.clone(),
+        }
+    }
+
+    pub fn parent_label(&self) -> String {
+        self.parent_run_id.clone().unwrap_or_default()
+    }
+}
@@ -1,16 +1,97 @@
-use chrono::Utc;
+use std::collections::BTreeSet;
+use std::time::Duration;
 
-use crate::telemetry::metrics::Metric;
+use chrono::{DateTime, Utc};
+use pyo3::prelude::*;
+
+use crate::telemetry::metrics::{Metric, MetricKind};
 
 pub struct Snapshot {
     pub metrics: Vec<Metric>,
+    /// Window rates and percentiles are computed over. Rates default to the last second
+    /// and percentiles to all metrics.
+    pub window: Option<Duration>,
 }
 
 impl Snapshot {
+    pub fn new(metrics: Vec<Metric>) -> Self {
+        Self {
+            metrics,
+            window: None,
+        }
+    }
+
+    /// Compute rates and percentiles over the last `window` only.
+    pub fn with_window(self, window: Option<Duration>) -> Snapshot {
+        Snapshot { window, ..self }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.metrics.is_empty()
     }
 
+    /// Metrics recorded at or after `start`.
+    pub fn since(self, start: DateTime<Utc>) -> Snapshot {
+        Snapshot {
+            metrics: self
+                .metrics
+                .into_iter()
+                .filter(|m| m.timestamp >= start)
+                .collect(),
+            window: self.window,
+        }
+    }
+
+    /// Metrics recorded at or before `end`.
+    pub fn until(self, end: DateTime<Utc>) -> Snapshot {
+        Snapshot {
+            metrics: self
+                .metrics
+                .into_iter()
+                .filter(|m| m.timestamp <= end)
+                .collect(),
+            window: self.window,
+        }
+    }
+
+    /// Metrics recorded during `phase`, e.g. `load` or `recall`.
+    pub fn phase(self, phase: &str) -> Snapshot {
+        Snapshot {
+            metrics: self
+                .metrics
+                .into_iter()
+                .filter(|m| m.metadata.get("phase").map(String::as_str) == Some(phase))
+                .collect(),
+            window: self.window,
+        }
+    }
+
+    /// Kind `name` was recorded as, if recorded.
+    pub fn kind(&self, name: &str) -> Option<MetricKind> {
+        self.metrics.iter().find(|m| m.name == name).map(|m| m.kind)
+    }
+
+    /// Aggregate of `name` according to its kind: the total of a counter, the latest value
+    /// of a gauge and the average of a histogram.
+    pub fn value(&self, name: &str) -> f64 {
+        match self.kind(name) {
+            Some(MetricKind::Counter) => self.total(name),
+            Some(MetricKind::Gauge) => self.latest(name),
+            Some(MetricKind::Histogram) => self.avg(name),
+            None => 0.0,
+        }
+    }
+
+    /// Most recently recorded value of `name`.
+    pub fn latest(&self, name: &str) -> f64 {
+        self.metrics
+            .iter()
+            .filter(|m| m.name == name)
+            .max_by_key(|m| m.timestamp)
+            .map(|m| m.value)
+            .unwrap_or(0.0)
+    }
+
     pub fn total(&self, name: &str) -> f64 {
         self.metrics
             .iter()
@@ -19,16 +100,35 @@ impl Snapshot {
             .sum()
     }
 
-    pub fn instantaneous_rate(&self, name: &str) -> f64 {
-        let now = Utc::now();
+    /// Sum of all metrics whose name starts with `prefix`.
+    pub fn total_prefix(&self, prefix: &str) -> f64 {
         self.metrics
             .iter()
-            .filter(|m| m.name == name)
-            .filter(|m| (now - m.timestamp).num_milliseconds() <= 1000)
+            .filter(|m| m.name.starts_with(prefix))
             .map(|m| m.value)
             .sum()
     }
 
+    /// Number of samples recorded for `name`.
+    pub fn count(&self, name: &str) -> usize {
+        self.metrics.iter().filter(|m| m.name == name).count()
+    }
+
+    /// Per-second rate of `name` over the window.
+    pub fn instantaneous_rate(&self, name: &str) -> f64 {
+        let window = self.window.unwrap_or(Duration::from_secs(1));
+        let since = Utc::now() - window;
+        let total: f64 = self
+            .metrics
+            .iter()
+            .filter(|m| m.name == name)
+            .filter(|m| m.timestamp >= since)
+            .map(|m| m.value)
+            .sum();
+
+        total / window.as_secs_f64()
+    }
+
     pub fn avg(&self, name: &str) -> f64 {
         let mut count = 0usize;
         let mut total = 0.0;
@@ -43,19 +143,114 @@ impl Snapshot {
         }
     }
 
+    /// Sample standard deviation.
+    pub fn stddev(&self, name: &str) -> f64 {
+        let count = self.count(name);
+        if count < 2 {
+            return 0.0;
+        }
+        let avg = self.avg(name);
+        let sum_sq: f64 = self
+            .metrics
+            .iter()
+            .filter(|m| m.name == name)
+            .map(|m| (m.value - avg).powi(2))
+            .sum();
+
+        (sum_sq / (count - 1) as f64).sqrt()
+    }
+
+    /// Quantile of `name`, over the window if set.
     pub fn quantile(&self, name: &str, quantile: f64) -> f64 {
+        let since = self.window.map(|window| Utc::now() - window);
         let mut values: Vec<f64> = self
             .metrics
             .iter()
             .filter(|m| m.name == name)
+            .filter(|m| since.is_none_or(|since| m.timestamp >= since))
             .map(|m| m.value)
             .collect();
-        if values.is_empty() {
-            return 0.0;
-        }
         values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        let len = values.len();
-        let idx = ((quantile * (len as f64 - 1.0)).round() as usize).min(len - 1);
-        values[idx]
+
+        quantile_of_sorted(&values, quantile)
+    }
+}
+
+/// Quantile of ascending `values`, 0 if empty.
+pub(crate) fn quantile_of_sorted(values: &[f64], quantile: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let len = values.len();
+    let idx = ((quantile * (len as f64 - 1.0)).round() as usize).min(len - 1);
+    values[idx]
+}
+
+/// Point-in-time view of collected metrics, exposed to Python.
+#[pyclass(name = "Snapshot")]
+pub struct PySnapshot {
+    inner: Snapshot,
+}
+
+impl From<Snapshot> for PySnapshot {
+    fn from(inner: Snapshot) -> Self {
+        Self { inner }
+    }
+}
+
+#[pymethods]
+impl PySnapshot {
+    fn __len__(&self) -> usize {
+        self.inner.metrics.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Distinct metric names in the snapshot.
+    fn names(&self) -> Vec<String> {
+        self.inner
+            .metrics
+            .iter()
+            .map(|m| m.name.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// `"counter"`, `"gauge"` or `"histogram"`, `None` if not recorded.
+    fn kind(&self, name: &str) -> Option<String> {
+        self.inner.kind(name).map(|kind| kind.to_string())
+    }
+
+    /// Total of a counter, latest value of a gauge or average of a histogram.
+    fn value(&self, name: &str) -> f64 {
+        self.inner.value(name)
+    }
+
+    fn latest(&self, name: &str) -> f64 {
+        self.inner.latest(name)
+    }
+
+    fn total(&self, name: &str) -> f64 {
+        self.inner.total(name)
+    }
+
+    fn total_prefix(&self, prefix: &str) -> f64 {
+        self.inner.total_prefix(prefix)
+    }
+
+    /// Per-second rate of `name` over the last second.
+    fn rate(&self, name: &str) -> f64 {
+        self.inner.instantaneous_rate(name)
+    }
+
+    fn avg(&self, name: &str) -> f64 {
+        self.inner.avg(name)
+    }
+
+    fn quantile(&self, name: &str, quantile: f64) -> f64 {
+        self.inner.quantile(name, quantile)
     }
 }
@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow_array::{ArrayRef, Float64Array, Int32Array, RecordBatch, StringArray, UInt64Array};
+use chrono::{DateTime, Utc};
+
+use crate::telemetry::manifest::RunInfo;
+use crate::telemetry::metrics::{Metric, MetricKind};
+use crate::telemetry::persist::{label_column, SCHEMA_VERSION};
+use crate::telemetry::snapshot::quantile_of_sorted;
+
+/// Leading columns of the summary, which labels must not shadow.
+const COLUMNS: [&str; 12] = [
+    "schema_version",
+    "metric",
+    "kind",
+    "count",
+    "sum",
+    "min",
+    "max",
+    "p50",
+    "p95",
+    "p99",
+    "p999",
+    "value",
+];
+
+const QUANTILES: [f64; 4] = [0.50, 0.95, 0.99, 0.999];
+
+/// Labels of each run, without per-sample ones like `worker_id`.
+pub(super) struct RunLabels<'a>(HashMap<&'a str, &'a BTreeMap<String, String>>);
+
+impl<'a> RunLabels<'a> {
+    pub fn new(runs: &'a [RunInfo]) -> Self {
+        Self(
+            runs.iter()
+                .map(|run| (run.run_id.as_str(), &run.config))
+                .collect(),
+        )
+    }
+
+    /// Labels of `run_id` and the phase of `sample`, or all labels of `sample` for runs not
+    /// started in the registry.
+    pub fn get(&self, run_id: &str, sample: &HashMap<String, String>) -> BTreeMap<String, String> {
+        match self.0.get(run_id) {
+            Some(config) => {
+                let mut labels = (*config).clone();
+                if let Some(phase) = sample.get("phase") {
+                    labels.insert("phase".to_string(), phase.clone());
+                }
+                labels
+            }
+            None => sample.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        }
+    }
+}
+
+/// Run ID and phase `metric` was recorded in, empty if not recorded in one.
+pub(super) fn run_phase(metric: &Metric) -> (&str, &str) {
+    let label = |key| metric.metadata.get(key).map_or("", String::as_str);
+    (label("run_id"), label("phase"))
+}
+
+/// Samples of one metric in one run.
+struct Group<'a> {
+    kind: MetricKind,
+    values: Vec<f64>,
+    /// Most recent sample, the value of a gauge
+    latest: (DateTime<Utc>, f64),
+    /// Labels of the first sample, used for runs not started in the registry
+    labels: &'a HashMap<String, String>,
+}
+
+/// One row per run, phase and metric, with the count, sum, range and percentiles of its
+/// samples, its `value` aggregated by kind, and the run's labels.
+pub fn to_summary_batch(metrics: &[Metric], runs: &[RunInfo]) -> anyhow::Result<RecordBatch> {
+    let mut groups = BTreeMap::<(&str, &str, &str), Group>::new();
+    for metric in metrics {
+        let (run_id, phase) = run_phase(metric);
+        let group = groups
+            .entry((run_id, phase, &metric.name))
+            .or_insert_with(|| Group {
+                kind: metric.kind,
+                values: Vec::new(),
+                latest: (metric.timestamp, metric.value),
+                labels: &metric.metadata,
+            });
+        group.values.push(metric.value);
+        if metric.timestamp >= group.latest.0 {
+            group.latest = (metric.timestamp, metric.value);
+        }
+    }
+
+    let runs = RunLabels::new(runs);
+    let labels = |run_id: &str, group: &Group| runs.get(run_id, group.labels);
+
+    let label_keys: BTreeSet<String> = groups
+        .iter()
+        .flat_map(|((run_id, _, _), group)| labels(run_id, group).into_keys())
+        .collect();
+
+    let mut fields = vec![
+        Field::new("schema_version", DataType::Int32, false),
+        Field::new("metric", DataType::Utf8, false),
+        Field::new("kind", DataType::Utf8, false),
+        Field::new("count", DataType::UInt64, false),
+    ];
+    for name in &COLUMNS[4..] {
+        fields.push(Field::new(*name, DataType::Float64, false));
+    }
+    for key in &label_keys {
+        fields.push(Field::new(
+            label_column(key, &COLUMNS),
+            DataType::Utf8,
+            false,
+        ));
+    }
+
+    let mut names = Vec::with_capacity(groups.len());
+    let mut kinds = Vec::with_capacity(groups.len());
+    let mut counts = Vec::with_capacity(groups.len());
+    // sum, min, max, quantiles, value
+    let mut stats: Vec<Vec<f64>> = (0..8).map(|_| Vec::with_capacity(groups.len())).collect();
+    let mut labels_vecs: Vec<Vec<String>> = (0..label_keys.len())
+        .map(|_| Vec::with_capacity(groups.len()))
+        .collect();
+
+    for ((run_id, _, name), mut group) in groups {
+        group
+            .values
+            .sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let values = &group.values;
+        let sum: f64 = values.iter().sum();
+
+        let value = match group.kind {
+            MetricKind::Counter => sum,
+            MetricKind::Gauge => group.latest.1,
+            MetricKind::Histogram => sum / values.len() as f64,
+        };
+
+        names.push(name.to_string());
+        kinds.push(group.kind.to_string());
+        counts.push(values.len() as u64);
+        stats[0].push(sum);
+        stats[1].push(values[0]);
+        stats[2].push(values[values.len() - 1]);
+        for (i, quantile) in QUANTILES.iter().enumerate() {
+            stats[3 + i].push(quantile_of_sorted(values, *quantile));
+        }
+        stats[7].push(value);
+
+        let labels = labels(run_id, &group);
+        for (i, key) in label_keys.iter().enumerate() {
+            labels_vecs[i].push(labels.get(key).cloned().unwrap_or_default());
+        }
+    }
+
+    let mut arrays: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![SCHEMA_VERSION; names.len()])) as ArrayRef,
+        Arc::new(StringArray::from(names)) as ArrayRef,
+        Arc::new(StringArray::from(kinds)) as ArrayRef,
+        Arc::new(UInt64Array::from(counts)) as ArrayRef,
+    ];
+    for values in stats {
+        arrays.push(Arc::new(Float64Array::from(values)) as ArrayRef);
+    }
+    for values in labels_vecs {
+        arrays.push(Arc::new(StringArray::from(values)) as ArrayRef);
+    }
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+}